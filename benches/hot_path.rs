@@ -0,0 +1,212 @@
+// TEE Attestation Service Agent — hot path benchmarks
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Criterion benchmarks for the steps of a key release:
+//
+//   rsa_keygen        ephemeral wrapping key generation
+//   oaep_unwrap       RSA-OAEP unwrap of the AES key
+//   gcm_decrypt       AES-GCM decryption of the secret payload
+//   base64_report     base64 encoding of SNP reports and TDX quotes
+//   pipeline          version, nonce and get_secret against a local mock
+//                     server, then unwrap and decrypt of the payload
+//
+// The pipeline reuses one wrapping key so that the mock server can return
+// a payload encrypted to it; key generation is measured on its own. Run
+// with `cargo bench`, and compare a change against a baseline with
+// `cargo bench -- --save-baseline before` then `--baseline before`.
+//
+// No unsafe code.
+
+use base64::{engine::general_purpose, Engine};
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+};
+use serde_json::json;
+use std::path::PathBuf;
+
+// The agent is a binary crate: include the modules under test directly.
+// Checking all targets builds benches with cfg(test), which brings in the
+// modules' test helpers and leaves their test imports unused.
+#[allow(dead_code, unused_imports)]
+#[path = "../src/cassette.rs"]
+mod cassette;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/crypto.rs"]
+mod crypto;
+#[cfg(feature = "fips")]
+#[allow(dead_code)]
+#[path = "../src/fips.rs"]
+mod fips;
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "../src/golden.rs"]
+mod golden;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/limits.rs"]
+mod limits;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/netif.rs"]
+mod netif;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/request_id.rs"]
+mod request_id;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/rng.rs"]
+mod rng;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/schema.rs"]
+mod schema;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/tas_api.rs"]
+mod tas_api;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/tls.rs"]
+mod tls;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/transcript.rs"]
+mod transcript;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/utils.rs"]
+mod utils;
+
+use crypto::{decrypt_secret_with_aes_key, encrypt_secret_with_aes_key, generate_wrapping_key};
+use tas_api::{tas_get_nonce, tas_get_secret_key, tas_get_version, RetryConfig, SecretRequest};
+use utils::SecretsPayload;
+
+const AES_KEY: [u8; 32] = [0x42; 32];
+const IV: [u8; 12] = [0x24; 12];
+/// Sizes of an SEV-SNP report, a TDX quote, and a quote with its
+/// certification data.
+const REPORT_SIZES: [usize; 3] = [0x4a0, 5006, 16384];
+
+fn bench_keygen(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rsa_keygen");
+    group.sample_size(10);
+    group.bench_function("2048", |b| b.iter(|| generate_wrapping_key().unwrap()));
+    group.finish();
+}
+
+fn bench_oaep_unwrap(c: &mut Criterion) {
+    let key = generate_wrapping_key().unwrap();
+    let wrapped = key.encrypt(&AES_KEY).unwrap();
+    c.bench_function("oaep_unwrap", |b| {
+        b.iter(|| key.unwrap_key(black_box(&wrapped)).unwrap())
+    });
+}
+
+fn bench_gcm_decrypt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gcm_decrypt");
+    for size in [32usize, 4096, 1 << 20] {
+        let (ciphertext, tag) =
+            encrypt_secret_with_aes_key(&AES_KEY, &IV, &mut vec![0x5a; size], b"").unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &ciphertext, |b, ct| {
+            b.iter_batched(
+                || ct.clone(),
+                |mut ct| decrypt_secret_with_aes_key(&AES_KEY, &IV, &mut ct, &tag, b"").unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_base64_report(c: &mut Criterion) {
+    let mut group = c.benchmark_group("base64_report");
+    for size in REPORT_SIZES {
+        let report = vec![0xa5u8; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &report, |b, report| {
+            b.iter(|| general_purpose::STANDARD.encode(report))
+        });
+    }
+    group.finish();
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let key = generate_wrapping_key().unwrap();
+    let wrapping_key = key.public_key_to_base64().unwrap();
+    let evidence = general_purpose::STANDARD.encode(vec![0u8; REPORT_SIZES[0]]);
+
+    let (blob, tag) = encrypt_secret_with_aes_key(&AES_KEY, &IV, &mut [0x5a; 64], b"").unwrap();
+    let wrapped_key = key.encrypt(&AES_KEY).unwrap();
+    let secret_key = json!({
+        "wrapped_key": general_purpose::STANDARD.encode(wrapped_key),
+        "blob": general_purpose::STANDARD.encode(blob),
+        "iv": general_purpose::STANDARD.encode(IV),
+        "tag": general_purpose::STANDARD.encode(tag),
+    });
+    let nonce = "0".repeat(64);
+    let nonce_body = json!({ "nonce": nonce }).to_string();
+    let secret_body = json!({ "secret_key": secret_key }).to_string();
+
+    let mut server = runtime.block_on(mockito::Server::new_async());
+    let _version = server
+        .mock("GET", "/version")
+        .with_body(r#"{"version": "bench"}"#)
+        .create();
+    let _nonce = server
+        .mock("GET", "/kb/v0/get_nonce")
+        .with_body(nonce_body)
+        .create();
+    let _secret = server
+        .mock("POST", "/kb/v0/get_secret")
+        .with_body(secret_body)
+        .create();
+    let uri = server.url();
+    let retry = RetryConfig {
+        max_retries: 0,
+        ..RetryConfig::default()
+    };
+
+    let mut group = c.benchmark_group("pipeline");
+    group.sample_size(20);
+    group.bench_function("mock_server", |b| {
+        b.to_async(&runtime).iter(|| async {
+            tas_get_version(&uri, "key", PathBuf::new(), &retry)
+                .await
+                .unwrap();
+            let nonce = tas_get_nonce(&uri, "key", PathBuf::new(), &retry)
+                .await
+                .unwrap();
+            let response = tas_get_secret_key(
+                &uri,
+                "key",
+                PathBuf::new(),
+                &retry,
+                &SecretRequest {
+                    nonce: &nonce,
+                    tee_evidence: &evidence,
+                    tee_type: "amd-sev-snp",
+                    policy_id: "bench",
+                    wrapping_key: &wrapping_key,
+                    report_data_binding: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+            let mut payload: SecretsPayload = serde_json::from_str(&response.secret_key).unwrap();
+            let aes_key = key.unwrap_key(&payload.wrapped_key).unwrap();
+            decrypt_secret_with_aes_key(&aes_key, &payload.iv, &mut payload.blob, &payload.tag, b"")
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_keygen,
+    bench_oaep_unwrap,
+    bench_gcm_decrypt,
+    bench_base64_report,
+    bench_pipeline
+);
+criterion_main!(benches);
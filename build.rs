@@ -0,0 +1,49 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Build script: records the git commit for `tas_agent version --verbose` and
+// generates the gRPC service code when the `grpc` feature is enabled.
+// Requires `protoc` in PATH for that feature only.
+
+use std::path::Path;
+use std::process::Command;
+
+// The commit being built: TAS_AGENT_GIT_COMMIT if set (builds from a source
+// tarball), else `git rev-parse HEAD`, else "unknown".
+fn git_commit() -> String {
+    if let Ok(commit) = std::env::var("TAS_AGENT_GIT_COMMIT") {
+        return commit;
+    }
+    Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=TAS_AGENT_GIT_COMMIT");
+    for path in [".git/HEAD", ".git/refs/heads"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    println!("cargo:rustc-env=TAS_AGENT_GIT_COMMIT={}", git_commit());
+
+    #[cfg(feature = "grpc")]
+    tonic_build::configure()
+        .build_client(false)
+        .compile_protos(
+            &[
+                "proto/attestation-agent.proto",
+                "proto/confidential-data-hub.proto",
+            ],
+            &["proto"],
+        )
+        .expect("failed to compile gRPC protos");
+}
@@ -163,10 +163,10 @@ echo "Building tarball package..."
 
 FINAL_DIR="$DESTDIR/tas_agent"
 
-# Build the executable (with askpass and passfifo features enabled)
+# Build the executable (with askpass, passfifo and daemon features enabled)
 $CARGO_CMD clean 2>/dev/null || true
 
-$CARGO_CMD build --release --features askpass,passfifo
+$CARGO_CMD build --release --features askpass,passfifo,daemon
 if [ $? -ne 0 ]; then
     echo "Build failed. Please check the output for errors."
     exit 1
@@ -205,11 +205,12 @@ if [ -f "$ROOTCERT" ]; then
     cp "$ROOTCERT" "$FINAL_DIR/etc/tas_agent/root_cert.pem"
 fi
 
-# Copy systemd units used by askpass and the dracut network fallback
+# Copy systemd units used by askpass, daemon mode and the dracut network fallback
 mkdir -p "$FINAL_DIR/usr/lib/systemd/system"
 cp scripts/systemd/tas-agent-askpass.path "$FINAL_DIR/usr/lib/systemd/system/"
 cp scripts/systemd/tas-agent-askpass.service "$FINAL_DIR/usr/lib/systemd/system/"
 cp scripts/systemd/tas-agent-network.service "$FINAL_DIR/usr/lib/systemd/system/"
+cp scripts/systemd/tas-agent-daemon.service "$FINAL_DIR/usr/lib/systemd/system/"
 
 # Copy modules-load.d config
 mkdir -p "$FINAL_DIR/etc/modules-load.d"
@@ -0,0 +1,43 @@
+// TEE Attestation Service Agent — base64 field fuzz target
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Feeds arbitrary strings through the base64 deserializers of every
+// SecretsPayload field, with the rest of the payload well formed, and
+// through the nonce decoder in every encoding.
+//
+// No unsafe code.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde_json::json;
+
+#[allow(dead_code)]
+#[path = "../../src/utils.rs"]
+mod utils;
+
+const FIELDS: [&str; 5] = ["wrapped_key", "blob", "iv", "tag", "algorithm"];
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    for field in FIELDS {
+        let mut payload = json!({
+            "wrapped_key": "AAAA",
+            "blob": "AAAA",
+            "iv": "AAAA",
+            "tag": "AAAA",
+        });
+        payload[field] = json!(text);
+        let _ = serde_json::from_value::<utils::SecretsPayload>(payload);
+    }
+    for encoding in [
+        utils::NonceEncoding::Raw,
+        utils::NonceEncoding::Hex,
+        utils::NonceEncoding::Base64,
+        utils::NonceEncoding::Auto,
+    ] {
+        let _ = utils::decode_nonce(&text, encoding);
+    }
+});
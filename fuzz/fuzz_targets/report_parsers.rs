@@ -0,0 +1,44 @@
+// TEE Attestation Service Agent — report parser fuzz target
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Runs the SNP report and TDX quote parsers (CoRIM measurements and local
+// policy input) over arbitrary bytes. The first byte selects the TEE type.
+//
+// No unsafe code.
+
+#![no_main]
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/corim.rs"]
+mod corim;
+#[allow(dead_code)]
+#[path = "../../src/local_policy.rs"]
+mod local_policy;
+#[allow(dead_code)]
+#[path = "../../src/request_id.rs"]
+mod request_id;
+#[allow(dead_code)]
+#[path = "../../src/rng.rs"]
+mod rng;
+#[allow(dead_code)]
+#[path = "../../src/tee_evidence.rs"]
+mod tee_evidence;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((selector, report)) = data.split_first() else {
+        return;
+    };
+    let tee_type = if selector & 1 == 0 {
+        "amd-sev-snp"
+    } else {
+        "intel-tdx"
+    };
+    let _ = corim::measurements(tee_type, report);
+    let token = String::from_utf8_lossy(report);
+    let _ = local_policy::input(tee_type, &STANDARD.encode(report), Some(&token), None);
+});
@@ -0,0 +1,23 @@
+// TEE Attestation Service Agent — SecretsPayload fuzz target
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Deserializes arbitrary bytes as the secret payload TAS returns.
+//
+// No unsafe code.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/limits.rs"]
+mod limits;
+#[allow(dead_code)]
+#[path = "../../src/utils.rs"]
+mod utils;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_json::from_slice::<utils::SecretsPayload>(data);
+});
@@ -0,0 +1,50 @@
+// TEE Attestation Service Agent — TAS response fuzz target
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Parses arbitrary bytes as a get_secret response body (success or error),
+// then the extracted secret as the payload the agent decrypts.
+//
+// No unsafe code.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/cassette.rs"]
+mod cassette;
+#[allow(dead_code)]
+#[path = "../../src/limits.rs"]
+mod limits;
+#[allow(dead_code)]
+#[path = "../../src/request_id.rs"]
+mod request_id;
+#[allow(dead_code)]
+#[path = "../../src/rng.rs"]
+mod rng;
+#[allow(dead_code)]
+#[path = "../../src/schema.rs"]
+mod schema;
+#[allow(dead_code)]
+#[path = "../../src/tas_api.rs"]
+mod tas_api;
+#[allow(dead_code)]
+#[path = "../../src/tls.rs"]
+mod tls;
+#[allow(dead_code)]
+#[path = "../../src/transcript.rs"]
+mod transcript;
+#[allow(dead_code)]
+#[path = "../../src/utils.rs"]
+mod utils;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(data) else {
+        return;
+    };
+    if let Ok(response) = tas_api::parse_secret_response(&json) {
+        let _ = serde_json::from_str::<utils::SecretsPayload>(&response.secret_key);
+    }
+});
@@ -36,10 +36,10 @@ for automatic LUKS unlock during early boot.
 %autosetup
 
 %build
-cargo build --release --features askpass
+cargo build --release --features askpass,daemon
 
 %check
-cargo test --features askpass
+cargo test --features askpass,daemon
 
 %install
 # --- tas-agent (core) ---
@@ -50,6 +50,7 @@ install -Dm600 config/api-key.sample %{buildroot}/etc/tas_agent/api-key
 install -Dm644 scripts/systemd/tas-agent-askpass.service %{buildroot}/usr/lib/systemd/system/tas-agent-askpass.service
 install -Dm644 scripts/systemd/tas-agent-askpass.path %{buildroot}/usr/lib/systemd/system/tas-agent-askpass.path
 install -Dm644 scripts/systemd/tas-agent-network.service %{buildroot}/usr/lib/systemd/system/tas-agent-network.service
+install -Dm644 scripts/systemd/tas-agent-daemon.service %{buildroot}/usr/lib/systemd/system/tas-agent-daemon.service
 install -Dm644 scripts/systemd/modules-load.d/tas-agent.conf %{buildroot}/etc/modules-load.d/tas-agent.conf
 
 # --- tas-agent-dracut ---
@@ -67,6 +68,7 @@ install -Dm755 scripts/dracut/tas-net-setup.sh %{buildroot}/usr/lib/dracut/modul
 /usr/lib/systemd/system/tas-agent-askpass.service
 /usr/lib/systemd/system/tas-agent-askpass.path
 /usr/lib/systemd/system/tas-agent-network.service
+/usr/lib/systemd/system/tas-agent-daemon.service
 %config(noreplace) /etc/modules-load.d/tas-agent.conf
 
 %files dracut
@@ -0,0 +1,58 @@
+#!/bin/bash
+set -euo pipefail
+# Copyright 2026 Hewlett Packard Enterprise Development LP.
+# SPDX-License-Identifier: MIT
+#
+# Report the peak resident set size of a tas_agent run, to compare builds.
+#
+# Usage: scripts/measure-rss.sh [-n RUNS] -- COMMAND [ARGS...]
+#
+# Example:
+#   cargo build --profile minimal --no-default-features --features minimal
+#   scripts/measure-rss.sh -n 5 -- target/minimal/tas_agent -c config.toml
+#
+# Requires GNU time (/usr/bin/time). The command's output is discarded; its
+# exit status is reported with each run.
+
+show_help() {
+    echo "Usage: $0 [-n RUNS] -- COMMAND [ARGS...]"
+    echo
+    echo "Options:"
+    echo "  -n RUNS   Number of runs (default: 3)"
+    echo "  -h        Show this help message and exit"
+}
+
+RUNS=3
+
+while [[ $# -gt 0 ]]; do
+    case "$1" in
+        -n) RUNS="$2"; shift 2 ;;
+        -h) show_help; exit 0 ;;
+        --) shift; break ;;
+        *) break ;;
+    esac
+done
+
+if [[ $# -eq 0 ]]; then
+    show_help
+    exit 1
+fi
+
+if [[ ! -x /usr/bin/time ]]; then
+    echo "GNU time (/usr/bin/time) is required" >&2
+    exit 1
+fi
+
+peak=0
+for run in $(seq 1 "$RUNS"); do
+    report=$(mktemp)
+    status=0
+    /usr/bin/time -f "%M" -o "$report" "$@" > /dev/null 2>&1 || status=$?
+    rss=$(tail -n 1 "$report")
+    rm -f "$report"
+    echo "run $run: peak RSS ${rss} KiB (exit status $status)"
+    if (( rss > peak )); then
+        peak=$rss
+    fi
+done
+echo "max peak RSS over $RUNS run(s): ${peak} KiB"
@@ -0,0 +1,106 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Audit trail for security-relevant agent decisions.
+//
+// Every audit event is emitted through the `log` facade with target "audit"
+// and, when `audit_log` is configured, appended as one JSON object per line
+// to that file. The file is opened in append mode with 0600 permissions and
+// each record is written with a single write call. Records of an attestation
+// attempt carry its `request_id` (see request_id.rs).
+
+use crate::request_id;
+use chrono::Utc;
+use log::{info, warn};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+static AUDIT_FILE: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Open the audit log file. Only the first successful call takes effect.
+pub fn init(path: Option<&Path>) {
+    let Some(path) = path else {
+        return;
+    };
+    if AUDIT_FILE.get().is_some() {
+        return;
+    }
+    match OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)
+    {
+        Ok(file) => {
+            let _ = AUDIT_FILE.set(Mutex::new(file));
+        }
+        Err(e) => warn!("unable to open audit log {:?}: {}", path, e),
+    }
+}
+
+/// Format a single audit record as a JSON line (without trailing newline).
+fn format_record(event: &str, fields: &[(&str, &str)]) -> String {
+    let mut record = serde_json::Map::new();
+    record.insert("timestamp".to_string(), Utc::now().to_rfc3339().into());
+    record.insert("event".to_string(), event.into());
+    if let Some(id) = request_id::current() {
+        record.insert("request_id".to_string(), id.into());
+    }
+    for (key, value) in fields {
+        record.insert(key.to_string(), (*value).into());
+    }
+    serde_json::Value::Object(record).to_string()
+}
+
+/// Record an audit event with the given key/value details.
+pub fn record(event: &str, fields: &[(&str, &str)]) {
+    let line = format_record(event, fields);
+    info!(target: "audit", "{}", line);
+
+    if let Some(file) = AUDIT_FILE.get() {
+        if let Ok(mut file) = file.lock() {
+            if let Err(e) = file.write_all(format!("{}\n", line).as_bytes()) {
+                warn!("unable to write audit log: {}", e);
+            }
+        }
+    }
+}
+
+/// Flush the audit log to storage, so that the records leading up to an
+/// interrupted run survive an immediate exit.
+pub fn flush() {
+    log::logger().flush();
+    if let Some(file) = AUDIT_FILE.get() {
+        if let Ok(file) = file.lock() {
+            if let Err(e) = file.sync_data() {
+                warn!("unable to flush audit log: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_record_contains_event_and_fields() {
+        let line = format_record("escrow-store", &[("dir", "/var/lib/x"), ("result", "ok")]);
+        let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(v["event"], "escrow-store");
+        assert_eq!(v["dir"], "/var/lib/x");
+        assert_eq!(v["result"], "ok");
+        assert!(v["timestamp"].is_string());
+    }
+
+    #[test]
+    fn test_format_record_is_single_line() {
+        let line = format_record("e", &[("detail", "multi\nline")]);
+        assert!(!line.contains('\n'));
+    }
+}
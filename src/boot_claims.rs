@@ -0,0 +1,119 @@
+// TEE Attestation Service Agent — boot configuration claims
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With measured direct boot, the launch measurement covers the kernel,
+// initrd and command line the host passed to the guest firmware. A verifier
+// enforcing such a policy needs to know which boot configuration to expect
+// the measurement for. With a `[boot_claims]` section the agent sends it as
+// claims next to the TEE report:
+//
+//   [boot_claims]
+//   initrd = "/boot/initrd.img"   # optional
+//
+//   "boot.cmdline"        the kernel command line, from /proc/cmdline
+//   "boot.initrd_sha256"  hex SHA-256 of `initrd`, if configured
+//   "boot.uki_image"      path of the booted unified kernel image and
+//   "boot.uki_stub"       name and version of its stub, from the
+//                         LoaderImageIdentifier and StubInfo EFI variables
+//                         set by systemd-boot and systemd-stub, if present
+//
+// The values are read by the guest and are only trustworthy as far as the
+// verifier can check them against the launch measurement. Boot claims
+// replace operator-defined claims (see claims.rs) of the same name.
+//
+// No unsafe code.
+
+use crate::cmdline::CMDLINE;
+use anyhow::{Context, Result};
+use log::debug;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Directory of the EFI variables.
+pub const EFIVARS: &str = "/sys/firmware/efi/efivars";
+
+/// Vendor GUID of the variables of the systemd boot loader interface.
+const LOADER_GUID: &str = "4a67b082-0a4c-41cf-b6c7-440b29bb8c4f";
+
+/// `[boot_claims]` section of the config file.
+#[derive(Deserialize, Clone, Default)]
+pub struct BootClaimsConfig {
+    /// Initrd to send the SHA-256 digest of
+    pub initrd: Option<PathBuf>,
+}
+
+/// The string value of an EFI variable: four bytes of attributes followed
+/// by a NUL-terminated UTF-16LE string.
+fn efi_string(var: &[u8]) -> Option<String> {
+    let units: Vec<u16> = var
+        .get(4..)?
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16(&units).ok().filter(|s| !s.is_empty())
+}
+
+/// The string value of the boot loader interface variable `name`, if set.
+fn loader_variable(name: &str) -> Option<String> {
+    let path = Path::new(EFIVARS).join(format!("{}-{}", name, LOADER_GUID));
+    match std::fs::read(&path) {
+        Ok(var) => efi_string(&var),
+        Err(e) => {
+            debug!("unable to read {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// The boot configuration claims of the running guest.
+pub fn collect(config: &BootClaimsConfig) -> Result<BTreeMap<String, String>> {
+    let mut claims = BTreeMap::new();
+    let cmdline =
+        std::fs::read_to_string(CMDLINE).with_context(|| format!("unable to read {}", CMDLINE))?;
+    claims.insert("boot.cmdline".to_string(), cmdline.trim_end().to_string());
+    if let Some(initrd) = &config.initrd {
+        let image =
+            std::fs::read(initrd).with_context(|| format!("unable to read {:?}", initrd))?;
+        claims.insert(
+            "boot.initrd_sha256".to_string(),
+            hex::encode(Sha256::digest(&image)),
+        );
+    }
+    if let Some(image) = loader_variable("LoaderImageIdentifier") {
+        claims.insert("boot.uki_image".to_string(), image);
+    }
+    if let Some(stub) = loader_variable("StubInfo") {
+        claims.insert("boot.uki_stub".to_string(), stub);
+    }
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn efi_var(value: &str) -> Vec<u8> {
+        let mut var = vec![0x06, 0, 0, 0];
+        for unit in value.encode_utf16().chain([0]) {
+            var.extend(unit.to_le_bytes());
+        }
+        var
+    }
+
+    #[test]
+    fn test_efi_string() {
+        assert_eq!(
+            efi_string(&efi_var("\\EFI\\Linux\\linux-6.8.efi")).as_deref(),
+            Some("\\EFI\\Linux\\linux-6.8.efi")
+        );
+        assert_eq!(efi_string(&efi_var("")), None);
+        assert_eq!(efi_string(&[0x06, 0]), None);
+        // Invalid UTF-16: an unpaired surrogate
+        assert_eq!(efi_string(&[0x06, 0, 0, 0, 0x00, 0xd8, 0, 0]), None);
+    }
+}
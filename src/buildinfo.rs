@@ -0,0 +1,102 @@
+// TEE Attestation Service Agent — build introspection
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// `tas_agent version` prints the crate version; `tas_agent version --verbose`
+// prints what the binary was built with as JSON, so that fleet inventory can
+// spot capability drift between images:
+//
+//   {"version": "0.1.0", "git_commit": "2a84d64...",
+//    "features": ["daemon", "hex-dump", "native-tls"],
+//    "tee_backends": ["amd-sev-snp", "amd-sev-snp-svsm", "intel-tdx"],
+//    "wrapping_algorithms": ["RSA-OAEP-SHA256"],
+//    "payload_algorithms": ["AES-GCM", "AES-GCM-STREAM", "AES-KWP"]}
+//
+// The git commit is recorded by build.rs, from TAS_AGENT_GIT_COMMIT or
+// `git rev-parse HEAD`, and is "unknown" outside a checkout.
+//
+// No unsafe code.
+
+use crate::evidence::SVSM_TEE_TYPE;
+use serde_json::{json, Value};
+
+/// Cargo features of the build, by name.
+const FEATURES: &[(&str, bool)] = &[
+    ("askpass", cfg!(feature = "askpass")),
+    ("daemon", cfg!(feature = "daemon")),
+    ("deterministic-rng", cfg!(feature = "deterministic-rng")),
+    ("evidence-fixtures", cfg!(feature = "evidence-fixtures")),
+    ("fips", cfg!(feature = "fips")),
+    ("gpu-nvidia", cfg!(feature = "gpu-nvidia")),
+    ("grpc", cfg!(feature = "grpc")),
+    ("hex-dump", cfg!(feature = "hex-dump")),
+    ("landlock", cfg!(feature = "landlock")),
+    ("minimal", cfg!(feature = "minimal")),
+    ("native-tls", cfg!(feature = "native-tls")),
+    ("passfifo", cfg!(feature = "passfifo")),
+    ("seccomp", cfg!(feature = "seccomp")),
+    ("snp-derived-key", cfg!(feature = "snp-derived-key")),
+    ("tls-keylog", cfg!(feature = "tls-keylog")),
+];
+
+/// Features enabled in this build.
+fn features() -> Vec<&'static str> {
+    FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// TEE types this build can collect evidence for.
+fn tee_backends() -> Vec<&'static str> {
+    let mut backends = vec!["amd-sev-snp", SVSM_TEE_TYPE, "intel-tdx"];
+    if cfg!(feature = "gpu-nvidia") {
+        backends.push("gpu-nvidia");
+    }
+    backends
+}
+
+/// The short version line.
+pub fn version() -> String {
+    format!("tas_agent {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Everything `version --verbose` reports.
+pub fn report() -> Value {
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_commit": env!("TAS_AGENT_GIT_COMMIT"),
+        "features": features(),
+        "tee_backends": tee_backends(),
+        "wrapping_algorithms": ["RSA-OAEP-SHA256"],
+        "payload_algorithms": ["AES-GCM", "AES-GCM-STREAM", "AES-KWP"],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let report = report();
+        assert_eq!(report["version"], env!("CARGO_PKG_VERSION"));
+        assert!(!report["git_commit"].as_str().unwrap().is_empty());
+        let features = report["features"].as_array().unwrap();
+        assert_eq!(
+            features.iter().any(|f| f == "daemon"),
+            cfg!(feature = "daemon")
+        );
+        assert!(report["tee_backends"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|t| t == "intel-tdx"));
+        let names: Vec<_> = FEATURES.iter().map(|(name, _)| *name).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+    }
+}
@@ -0,0 +1,354 @@
+// TEE Attestation Service Agent — offline evidence bundles
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Guests without a route to TAS release their secrets through a connected
+// host that carries a JSON bundle back and forth:
+//
+//   1. On the connected host, fetch a nonce from TAS into a file.
+//   2. On the guest, `tas_agent evidence export --nonce-file nonce.txt
+//      --out bundle.json --key wrapping.key` collects evidence bound to the
+//      nonce and a fresh wrapping key. The bundle holds the get_secret
+//      request; the private wrapping key stays on the guest (mode 0600).
+//   3. On the connected host, `tas_agent submit --bundle bundle.json` sends
+//      the request to TAS and adds the response, with the wrapped secret,
+//      to the bundle.
+//   4. On the guest, `tas_agent evidence import --bundle bundle.json --key
+//      wrapping.key` checks the response like an online release, unwraps
+//      the secret and writes it like the normal mode, then removes the key.
+//
+// The bundle holds nothing secret: the secret in the response is wrapped to
+// the key that never leaves the guest. TAS must accept the nonce for as long
+// as the round trip takes.
+//
+// No unsafe code.
+
+use crate::crypto::{
+    generate_wrapping_key, RsaKey, WrappingKeyEncoding, NONCE_LOCAL_ENTROPY, NONCE_SHA512,
+};
+use crate::escrow::write_private;
+use crate::hooks::ErrorClass;
+use crate::tas_api::{tas_get_secret_key, EvidenceSignature, SecretRequest, SecretResponse};
+use crate::{
+    attest_prepared, audit, kbs, open_response, postprocess, resolve_session, Attestation,
+    CliOverrides, Preparation,
+};
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Version of the bundle format.
+const VERSION: u32 = 1;
+
+/// A get_secret request collected offline, and the response once submitted.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Bundle {
+    version: u32,
+    /// The nonce as TAS returned it, including the JSON quotes
+    nonce: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nonce_construction: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    local_entropy: Option<String>,
+    tee_type: String,
+    tee_evidence: String,
+    /// Policy ID, or the key ID of a KBS resource URI
+    policy_id: String,
+    wrapping_key: String,
+    report_data_binding: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    component_evidence: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attestation_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    evidence_signature: Option<EvidenceSignature>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    claims: Option<BTreeMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    platform_metadata: Option<Value>,
+    /// The get_secret response, added by `submit`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response: Option<SecretResponse>,
+}
+
+/// The nonce in `text` in the form `tas_get_nonce` returns it, whether the
+/// file holds it as a JSON string or bare.
+fn parse_nonce(text: &str) -> Result<String> {
+    let nonce = text.trim().trim_matches('"');
+    if nonce.is_empty() {
+        return Err(anyhow!("no nonce"));
+    }
+    Ok(Value::String(nonce.to_string()).to_string())
+}
+
+fn read(path: &Path) -> Result<Bundle> {
+    let json = fs::read_to_string(path).with_context(|| format!("unable to read {:?}", path))?;
+    let bundle: Bundle =
+        serde_json::from_str(&json).with_context(|| format!("invalid bundle {:?}", path))?;
+    if bundle.version != VERSION {
+        return Err(anyhow!(
+            "unsupported bundle version {} in {:?}",
+            bundle.version,
+            path
+        ));
+    }
+    Ok(bundle)
+}
+
+fn write(path: &Path, bundle: &Bundle) -> Result<()> {
+    let json = serde_json::to_string_pretty(bundle)?;
+    fs::write(path, json + "\n").with_context(|| format!("unable to write {:?}", path))
+}
+
+/// Collect evidence bound to the nonce in `nonce_file` into a bundle at
+/// `out`, keeping the private wrapping key at `key_path`.
+pub async fn export(
+    config_path: Option<PathBuf>,
+    overrides: CliOverrides,
+    nonce_file: &Path,
+    out: &Path,
+    key_path: &Path,
+) -> Result<()> {
+    let session = resolve_session(config_path, Some(overrides))?;
+    let policy_id = session
+        .policy_id
+        .clone()
+        .ok_or_else(|| ErrorClass::Config.error("server policy ID is required"))?;
+    let policy_id = kbs::key_id(&policy_id, &session.server_uri)?;
+
+    let nonce = fs::read_to_string(nonce_file)
+        .with_context(|| format!("unable to read {:?}", nonce_file))
+        .and_then(|text| parse_nonce(&text))
+        .map_err(|e| ErrorClass::Config.error(format!("Nonce Decode Error: {:#}", e)))?;
+    debug!("Nonce: {}", nonce);
+
+    let rsa_wrapping_key =
+        tokio::task::spawn_blocking(|| generate_wrapping_key().map_err(|e| e.to_string()))
+            .await
+            .map_err(|e| {
+                ErrorClass::Crypto.error(format!("failed to generate wrapping key: {}", e))
+            })?
+            .map_err(|e| {
+                ErrorClass::Crypto.error(format!("failed to generate wrapping key: {}", e))
+            })?;
+    let attestation = attest_prepared(
+        session,
+        Preparation {
+            rsa_wrapping_key,
+            nonce,
+        },
+    )
+    .await?;
+
+    let der = Zeroizing::new(
+        attestation
+            .rsa_wrapping_key
+            .private_key_to_der()
+            .map_err(|e| anyhow!("failed to encode wrapping key: {}", e))?,
+    );
+    write_private(key_path, &der)?;
+
+    let bundle = Bundle {
+        version: VERSION,
+        nonce: attestation.nonce,
+        nonce_construction: attestation.nonce_construction.map(str::to_string),
+        local_entropy: attestation.local_entropy,
+        tee_type: attestation.tee_type,
+        tee_evidence: attestation.tee_evidence,
+        policy_id,
+        wrapping_key: attestation.wrapping_key,
+        report_data_binding: attestation.key_binding_enabled,
+        component_evidence: attestation.component_evidence,
+        attestation_token: attestation.attestation_token,
+        evidence_signature: attestation.evidence_signature,
+        claims: attestation.session.claims,
+        platform_metadata: attestation.platform_metadata,
+        response: None,
+    };
+    write(out, &bundle)?;
+    audit::record("bundle-export", &[("tee_type", &bundle.tee_type)]);
+    Ok(())
+}
+
+/// Send the request in the bundle at `path` to TAS and add the response.
+pub async fn submit(
+    config_path: Option<PathBuf>,
+    overrides: CliOverrides,
+    path: &Path,
+) -> Result<()> {
+    let session = resolve_session(config_path, Some(overrides))?;
+    let mut bundle = read(path)?;
+    let session = session.for_key(&bundle.policy_id).unwrap_or(session);
+    if bundle.response.is_some() {
+        warn!("Replacing the response already in {:?}", path);
+    }
+
+    let response = tas_get_secret_key(
+        &session.server_uri,
+        &session.api_key,
+        session.cert_path.clone(),
+        &session.retry_config,
+        &SecretRequest {
+            nonce: &bundle.nonce,
+            tee_evidence: &bundle.tee_evidence,
+            tee_type: &bundle.tee_type,
+            policy_id: &bundle.policy_id,
+            wrapping_key: &bundle.wrapping_key,
+            report_data_binding: bundle.report_data_binding,
+            component_evidence: bundle.component_evidence.as_ref(),
+            attestation_token: bundle.attestation_token.as_deref(),
+            nonce_construction: bundle.nonce_construction.as_deref(),
+            evidence_signature: bundle.evidence_signature.as_ref(),
+            claims: bundle.claims.as_ref(),
+            local_entropy: bundle.local_entropy.as_deref(),
+            platform_metadata: bundle.platform_metadata.as_ref(),
+            ..Default::default()
+        },
+    )
+    .await
+    .map_err(|e| {
+        let class = if e.request_failed {
+            ErrorClass::Network
+        } else {
+            ErrorClass::Release
+        };
+        class.error(format!("TAS Secret Error: {}", e))
+    })?;
+
+    bundle.response = Some(response);
+    write(path, &bundle)?;
+    audit::record("bundle-submit", &[("policy_id", &bundle.policy_id)]);
+    Ok(())
+}
+
+/// Unwrap the secret of the submitted bundle at `path` with the wrapping key
+/// at `key_path`, which is removed afterwards.
+pub async fn import(
+    config_path: Option<PathBuf>,
+    overrides: CliOverrides,
+    path: &Path,
+    key_path: &Path,
+) -> Result<Vec<u8>> {
+    let session = resolve_session(config_path, Some(overrides))?;
+    let bundle = read(path)?;
+    let response = bundle
+        .response
+        .ok_or_else(|| anyhow!("{:?} has not been submitted to TAS", path))?;
+
+    let der = Zeroizing::new(
+        fs::read(key_path).with_context(|| format!("unable to read {:?}", key_path))?,
+    );
+    let rsa_wrapping_key = RsaKey::from_private_key_der(&der)
+        .map_err(|e| anyhow!("invalid wrapping key {:?}: {}", key_path, e))?;
+    // In the encoding the bundle was exported with
+    let wrapping_key = rsa_wrapping_key
+        .public_key_encoded(WrappingKeyEncoding::of(&bundle.wrapping_key))
+        .map_err(|e| anyhow!("failed to encode wrapping key: {}", e))?;
+    if wrapping_key != bundle.wrapping_key {
+        return Err(anyhow!(
+            "{:?} is not the wrapping key of {:?}",
+            key_path,
+            path
+        ));
+    }
+    let nonce_construction = match bundle.nonce_construction.as_deref() {
+        None => None,
+        Some(NONCE_SHA512) => Some(NONCE_SHA512),
+        Some(NONCE_LOCAL_ENTROPY) => Some(NONCE_LOCAL_ENTROPY),
+        Some(other) => return Err(anyhow!("unknown nonce construction {:?}", other)),
+    };
+
+    let attestation = Attestation {
+        session,
+        nonce: bundle.nonce,
+        nonce_construction,
+        local_entropy: bundle.local_entropy,
+        evidence_signature: bundle.evidence_signature,
+        tee_evidence: bundle.tee_evidence,
+        tee_type: bundle.tee_type,
+        rsa_wrapping_key,
+        ec_wrapping_key: None,
+        wrapping_key,
+        key_binding_enabled: bundle.report_data_binding,
+        report_data: None,
+        component_evidence: bundle.component_evidence,
+        attestation_token: bundle.attestation_token,
+        platform_metadata: bundle.platform_metadata,
+    };
+    let secret = open_response(&attestation, response)?;
+    let secret = postprocess(&attestation.session, &bundle.policy_id, secret)?;
+
+    // The key has served its one release
+    if let Err(e) = fs::remove_file(key_path) {
+        warn!("unable to remove {:?}: {}", key_path, e);
+    }
+    audit::record("bundle-import", &[("tee_type", &attestation.tee_type)]);
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_nonce() {
+        let nonce = "\"0123456789abcdef\"";
+        assert_eq!(parse_nonce("0123456789abcdef\n").unwrap(), nonce);
+        assert_eq!(parse_nonce("\"0123456789abcdef\"\n").unwrap(), nonce);
+        assert!(parse_nonce(" \n").is_err());
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("bundle.json");
+        let mut bundle = Bundle {
+            version: VERSION,
+            nonce: "\"abc\"".to_string(),
+            nonce_construction: Some(NONCE_SHA512.to_string()),
+            local_entropy: None,
+            tee_type: "amd-sev-snp".to_string(),
+            tee_evidence: "cmVwb3J0".to_string(),
+            policy_id: "policy-1".to_string(),
+            wrapping_key: "a2V5".to_string(),
+            report_data_binding: true,
+            component_evidence: None,
+            attestation_token: None,
+            evidence_signature: None,
+            claims: None,
+            platform_metadata: None,
+            response: None,
+        };
+        write(&path, &bundle).unwrap();
+        let json = fs::read_to_string(&path).unwrap();
+        assert!(
+            json.contains("\"nonce-construction\": \"sha512\""),
+            "{}",
+            json
+        );
+        assert!(!json.contains("response"), "{}", json);
+
+        bundle.response = Some(SecretResponse {
+            secret_key: "{\"blob\":\"\"}".to_string(),
+            attestation_result: Some("eyJ9".to_string()),
+            server_attestation: None,
+        });
+        write(&path, &bundle).unwrap();
+        let read_back = read(&path).unwrap();
+        let response = read_back.response.unwrap();
+        assert_eq!(response.secret_key, "{\"blob\":\"\"}");
+        assert_eq!(response.attestation_result.as_deref(), Some("eyJ9"));
+        assert_eq!(read_back.nonce, "\"abc\"");
+
+        fs::write(&path, json.replace("\"version\": 1", "\"version\": 2")).unwrap();
+        assert!(read(&path).is_err());
+    }
+}
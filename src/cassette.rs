@@ -0,0 +1,265 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Record/replay of TAS REST API interactions.
+//
+// When the TAS_AGENT_RECORD environment variable names a file, every
+// successful TAS API exchange is appended to it as one JSON object per line
+// (a "cassette"):
+//
+//   {"method": "POST", "path": "/kb/v0/get_secret", "request": {...},
+//    "status": 200, "response": {...}}
+//
+// Evidence, keys, tokens and secrets are replaced with "<scrubbed>" before
+// anything is written, and headers (including the API key) are never
+// recorded, so a cassette keeps only the shape of the protocol. Cassettes
+// checked in under testdata/cassettes, one per server version of interest,
+// are replayed in tests: each request must carry every field that was
+// recorded and gets the recorded response.
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+/// Environment variable naming the cassette to record to.
+pub const RECORD_ENV: &str = "TAS_AGENT_RECORD";
+
+/// Placeholder for scrubbed values.
+const SCRUBBED: &str = "<scrubbed>";
+
+/// Fields whose values are never recorded.
+const SENSITIVE_FIELDS: &[&str] = &[
+    "tee-evidence",
+    "wrapping-key",
+    "component-evidence",
+    "attestation-token",
+    "attestation-result",
+    "secret_key",
+];
+
+static CASSETTE: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// One recorded request and its response.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Interaction {
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<Value>,
+    pub status: u16,
+    pub response: Value,
+}
+
+/// Open the cassette named by TAS_AGENT_RECORD, if set.
+pub fn init() {
+    let Some(path) = std::env::var_os(RECORD_ENV) else {
+        return;
+    };
+    let path = Path::new(&path);
+    match OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)
+    {
+        Ok(file) => {
+            warn!("Recording TAS API interactions to {:?}", path);
+            let _ = CASSETTE.set(Mutex::new(file));
+        }
+        Err(e) => warn!("unable to open cassette {:?}: {}", path, e),
+    }
+}
+
+/// Replace the values of `fields` in `value`, at any depth, with
+/// `placeholder`.
+pub(crate) fn redact(value: &Value, fields: &[&str], placeholder: &str) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = if fields.contains(&key.as_str()) {
+                        placeholder.into()
+                    } else {
+                        redact(value, fields, placeholder)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| redact(item, fields, placeholder))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Replace the values of sensitive fields in `value`, at any depth.
+fn scrub(value: &Value) -> Value {
+    redact(value, SENSITIVE_FIELDS, SCRUBBED)
+}
+
+/// Format a scrubbed interaction as a JSON line (without trailing newline).
+fn format_interaction(
+    method: &str,
+    path: &str,
+    request: Option<&Value>,
+    status: u16,
+    response: &Value,
+) -> String {
+    let interaction = Interaction {
+        method: method.to_string(),
+        path: path.to_string(),
+        request: request.map(scrub),
+        status,
+        response: scrub(response),
+    };
+    serde_json::to_string(&interaction).unwrap_or_default()
+}
+
+/// Whether TAS API exchanges are being recorded.
+pub fn recording() -> bool {
+    CASSETTE.get().is_some()
+}
+
+/// Record a TAS API exchange, if recording is enabled.
+pub fn record(method: &str, path: &str, request: Option<&Value>, status: u16, response: &Value) {
+    let Some(file) = CASSETTE.get() else {
+        return;
+    };
+    let mut line = format_interaction(method, path, request, status, response);
+    line.push('\n');
+    if let Ok(mut file) = file.lock() {
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            warn!("unable to record TAS API interaction: {}", e);
+        }
+    }
+}
+
+/// Serve the interactions of `cassette` from `server`, in order.
+#[cfg(test)]
+pub async fn replay(server: &mut mockito::ServerGuard, cassette: &str) -> Vec<mockito::Mock> {
+    let mut mocks = Vec::new();
+    for line in cassette.lines().filter(|line| !line.trim().is_empty()) {
+        let interaction: Interaction = serde_json::from_str(line).expect("invalid cassette line");
+        let mut mock = server.mock(interaction.method.as_str(), interaction.path.as_str());
+        if let Some(Value::Object(request)) = &interaction.request {
+            // The live request must carry every recorded field
+            let fields = request
+                .keys()
+                .map(|key| mockito::Matcher::Regex(format!("\"{}\"\\s*:", regex_escape(key))))
+                .collect();
+            mock = mock.match_body(mockito::Matcher::AllOf(fields));
+        }
+        let body = match &interaction.response {
+            Value::String(text) => text.clone(),
+            json => json.to_string(),
+        };
+        mocks.push(
+            mock.with_status(interaction.status as usize)
+                .with_header("content-type", "application/json")
+                .with_body(body)
+                .create_async()
+                .await,
+        );
+    }
+    mocks
+}
+
+#[cfg(test)]
+fn regex_escape(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| {
+            let escape = !c.is_ascii_alphanumeric() && c != '_' && c != '-';
+            escape.then_some('\\').into_iter().chain(std::iter::once(c))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tas_api::{
+        tas_get_nonce, tas_get_secret_key, tas_get_version, RetryConfig, SecretRequest,
+    };
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    const TAS_CASSETTE: &str = include_str!("../testdata/cassettes/tas-get-secret.jsonl");
+
+    #[test]
+    fn test_format_interaction_scrubs_secrets() {
+        let request = json!({
+            "tee-type": "amd-sev-snp",
+            "tee-evidence": "cmVwb3J0",
+            "wrapping-key": "a2V5",
+            "component-evidence": [{"evidence": "Z3B1"}]
+        });
+        let line = format_interaction(
+            "POST",
+            "/kb/v0/get_secret",
+            Some(&request),
+            200,
+            &json!({"secret_key": {"wrapped_key": "d2s="}}),
+        );
+        let interaction: Interaction = serde_json::from_str(&line).unwrap();
+        let request = interaction.request.unwrap();
+        assert_eq!(request["tee-type"], "amd-sev-snp");
+        assert_eq!(request["tee-evidence"], SCRUBBED);
+        assert_eq!(request["component-evidence"], SCRUBBED);
+        assert_eq!(interaction.response["secret_key"], SCRUBBED);
+        assert!(!line.contains("cmVwb3J0") && !line.contains("d2s="));
+    }
+
+    #[test]
+    fn test_regex_escape() {
+        assert_eq!(regex_escape("tee-type"), "tee-type");
+        assert_eq!(regex_escape("a.b"), "a\\.b");
+    }
+
+    #[tokio::test]
+    async fn test_replay_tas_cassette() {
+        let mut server = mockito::Server::new_async().await;
+        let mocks = replay(&mut server, TAS_CASSETTE).await;
+        let uri = server.url();
+        let retry = RetryConfig {
+            max_retries: 0,
+            ..RetryConfig::default()
+        };
+
+        let version = tas_get_version(&uri, "key", PathBuf::new(), &retry).await;
+        assert!(version.is_ok(), "{:?}", version);
+        let nonce = tas_get_nonce(&uri, "key", PathBuf::new(), &retry)
+            .await
+            .unwrap();
+        let secret = tas_get_secret_key(
+            &uri,
+            "key",
+            PathBuf::new(),
+            &retry,
+            &SecretRequest {
+                nonce: &nonce,
+                tee_evidence: "cmVwb3J0",
+                tee_type: "amd-sev-snp",
+                policy_id: "policy",
+                wrapping_key: "a2V5",
+                report_data_binding: true,
+                ..Default::default()
+            },
+        )
+        .await;
+        assert!(secret.is_ok(), "{:?}", secret.err());
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+    }
+}
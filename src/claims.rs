@@ -0,0 +1,78 @@
+// TEE Attestation Service Agent — operator-defined claims
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Hardware evidence says what runs, not where it is deployed. Claims are
+// key/value strings about the deployment that are sent with the evidence in
+// the get_secret request, so that a server-side policy can also condition
+// the release on them:
+//
+//   [claims]
+//   workload_id = "payments-api"
+//   image_digest = "sha256:4f1c..."
+//   environment = "production"
+//
+// and `--claim KEY=VALUE`, repeatable, which takes precedence over a claim
+// of the same key in the config file. The request carries them as
+//
+//   "claims": {"environment": "production", "workload_id": "payments-api"}
+//
+// Claims are asserted by whoever configures the agent, not measured by the
+// TEE; with `[evidence_signing]` they are covered by the evidence signature
+// like the rest of the request.
+//
+// No unsafe code.
+
+use std::collections::BTreeMap;
+
+/// Parse a `--claim KEY=VALUE` argument.
+pub fn parse(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected KEY=VALUE, got {:?}", arg)),
+    }
+}
+
+/// The claims of the `[claims]` section with those of the command line
+/// added, or None if there are none.
+pub fn merge(
+    config: Option<BTreeMap<String, String>>,
+    cli: &[(String, String)],
+) -> Option<BTreeMap<String, String>> {
+    let mut claims = config.unwrap_or_default();
+    claims.extend(cli.iter().cloned());
+    (!claims.is_empty()).then_some(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("image_digest=sha256:ab=cd").unwrap(),
+            ("image_digest".to_string(), "sha256:ab=cd".to_string())
+        );
+        assert_eq!(parse("env=").unwrap().1, "");
+        assert!(parse("workload_id").is_err());
+        assert!(parse("=value").is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let config = BTreeMap::from([
+            ("environment".to_string(), "staging".to_string()),
+            ("workload_id".to_string(), "payments-api".to_string()),
+        ]);
+        let cli = [("environment".to_string(), "production".to_string())];
+        let claims = merge(Some(config), &cli).unwrap();
+        assert_eq!(claims["environment"], "production");
+        assert_eq!(claims["workload_id"], "payments-api");
+        assert_eq!(merge(None, &[]), None);
+        assert_eq!(merge(Some(BTreeMap::new()), &[]), None);
+    }
+}
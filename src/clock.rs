@@ -0,0 +1,119 @@
+// TEE Attestation Service Agent — clock-skew tolerant time checks
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// A freshly booted guest often runs with a wrong clock until NTP has
+// synchronized it, so the time claims of the tokens the agent checks (the
+// EAR attestation result and the server attestation) are compared with a
+// tolerance of `clock_skew_secs` (default 60) at the top level of the config
+// file:
+//
+//   - `exp` must be later than now - skew,
+//   - `nbf` and `iat`, if present, must not be later than now + skew.
+//
+// These tokens are issued for the nonce of the running attestation, moments
+// ago, so a time claim outside the window means the clocks of the agent and
+// the issuer disagree. Such failures say "clock skew" and are classified as
+// `clock-skew` for hooks and `--error-format json`, where they are retryable
+// once the clock is synchronized.
+//
+// No unsafe code.
+
+use crate::hooks::ErrorClass;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// Default tolerance between the local clock and a token issuer's.
+const DEFAULT_SKEW_SECS: u64 = 60;
+
+static SKEW_SECS: OnceLock<u64> = OnceLock::new();
+
+/// Tolerate `secs` of clock skew in all later time checks.
+pub fn configure(secs: u64) {
+    let _ = SKEW_SECS.set(secs);
+}
+
+fn skew_secs() -> i64 {
+    let secs = SKEW_SECS.get().copied().unwrap_or(DEFAULT_SKEW_SECS);
+    i64::try_from(secs).unwrap_or(i64::MAX)
+}
+
+/// The integer time claim `name` of `claims`, if present.
+fn time_claim(claims: &Value, name: &str) -> Result<Option<i64>> {
+    claims
+        .get(name)
+        .map(|value| {
+            value
+                .as_i64()
+                .ok_or_else(|| anyhow!("invalid {} claim", name))
+        })
+        .transpose()
+}
+
+/// Check the `exp`, `nbf` and `iat` claims of a token at `now`, tolerating
+/// `skew` seconds of clock skew.
+fn check_times_with(claims: &Value, now: i64, skew: i64) -> Result<()> {
+    if let Some(exp) = time_claim(claims, "exp")? {
+        if exp.saturating_add(skew) <= now {
+            return Err(ErrorClass::ClockSkew.error(format!(
+                "clock skew: token expired at {}, {}s before the local time; \
+                 check that the system clock is synchronized",
+                exp,
+                now - exp
+            )));
+        }
+    }
+    for name in ["nbf", "iat"] {
+        if let Some(time) = time_claim(claims, name)? {
+            if time > now.saturating_add(skew) {
+                return Err(ErrorClass::ClockSkew.error(format!(
+                    "clock skew: token {} is {}, {}s after the local time; \
+                     check that the system clock is synchronized",
+                    name,
+                    time,
+                    time - now
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check the `exp`, `nbf` and `iat` claims of a token at `now`.
+pub fn check_times(claims: &Value, now: i64) -> Result<()> {
+    check_times_with(claims, now, skew_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_check_times() {
+        let claims = json!({"iat": 1000, "nbf": 1000, "exp": 1300});
+        assert!(check_times_with(&claims, 1100, 60).is_ok());
+        // Within the window on either side
+        assert!(check_times_with(&claims, 950, 60).is_ok());
+        assert!(check_times_with(&claims, 1350, 60).is_ok());
+
+        let err = check_times_with(&claims, 1400, 60).unwrap_err();
+        assert!(
+            err.to_string().starts_with("clock skew: token expired"),
+            "{}",
+            err
+        );
+        let err = check_times_with(&claims, 900, 60).unwrap_err();
+        assert!(
+            err.to_string().contains("token nbf is 1000, 100s after"),
+            "{}",
+            err
+        );
+        assert!(check_times_with(&claims, 900, 0).is_err());
+
+        assert!(check_times_with(&json!({}), 1000, 0).is_ok());
+        assert!(check_times_with(&json!({"exp": "soon"}), 1000, 60).is_err());
+    }
+}
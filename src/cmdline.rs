@@ -0,0 +1,155 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Configuration from the kernel command line.
+//
+// In an initramfs there may be no writable config file, and the host passes
+// the agent's settings through the bootloader instead. With
+// `--kernel-cmdline` (or `kernel_cmdline = true` in the config file)
+// parameters of the form
+//
+//   tas_agent.server=https://tas.example.com:5000 tas_agent.key_id=luks
+//
+// in /proc/cmdline override the config file; command-line options override
+// both. Values may be double-quoted. Parameters after a lone `--` belong to
+// init and are ignored. /proc/cmdline is read once per process.
+
+use crate::Config;
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+/// The kernel command line.
+pub const CMDLINE: &str = "/proc/cmdline";
+
+/// Prefix of the agent's parameters.
+const PREFIX: &str = "tas_agent.";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static PARAMS: OnceLock<Vec<(String, String)>> = OnceLock::new();
+
+/// Take configuration from the kernel command line, whatever the config file
+/// says.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// The agent's parameters on `cmdline`, without their prefix.
+fn parse(cmdline: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in cmdline.chars().chain(std::iter::once(' ')) {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if word == "--" {
+                    break;
+                }
+                if let Some((name, value)) = word
+                    .strip_prefix(PREFIX)
+                    .and_then(|param| param.split_once('='))
+                {
+                    params.push((name.to_string(), value.to_string()));
+                }
+                word.clear();
+            }
+            c => word.push(c),
+        }
+    }
+    params
+}
+
+fn params() -> &'static [(String, String)] {
+    PARAMS.get_or_init(|| match std::fs::read_to_string(CMDLINE) {
+        Ok(cmdline) => parse(&cmdline),
+        Err(e) => {
+            warn!("unable to read {}: {}", CMDLINE, e);
+            Vec::new()
+        }
+    })
+}
+
+fn number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    value.parse().map_err(|_| {
+        anyhow!(
+            "invalid {}{}={:?} on the kernel command line",
+            PREFIX,
+            name,
+            value
+        )
+    })
+}
+
+/// Override `cfg` with the parameters on the kernel command line.
+pub(crate) fn apply(cfg: &mut Config) -> Result<()> {
+    apply_params(cfg, params())
+}
+
+fn apply_params(cfg: &mut Config, params: &[(String, String)]) -> Result<()> {
+    for (name, value) in params {
+        debug!("Kernel command line: {}{}={}", PREFIX, name, value);
+        match name.as_str() {
+            "server" | "server_uri" => cfg.server_uri = Some(value.clone()),
+            "allow_insecure" => cfg.allow_insecure = Some(number(name, value)?),
+            "key_id" | "policy_id" => cfg.policy_id = Some(value.clone()),
+            "api_key" => cfg.api_key = Some(PathBuf::from(value)),
+            "cert" | "cert_path" => cfg.cert_path = Some(PathBuf::from(value)),
+            "max_retries" => cfg.max_retries = Some(number(name, value)?),
+            "retry_min_backoff_secs" => cfg.retry_min_backoff_secs = Some(number(name, value)?),
+            "retry_max_backoff_secs" => cfg.retry_max_backoff_secs = Some(number(name, value)?),
+            "tee_type" => {
+                cfg.tsm.get_or_insert_with(Default::default).tee_type = Some(value.clone())
+            }
+            _ => warn!("Ignoring unknown kernel parameter {}{}", PREFIX, name),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let params = parse(
+            "BOOT_IMAGE=/vmlinuz root=/dev/vda1 tas_agent.server=https://tas:5000 \
+             tas_agent.key_id=\"luks key\" tas_agent.debug quiet -- tas_agent.cert=/x\n",
+        );
+        assert_eq!(
+            params,
+            vec![
+                ("server".to_string(), "https://tas:5000".to_string()),
+                ("key_id".to_string(), "luks key".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_params() {
+        let mut cfg = Config {
+            server_uri: Some("https://config:5000".to_string()),
+            ..Config::default()
+        };
+        let params = parse("tas_agent.server=https://tas:5000 tas_agent.key_id=luks tas_agent.max_retries=5 tas_agent.allow_insecure=true tas_agent.unknown=1");
+        apply_params(&mut cfg, &params).unwrap();
+        assert_eq!(cfg.server_uri.as_deref(), Some("https://tas:5000"));
+        assert_eq!(cfg.policy_id.as_deref(), Some("luks"));
+        assert_eq!(cfg.max_retries, Some(5));
+        assert_eq!(cfg.allow_insecure, Some(true));
+
+        let params = parse("tas_agent.max_retries=many");
+        let err = apply_params(&mut cfg, &params).unwrap_err();
+        assert!(err.to_string().contains("tas_agent.max_retries"), "{}", err);
+    }
+}
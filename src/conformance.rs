@@ -0,0 +1,415 @@
+// TEE Attestation Service Agent — TAS conformance suite
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// `tas_agent conformance` runs a scripted suite against the configured TAS
+// server and prints a pass/fail report, so that server implementers can
+// check their compatibility with the agent:
+//
+//   version             GET /version returns a non-empty "version" string
+//   nonce-format        get_nonce returns a 64-character "nonce" string
+//   nonce-unique        consecutive nonces differ
+//   unknown-nonce       get_secret rejects a nonce it never issued
+//   malformed-evidence  get_secret rejects evidence that is not base64
+//   truncated-evidence  get_secret rejects evidence too short for a report
+//   unknown-tee-type    get_secret rejects an unknown TEE type
+//   payload-schema      a real release returns a payload the agent decrypts
+//   stale-nonce         a release after --nonce-lifetime is rejected
+//
+// The rejection probes send one deliberately invalid field each, without
+// retries, and pass on a 4xx response: a 2xx or 5xx fails them. Without TEE
+// evidence the probes cannot tell which check the server failed the request
+// on, only that it refused it cleanly. The last two checks attest for real
+// and are skipped without a policy ID or TEE; stale-nonce also needs
+// --nonce-lifetime.
+//
+// No unsafe code.
+
+use crate::corim::SNP_REPORT_LEN;
+use crate::crypto::generate_wrapping_key;
+use crate::hooks::ErrorClass;
+use crate::tas_api::{create_tas_client, RetryConfig};
+use crate::{attest, release, resolve_session, CliOverrides};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine};
+use reqwest_middleware::ClientWithMiddleware;
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use zeroize::Zeroize;
+
+/// Length of the nonces the agent binds into TEE evidence.
+const NONCE_LEN: usize = 64;
+
+/// Result of one check.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Pass(String),
+    Fail(String),
+    Skip(String),
+}
+
+/// One line of the report.
+#[derive(Debug)]
+struct Check {
+    name: &'static str,
+    outcome: Outcome,
+}
+
+impl Check {
+    fn new(name: &'static str, outcome: Outcome) -> Self {
+        Check { name, outcome }
+    }
+}
+
+/// Direct access to the TAS REST API, for requests the agent never sends.
+struct Probe {
+    client: ClientWithMiddleware,
+    server_uri: String,
+    api_key: String,
+    policy_id: String,
+    wrapping_key: String,
+}
+
+impl Probe {
+    /// GET `path` and return its JSON body.
+    async fn get(&self, path: &str) -> Result<Value, String> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.server_uri, path))
+            .header("X-API-KEY", &self.api_key)
+            .send()
+            .await
+            .map_err(|e| format!("request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+        response
+            .json::<Value>()
+            .await
+            .map_err(|e| format!("invalid JSON: {}", e))
+    }
+
+    async fn nonce(&self) -> Result<String, String> {
+        let json = self.get("/kb/v0/get_nonce").await?;
+        json.get("nonce")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| "no \"nonce\" string in response".to_string())
+    }
+
+    /// POST get_secret with `nonce`, `evidence` and `tee_type`, expecting a
+    /// client error. Without `nonce`, a fresh one is fetched first.
+    async fn expect_rejection(
+        &self,
+        nonce: Option<&str>,
+        evidence: &str,
+        tee_type: &str,
+    ) -> Outcome {
+        let nonce = match nonce {
+            Some(nonce) => nonce.to_string(),
+            None => match self.nonce().await {
+                Ok(nonce) => nonce,
+                Err(e) => return Outcome::Fail(format!("unable to get a nonce: {}", e)),
+            },
+        };
+        let body = json!({
+            "tee-type": tee_type,
+            "nonce": nonce,
+            "tee-evidence": evidence,
+            "policy-id": self.policy_id,
+            "wrapping-key": self.wrapping_key,
+            "report-data-binding": true,
+        });
+        let response = self
+            .client
+            .post(format!("{}/kb/v0/get_secret", self.server_uri))
+            .header("X-API-KEY", &self.api_key)
+            .json(&body)
+            .send()
+            .await;
+        match response.map(|response| response.status()) {
+            Ok(status) if status.is_client_error() => {
+                Outcome::Pass(format!("rejected with HTTP {}", status))
+            }
+            Ok(status) if status.is_success() => Outcome::Fail("accepted".to_string()),
+            Ok(status) => Outcome::Fail(format!("HTTP {} instead of a 4xx rejection", status)),
+            Err(e) => Outcome::Fail(format!("request failed: {}", e)),
+        }
+    }
+}
+
+/// Run the checks that need no TEE.
+async fn run_probes(probe: &Probe) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let version = match probe.get("/version").await {
+        Ok(json) => match json.get("version").and_then(Value::as_str) {
+            Some(version) if !version.is_empty() => Outcome::Pass(format!("TAS {}", version)),
+            _ => Outcome::Fail("no non-empty \"version\" string in response".to_string()),
+        },
+        Err(e) => Outcome::Fail(e),
+    };
+    checks.push(Check::new("version", version));
+
+    let first = probe.nonce().await;
+    let format = match &first {
+        Ok(nonce) if nonce.len() == NONCE_LEN => Outcome::Pass(format!("{} characters", NONCE_LEN)),
+        Ok(nonce) => Outcome::Fail(format!(
+            "{} characters, the agent requires {}",
+            nonce.len(),
+            NONCE_LEN
+        )),
+        Err(e) => Outcome::Fail(e.clone()),
+    };
+    checks.push(Check::new("nonce-format", format));
+    let unique = match (&first, probe.nonce().await) {
+        (Ok(first), Ok(second)) if *first != second => {
+            Outcome::Pass("fresh nonce per request".into())
+        }
+        (Ok(_), Ok(_)) => Outcome::Fail("the same nonce was returned twice".to_string()),
+        (Err(_), _) => Outcome::Skip("no nonce".to_string()),
+        (_, Err(e)) => Outcome::Fail(e),
+    };
+    checks.push(Check::new("nonce-unique", unique));
+
+    let report = general_purpose::STANDARD.encode([0u8; SNP_REPORT_LEN]);
+    let unissued = "0".repeat(NONCE_LEN);
+    let probes = [
+        (
+            "unknown-nonce",
+            Some(unissued.as_str()),
+            report.as_str(),
+            "amd-sev-snp",
+        ),
+        (
+            "malformed-evidence",
+            None,
+            "%%% not base64 %%%",
+            "amd-sev-snp",
+        ),
+        (
+            "truncated-evidence",
+            None,
+            "AAAAAAAAAAAAAAAAAAAAAA==",
+            "amd-sev-snp",
+        ),
+        ("unknown-tee-type", None, report.as_str(), "conformance-tee"),
+    ];
+    for (name, nonce, evidence, tee_type) in probes {
+        let outcome = probe.expect_rejection(nonce, evidence, tee_type).await;
+        checks.push(Check::new(name, outcome));
+    }
+    checks
+}
+
+/// Format `checks` as the report printed to stdout.
+fn report(checks: &[Check]) -> String {
+    let mut out = String::new();
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for check in checks {
+        let (label, detail) = match &check.outcome {
+            Outcome::Pass(detail) => {
+                passed += 1;
+                ("PASS", detail)
+            }
+            Outcome::Fail(detail) => {
+                failed += 1;
+                ("FAIL", detail)
+            }
+            Outcome::Skip(detail) => {
+                skipped += 1;
+                ("SKIP", detail)
+            }
+        };
+        let _ = writeln!(out, "{}  {:<20}{}", label, check.name, detail);
+    }
+    let _ = writeln!(
+        out,
+        "{} checks: {} passed, {} failed, {} skipped",
+        checks.len(),
+        passed,
+        failed,
+        skipped
+    );
+    out
+}
+
+/// Run the conformance suite against the configured server and print the
+/// report. Fails if any check failed.
+pub async fn run(
+    config_path: Option<PathBuf>,
+    overrides: CliOverrides,
+    nonce_lifetime: Option<u64>,
+) -> Result<()> {
+    let session = resolve_session(config_path, Some(overrides))?;
+
+    // Invalid requests must be answered, not retried
+    let no_retry = RetryConfig {
+        max_retries: 0,
+        ..session.retry_config.clone()
+    };
+    let wrapping_key = generate_wrapping_key()
+        .and_then(|key| key.public_key_to_base64())
+        .map_err(|e| ErrorClass::Crypto.error(format!("failed to generate wrapping key: {}", e)))?;
+    let probe = Probe {
+        client: create_tas_client(&session.server_uri, session.cert_path.clone(), &no_retry)
+            .map_err(|e| anyhow!(e))?,
+        server_uri: session.server_uri.clone(),
+        api_key: session.api_key.to_string(),
+        policy_id: session
+            .policy_id
+            .clone()
+            .unwrap_or_else(|| "conformance".to_string()),
+        wrapping_key,
+    };
+    let mut checks = run_probes(&probe).await;
+
+    match session.policy_id.clone() {
+        None => {
+            let reason = "no policy ID configured".to_string();
+            checks.push(Check::new("payload-schema", Outcome::Skip(reason.clone())));
+            checks.push(Check::new("stale-nonce", Outcome::Skip(reason)));
+        }
+        Some(policy_id) => match attest(session).await {
+            Err(e) => {
+                let reason = format!("no attestation: {:#}", e);
+                checks.push(Check::new("payload-schema", Outcome::Skip(reason.clone())));
+                checks.push(Check::new("stale-nonce", Outcome::Skip(reason)));
+            }
+            Ok(attestation) => {
+                let schema = match release(&attestation, &policy_id).await {
+                    Ok(mut secret) => {
+                        let detail = format!("{}-byte secret released and decrypted", secret.len());
+                        secret.zeroize();
+                        Outcome::Pass(detail)
+                    }
+                    Err(e) => Outcome::Fail(format!("{:#}", e)),
+                };
+                checks.push(Check::new("payload-schema", schema));
+
+                let stale = match nonce_lifetime {
+                    None => Outcome::Skip("--nonce-lifetime not given".to_string()),
+                    Some(secs) => {
+                        tokio::time::sleep(Duration::from_secs(secs + 1)).await;
+                        match release(&attestation, &policy_id).await {
+                            Ok(mut secret) => {
+                                secret.zeroize();
+                                Outcome::Fail(format!("released with a nonce over {}s old", secs))
+                            }
+                            Err(e) => Outcome::Pass(format!("rejected: {:#}", e)),
+                        }
+                    }
+                };
+                checks.push(Check::new("stale-nonce", stale));
+            }
+        },
+    }
+
+    print!("{}", report(&checks));
+    let failed = checks
+        .iter()
+        .filter(|check| matches!(check.outcome, Outcome::Fail(_)))
+        .count();
+    if failed > 0 {
+        return Err(anyhow!("{} conformance check(s) failed", failed));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn probe(server: &mockito::ServerGuard) -> Probe {
+        let retry = RetryConfig {
+            max_retries: 0,
+            ..RetryConfig::default()
+        };
+        Probe {
+            client: create_tas_client(&server.url(), PathBuf::new(), &retry).unwrap(),
+            server_uri: server.url(),
+            api_key: "key".to_string(),
+            policy_id: "conformance".to_string(),
+            wrapping_key: "a2V5".to_string(),
+        }
+    }
+
+    fn outcome<'a>(checks: &'a [Check], name: &str) -> &'a Outcome {
+        &checks
+            .iter()
+            .find(|check| check.name == name)
+            .unwrap()
+            .outcome
+    }
+
+    #[tokio::test]
+    async fn test_conformant_server_passes() {
+        let mut server = mockito::Server::new_async().await;
+        let _version = server
+            .mock("GET", "/version")
+            .with_body(r#"{"version": "1.2.0"}"#)
+            .create_async()
+            .await;
+        let counter = AtomicUsize::new(0);
+        let _nonce = server
+            .mock("GET", "/kb/v0/get_nonce")
+            .with_body_from_request(move |_| {
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                format!(r#"{{"nonce": "{:064x}"}}"#, n + 1).into_bytes()
+            })
+            .create_async()
+            .await;
+        let _secret = server
+            .mock("POST", "/kb/v0/get_secret")
+            .with_status(400)
+            .with_body(r#"{"error": "invalid evidence"}"#)
+            .create_async()
+            .await;
+
+        let checks = run_probes(&probe(&server).await).await;
+        for check in &checks {
+            assert!(matches!(check.outcome, Outcome::Pass(_)), "{:?}", check);
+        }
+        assert_eq!(checks.len(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_lenient_server_fails() {
+        let mut server = mockito::Server::new_async().await;
+        let _version = server
+            .mock("GET", "/version")
+            .with_body(r#"{"version": 2}"#)
+            .create_async()
+            .await;
+        let _nonce = server
+            .mock("GET", "/kb/v0/get_nonce")
+            .with_body(r#"{"nonce": "short"}"#)
+            .create_async()
+            .await;
+        let _secret = server
+            .mock("POST", "/kb/v0/get_secret")
+            .with_status(500)
+            .create_async()
+            .await;
+
+        let checks = run_probes(&probe(&server).await).await;
+        assert!(matches!(outcome(&checks, "version"), Outcome::Fail(_)));
+        assert!(matches!(outcome(&checks, "nonce-format"), Outcome::Fail(_)));
+        assert!(matches!(outcome(&checks, "nonce-unique"), Outcome::Fail(_)));
+        assert!(matches!(
+            outcome(&checks, "unknown-nonce"),
+            Outcome::Fail(_)
+        ));
+
+        let report = report(&checks);
+        assert!(report.contains("FAIL  unknown-tee-type"), "{report}");
+        assert!(
+            report.ends_with("7 checks: 0 passed, 7 failed, 0 skipped\n"),
+            "{report}"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+// TEE Attestation Service Agent — reference value export
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// `tas_agent corim` collects a TEE report on the current machine, extracts
+// its launch measurements and prints them as a CoMID (Concise Module
+// Identifier, draft-ietf-rats-corim) reference value template in the JSON
+// form accepted by Veraison's `cocli comid create`:
+//
+//   amd-sev-snp   MEASUREMENT
+//   intel-tdx     MRTD, RTMR0, RTMR1, RTMR2, RTMR3
+//
+// Run it on a golden machine, fill in the entity, review the values, then
+// compile, wrap into a CoRIM and sign it with the verifier's tooling before
+// loading it. Nothing is sent to TAS; the report data is random.
+//
+// No unsafe code.
+
+use crate::hooks::ErrorClass;
+use crate::request_id::uuid_v4;
+use crate::rng::rng;
+use crate::tee_evidence::tee_get_evidence;
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine};
+use rand::RngCore;
+use serde_json::{json, Value};
+
+/// Size of an AMD SEV-SNP attestation report.
+pub const SNP_REPORT_LEN: usize = 0x4a0;
+/// Offset of MEASUREMENT in an SEV-SNP report.
+const SNP_MEASUREMENT: usize = 0x90;
+/// Size of an SHA-384 measurement register.
+const SHA384_LEN: usize = 48;
+/// Offsets of MRTD and RTMR0 in a TDX TD report body.
+const TDX_MRTD: usize = 136;
+const TDX_RTMR0: usize = 328;
+/// Size of a TDX quote header.
+const TDX_HEADER_LEN: usize = 48;
+
+/// A named SHA-384 measurement.
+pub type Measurement = (&'static str, Vec<u8>);
+
+fn register(report: &[u8], offset: usize) -> Result<Vec<u8>> {
+    report
+        .get(offset..offset + SHA384_LEN)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| anyhow!("report is truncated"))
+}
+
+/// Offset of the TD report body in a TDX quote.
+pub fn tdx_body(quote: &[u8]) -> Result<usize> {
+    let version = quote
+        .get(..2)
+        .map(|v| u16::from_le_bytes([v[0], v[1]]))
+        .ok_or_else(|| anyhow!("TDX quote is truncated"))?;
+    // Version 5 quotes put a type and size descriptor before the body
+    match version {
+        4 => Ok(TDX_HEADER_LEN),
+        5 => Ok(TDX_HEADER_LEN + 6),
+        _ => Err(anyhow!("unsupported TDX quote version {}", version)),
+    }
+}
+
+/// Extract the launch measurements from a raw TEE report of `tee_type`.
+pub fn measurements(tee_type: &str, report: &[u8]) -> Result<Vec<Measurement>> {
+    match tee_type {
+        "amd-sev-snp" => {
+            if report.len() < SNP_REPORT_LEN {
+                return Err(anyhow!("SEV-SNP report is truncated"));
+            }
+            Ok(vec![("MEASUREMENT", register(report, SNP_MEASUREMENT)?)])
+        }
+        "intel-tdx" => {
+            let body = tdx_body(report)?;
+            let mut values = vec![("MRTD", register(report, body + TDX_MRTD)?)];
+            for (i, name) in ["RTMR0", "RTMR1", "RTMR2", "RTMR3"].into_iter().enumerate() {
+                values.push((name, register(report, body + TDX_RTMR0 + i * SHA384_LEN)?));
+            }
+            Ok(values)
+        }
+        other => Err(anyhow!(
+            "no reference values defined for TEE type {}",
+            other
+        )),
+    }
+}
+
+/// Build the CoMID template for `measurements` of `tee_type`.
+pub fn comid(tee_type: &str, measurements: &[Measurement], tag_id: &str) -> Value {
+    let (vendor, model) = match tee_type {
+        "amd-sev-snp" => ("AMD", "SEV-SNP"),
+        "intel-tdx" => ("Intel", "TDX"),
+        other => ("", other),
+    };
+    let measurements: Vec<Value> = measurements
+        .iter()
+        .map(|(name, digest)| {
+            let digest = format!("sha-384;{}", general_purpose::STANDARD.encode(digest));
+            json!({
+                "key": {"type": "string", "value": name},
+                "value": {"digests": [digest]}
+            })
+        })
+        .collect();
+    json!({
+        "lang": "en-US",
+        "tag-identity": {"id": tag_id, "version": 0},
+        "entities": [{
+            "name": "CHANGEME",
+            "regid": "https://example.com",
+            "roles": ["tagCreator", "creator", "maintainer"]
+        }],
+        "triples": {
+            "reference-values": [{
+                "environment": {"class": {"vendor": vendor, "model": model}},
+                "measurements": measurements
+            }]
+        }
+    })
+}
+
+/// Collect a TEE report on this machine and return its CoMID template.
+pub fn export() -> Result<Value> {
+    // The report data is irrelevant to the measurements
+    let mut report_data = [0u8; 64];
+    rng().fill_bytes(&mut report_data);
+    let evidence = tee_get_evidence(&"0".repeat(64), Some(&report_data))
+        .map_err(|e| ErrorClass::Evidence.error(format!("TEE evidence Error: {}", e)))?;
+    let tee_type = evidence.tee_type.as_str();
+    let values = measurements(tee_type, &evidence.report)?;
+    let mut tag_id = [0u8; 16];
+    rng().fill_bytes(&mut tag_id);
+    Ok(comid(tee_type, &values, &uuid_v4(tag_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snp_measurements() {
+        let mut report = vec![0u8; SNP_REPORT_LEN];
+        report[SNP_MEASUREMENT..SNP_MEASUREMENT + SHA384_LEN].fill(0xab);
+        let values = measurements("amd-sev-snp", &report).unwrap();
+        assert_eq!(values, vec![("MEASUREMENT", vec![0xab; SHA384_LEN])]);
+        assert!(measurements("amd-sev-snp", &report[..0x100]).is_err());
+    }
+
+    #[test]
+    fn test_tdx_measurements() {
+        let mut quote = vec![0u8; 1024];
+        quote[0] = 4;
+        let body = TDX_HEADER_LEN;
+        quote[body + TDX_MRTD..body + TDX_MRTD + SHA384_LEN].fill(0x11);
+        let rtmr3 = body + TDX_RTMR0 + 3 * SHA384_LEN;
+        quote[rtmr3..rtmr3 + SHA384_LEN].fill(0x33);
+
+        let values = measurements("intel-tdx", &quote).unwrap();
+        let names: Vec<&str> = values.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["MRTD", "RTMR0", "RTMR1", "RTMR2", "RTMR3"]);
+        assert_eq!(values[0].1, vec![0x11; SHA384_LEN]);
+        assert_eq!(values[4].1, vec![0x33; SHA384_LEN]);
+
+        quote[0] = 3;
+        assert!(measurements("intel-tdx", &quote).is_err());
+        assert!(measurements("intel-tdx", &quote[..100]).is_err());
+    }
+
+    #[test]
+    fn test_comid() {
+        let comid = comid(
+            "amd-sev-snp",
+            &[("MEASUREMENT", vec![0; 3])],
+            "00000000-0000-4000-8000-000000000000",
+        );
+        let reference = &comid["triples"]["reference-values"][0];
+        assert_eq!(reference["environment"]["class"]["vendor"], "AMD");
+        assert_eq!(
+            reference["measurements"][0]["value"]["digests"][0],
+            "sha-384;AAAA"
+        );
+    }
+}
@@ -5,10 +5,21 @@
 //
 // This module provides the client application with the ability do cryptographic operations.
 
+use crate::rng::rng;
 use base64::Engine;
+use rand::RngCore;
+#[cfg(feature = "fips")]
+use rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey};
 use rsa::{
-    pkcs1::EncodeRsaPrivateKey, pkcs1::EncodeRsaPublicKey, sha2::Sha256, Oaep, RsaPrivateKey,
-    RsaPublicKey,
+    pkcs1::DecodeRsaPrivateKey,
+    pkcs1::EncodeRsaPrivateKey,
+    pkcs1::EncodeRsaPublicKey,
+    pkcs1v15::SigningKey,
+    pkcs8::EncodePublicKey,
+    sha2::Sha256,
+    signature::{SignatureEncoding, Signer},
+    traits::PublicKeyParts,
+    Oaep, RsaPrivateKey, RsaPublicKey,
 };
 
 use aes_gcm::{
@@ -18,6 +29,7 @@ use aes_gcm::{
 
 use aes_kw::KekAes256;
 
+use serde::Deserialize;
 use sha2::{Digest, Sha512};
 use std::error::Error;
 
@@ -55,21 +67,27 @@ impl std::fmt::Display for RsaKey {
 }
 impl RsaKey {
     /// Encrypt a message using the public key
-    #[allow(dead_code)]
-    fn encrypt(&self, message: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn encrypt(&self, message: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
         let padding = Oaep::new::<Sha256>();
-        let encrypted_message =
-            self.public_key
-                .encrypt(&mut rand::thread_rng(), padding, message)?;
+        let encrypted_message = self.public_key.encrypt(&mut rng(), padding, message)?;
         Ok(encrypted_message)
     }
 
     /// Decrypts a message using the private key
     #[allow(dead_code)]
     pub fn decrypt(&self, encrypted_message: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        let padding = Oaep::new::<Sha256>();
-        let decrypted_message = self.private_key.decrypt(padding, encrypted_message)?;
-        Ok(decrypted_message)
+        #[cfg(feature = "fips")]
+        return crate::fips::rsa_oaep_decrypt(
+            self.private_key.to_pkcs8_der()?.as_bytes(),
+            encrypted_message,
+        );
+        #[cfg(not(feature = "fips"))]
+        {
+            let padding = Oaep::new::<Sha256>();
+            let decrypted_message = self.private_key.decrypt(padding, encrypted_message)?;
+            Ok(decrypted_message)
+        }
     }
 
     /// Converts public key to DER format
@@ -81,6 +99,26 @@ impl RsaKey {
         Ok(der.to_vec())
     }
 
+    /// Converts public key to SubjectPublicKeyInfo DER format
+    pub fn public_key_to_spki_der(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let der = self
+            .public_key
+            .to_public_key_der()
+            .map_err(|e| format!("Failed to convert public key to SPKI DER: {}", e))?;
+        Ok(der.to_vec())
+    }
+
+    /// Signs a message with RSASSA-PKCS1-v1_5 over SHA-256
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        let signing_key = SigningKey::<Sha256>::new(self.private_key.clone());
+        signing_key.sign(message).to_vec()
+    }
+
+    /// Size of the modulus in bytes
+    pub fn size(&self) -> usize {
+        self.public_key.size()
+    }
+
     /// Encodes DER public key to base64
     pub fn public_key_to_base64(&self) -> Result<String, Box<dyn Error>> {
         let der = self.public_key_to_der()?;
@@ -88,6 +126,40 @@ impl RsaKey {
         Ok(base64)
     }
 
+    /// Encodes the public key for the wrapping-key field of a key release
+    /// request
+    pub fn public_key_encoded(
+        &self,
+        encoding: WrappingKeyEncoding,
+    ) -> Result<String, Box<dyn Error>> {
+        match encoding {
+            WrappingKeyEncoding::Base64Der => self.public_key_to_base64(),
+            WrappingKeyEncoding::Pem => Ok(self
+                .public_key
+                .to_pkcs1_pem(rsa::pkcs1::LineEnding::LF)
+                .map_err(|e| format!("Failed to convert public key to PEM: {}", e))?),
+        }
+    }
+
+    /// Encodes the private key as PKCS#1 DER, for keeping it sealed
+    pub fn private_key_to_der(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let der = self
+            .private_key
+            .to_pkcs1_der()
+            .map_err(|e| format!("Failed to convert private key to DER: {}", e))?;
+        Ok(der.as_bytes().to_vec())
+    }
+
+    /// Loads a key pair from a PKCS#1 DER private key
+    pub fn from_private_key_der(der: &[u8]) -> Result<RsaKey, Box<dyn Error>> {
+        let private_key = RsaPrivateKey::from_pkcs1_der(der)
+            .map_err(|e| format!("Failed to load private key from DER: {}", e))?;
+        Ok(RsaKey {
+            public_key: RsaPublicKey::from(&private_key),
+            private_key,
+        })
+    }
+
     /// Unwraps the secret's AES encryption key
     pub fn unwrap_key(&self, encrypted_key: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
         let decrypted_key = self.decrypt(encrypted_key)?;
@@ -95,15 +167,67 @@ impl RsaKey {
     }
 }
 
+/// Encoding of the public wrapping key in the get_secret request.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WrappingKeyEncoding {
+    /// Base64 of the PKCS#1 DER public key
+    #[default]
+    Base64Der,
+    /// PKCS#1 PEM public key, for key brokers that expect PEM
+    Pem,
+}
+
+impl WrappingKeyEncoding {
+    /// The name of the encoding, as in the config file and the TAS version
+    /// probe.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WrappingKeyEncoding::Base64Der => "base64-der",
+            WrappingKeyEncoding::Pem => "pem",
+        }
+    }
+
+    /// The encoding of an encoded public key.
+    pub fn of(encoded: &str) -> Self {
+        if encoded.starts_with("-----BEGIN") {
+            WrappingKeyEncoding::Pem
+        } else {
+            WrappingKeyEncoding::Base64Der
+        }
+    }
+
+    /// Agree on an encoding with a server that accepts the encodings of
+    /// `supported`. The `preferred` encoding is used if the server accepts
+    /// it, else the first one the server accepts; a server that lists
+    /// nothing predates the capability and gets the preferred encoding.
+    pub fn negotiate(preferred: Option<Self>, supported: &[String]) -> Self {
+        if supported.is_empty() {
+            return preferred.unwrap_or_default();
+        }
+        let accepted = |encoding: Self| supported.iter().any(|s| s == encoding.as_str());
+        match preferred {
+            Some(encoding) if accepted(encoding) => encoding,
+            _ => [WrappingKeyEncoding::Base64Der, WrappingKeyEncoding::Pem]
+                .into_iter()
+                .find(|&encoding| accepted(encoding))
+                .unwrap_or(preferred.unwrap_or_default()),
+        }
+    }
+}
+
 fn generate_key_pair(key_bits: usize) -> Result<(RsaPublicKey, RsaPrivateKey), Box<dyn Error>> {
-    let mut rng = rand::thread_rng();
     // Return error is key bits is not 2048 or 3072 or 4096
     if key_bits != 2048 && key_bits != 3072 && key_bits != 4096 {
         return Err("Key bits must be 2048, 3072 or 4096".into());
     }
 
     let bits = key_bits;
-    let private_key = RsaPrivateKey::new(&mut rng, bits)?;
+    #[cfg(not(feature = "fips"))]
+    let private_key = RsaPrivateKey::new(&mut rng(), bits)?;
+    // Generated inside the validated module
+    #[cfg(feature = "fips")]
+    let private_key = RsaPrivateKey::from_pkcs8_der(&crate::fips::generate_rsa_pkcs8(bits)?)?;
     let public_key = RsaPublicKey::from(&private_key);
     Ok((public_key, private_key))
 }
@@ -121,6 +245,7 @@ pub fn decrypt_secret_with_aes_key(
     iv: &[u8],
     ciphertext: &mut [u8],
     tag: &[u8],
+    aad: &[u8],
 ) -> Result<Vec<u8>, Box<dyn Error>> {
     // AES-256-GCM decryption
     // Check if the key length is 32 bytes (256 bits)
@@ -132,19 +257,34 @@ pub fn decrypt_secret_with_aes_key(
         return Err("AES-GCM IV length must be 12 bytes (96 bits)".into());
     }
 
-    let cipher = Aes256Gcm::new_from_slice(aes_key)?;
-    let nonce = Nonce::from_slice(iv);
-    cipher
-        .decrypt_in_place_detached(nonce, b"", ciphertext, tag.into())
-        .map_err(|e| format!("Decryption error: {:?}", e))?;
+    gcm_open(aes_key, iv, aad, ciphertext, tag)?;
     Ok(ciphertext.to_vec())
 }
 
+/// AES-256-GCM decryption of `buf` in place with a detached tag.
+#[cfg(not(feature = "fips"))]
+fn gcm_open(
+    aes_key: &[u8],
+    nonce: &[u8],
+    aad: &[u8],
+    buf: &mut [u8],
+    tag: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    Aes256Gcm::new_from_slice(aes_key)?
+        .decrypt_in_place_detached(Nonce::from_slice(nonce), aad, buf, tag.into())
+        .map_err(|e| format!("Decryption error: {:?}", e))?;
+    Ok(())
+}
+
+#[cfg(feature = "fips")]
+use crate::fips::aes_gcm_open as gcm_open;
+
 #[allow(dead_code)]
 pub fn encrypt_secret_with_aes_key(
     aes_key: &[u8],
     iv: &[u8],
     plaintext: &mut [u8],
+    aad: &[u8],
 ) -> Result<(Vec<u8>, Vec<u8>), Box<dyn Error>> {
     // AES-256-GCM encryption
     // Check if the key length is 32 bytes (256 bits)
@@ -158,14 +298,134 @@ pub fn encrypt_secret_with_aes_key(
     let cipher = Aes256Gcm::new_from_slice(aes_key)?;
     let nonce = Nonce::from_slice(iv);
     let tag = cipher
-        .encrypt_in_place_detached(nonce, b"", plaintext)
+        .encrypt_in_place_detached(nonce, aad, plaintext)
         .map_err(|e| format!("Encryption error: {:?}", e))?;
 
     Ok((plaintext.to_vec(), tag.to_vec()))
 }
 
+/// Plaintext bytes per chunk of an "AES-GCM-STREAM" payload; every chunk but
+/// the last is exactly this long.
+pub const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Length of the nonce prefix carried in the `iv` field of an
+/// "AES-GCM-STREAM" payload.
+pub const STREAM_PREFIX_SIZE: usize = 7;
+
+const GCM_TAG_SIZE: usize = 16;
+
+/// Nonce of chunk `index` of a chunked payload: the 7-byte prefix, the
+/// big-endian chunk index and a final-chunk flag (the STREAM construction),
+/// so chunks cannot be reordered, dropped or truncated undetected.
+fn stream_nonce(prefix: &[u8], index: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..STREAM_PREFIX_SIZE].copy_from_slice(prefix);
+    nonce[STREAM_PREFIX_SIZE..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+fn check_stream_params(aes_key: &[u8], prefix: &[u8]) -> Result<(), Box<dyn Error>> {
+    if aes_key.len() != 32 {
+        return Err("AES key length must be 32 bytes (256 bits)".into());
+    }
+    if prefix.len() != STREAM_PREFIX_SIZE {
+        return Err(format!(
+            "AES-GCM-STREAM nonce prefix must be {} bytes",
+            STREAM_PREFIX_SIZE
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Decrypt a chunked AES-256-GCM payload in place.
+///
+/// `blob` is a sequence of chunks, each the ciphertext of up to
+/// `STREAM_CHUNK_SIZE` plaintext bytes followed by its 16-byte tag. Chunks
+/// are authenticated and decrypted one at a time and the plaintext compacted
+/// to the front of `blob`, which is truncated to the plaintext length, so
+/// ciphertext and plaintext never occupy separate buffers. Every chunk is
+/// authenticated together with `aad`. On error `blob` may hold partially
+/// decrypted data and the caller must discard it.
+pub fn decrypt_chunked_in_place(
+    aes_key: &[u8],
+    prefix: &[u8],
+    blob: &mut Vec<u8>,
+    aad: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    check_stream_params(aes_key, prefix)?;
+    let mut read = 0;
+    let mut written = 0;
+    let mut index: u32 = 0;
+    loop {
+        let len = (blob.len() - read).min(STREAM_CHUNK_SIZE + GCM_TAG_SIZE);
+        if len < GCM_TAG_SIZE {
+            return Err("AES-GCM-STREAM payload truncated".into());
+        }
+        let last = read + len == blob.len();
+        let end = read + len - GCM_TAG_SIZE;
+        let mut tag = [0u8; GCM_TAG_SIZE];
+        tag.copy_from_slice(&blob[end..read + len]);
+        let nonce = stream_nonce(prefix, index, last);
+        gcm_open(aes_key, &nonce, aad, &mut blob[read..end], &tag)
+            .map_err(|e| format!("{} in chunk {}", e, index))?;
+        blob.copy_within(read..end, written);
+        written += end - read;
+        read += len;
+        if last {
+            break;
+        }
+        index = index
+            .checked_add(1)
+            .ok_or("AES-GCM-STREAM payload has too many chunks")?;
+    }
+    blob.truncate(written);
+    Ok(())
+}
+
+/// Encrypt `plaintext` into the chunked framing read by
+/// `decrypt_chunked_in_place`.
+#[allow(dead_code)]
+pub fn encrypt_chunked(
+    aes_key: &[u8],
+    prefix: &[u8],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    check_stream_params(aes_key, prefix)?;
+    let cipher = Aes256Gcm::new_from_slice(aes_key)?;
+    let chunks = plaintext.len().div_ceil(STREAM_CHUNK_SIZE).max(1);
+    let mut blob = Vec::with_capacity(plaintext.len() + chunks * GCM_TAG_SIZE);
+    for index in 0..chunks {
+        let start = index * STREAM_CHUNK_SIZE;
+        let end = (start + STREAM_CHUNK_SIZE).min(plaintext.len());
+        let offset = blob.len();
+        blob.extend_from_slice(&plaintext[start..end]);
+        let index = u32::try_from(index).map_err(|_| "plaintext too large")?;
+        let nonce = stream_nonce(prefix, index, end == plaintext.len());
+        let tag = cipher
+            .encrypt_in_place_detached(Nonce::from_slice(&nonce), aad, &mut blob[offset..])
+            .map_err(|e| format!("Encryption error: {:?}", e))?;
+        blob.extend_from_slice(tag.as_slice());
+    }
+    Ok(blob)
+}
+
+/// Hex SHA-256 of the base64 TEE evidence sent to TAS, the alternative to
+/// the nonce as the authenticated data of a secret payload.
+pub fn evidence_hash(tee_evidence: &str) -> String {
+    hex::encode(sha2::Sha256::digest(tee_evidence.as_bytes()))
+}
+
+/// Whether `aad`, the authenticated data of a secret payload, binds it to
+/// this attestation: it is either the request nonce or the `evidence_hash`
+/// of the evidence sent with it.
+pub fn payload_bound_to(aad: &[u8], nonce: &str, tee_evidence: &str) -> bool {
+    aad == nonce.trim_matches('"').as_bytes() || aad == evidence_hash(tee_evidence).as_bytes()
+}
+
 /// Wrap a secret using AES Key Wrapping with Padding (RFC 5649)
-#[cfg_attr(not(test), allow(dead_code))]
 pub(crate) fn wrap_secret_with_aes_key_wrap(
     aes_key: &[u8],
     secret: &[u8],
@@ -206,26 +466,78 @@ pub fn unwrap_secret_with_aes_key_wrap(
         return Err(format!("AES key must be 32 bytes, got {} bytes", aes_key.len()).into());
     }
 
-    let key_array: [u8; 32] = aes_key
-        .try_into()
-        .map_err(|_| "Failed to convert AES key to 32-byte array")?;
-
-    let kek = KekAes256::from(key_array);
-
     if wrapped_secret.len() < 16 {
         return Err("Wrapped secret too short for AES Key Wrap with Padding".into());
     }
 
-    let max_unwrapped_size = wrapped_secret.len() - 8;
-    let mut unwrapped_buffer = vec![0u8; max_unwrapped_size];
+    #[cfg(feature = "fips")]
+    return crate::fips::aes_kwp_unwrap(aes_key, wrapped_secret);
 
-    let unwrapped_slice = kek
-        .unwrap_with_padding(wrapped_secret, &mut unwrapped_buffer)
-        .map_err(|e| -> Box<dyn Error> {
-            format!("AES Key Wrap unwrapping failed: {:?}", e).into()
-        })?;
+    #[cfg(not(feature = "fips"))]
+    {
+        let key_array: [u8; 32] = aes_key
+            .try_into()
+            .map_err(|_| "Failed to convert AES key to 32-byte array")?;
+
+        let kek = KekAes256::from(key_array);
+
+        let max_unwrapped_size = wrapped_secret.len() - 8;
+        let mut unwrapped_buffer = vec![0u8; max_unwrapped_size];
+
+        let unwrapped_slice = kek
+            .unwrap_with_padding(wrapped_secret, &mut unwrapped_buffer)
+            .map_err(|e| -> Box<dyn Error> {
+                format!("AES Key Wrap unwrapping failed: {:?}", e).into()
+            })?;
+
+        Ok(unwrapped_slice.to_vec())
+    }
+}
 
-    Ok(unwrapped_slice.to_vec())
+/// Length of the nonce as bound into REPORT_DATA.
+pub const NONCE_LEN: usize = 64;
+
+/// Nonce construction: SHA-512 of a nonce that is not 64 bytes long.
+pub const NONCE_SHA512: &str = "sha512";
+
+/// The `NONCE_LEN` bytes standing for `nonce` in the key binding, and the
+/// construction used, if any. Nonces of other lengths are rejected unless
+/// `hash` is set, in which case they are replaced by their SHA-512.
+pub fn nonce_input(nonce: &[u8], hash: bool) -> Result<(Vec<u8>, Option<&'static str>), String> {
+    if nonce.len() == NONCE_LEN {
+        return Ok((nonce.to_vec(), None));
+    }
+    if !hash {
+        return Err(format!(
+            "nonce is {} bytes long, expected {}; set hash_nonce = true to accept it",
+            nonce.len(),
+            NONCE_LEN
+        ));
+    }
+    Ok((Sha512::digest(nonce).to_vec(), Some(NONCE_SHA512)))
+}
+
+/// Number of local random bytes mixed into the nonce.
+pub const LOCAL_ENTROPY_LEN: usize = 32;
+
+/// Nonce construction: SHA-512 of the nonce followed by local random bytes.
+pub const NONCE_LOCAL_ENTROPY: &str = "sha512-local-entropy";
+
+/// Fresh random bytes to mix into the nonce with `mix_local_entropy`.
+pub fn local_entropy() -> [u8; LOCAL_ENTROPY_LEN] {
+    let mut entropy = [0u8; LOCAL_ENTROPY_LEN];
+    rng().fill_bytes(&mut entropy);
+    entropy
+}
+
+/// SHA-512(nonce || entropy), standing for a nonce of any length in the key
+/// binding, so that the evidence is fresh even if the server's nonces are
+/// not.
+pub fn mix_local_entropy(nonce: &[u8], entropy: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha512::new();
+    hasher.update(nonce);
+    hasher.update(entropy);
+    hasher.finalize().to_vec()
 }
 
 /// Computes SHA-512(nonce || pubkey_der) for CPU-only key binding.
@@ -239,10 +551,9 @@ pub fn compute_report_data_binding(nonce: &[u8], pubkey_der: &[u8]) -> Vec<u8> {
 
 /// Computes SHA-512(nonce || pubkey_der || component_hashes) for composable attestation.
 /// `component_hashes` is the concatenated SHA-512 hashes of each component's evidence,
-/// ordered by device index within each category.
+/// ordered by device index within each category, followed by the evidence signing
+/// key, if any (see custody.rs).
 /// Returns raw 64-byte hash.
-// Any component feature
-#[cfg(feature = "gpu-nvidia")]
 pub fn compute_report_data_binding_with_components(
     nonce: &[u8],
     pubkey_der: &[u8],
@@ -268,15 +579,8 @@ pub fn hash_evidence(evidence: &[u8]) -> Vec<u8> {
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn test_rsa_decryption() {
-        let rsa_key = generate_wrapping_key().unwrap();
-        let message = b"Hello, world!";
-        let encrypted_message = rsa_key.encrypt(message).unwrap();
-        let decrypted_message = rsa_key.decrypt(&encrypted_message).unwrap();
-        assert_eq!(message.to_vec(), decrypted_message);
-    }
+    use proptest::prelude::*;
+    use std::sync::OnceLock;
 
     #[test]
     fn test_compute_report_data_binding_length() {
@@ -291,6 +595,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_nonce_input() {
+        let nonce = [b'n'; 64];
+        assert_eq!(nonce_input(&nonce, false).unwrap(), (nonce.to_vec(), None));
+        assert_eq!(nonce_input(&nonce, true).unwrap(), (nonce.to_vec(), None));
+
+        let short = b"0123456789abcdef0123456789abcdef";
+        let err = nonce_input(short, false).unwrap_err();
+        assert!(err.contains("32 bytes"), "{}", err);
+        let (hashed, construction) = nonce_input(short, true).unwrap();
+        assert_eq!(construction, Some(NONCE_SHA512));
+        assert_eq!(hashed, Sha512::digest(short).to_vec());
+        assert_eq!(hashed.len(), NONCE_LEN);
+    }
+
+    #[test]
+    fn test_mix_local_entropy() {
+        let short = b"0123456789abcdef";
+        let entropy = local_entropy();
+        assert_ne!(entropy, local_entropy());
+        let mixed = mix_local_entropy(short, &entropy);
+        assert_eq!(mixed.len(), NONCE_LEN);
+        assert_eq!(
+            mixed,
+            Sha512::digest([&short[..], &entropy].concat()).to_vec()
+        );
+        assert_ne!(mixed, mix_local_entropy(short, &local_entropy()));
+    }
+
     #[test]
     fn test_compute_report_data_binding_deterministic() {
         let nonce = b"0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
@@ -319,18 +652,6 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_aes_decryption() {
-        let aes_key = [0u8; 32]; // 256-bit key
-        let iv = [0u8; 12]; // 96-bit IV (nonce) for AES-GCM
-        let plaintext = b"Hello, world!".to_vec();
-        let (mut ciphertext, tag) =
-            encrypt_secret_with_aes_key(&aes_key, &iv, &mut plaintext.clone()).unwrap();
-        let decrypted_data =
-            decrypt_secret_with_aes_key(&aes_key, &iv, &mut ciphertext, &tag).unwrap();
-        assert_eq!(b"Hello, world!".to_vec(), decrypted_data);
-    }
-
     // --- public_key_to_der tests ---
 
     #[test]
@@ -366,6 +687,19 @@ mod tests {
         assert_eq!(unwrapped, aes_key.to_vec());
     }
 
+    #[test]
+    fn test_sign_verifies() {
+        use rsa::pkcs1v15::{Signature, VerifyingKey};
+        use rsa::signature::Verifier;
+
+        let rsa_key = generate_wrapping_key().unwrap();
+        let signature = rsa_key.sign(b"claims");
+        let signature = Signature::try_from(signature.as_slice()).unwrap();
+        let verifying_key = VerifyingKey::<Sha256>::new(rsa_key.public_key.clone());
+        assert!(verifying_key.verify(b"claims", &signature).is_ok());
+        assert!(verifying_key.verify(b"other", &signature).is_err());
+    }
+
     // --- generate_key_pair with different sizes ---
 
     #[test]
@@ -390,7 +724,7 @@ mod tests {
         let iv = [0u8; 12];
         let mut ciphertext = vec![0u8; 16];
         let tag = [0u8; 16];
-        let result = decrypt_secret_with_aes_key(&bad_key, &iv, &mut ciphertext, &tag);
+        let result = decrypt_secret_with_aes_key(&bad_key, &iv, &mut ciphertext, &tag, b"");
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("32 bytes"));
     }
@@ -401,7 +735,7 @@ mod tests {
         let bad_iv = [0u8; 16]; // 128-bit, should be 96-bit
         let mut ciphertext = vec![0u8; 16];
         let tag = [0u8; 16];
-        let result = decrypt_secret_with_aes_key(&key, &bad_iv, &mut ciphertext, &tag);
+        let result = decrypt_secret_with_aes_key(&key, &bad_iv, &mut ciphertext, &tag, b"");
         assert!(result.is_err());
     }
 
@@ -410,7 +744,7 @@ mod tests {
         let bad_key = [0u8; 16];
         let iv = [0u8; 12];
         let mut plaintext = b"test data".to_vec();
-        let result = encrypt_secret_with_aes_key(&bad_key, &iv, &mut plaintext);
+        let result = encrypt_secret_with_aes_key(&bad_key, &iv, &mut plaintext, b"");
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("32 bytes"));
     }
@@ -420,10 +754,107 @@ mod tests {
         let key = [0u8; 32];
         let bad_iv = [0u8; 16];
         let mut plaintext = b"test data".to_vec();
-        let result = encrypt_secret_with_aes_key(&key, &bad_iv, &mut plaintext);
+        let result = encrypt_secret_with_aes_key(&key, &bad_iv, &mut plaintext, b"");
         assert!(result.is_err());
     }
 
+    // --- chunked AES-GCM ---
+
+    const STREAM_KEY: [u8; 32] = [7u8; 32];
+    const STREAM_PREFIX: [u8; STREAM_PREFIX_SIZE] = [1, 2, 3, 4, 5, 6, 7];
+
+    #[test]
+    fn test_chunked_roundtrip() {
+        for len in [
+            0,
+            1,
+            STREAM_CHUNK_SIZE - 1,
+            STREAM_CHUNK_SIZE,
+            STREAM_CHUNK_SIZE + 1,
+            3 * STREAM_CHUNK_SIZE,
+        ] {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let mut blob = encrypt_chunked(&STREAM_KEY, &STREAM_PREFIX, &plaintext, b"").unwrap();
+            let chunks = len.div_ceil(STREAM_CHUNK_SIZE).max(1);
+            assert_eq!(blob.len(), len + chunks * GCM_TAG_SIZE);
+            decrypt_chunked_in_place(&STREAM_KEY, &STREAM_PREFIX, &mut blob, b"").unwrap();
+            assert_eq!(blob, plaintext, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_chunked_rejects_dropped_last_chunk() {
+        let plaintext = vec![0x5a; 2 * STREAM_CHUNK_SIZE];
+        let mut blob = encrypt_chunked(&STREAM_KEY, &STREAM_PREFIX, &plaintext, b"").unwrap();
+        blob.truncate(STREAM_CHUNK_SIZE + GCM_TAG_SIZE);
+        assert!(decrypt_chunked_in_place(&STREAM_KEY, &STREAM_PREFIX, &mut blob, b"").is_err());
+    }
+
+    #[test]
+    fn test_chunked_rejects_reordered_chunks() {
+        let plaintext: Vec<u8> = (0..3 * STREAM_CHUNK_SIZE).map(|i| (i / 7) as u8).collect();
+        let mut blob = encrypt_chunked(&STREAM_KEY, &STREAM_PREFIX, &plaintext, b"").unwrap();
+        let chunk = STREAM_CHUNK_SIZE + GCM_TAG_SIZE;
+        let (first, rest) = blob.split_at_mut(chunk);
+        first.swap_with_slice(&mut rest[..chunk]);
+        assert!(decrypt_chunked_in_place(&STREAM_KEY, &STREAM_PREFIX, &mut blob, b"").is_err());
+    }
+
+    #[test]
+    fn test_chunked_rejects_bad_input() {
+        let mut blob = encrypt_chunked(&STREAM_KEY, &STREAM_PREFIX, b"secret", b"").unwrap();
+        let mut wrong_prefix = STREAM_PREFIX;
+        wrong_prefix[0] ^= 1;
+        assert!(
+            decrypt_chunked_in_place(&STREAM_KEY, &wrong_prefix, &mut blob.clone(), b"").is_err()
+        );
+        assert!(decrypt_chunked_in_place(&STREAM_KEY, &[0u8; 12], &mut blob.clone(), b"").is_err());
+        blob.truncate(GCM_TAG_SIZE - 1);
+        assert!(decrypt_chunked_in_place(&STREAM_KEY, &STREAM_PREFIX, &mut blob, b"").is_err());
+    }
+
+    #[test]
+    fn test_gcm_aad_mismatch_rejected() {
+        let key = [0x11u8; 32];
+        let iv = [0x22u8; 12];
+        let (mut ciphertext, tag) =
+            encrypt_secret_with_aes_key(&key, &iv, &mut b"secret".to_vec(), b"n0nce").unwrap();
+        assert!(
+            decrypt_secret_with_aes_key(&key, &iv, &mut ciphertext.clone(), &tag, b"other")
+                .is_err()
+        );
+        assert!(
+            decrypt_secret_with_aes_key(&key, &iv, &mut ciphertext.clone(), &tag, b"").is_err()
+        );
+        let plaintext = decrypt_secret_with_aes_key(&key, &iv, &mut ciphertext, &tag, b"n0nce");
+        assert_eq!(plaintext.unwrap(), b"secret");
+
+        let mut blob = encrypt_chunked(&STREAM_KEY, &STREAM_PREFIX, b"secret", b"n0nce").unwrap();
+        assert!(
+            decrypt_chunked_in_place(&STREAM_KEY, &STREAM_PREFIX, &mut blob.clone(), b"").is_err()
+        );
+        decrypt_chunked_in_place(&STREAM_KEY, &STREAM_PREFIX, &mut blob, b"n0nce").unwrap();
+        assert_eq!(blob, b"secret");
+    }
+
+    #[test]
+    fn test_payload_bound_to() {
+        let evidence = "ZXZpZGVuY2U=";
+        assert!(payload_bound_to(b"n0nce", "\"n0nce\"", evidence));
+        assert!(payload_bound_to(
+            evidence_hash(evidence).as_bytes(),
+            "n0nce",
+            evidence
+        ));
+        assert!(!payload_bound_to(b"other", "n0nce", evidence));
+        assert!(!payload_bound_to(
+            evidence_hash("b3RoZXI=").as_bytes(),
+            "n0nce",
+            evidence
+        ));
+        assert!(!payload_bound_to(b"", "n0nce", evidence));
+    }
+
     // --- compute_report_data_binding edge cases ---
 
     #[test]
@@ -557,4 +988,238 @@ mod tests {
         let wrapped = wrap_secret_with_aes_key_wrap(&key1, b"sensitive_data").unwrap();
         assert!(unwrap_secret_with_aes_key_wrap(&key2, &wrapped).is_err());
     }
+
+    // --- golden encodings ---
+
+    /// The test-only wrapping key in testdata/golden.
+    fn golden_key() -> RsaKey {
+        let pem = std::fs::read_to_string(crate::golden::path("wrapping-key.pem")).unwrap();
+        let private_key = RsaPrivateKey::from_pkcs1_pem(&pem).unwrap();
+        RsaKey {
+            public_key: RsaPublicKey::from(&private_key),
+            private_key,
+        }
+    }
+
+    #[test]
+    fn test_public_key_encoded() {
+        let key = golden_key();
+        let base64 = key
+            .public_key_encoded(WrappingKeyEncoding::Base64Der)
+            .unwrap();
+        assert_eq!(base64, key.public_key_to_base64().unwrap());
+        assert_eq!(
+            WrappingKeyEncoding::of(&base64),
+            WrappingKeyEncoding::Base64Der
+        );
+
+        let pem = key.public_key_encoded(WrappingKeyEncoding::Pem).unwrap();
+        assert!(
+            pem.starts_with("-----BEGIN RSA PUBLIC KEY-----\n"),
+            "{}",
+            pem
+        );
+        assert_eq!(WrappingKeyEncoding::of(&pem), WrappingKeyEncoding::Pem);
+        let body: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+        assert_eq!(body, base64);
+    }
+
+    #[test]
+    fn test_negotiate_wrapping_key_encoding() {
+        use WrappingKeyEncoding::{Base64Der, Pem};
+        let list = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        // Servers predating the capability
+        assert_eq!(WrappingKeyEncoding::negotiate(None, &[]), Base64Der);
+        assert_eq!(WrappingKeyEncoding::negotiate(Some(Pem), &[]), Pem);
+
+        let both = list(&["pem", "base64-der"]);
+        assert_eq!(WrappingKeyEncoding::negotiate(None, &both), Base64Der);
+        assert_eq!(WrappingKeyEncoding::negotiate(Some(Pem), &both), Pem);
+
+        let pem_only = list(&["pem"]);
+        assert_eq!(WrappingKeyEncoding::negotiate(None, &pem_only), Pem);
+        assert_eq!(
+            WrappingKeyEncoding::negotiate(Some(Base64Der), &pem_only),
+            Pem
+        );
+
+        // Nothing in common: keep the preference and let the server decide
+        let unknown = list(&["jwk"]);
+        assert_eq!(WrappingKeyEncoding::negotiate(Some(Pem), &unknown), Pem);
+    }
+
+    #[test]
+    fn test_wrapping_key_encoding_golden() {
+        let key = golden_key();
+        let wrapping_key = key.public_key_to_base64().unwrap();
+        crate::golden::assert_golden("wrapping-key.b64", &wrapping_key);
+
+        let nonce = b"0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let binding = compute_report_data_binding(nonce, &key.public_key_to_der().unwrap());
+        crate::golden::assert_golden("report-data.hex", &hex::encode(binding));
+    }
+
+    #[test]
+    fn test_private_key_der_roundtrip() {
+        let key = golden_key();
+        let der = key.private_key_to_der().unwrap();
+        let loaded = RsaKey::from_private_key_der(&der).unwrap();
+        assert_eq!(
+            loaded.public_key_to_der().unwrap(),
+            key.public_key_to_der().unwrap()
+        );
+        let encrypted = key.encrypt(b"secret").unwrap();
+        assert_eq!(loaded.unwrap_key(&encrypted).unwrap(), b"secret");
+        assert!(RsaKey::from_private_key_der(&der[1..]).is_err());
+    }
+
+    // --- property-based round trips ---
+
+    /// Supported RSA key sizes, in bits.
+    const KEY_BITS: [usize; 3] = [2048, 3072, 4096];
+
+    /// One key per supported size, generated on first use: RSA key
+    /// generation would otherwise dominate the run time.
+    fn rsa_key(bits: usize) -> &'static RsaKey {
+        static KEYS: [OnceLock<RsaKey>; 3] = [OnceLock::new(), OnceLock::new(), OnceLock::new()];
+        let slot = KEY_BITS.iter().position(|&b| b == bits).unwrap();
+        KEYS[slot].get_or_init(|| {
+            let (public_key, private_key) = generate_key_pair(bits).unwrap();
+            RsaKey {
+                public_key,
+                private_key,
+            }
+        })
+    }
+
+    /// Largest message RSA-OAEP with SHA-256 encrypts under a `bits` key.
+    fn oaep_capacity(bits: usize) -> usize {
+        bits / 8 - 2 * 32 - 2
+    }
+
+    /// A key size and a message that fits it.
+    fn rsa_message() -> impl Strategy<Value = (usize, Vec<u8>)> {
+        prop::sample::select(KEY_BITS.to_vec()).prop_flat_map(|bits| {
+            (
+                Just(bits),
+                prop::collection::vec(any::<u8>(), 0..=oaep_capacity(bits)),
+            )
+        })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn prop_rsa_oaep_round_trip((bits, message) in rsa_message()) {
+            let key = rsa_key(bits);
+            prop_assert_eq!(key.size(), bits / 8);
+            let encrypted = key.encrypt(&message).unwrap();
+            prop_assert_eq!(encrypted.len(), bits / 8);
+            prop_assert_eq!(key.decrypt(&encrypted).unwrap(), message.clone());
+            prop_assert_eq!(key.unwrap_key(&encrypted).unwrap(), message);
+        }
+
+        #[test]
+        fn prop_rsa_oaep_rejects_oversized_message(
+            bits in prop::sample::select(KEY_BITS.to_vec()),
+            extra in 1..64usize,
+        ) {
+            let message = vec![0x5Au8; oaep_capacity(bits) + extra];
+            prop_assert!(rsa_key(bits).encrypt(&message).is_err());
+        }
+
+        #[test]
+        fn prop_rsa_oaep_rejects_corrupted_ciphertext(
+            (bits, message) in rsa_message(),
+            index in any::<prop::sample::Index>(),
+            flip in 1..=255u8,
+        ) {
+            let key = rsa_key(bits);
+            let mut encrypted = key.encrypt(&message).unwrap();
+            let index = index.index(encrypted.len());
+            encrypted[index] ^= flip;
+            prop_assert!(key.decrypt(&encrypted).is_err());
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_aes_gcm_round_trip(
+            key in any::<[u8; 32]>(),
+            iv in any::<[u8; 12]>(),
+            plaintext in prop::collection::vec(any::<u8>(), 0..4096),
+        ) {
+            let (mut ciphertext, tag) =
+                encrypt_secret_with_aes_key(&key, &iv, &mut plaintext.clone(), b"").unwrap();
+            prop_assert_eq!(ciphertext.len(), plaintext.len());
+            prop_assert_eq!(tag.len(), 16);
+            let decrypted = decrypt_secret_with_aes_key(&key, &iv, &mut ciphertext, &tag, b"").unwrap();
+            prop_assert_eq!(decrypted, plaintext);
+        }
+
+        #[test]
+        fn prop_aes_gcm_rejects_corrupted_tag(
+            key in any::<[u8; 32]>(),
+            iv in any::<[u8; 12]>(),
+            plaintext in prop::collection::vec(any::<u8>(), 0..1024),
+            index in 0..16usize,
+            flip in 1..=255u8,
+        ) {
+            let (mut ciphertext, mut tag) =
+                encrypt_secret_with_aes_key(&key, &iv, &mut plaintext.clone(), b"").unwrap();
+            tag[index] ^= flip;
+            prop_assert!(decrypt_secret_with_aes_key(&key, &iv, &mut ciphertext, &tag, b"").is_err());
+        }
+
+        #[test]
+        fn prop_aes_gcm_rejects_corrupted_ciphertext(
+            key in any::<[u8; 32]>(),
+            iv in any::<[u8; 12]>(),
+            plaintext in prop::collection::vec(any::<u8>(), 1..1024),
+            index in any::<prop::sample::Index>(),
+            flip in 1..=255u8,
+        ) {
+            let (mut ciphertext, tag) =
+                encrypt_secret_with_aes_key(&key, &iv, &mut plaintext.clone(), b"").unwrap();
+            let index = index.index(ciphertext.len());
+            ciphertext[index] ^= flip;
+            prop_assert!(decrypt_secret_with_aes_key(&key, &iv, &mut ciphertext, &tag, b"").is_err());
+        }
+
+        #[test]
+        fn prop_aes_kw_round_trip(
+            key in any::<[u8; 32]>(),
+            secret in prop::collection::vec(any::<u8>(), 1..1024),
+        ) {
+            let wrapped = wrap_secret_with_aes_key_wrap(&key, &secret).unwrap();
+            prop_assert_eq!(wrapped.len(), secret.len().div_ceil(8) * 8 + 8);
+            prop_assert_eq!(unwrap_secret_with_aes_key_wrap(&key, &wrapped).unwrap(), secret);
+        }
+
+        #[test]
+        fn prop_aes_kw_rejects_corruption(
+            key in any::<[u8; 32]>(),
+            secret in prop::collection::vec(any::<u8>(), 1..1024),
+            index in any::<prop::sample::Index>(),
+            flip in 1..=255u8,
+        ) {
+            let mut wrapped = wrap_secret_with_aes_key_wrap(&key, &secret).unwrap();
+            let index = index.index(wrapped.len());
+            wrapped[index] ^= flip;
+            prop_assert!(unwrap_secret_with_aes_key_wrap(&key, &wrapped).is_err());
+        }
+
+        #[test]
+        fn prop_aes_kw_rejects_wrong_key(
+            key in any::<[u8; 32]>(),
+            other in any::<[u8; 32]>(),
+            secret in prop::collection::vec(any::<u8>(), 1..1024),
+        ) {
+            prop_assume!(key != other);
+            let wrapped = wrap_secret_with_aes_key_wrap(&key, &secret).unwrap();
+            prop_assert!(unwrap_secret_with_aes_key_wrap(&other, &wrapped).is_err());
+        }
+    }
 }
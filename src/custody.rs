@@ -0,0 +1,175 @@
+// TEE Attestation Service Agent — chain of custody for evidence
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// The TEE report covers only its report data. Everything else sent with it
+// (GPU evidence, event logs inside the evidence blob, the verifier token)
+// could be modified by a relay between the agent and the verifier, such as
+// the connected host of an offline bundle. With an `[evidence_signing]`
+// section the agent signs the whole request with a key whose public half is
+// bound into the report data:
+//
+//   [evidence_signing]
+//   key = "generated"   # a fresh P-256 key per attestation (ES256), or
+//                       # "tpm" for the TPM signing key (RS256)
+//
+// The report data binding gains the SubjectPublicKeyInfo DER of the key as
+// its last input:
+//
+//   SHA-512(nonce || pubkey_der [|| gpu_hashes] || signing_key_spki)
+//
+// and the get_secret request carries
+//
+//   "evidence-signature": {"alg": "ES256" | "RS256",
+//                          "key": "<base64 SPKI DER>",
+//                          "signature": "<base64>"}
+//
+// The signature is over the request body without its `evidence-signature`
+// and `policy-id` members, serialized as compact JSON with the members of
+// every object in lexicographic order. ES256 signatures are r || s.
+//
+// No unsafe code.
+
+use crate::rng::rng;
+use crate::tas_api::{EvidenceSignature, SecretRequest};
+use crate::tpm;
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use p256::ecdsa::{self, signature::Signer as _};
+use p256::pkcs8::EncodePublicKey;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Key the evidence is signed with.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CustodyKey {
+    /// A P-256 key generated for each attestation
+    #[default]
+    Generated,
+    /// The TPM's owner-hierarchy signing primary
+    Tpm,
+}
+
+/// `[evidence_signing]` section of the config file.
+#[derive(Deserialize, Clone, Default)]
+pub struct EvidenceSigningConfig {
+    /// Key to sign the evidence with (default: generated)
+    #[serde(default)]
+    pub key: CustodyKey,
+}
+
+enum Key {
+    Generated(ecdsa::SigningKey),
+    Tpm,
+}
+
+/// The key signing one attestation's evidence.
+pub struct Signer {
+    key: Key,
+    /// SubjectPublicKeyInfo DER of the key
+    public_key: Vec<u8>,
+}
+
+impl Signer {
+    pub fn new(config: &EvidenceSigningConfig) -> Result<Self> {
+        match config.key {
+            CustodyKey::Generated => {
+                let key = ecdsa::SigningKey::random(&mut rng());
+                let public_key = key
+                    .verifying_key()
+                    .to_public_key_der()
+                    .map_err(|e| anyhow!("unable to encode signing key: {}", e))?
+                    .as_bytes()
+                    .to_vec();
+                Ok(Signer {
+                    key: Key::Generated(key),
+                    public_key,
+                })
+            }
+            CustodyKey::Tpm => Ok(Signer {
+                key: Key::Tpm,
+                public_key: tpm::signing_key().context("unable to load TPM signing key")?,
+            }),
+        }
+    }
+
+    /// SubjectPublicKeyInfo DER of the key, as bound into the report data.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Sign `request`, whose `policy_id` is ignored.
+    pub fn sign(&self, request: &SecretRequest) -> Result<EvidenceSignature> {
+        let message = signed_message(request)?;
+        let (alg, signature) = match &self.key {
+            Key::Generated(key) => {
+                let signature: ecdsa::Signature = key.sign(&message);
+                ("ES256", signature.to_bytes().to_vec())
+            }
+            Key::Tpm => (
+                "RS256",
+                tpm::sign(&message).context("unable to sign with TPM key")?,
+            ),
+        };
+        Ok(EvidenceSignature {
+            alg: alg.to_string(),
+            key: STANDARD.encode(&self.public_key),
+            signature: STANDARD.encode(signature),
+        })
+    }
+}
+
+/// The bytes signed for `request`.
+fn signed_message(request: &SecretRequest) -> Result<Vec<u8>> {
+    // Value objects keep their members sorted
+    let mut body = serde_json::to_value(request)?;
+    let members = body
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("request is not a JSON object"))?;
+    members.remove("evidence-signature");
+    members.remove("policy-id");
+    Ok(serde_json::to_vec(&Value::Object(members.clone()))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_message() {
+        let components = serde_json::json!({"gpu": [{"evidence": "Z3B1", "device_index": 0}]});
+        let request = SecretRequest {
+            nonce: "\"abc\"",
+            tee_evidence: "cmVwb3J0",
+            tee_type: "amd-sev-snp",
+            policy_id: "policy-1",
+            wrapping_key: "a2V5",
+            report_data_binding: true,
+            component_evidence: Some(&components),
+            ..Default::default()
+        };
+        let message = signed_message(&request).unwrap();
+        assert_eq!(
+            String::from_utf8(message).unwrap(),
+            r#"{"component-evidence":{"gpu":[{"device_index":0,"evidence":"Z3B1"}]},"nonce":"\"abc\"","report-data-binding":true,"tee-evidence":"cmVwb3J0","tee-type":"amd-sev-snp","wrapping-key":"a2V5"}"#
+        );
+
+        // The policy ID is not signed
+        let other = SecretRequest {
+            nonce: "\"abc\"",
+            tee_evidence: "cmVwb3J0",
+            tee_type: "amd-sev-snp",
+            policy_id: "policy-2",
+            wrapping_key: "a2V5",
+            report_data_binding: true,
+            component_evidence: Some(&components),
+            ..Default::default()
+        };
+        assert_eq!(
+            signed_message(&other).unwrap(),
+            signed_message(&request).unwrap()
+        );
+    }
+}
@@ -0,0 +1,1139 @@
+// TEE Attestation Service Agent — long-running daemon
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Keeps the agent resident and re-attests periodically so consumers always
+// have a fresh secret available, instead of the one-shot run-and-exit model.
+//
+// Each configured policy ID is released on start-up and then on the
+// schedule (see schedule.rs), on SIGUSR1, and on demand for the local API
+// and gRPC clients (see local_api.rs and grpc.rs). Releases run concurrently
+// and share recent attestations; failures back off (see streak.rs). The
+// latest secrets are published to `secrets_dir`, and SIGHUP reloads the
+// config. See README "With Daemon Mode" for the details.
+//
+// No unsafe code.
+
+use crate::audit;
+use crate::hardening;
+use crate::health::{self, Health};
+use crate::hooks::{self, ErrorClass, HooksConfig};
+use crate::kbs::ResourceUri;
+use crate::key_pool::{self, KeyPoolConfig};
+use crate::local_api::{self, Access, AccessControl, ApiState};
+use crate::output;
+use crate::privsep;
+use crate::request_id;
+use crate::schedule::{ScheduleConfig, Scheduler, RESUME_CHECK_PERIOD};
+use crate::sd_notify;
+use crate::state::StateFile;
+use crate::streak::Alert;
+use crate::tas_api;
+use crate::telemetry;
+use crate::{Attestation, CliOverrides};
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{Mutex, RwLock, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{
+    interval, interval_at, sleep_until, Duration, Instant, Interval, MissedTickBehavior,
+};
+use zeroize::Zeroize;
+
+/// Default number of seconds between re-attestations.
+const DEFAULT_INTERVAL_SECS: u64 = 3600;
+
+/// Default number of seconds an attestation is shared between key releases.
+const DEFAULT_NONCE_WINDOW_SECS: u64 = 30;
+
+/// Default number of seconds between connection warm-up probes.
+const DEFAULT_WARM_UP_SECS: u64 = 60;
+
+/// `[daemon]` section of the config file.
+#[derive(Deserialize, Default, Clone)]
+pub struct DaemonConfig {
+    /// Seconds between re-attestations (default: 3600)
+    pub interval_secs: Option<u64>,
+    /// Policy IDs to keep fresh secrets for (default: the top-level policy_id)
+    pub policy_ids: Option<Vec<String>>,
+    /// Publish each secret as `<secrets_dir>/<policy_id>` (mode 0600)
+    pub secrets_dir: Option<PathBuf>,
+    /// Serve secrets to local processes on this unix socket
+    pub socket_path: Option<PathBuf>,
+    /// UIDs allowed to request secrets over the socket (default: [0])
+    pub allowed_uids: Option<Vec<u32>>,
+    /// GIDs allowed to request secrets over the socket
+    pub allowed_gids: Option<Vec<u32>>,
+    /// Seconds an attestation is reused for further key releases (default: 30,
+    /// 0 attests for every release)
+    pub nonce_window_secs: Option<u64>,
+    /// Seconds between TAS version probes keeping the connection warm
+    /// (default: 60, 0 disables them)
+    pub warm_up_secs: Option<u64>,
+    /// Re-attestation schedule profiles (default: every `interval_secs`)
+    pub schedules: Option<BTreeMap<String, ScheduleConfig>>,
+    /// Loopback address to serve /healthz and /readyz on
+    pub health_address: Option<std::net::SocketAddr>,
+    /// Persist non-sensitive state (release times, failures) to this file
+    pub state_file: Option<PathBuf>,
+    /// Longest back-off after repeated failed releases of a policy ID
+    /// (default: interval_secs)
+    pub max_backoff_secs: Option<u64>,
+    /// Run everything but evidence collection as this user
+    pub user: Option<String>,
+    /// Serve the gRPC API on this unix socket
+    #[cfg(feature = "grpc")]
+    pub grpc_socket: Option<PathBuf>,
+}
+
+/// A released secret, zeroized when dropped.
+struct CachedSecret(Vec<u8>);
+
+impl Drop for CachedSecret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Latest secret for each policy ID.
+#[derive(Default)]
+pub struct SecretStore {
+    secrets: RwLock<HashMap<String, CachedSecret>>,
+}
+
+impl SecretStore {
+    /// Replace the secret held for `policy_id`.
+    pub async fn insert(&self, policy_id: &str, value: Vec<u8>) {
+        self.secrets
+            .write()
+            .await
+            .insert(policy_id.to_string(), CachedSecret(value));
+    }
+
+    /// Return a copy of the secret held for `policy_id`, if any.
+    pub async fn get(&self, policy_id: &str) -> Option<Vec<u8>> {
+        self.secrets
+            .read()
+            .await
+            .get(policy_id)
+            .map(|s| s.0.clone())
+    }
+
+    /// Drop the secret held for `policy_id`, if any.
+    async fn remove(&self, policy_id: &str) {
+        self.secrets.write().await.remove(policy_id);
+    }
+}
+
+/// Releases secrets for the refresh loop and local API clients.
+pub struct Releaser {
+    config_path: Option<PathBuf>,
+    overrides: CliOverrides,
+    store: Arc<SecretStore>,
+    /// Configured policy IDs and the nonce window, updated on reload.
+    policy: std::sync::RwLock<(Vec<String>, Duration)>,
+    /// The attestation shared by releases within the nonce window.
+    attestation: Mutex<Option<(Instant, Arc<Attestation>)>>,
+    /// Serialises releases per policy ID.
+    key_locks: std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>,
+    /// Programs run after each release, updated on reload.
+    hooks: std::sync::RwLock<Option<HooksConfig>>,
+    /// Release bookkeeping persisted across restarts.
+    state: Option<StateFile>,
+    /// Longest back-off after failed releases, updated on reload.
+    max_backoff: std::sync::RwLock<Duration>,
+    /// Outcome of the latest release.
+    pub health: Health,
+}
+
+impl Releaser {
+    pub fn new(
+        config_path: Option<PathBuf>,
+        overrides: CliOverrides,
+        store: Arc<SecretStore>,
+        policy_ids: Vec<String>,
+        nonce_window: Duration,
+    ) -> Self {
+        Releaser {
+            config_path,
+            overrides,
+            store,
+            policy: std::sync::RwLock::new((policy_ids, nonce_window)),
+            attestation: Mutex::new(None),
+            key_locks: std::sync::Mutex::new(HashMap::new()),
+            hooks: std::sync::RwLock::new(None),
+            state: None,
+            max_backoff: std::sync::RwLock::new(Duration::from_secs(DEFAULT_INTERVAL_SECS)),
+            health: Health::default(),
+        }
+    }
+
+    /// Record every release in `state`.
+    fn with_state_file(mut self, state: StateFile) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Apply reloaded settings.
+    fn reconfigure(&self, policy_ids: Vec<String>, nonce_window: Duration) {
+        if let Ok(mut policy) = self.policy.write() {
+            *policy = (policy_ids, nonce_window);
+        }
+    }
+
+    /// Replace the release hooks.
+    fn set_hooks(&self, hooks: Option<HooksConfig>) {
+        if let Ok(mut current) = self.hooks.write() {
+            *current = hooks;
+        }
+    }
+
+    /// Replace the longest back-off after failed releases.
+    fn set_max_backoff(&self, max_backoff: Duration) {
+        if let Ok(mut current) = self.max_backoff.write() {
+            *current = max_backoff;
+        }
+    }
+
+    fn is_configured(&self, policy_id: &str) -> bool {
+        self.policy
+            .read()
+            .is_ok_and(|policy| policy.0.iter().any(|id| id == policy_id))
+    }
+
+    /// The configured policy IDs, in config order.
+    pub fn policy_ids(&self) -> Vec<String> {
+        self.policy
+            .read()
+            .map(|policy| policy.0.clone())
+            .unwrap_or_default()
+    }
+
+    fn nonce_window(&self) -> Duration {
+        self.policy
+            .read()
+            .map(|policy| policy.1)
+            .unwrap_or_default()
+    }
+
+    fn key_lock(&self, policy_id: &str) -> Arc<Mutex<()>> {
+        let mut locks = self.key_locks.lock().unwrap_or_else(|e| e.into_inner());
+        locks.entry(policy_id.to_string()).or_default().clone()
+    }
+
+    /// Drop the cached attestation so the next release attests afresh.
+    pub async fn invalidate(&self) {
+        *self.attestation.lock().await = None;
+    }
+
+    /// Return the current attestation, attesting again once the nonce window
+    /// has passed. Concurrent callers wait for a single attestation.
+    async fn attestation(&self) -> Result<Arc<Attestation>> {
+        let mut current = self.attestation.lock().await;
+        if let Some((started, attestation)) = current.as_ref() {
+            let age = started.elapsed();
+            if age < self.nonce_window() {
+                debug!("Reusing attestation from {}s ago", age.as_secs());
+                return Ok(attestation.clone());
+            }
+        }
+
+        let started = Instant::now();
+        let mut session =
+            crate::resolve_session(self.config_path.clone(), Some(self.overrides.clone()))?;
+        // The probe only settles the wrapping key; without it the release
+        // goes ahead with the defaults, as it did before servers advertised
+        let (version, preparation) =
+            tokio::join!(session.cached_server_version(), crate::prepare(&session));
+        match version {
+            Ok(version) => session.negotiate(&version),
+            Err(e) => debug!("Unable to probe the TAS server version: {}", e),
+        }
+        let attestation = Arc::new(crate::attest_prepared(session, preparation?).await?);
+        *current = Some((started, attestation.clone()));
+        Ok(attestation)
+    }
+
+    /// Check that TAS is reachable with the current configuration.
+    pub async fn probe_tas(&self) -> Result<String> {
+        let session =
+            crate::resolve_session(self.config_path.clone(), Some(self.overrides.clone()))?;
+        session
+            .server_version()
+            .await
+            .map(|version| version.version)
+            .map_err(|e| anyhow!(e))
+    }
+
+    /// Release the secret for `policy_id`, store it, record the outcome and
+    /// run the matching hook.
+    async fn release(&self, policy_id: &str) -> Result<Vec<u8>> {
+        request_id::scope(self.release_attempt(policy_id)).await
+    }
+
+    /// One release of `policy_id`, under its own request ID.
+    async fn release_attempt(&self, policy_id: &str) -> Result<Vec<u8>> {
+        let (result, tee_type) = self.try_release(policy_id).await;
+        self.health.record(&result);
+        self.alert(policy_id, &result);
+        if let Some(state) = &self.state {
+            state.record(policy_id, &result);
+        }
+        let hooks = self.hooks.read().ok().and_then(|hooks| hooks.clone());
+        hooks::run(hooks.as_ref(), policy_id, tee_type.as_deref(), &result).await;
+        result
+    }
+
+    /// Track the failure streak of `policy_id` and log the outcome of a
+    /// release once per streak rather than for every attempt.
+    fn alert<T>(&self, policy_id: &str, result: &Result<T>) {
+        let max_backoff = self.max_backoff.read().map(|max| *max).unwrap_or_default();
+        let err = match result {
+            Ok(_) => {
+                if let Alert::Recovered(failures) =
+                    self.health.streaks.record(policy_id, result, max_backoff)
+                {
+                    info!(
+                        "Release of {} succeeded after {} failure(s)",
+                        policy_id, failures
+                    );
+                }
+                return;
+            }
+            Err(e) => e,
+        };
+        match self.health.streaks.record(policy_id, result, max_backoff) {
+            Alert::Failed | Alert::Changed => {
+                warn!("Release failed for {}: {:#}", policy_id, err)
+            }
+            Alert::Escalated => {
+                let (failures, _, wait) =
+                    self.health.streaks.hold_off(policy_id).unwrap_or_default();
+                error!(
+                    "Release of {} has failed {} times in a row, retrying in {}s: {:#}",
+                    policy_id,
+                    failures,
+                    wait.as_secs(),
+                    err
+                );
+            }
+            _ => debug!("Release failed again for {}: {:#}", policy_id, err),
+        }
+    }
+
+    /// Release the secret for `policy_id`, also returning the TEE type if
+    /// evidence was collected.
+    async fn try_release(&self, policy_id: &str) -> (Result<Vec<u8>>, Option<String>) {
+        let attestation = match self.attestation().await {
+            Ok(attestation) => attestation,
+            Err(e) => {
+                let session =
+                    crate::resolve_session(self.config_path.clone(), Some(self.overrides.clone()));
+                if let Some(reporter) = session.ok().as_ref().and_then(telemetry::Reporter::new) {
+                    reporter.report(&e, None, None).await;
+                }
+                return (Err(e), None);
+            }
+        };
+        let tee_type = Some(attestation.tee_type().to_string());
+        match crate::release(&attestation, policy_id).await {
+            Ok(key) => {
+                self.store.insert(policy_id, key.clone()).await;
+                (Ok(key), tee_type)
+            }
+            Err(e) => {
+                if let Some(reporter) = telemetry::Reporter::new(&attestation.session) {
+                    let evidence = Some(attestation.tee_evidence.as_str());
+                    reporter
+                        .report(&e, Some(&attestation.tee_type), evidence)
+                        .await;
+                }
+                // TAS may not accept the nonce again; attest afresh next time.
+                let mut current = self.attestation.lock().await;
+                if matches!(current.as_ref(), Some((_, a)) if Arc::ptr_eq(a, &attestation)) {
+                    *current = None;
+                }
+                (Err(e), tee_type)
+            }
+        }
+    }
+
+    /// Release a fresh secret for `policy_id`, replacing the stored one.
+    async fn refresh(&self, policy_id: &str) -> Result<Vec<u8>> {
+        let lock = self.key_lock(policy_id);
+        let _guard = lock.lock().await;
+        self.release(policy_id).await
+    }
+
+    /// Return the stored secret for `policy_id`, releasing it first if the
+    /// daemon has none yet. With `force`, the stored secret and the cached
+    /// attestation are bypassed. Returns Ok(None) for unconfigured policy IDs.
+    pub async fn get_or_release(&self, policy_id: &str, force: bool) -> Result<Option<Vec<u8>>> {
+        if !self.is_configured(policy_id) {
+            return Ok(None);
+        }
+        // Don't add to the load of a failing server, forced or not
+        if let Some((failures, last_error, wait)) = self.health.streaks.hold_off(policy_id) {
+            if force || self.store.get(policy_id).await.is_none() {
+                return Err(anyhow!(
+                    "{} ({} failed release(s), retrying in {}s)",
+                    last_error,
+                    failures,
+                    wait.as_secs()
+                ));
+            }
+        }
+        if force {
+            self.invalidate().await;
+            return self.refresh(policy_id).await.map(Some);
+        }
+        if let Some(secret) = self.store.get(policy_id).await {
+            return Ok(Some(secret));
+        }
+
+        let lock = self.key_lock(policy_id);
+        let _guard = lock.lock().await;
+        // A concurrent request may have released it while we waited.
+        if let Some(secret) = self.store.get(policy_id).await {
+            return Ok(Some(secret));
+        }
+        self.release(policy_id).await.map(Some)
+    }
+}
+
+/// Daemon settings derived from the config file, re-read on SIGHUP.
+struct Settings {
+    policy_ids: Vec<String>,
+    secrets_dir: Option<PathBuf>,
+    period: Duration,
+    scheduler: Scheduler,
+    nonce_window: Duration,
+    max_parallel_releases: usize,
+    key_pool: Option<KeyPoolConfig>,
+    warm_up: Option<Duration>,
+    socket_path: Option<PathBuf>,
+    access: Access,
+    health_address: Option<std::net::SocketAddr>,
+    state_file: Option<PathBuf>,
+    max_backoff: Duration,
+    user: Option<String>,
+    hooks: Option<HooksConfig>,
+    #[cfg(feature = "grpc")]
+    grpc_socket: Option<PathBuf>,
+}
+
+/// Load and validate the daemon settings.
+fn load_settings(config_path: &Option<PathBuf>, overrides: &CliOverrides) -> Result<Settings> {
+    let cfg = crate::load_config(config_path.clone())?;
+    let max_parallel_releases = cfg.max_parallel_releases();
+    let daemon_cfg = cfg.daemon.unwrap_or_default();
+
+    let policy_ids = match daemon_cfg.policy_ids {
+        Some(ids) if !ids.is_empty() => ids,
+        _ => vec![overrides
+            .policy_id
+            .clone()
+            .or(cfg.policy_id)
+            .ok_or_else(|| ErrorClass::Config.error("server policy ID is required"))?],
+    };
+    for policy_id in &policy_ids {
+        validate_policy_id(policy_id)?;
+    }
+
+    let secrets_dir = daemon_cfg.secrets_dir;
+    if let Some(dir) = &secrets_dir {
+        std::fs::create_dir_all(dir).with_context(|| format!("unable to create {:?}", dir))?;
+    }
+
+    let period = Duration::from_secs(
+        daemon_cfg
+            .interval_secs
+            .unwrap_or(DEFAULT_INTERVAL_SECS)
+            .max(1),
+    );
+
+    let scheduler = Scheduler::new(
+        &daemon_cfg.schedules.unwrap_or_default(),
+        &policy_ids,
+        period,
+    )?;
+
+    let nonce_window = Duration::from_secs(
+        daemon_cfg
+            .nonce_window_secs
+            .unwrap_or(DEFAULT_NONCE_WINDOW_SECS),
+    );
+
+    Ok(Settings {
+        policy_ids,
+        secrets_dir,
+        period,
+        scheduler,
+        nonce_window,
+        max_parallel_releases,
+        key_pool: cfg.key_pool,
+        warm_up: match daemon_cfg.warm_up_secs.unwrap_or(DEFAULT_WARM_UP_SECS) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        },
+        socket_path: daemon_cfg.socket_path,
+        access: Access {
+            allowed_uids: daemon_cfg.allowed_uids.unwrap_or_else(|| vec![0]),
+            allowed_gids: daemon_cfg.allowed_gids.unwrap_or_default(),
+        },
+        health_address: daemon_cfg.health_address,
+        state_file: daemon_cfg.state_file,
+        max_backoff: daemon_cfg
+            .max_backoff_secs
+            .map_or(period, Duration::from_secs),
+        user: daemon_cfg.user,
+        hooks: cfg.hooks,
+        #[cfg(feature = "grpc")]
+        grpc_socket: daemon_cfg.grpc_socket,
+    })
+}
+
+/// Policy IDs in `old` that are no longer in `new`.
+fn removed_policy_ids(old: &[String], new: &[String]) -> Vec<String> {
+    old.iter().filter(|id| !new.contains(id)).cloned().collect()
+}
+
+/// Switch from `current` to the reloaded settings `new`, withdrawing secrets
+/// that are no longer configured or published elsewhere.
+async fn apply_reload(
+    current: &mut Settings,
+    mut new: Settings,
+    store: &SecretStore,
+    releaser: &Releaser,
+    access: &AccessControl,
+) {
+    let removed = removed_policy_ids(&current.policy_ids, &new.policy_ids);
+    for policy_id in &removed {
+        info!("Policy ID {} removed from configuration", policy_id);
+        store.remove(policy_id).await;
+    }
+    // Secrets still configured are republished on the next refresh.
+    if current.secrets_dir != new.secrets_dir {
+        unpublish_all(&current.policy_ids, current.secrets_dir.as_deref());
+    } else {
+        unpublish_all(&removed, current.secrets_dir.as_deref());
+    }
+
+    if new.socket_path != current.socket_path {
+        warn!(
+            "Changing socket_path requires a restart; keeping {:?}",
+            current.socket_path
+        );
+        new.socket_path = current.socket_path.take();
+    }
+    if new.health_address != current.health_address {
+        warn!(
+            "Changing health_address requires a restart; keeping {:?}",
+            current.health_address
+        );
+        new.health_address = current.health_address;
+    }
+    if new.state_file != current.state_file {
+        warn!(
+            "Changing state_file requires a restart; keeping {:?}",
+            current.state_file
+        );
+        new.state_file = current.state_file.take();
+    }
+    if new.user != current.user {
+        warn!(
+            "Changing user requires a restart; keeping {:?}",
+            current.user
+        );
+        new.user = current.user.take();
+    }
+    #[cfg(feature = "grpc")]
+    if new.grpc_socket != current.grpc_socket {
+        warn!(
+            "Changing grpc_socket requires a restart; keeping {:?}",
+            current.grpc_socket
+        );
+        new.grpc_socket = current.grpc_socket.take();
+    }
+    releaser.reconfigure(new.policy_ids.clone(), new.nonce_window);
+    releaser.set_hooks(new.hooks.clone());
+    releaser.set_max_backoff(new.max_backoff);
+    releaser.health.streaks.retain(&new.policy_ids);
+    access.set(new.access.clone());
+
+    *current = new;
+}
+
+/// Reject policy IDs that cannot safely be used as a file name. KBS resource
+/// URIs are accepted and published under their resource path.
+fn validate_policy_id(policy_id: &str) -> Result<()> {
+    if ResourceUri::parse(policy_id)?.is_some() {
+        return Ok(());
+    }
+    if policy_id.is_empty()
+        || policy_id == "."
+        || policy_id == ".."
+        || policy_id.contains('/')
+        || policy_id.contains('\0')
+    {
+        return Err(anyhow!("invalid policy ID {:?}", policy_id));
+    }
+    Ok(())
+}
+
+/// Where the secret for `policy_id` is published in `dir`.
+fn secret_path(dir: &Path, policy_id: &str) -> PathBuf {
+    match ResourceUri::parse(policy_id) {
+        Ok(Some(uri)) => dir.join(uri.path()),
+        _ => dir.join(policy_id),
+    }
+}
+
+/// Release the secret for every policy ID concurrently, at most `parallelism`
+/// at a time, and update the store and the published files. Policy IDs
+/// backing off after failures are skipped unless `force` is set, which also
+/// discards the cached attestation. Returns the number of policy IDs
+/// refreshed.
+async fn refresh_all(
+    releaser: &Arc<Releaser>,
+    policy_ids: &[String],
+    secrets_dir: Option<&Path>,
+    force: bool,
+    parallelism: usize,
+) -> usize {
+    if force {
+        releaser.invalidate().await;
+    }
+    let permits = Arc::new(Semaphore::new(parallelism));
+    let mut tasks = JoinSet::new();
+    for policy_id in policy_ids {
+        if !force {
+            if let Some((failures, _, wait)) = releaser.health.streaks.hold_off(policy_id) {
+                debug!(
+                    "Skipping {} after {} failure(s), retrying in {}s",
+                    policy_id,
+                    failures,
+                    wait.as_secs()
+                );
+                continue;
+            }
+        }
+        let releaser = releaser.clone();
+        let permits = permits.clone();
+        let policy_id = policy_id.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let result = releaser.refresh(&policy_id).await;
+            (policy_id, result)
+        });
+    }
+
+    let mut refreshed = 0;
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((policy_id, Ok(mut key))) => {
+                if let Some(dir) = secrets_dir {
+                    if let Err(e) = publish(&secret_path(dir, &policy_id), &key) {
+                        warn!("Failed to publish secret for {}: {:#}", policy_id, e);
+                    }
+                }
+                key.zeroize();
+                refreshed += 1;
+                info!("Re-attested and refreshed secret for {}", policy_id);
+            }
+            // Alerted by the releaser, once per failure streak
+            Ok((policy_id, Err(e))) => {
+                debug!("Re-attestation failed for {}: {:#}", policy_id, e)
+            }
+            // The panic hook has reported it; the message may hold key material
+            Err(e) if e.is_panic() => warn!("Re-attestation task panicked"),
+            Err(e) => warn!("Re-attestation task failed: {}", e),
+        }
+    }
+    refreshed
+}
+
+/// Timer for connection warm-up probes, first firing one `period` from now.
+fn warm_up_interval(period: Option<Duration>) -> Interval {
+    let period = period.unwrap_or(Duration::from_secs(DEFAULT_WARM_UP_SECS));
+    let mut timer = interval_at(Instant::now() + period, period);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    timer
+}
+
+/// Probe TAS in the background, keeping the pooled connection and TLS
+/// session warm and noticing an unreachable server before a secret is needed.
+fn warm_up(releaser: &Arc<Releaser>) {
+    let releaser = releaser.clone();
+    tokio::spawn(async move {
+        let reachable = match releaser.probe_tas().await {
+            Ok(version) => {
+                debug!("TAS connection warm (version {})", version);
+                true
+            }
+            Err(e) => {
+                warn!("TAS connectivity check failed: {:#}", e);
+                false
+            }
+        };
+        releaser.health.record_probe(reachable).await;
+    });
+}
+
+/// Refill the wrapping key pool in the background, ready for the next
+/// round of releases.
+fn top_up(pool: KeyPoolConfig) {
+    tokio::task::spawn_blocking(move || match key_pool::fill(&pool) {
+        Ok(0) => {}
+        Ok(added) => debug!("Added {} wrapping key(s) to the pool", added),
+        Err(e) => warn!("Unable to refill the wrapping key pool: {:#}", e),
+    });
+}
+
+/// Notify systemd of a state change, logging rather than failing on errors.
+fn notify(state: &str) {
+    if let Err(e) = sd_notify::notify(state) {
+        warn!("sd_notify failed: {:#}", e);
+    }
+}
+
+/// Write a secret file, creating the directories of a KBS resource path.
+fn publish(path: &Path, key: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("unable to create {:?}", parent))?;
+    }
+    output::write_secret_file(path, key)
+}
+
+/// Overwrite and remove all secrets published in `secrets_dir`.
+fn unpublish_all(policy_ids: &[String], secrets_dir: Option<&Path>) {
+    let Some(dir) = secrets_dir else {
+        return;
+    };
+    for policy_id in policy_ids {
+        let path = secret_path(dir, policy_id);
+        if path.exists() {
+            if let Err(e) = output::shred_file(&path) {
+                warn!("Failed to remove {:?}: {:#}", path, e);
+            }
+        }
+    }
+}
+
+/// Main daemon loop. Runs until SIGTERM or SIGINT.
+pub async fn run_daemon(config_path: Option<PathBuf>, overrides: CliOverrides) -> Result<()> {
+    // The daemon process gets its config from the evidence helper
+    let connected = privsep::connect()?;
+    if !connected {
+        // Only `user` is needed here: the directories the daemon writes to
+        // are created by the daemon process, so that it owns them
+        let cfg = crate::load_config(config_path.clone())?;
+        if let Some(user) = cfg.daemon.and_then(|daemon| daemon.user) {
+            return privsep::run_helper(&user, config_path).await;
+        }
+    }
+    let mut settings = load_settings(&config_path, &overrides)?;
+    // Secrets stay in memory for the daemon's lifetime
+    if !hardening::core_dumps_disabled() {
+        return Err(anyhow!(
+            "core dumps could not be disabled; refusing to hold secrets in a dumpable process"
+        ));
+    }
+
+    // Keep connections to TAS open between releases
+    tas_api::share_connections();
+
+    let mut sigterm =
+        signal(SignalKind::terminate()).context("failed to register SIGTERM handler")?;
+    let mut sigint =
+        signal(SignalKind::interrupt()).context("failed to register SIGINT handler")?;
+    let mut sigusr1 =
+        signal(SignalKind::user_defined1()).context("failed to register SIGUSR1 handler")?;
+    let mut sighup = signal(SignalKind::hangup()).context("failed to register SIGHUP handler")?;
+
+    info!(
+        "TAS Agent: daemon started for {} policy ID(s)",
+        settings.policy_ids.len()
+    );
+
+    let store = Arc::new(SecretStore::default());
+    let mut releaser = Releaser::new(
+        config_path.clone(),
+        overrides.clone(),
+        store.clone(),
+        settings.policy_ids.clone(),
+        settings.nonce_window,
+    );
+    // Don't retry straight away if the previous run was failing.
+    let mut hold_off = None;
+    if let Some(path) = &settings.state_file {
+        let state_file = StateFile::load(path.clone());
+        hold_off =
+            state_file
+                .state()
+                .hold_off(&settings.policy_ids, settings.max_backoff, Utc::now());
+        releaser = releaser.with_state_file(state_file);
+    }
+    let releaser = Arc::new(releaser);
+    releaser.set_hooks(settings.hooks.clone());
+    releaser.set_max_backoff(settings.max_backoff);
+
+    let access = Arc::new(AccessControl::new(settings.access.clone()));
+    let api_server = match &settings.socket_path {
+        Some(path) => {
+            let listener = local_api::bind(path)?;
+            let state = Arc::new(ApiState::new(releaser.clone(), access.clone()));
+            info!("Serving secrets on {:?}", path);
+            Some(tokio::spawn(local_api::serve(listener, state)))
+        }
+        None => None,
+    };
+
+    let health_server = settings.health_address.map(|addr| {
+        let releaser = releaser.clone();
+        tokio::spawn(async move {
+            if let Err(e) = health::serve(addr, releaser).await {
+                warn!("{:#}", e);
+            }
+        })
+    });
+
+    #[cfg(feature = "grpc")]
+    let grpc_server = match &settings.grpc_socket {
+        Some(path) => {
+            let listener = crate::grpc::bind(path)?;
+            let store = store.clone();
+            let access = access.clone();
+            Some(tokio::spawn(async move {
+                if let Err(e) = crate::grpc::serve(listener, store, access).await {
+                    warn!("{:#}", e);
+                }
+            }))
+        }
+        None => None,
+    };
+
+    let mut resume_check = interval(RESUME_CHECK_PERIOD);
+    resume_check.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    // The watchdog is fed from this loop, so it stops if a refresh hangs.
+    let watchdog_period = sd_notify::watchdog_interval();
+    let mut watchdog = interval(watchdog_period.unwrap_or(settings.period));
+    if let Some(p) = watchdog_period {
+        debug!("systemd watchdog enabled, keepalive every {:?}", p);
+    }
+    let mut ready = false;
+    let mut warm_up_timer = warm_up_interval(settings.warm_up);
+
+    // Attest everything on start-up, then as scheduled.
+    let mut startup = hold_off.map(|wait| {
+        info!(
+            "Previous releases failed, waiting {}s before attesting",
+            wait.as_secs()
+        );
+        Instant::now() + wait
+    });
+    let mut pending = match startup {
+        Some(_) => None,
+        None => Some((settings.policy_ids.clone(), false)),
+    };
+
+    loop {
+        let (policy_ids, force) = match pending.take() {
+            Some(pending) => pending,
+            None => {
+                let deadline = settings.scheduler.next_deadline();
+                let retry = releaser.health.streaks.next_retry();
+                tokio::select! {
+                    _ = sigterm.recv() => {
+                        info!("Received SIGTERM, exiting cleanly");
+                        break;
+                    }
+                    _ = sigint.recv() => {
+                        info!("Received SIGINT, exiting cleanly");
+                        break;
+                    }
+                    _ = watchdog.tick(), if watchdog_period.is_some() => {
+                        notify("WATCHDOG=1");
+                        continue;
+                    }
+                    _ = warm_up_timer.tick(), if settings.warm_up.is_some() => {
+                        warm_up(&releaser);
+                        continue;
+                    }
+                    _ = sighup.recv() => {
+                        info!("Received SIGHUP, reloading configuration");
+                        notify("RELOADING=1");
+                        let reloaded = privsep::reload_config()
+                            .and_then(|()| load_settings(&config_path, &overrides));
+                        match reloaded {
+                            Ok(new) => {
+                                apply_reload(&mut settings, new, &store, &releaser, &access).await;
+                                warm_up_timer = warm_up_interval(settings.warm_up);
+                                audit::record("daemon-reload", &[("result", "ok")]);
+                                if ready {
+                                    notify("READY=1");
+                                }
+                                // The cached attestation holds the old endpoint and API key.
+                                (settings.policy_ids.clone(), true)
+                            }
+                            Err(e) => {
+                                warn!(
+                                    "Configuration reload failed, keeping previous settings: {:#}",
+                                    e
+                                );
+                                audit::record("daemon-reload", &[("result", "rejected")]);
+                                if ready {
+                                    notify("READY=1");
+                                }
+                                continue;
+                            }
+                        }
+                    }
+                    _ = sigusr1.recv() => {
+                        info!("Re-attestation requested");
+                        (settings.policy_ids.clone(), true)
+                    }
+                    _ = sleep_until(startup.unwrap_or_else(Instant::now)), if startup.is_some() => {
+                        startup = None;
+                        (settings.policy_ids.clone(), false)
+                    }
+                    _ = sleep_until(retry.unwrap_or_else(Instant::now)), if retry.is_some() => {
+                        let due = releaser.health.streaks.take_due(Instant::now());
+                        if due.is_empty() {
+                            continue;
+                        }
+                        debug!("Retrying failed releases");
+                        (due, false)
+                    }
+                    _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                        let due = settings.scheduler.take_due(Instant::now());
+                        if due.is_empty() {
+                            continue;
+                        }
+                        debug!("Scheduled re-attestation");
+                        (due, false)
+                    }
+                    _ = resume_check.tick(), if settings.scheduler.watches_resume() => {
+                        let due = settings.scheduler.check_resume();
+                        if due.is_empty() {
+                            continue;
+                        }
+                        (due, true)
+                    }
+                }
+            }
+        };
+
+        let refreshed = refresh_all(
+            &releaser,
+            &policy_ids,
+            settings.secrets_dir.as_deref(),
+            force,
+            settings.max_parallel_releases,
+        )
+        .await;
+        notify(&format!(
+            "STATUS=Secrets refreshed for {}/{} policy ID(s)",
+            refreshed,
+            policy_ids.len()
+        ));
+        if !ready && refreshed > 0 {
+            notify("READY=1");
+            ready = true;
+        }
+        if let Some(pool) = &settings.key_pool {
+            top_up(pool.clone());
+        }
+    }
+
+    notify("STOPPING=1");
+
+    if let Some(server) = api_server {
+        server.abort();
+    }
+    if let Some(health_server) = health_server {
+        health_server.abort();
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_server) = grpc_server {
+        grpc_server.abort();
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(path) = &settings.grpc_socket {
+        let _ = std::fs::remove_file(path);
+    }
+    if let Some(path) = &settings.socket_path {
+        let _ = std::fs::remove_file(path);
+    }
+    unpublish_all(&settings.policy_ids, settings.secrets_dir.as_deref());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_policy_id() {
+        assert!(validate_policy_id("luks-root").is_ok());
+        assert!(validate_policy_id("").is_err());
+        assert!(validate_policy_id("..").is_err());
+        assert!(validate_policy_id("a/b").is_err());
+        assert!(validate_policy_id("kbs:///default/key/luks").is_ok());
+        assert!(validate_policy_id("kbs:///default/../luks").is_err());
+    }
+
+    #[test]
+    fn test_secret_path() {
+        let dir = Path::new("/run/tas_agent");
+        assert_eq!(
+            secret_path(dir, "luks-root"),
+            PathBuf::from("/run/tas_agent/luks-root")
+        );
+        assert_eq!(
+            secret_path(dir, "kbs:///default/key/luks"),
+            PathBuf::from("/run/tas_agent/default/key/luks")
+        );
+    }
+
+    #[test]
+    fn test_daemon_config_parse() {
+        let cfg: DaemonConfig = toml::from_str(
+            r#"
+            interval_secs = 60
+            policy_ids = ["a", "b"]
+            secrets_dir = "/run/tas_agent"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cfg.interval_secs, Some(60));
+        assert_eq!(cfg.policy_ids.unwrap(), vec!["a", "b"]);
+        assert_eq!(cfg.secrets_dir, Some(PathBuf::from("/run/tas_agent")));
+    }
+
+    #[tokio::test]
+    async fn test_secret_store_replaces_value() {
+        let store = SecretStore::default();
+        assert!(store.get("k").await.is_none());
+
+        store.insert("k", b"first".to_vec()).await;
+        store.insert("k", b"second".to_vec()).await;
+        assert_eq!(store.get("k").await.unwrap(), b"second");
+    }
+
+    #[test]
+    fn test_removed_policy_ids() {
+        let old = vec!["a".to_string(), "b".to_string()];
+        let new = vec!["b".to_string(), "c".to_string()];
+        assert_eq!(removed_policy_ids(&old, &new), vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn test_load_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config.toml");
+        std::fs::write(
+            &config,
+            r#"
+            policy_id = "fallback"
+            max_parallel_releases = 0
+            [daemon]
+            interval_secs = 0
+            warm_up_secs = 0
+            allowed_gids = [42]
+            "#,
+        )
+        .unwrap();
+
+        let settings = load_settings(&Some(config.clone()), &CliOverrides::default()).unwrap();
+        assert_eq!(settings.policy_ids, vec!["fallback"]);
+        assert_eq!(settings.period, Duration::from_secs(1));
+        assert_eq!(settings.max_parallel_releases, 1);
+        assert_eq!(settings.warm_up, None);
+        assert!(settings.scheduler.next_deadline().is_some());
+        assert_eq!(settings.access.allowed_uids, vec![0]);
+        assert_eq!(settings.access.allowed_gids, vec![42]);
+
+        std::fs::write(&config, "[daemon]\npolicy_ids = [\"../x\"]\n").unwrap();
+        assert!(load_settings(&Some(config.clone()), &CliOverrides::default()).is_err());
+
+        std::fs::write(
+            &config,
+            "policy_id = \"a\"\n[daemon.schedules.nightly]\ncron = \"0 2 * *\"\n",
+        )
+        .unwrap();
+        assert!(load_settings(&Some(config), &CliOverrides::default()).is_err());
+    }
+
+    fn releaser(store: Arc<SecretStore>) -> Releaser {
+        Releaser::new(
+            None,
+            CliOverrides::default(),
+            store,
+            vec!["luks".to_string()],
+            Duration::from_secs(30),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_get_or_release_uses_store() {
+        let store = Arc::new(SecretStore::default());
+        store.insert("luks", b"secret".to_vec()).await;
+        let releaser = releaser(store.clone());
+
+        let secret = releaser.get_or_release("luks", false).await.unwrap();
+        assert_eq!(secret.unwrap(), b"secret");
+        // Unconfigured policy IDs are never released, even if stored.
+        store.insert("other", b"x".to_vec()).await;
+        assert!(releaser
+            .get_or_release("other", false)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_releaser_reconfigure_and_key_lock() {
+        let releaser = releaser(Arc::new(SecretStore::default()));
+        assert!(Arc::ptr_eq(
+            &releaser.key_lock("luks"),
+            &releaser.key_lock("luks")
+        ));
+        assert!(!Arc::ptr_eq(
+            &releaser.key_lock("luks"),
+            &releaser.key_lock("other")
+        ));
+
+        releaser.reconfigure(vec!["other".to_string()], Duration::ZERO);
+        assert!(!releaser.is_configured("luks"));
+        assert!(releaser.is_configured("other"));
+        assert_eq!(releaser.nonce_window(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_secret_store_remove() {
+        let store = SecretStore::default();
+        store.insert("k", b"secret".to_vec()).await;
+        store.remove("k").await;
+        assert!(store.get("k").await.is_none());
+    }
+
+    #[test]
+    fn test_unpublish_all_removes_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = vec!["a".to_string(), "b".to_string()];
+        output::write_secret_file(&dir.path().join("a"), b"secret").unwrap();
+
+        unpublish_all(&ids, Some(dir.path()));
+        assert!(!dir.path().join("a").exists());
+    }
+}
@@ -0,0 +1,176 @@
+// TEE Attestation Service Agent — SEV-SNP derived keys
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With a `[snp_derived_key]` section in a build with the `snp-derived-key`
+// feature, the secret released by TAS is not used directly. The agent asks
+// the AMD secure processor for a key derived from the platform and the
+// guest (the SNP_GET_DERIVED_KEY guest request on /dev/sev-guest) and
+// delivers
+//
+//   HKDF-SHA256(ikm = released secret, salt = derived key, info) [length]
+//
+// The result depends on both the TAS release and this platform: a secret
+// intercepted in transit, or released to a different guest or host, does
+// not yield the disk key. Which guest properties go into the derived key is
+// chosen with `guest_fields`:
+//
+//   [snp_derived_key]
+//   root_key = "vcek"             # or "vmrk"
+//   guest_fields = ["measurement", "policy"]
+//   info = "tas_agent luks"
+//   length = 32
+//
+// Any change to a selected field (e.g. a new measurement after a firmware
+// update) changes the delivered key, so the selection must match how the
+// volume is re-keyed. The offline escrow is mixed the same way.
+//
+// No unsafe code.
+
+use anyhow::{anyhow, Context, Result};
+use hkdf::Hkdf;
+use log::debug;
+use serde::Deserialize;
+use sev::firmware::guest::{DerivedKey, Firmware, GuestFieldSelect};
+use sha2::Sha256;
+use zeroize::{Zeroize, Zeroizing};
+
+/// SEV guest device the derived key is requested from.
+pub const DEVICE: &str = "/dev/sev-guest";
+
+/// Default length of the delivered key in bytes.
+const DEFAULT_LENGTH: usize = 32;
+
+/// Default HKDF info string.
+const DEFAULT_INFO: &str = "tas_agent snp-derived-key";
+
+/// GUEST_FIELD_SELECT bits of the SNP_GET_DERIVED_KEY request.
+const GUEST_FIELDS: &[(&str, u64)] = &[
+    ("policy", 1 << 0),
+    ("image_id", 1 << 1),
+    ("family_id", 1 << 2),
+    ("measurement", 1 << 3),
+    ("guest_svn", 1 << 4),
+    ("tcb_version", 1 << 5),
+];
+
+/// `[snp_derived_key]` section of the config file.
+#[derive(Deserialize, Clone, Default)]
+pub struct DerivedKeyConfig {
+    /// Root of the derivation: "vcek" (default) or "vmrk"
+    pub root_key: Option<String>,
+    /// Guest properties mixed into the key (default: ["measurement"])
+    pub guest_fields: Option<Vec<String>>,
+    /// VMPL to derive for; at least the agent's own (default: 0)
+    pub vmpl: Option<u32>,
+    /// Guest SVN to derive for, when "guest_svn" is selected
+    pub guest_svn: Option<u32>,
+    /// TCB version to derive for, when "tcb_version" is selected
+    pub tcb_version: Option<u64>,
+    /// HKDF info string (default: "tas_agent snp-derived-key")
+    pub info: Option<String>,
+    /// Length of the delivered key in bytes (default: 32)
+    pub length: Option<usize>,
+}
+
+/// GUEST_FIELD_SELECT value for the named fields.
+fn field_select(fields: &[String]) -> Result<u64> {
+    fields.iter().try_fold(0, |bits, field| {
+        GUEST_FIELDS
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, bit)| bits | bit)
+            .ok_or_else(|| anyhow!("unknown guest field {:?}", field))
+    })
+}
+
+/// Build the SNP_GET_DERIVED_KEY request for `config`.
+fn request(config: &DerivedKeyConfig) -> Result<DerivedKey> {
+    let root_key_select = match config.root_key.as_deref().unwrap_or("vcek") {
+        "vcek" => false,
+        "vmrk" => true,
+        other => return Err(anyhow!("unknown root key {:?}", other)),
+    };
+    let fields = match &config.guest_fields {
+        Some(fields) => field_select(fields)?,
+        None => field_select(&["measurement".to_string()])?,
+    };
+    Ok(DerivedKey::new(
+        root_key_select,
+        GuestFieldSelect(fields),
+        config.vmpl.unwrap_or(0),
+        config.guest_svn.unwrap_or(0),
+        config.tcb_version.unwrap_or(0),
+    ))
+}
+
+/// HKDF-SHA256 of `secret` with `salt` and `info`, `length` bytes long.
+fn mix(secret: &[u8], salt: &[u8], info: &[u8], length: usize) -> Result<Vec<u8>> {
+    let mut key = vec![0u8; length];
+    Hkdf::<Sha256>::new(Some(salt), secret)
+        .expand(info, &mut key)
+        .map_err(|_| anyhow!("invalid key length {}", length))?;
+    Ok(key)
+}
+
+/// Replace `secret` with its mix with this platform's derived key.
+pub fn apply(config: &DerivedKeyConfig, mut secret: Vec<u8>) -> Result<Vec<u8>> {
+    let request = request(config)?;
+    let mut firmware = Firmware::open().with_context(|| format!("unable to open {}", DEVICE))?;
+    let derived = Zeroizing::new(
+        firmware
+            .get_derived_key(None, request)
+            .map_err(|e| anyhow!("derived key request failed: {}", e))?,
+    );
+    debug!("Mixing the SNP derived key into the released secret");
+    let mixed = mix(
+        &secret,
+        derived.as_slice(),
+        config.info.as_deref().unwrap_or(DEFAULT_INFO).as_bytes(),
+        config.length.unwrap_or(DEFAULT_LENGTH),
+    );
+    secret.zeroize();
+    mixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_select() {
+        let fields = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(field_select(&fields(&["measurement"])).unwrap(), 0x8);
+        assert_eq!(
+            field_select(&fields(&["policy", "measurement", "tcb_version"])).unwrap(),
+            0x29
+        );
+        assert_eq!(field_select(&[]).unwrap(), 0);
+        assert!(field_select(&fields(&["host_data"])).is_err());
+
+        let config = DerivedKeyConfig {
+            root_key: Some("vlek".to_string()),
+            ..Default::default()
+        };
+        assert!(request(&config).is_err());
+    }
+
+    #[test]
+    fn test_mix_rfc5869() {
+        // RFC 5869, test case 1
+        let okm = mix(
+            &[0x0b; 22],
+            &hex::decode("000102030405060708090a0b0c").unwrap(),
+            &hex::decode("f0f1f2f3f4f5f6f7f8f9").unwrap(),
+            42,
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(okm),
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf\
+             34007208d5b887185865"
+        );
+        assert!(mix(b"secret", b"salt", b"", 255 * 32 + 1).is_err());
+    }
+}
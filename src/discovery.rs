@@ -0,0 +1,390 @@
+// TEE Attestation Service Agent — TAS endpoint discovery from cloud metadata
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With a `[discovery]` section, a server URI or policy ID that is configured
+// neither on the command line nor in the config file is looked up in the
+// instance metadata service, so that one golden image serves every
+// deployment. Each value is taken from the instance tag (AWS, Azure) or
+// custom metadata attribute (GCP) named by `server_uri_key` and
+// `policy_id_key` (default: tas-server-uri and tas-key-id), or failing that
+// from a line of the instance's user-data:
+//
+//   #cloud-config
+//   tas-server-uri: https://tas.example.com:5000
+//   tas-key-id: luks
+//
+// (`NAME: VALUE` or `NAME=VALUE`, optionally commented out with `#`, so the
+// lines also fit in a shell script). AWS instance tags are only visible in
+// the metadata service when the instance allows it; requests use IMDSv2.
+// With `provider = "auto"` the cloud is recognized by the DMI system vendor.
+//
+// The metadata service is plain HTTP on a link-local address, and the
+// session is resolved synchronously, so it is queried with bare HTTP/1.0
+// requests rather than the TAS client. Whoever controls the instance's tags
+// and user-data chooses the TAS server: the CA certificate remains the
+// protection against a server of the wrong party.
+//
+// No unsafe code.
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{debug, info};
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+/// Address of the instance metadata service of all supported clouds.
+const IMDS_ADDR: &str = "169.254.169.254:80";
+
+/// DMI system vendor, which identifies the cloud.
+pub const DMI_VENDOR: &str = "/sys/class/dmi/id/sys_vendor";
+
+const DEFAULT_SERVER_URI_KEY: &str = "tas-server-uri";
+const DEFAULT_POLICY_ID_KEY: &str = "tas-key-id";
+const DEFAULT_TIMEOUT_SECS: u64 = 2;
+
+/// Cloud whose metadata service is queried.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    /// Recognize the cloud by the DMI system vendor
+    #[default]
+    Auto,
+    Aws,
+    Azure,
+    Gcp,
+}
+
+/// `[discovery]` section of the config file.
+#[derive(Deserialize, Clone, Default)]
+pub struct DiscoveryConfig {
+    /// Cloud to query (default: auto)
+    #[serde(default)]
+    pub provider: Provider,
+    /// Tag or attribute holding the server URI (default: tas-server-uri)
+    pub server_uri_key: Option<String>,
+    /// Tag or attribute holding the policy ID (default: tas-key-id)
+    pub policy_id_key: Option<String>,
+    /// Seconds to wait for each metadata request (default: 2)
+    pub timeout_secs: Option<u64>,
+}
+
+/// Values found in the instance metadata.
+#[derive(Debug, Default, PartialEq)]
+pub struct Discovered {
+    pub server_uri: Option<String>,
+    pub policy_id: Option<String>,
+}
+
+/// The cloud named by the DMI system vendor `vendor`.
+fn provider_of(vendor: &str) -> Option<Provider> {
+    match vendor.trim() {
+        "Amazon EC2" => Some(Provider::Aws),
+        "Microsoft Corporation" => Some(Provider::Azure),
+        "Google" => Some(Provider::Gcp),
+        _ => None,
+    }
+}
+
+fn detect() -> Result<Provider> {
+    let vendor = std::fs::read_to_string(DMI_VENDOR)
+        .with_context(|| format!("unable to read {}", DMI_VENDOR))?;
+    provider_of(&vendor).ok_or_else(|| {
+        anyhow!(
+            "unknown cloud vendor {:?}, set [discovery] provider",
+            vendor.trim()
+        )
+    })
+}
+
+/// Body of the HTTP `response`, or None if the resource does not exist.
+fn parse_response(response: &[u8]) -> Result<Option<String>> {
+    let text = String::from_utf8_lossy(response);
+    let (head, body) = text
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| anyhow!("truncated metadata response"))?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .ok_or_else(|| anyhow!("invalid metadata response"))?;
+    match status {
+        "200" => Ok(Some(body.to_string())),
+        "404" => Ok(None),
+        status => Err(anyhow!("metadata service returned status {}", status)),
+    }
+}
+
+/// The metadata service of one instance.
+struct Imds {
+    addr: SocketAddr,
+    timeout: Duration,
+}
+
+impl Imds {
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<Option<String>> {
+        debug!("Instance metadata: {} {}", method, path);
+        let mut stream = TcpStream::connect_timeout(&self.addr, self.timeout)
+            .context("instance metadata service unreachable")?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let mut request = format!(
+            "{} {} HTTP/1.0\r\nHost: {}\r\n",
+            method,
+            path,
+            self.addr.ip()
+        );
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        parse_response(&response)
+    }
+}
+
+/// The value of `name` in the user-data `text`.
+fn find_value(text: &str, name: &str) -> Option<String> {
+    text.lines().find_map(|line| {
+        let line = line.trim_start().trim_start_matches('#').trim();
+        let value = line
+            .strip_prefix(name)?
+            .trim_start()
+            .strip_prefix([':', '='])?
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'');
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
+/// The value of the tag `name` in an Azure compute metadata document.
+fn azure_tag(compute: &Value, name: &str) -> Option<String> {
+    compute
+        .get("tagsList")?
+        .as_array()?
+        .iter()
+        .find(|tag| tag.get("name").and_then(Value::as_str) == Some(name))?
+        .get("value")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// A connection to the metadata service of one cloud.
+enum Instance {
+    Aws { imds: Imds, token: String },
+    Azure { compute: Value },
+    Gcp { imds: Imds },
+}
+
+impl Instance {
+    fn connect(provider: Provider, imds: Imds) -> Result<Self> {
+        Ok(match provider {
+            Provider::Auto => unreachable!("provider detected before connecting"),
+            Provider::Aws => {
+                let token = imds
+                    .request(
+                        "PUT",
+                        "/latest/api/token",
+                        &[("X-aws-ec2-metadata-token-ttl-seconds", "60")],
+                    )?
+                    .ok_or_else(|| anyhow!("no IMDSv2 token"))?;
+                Instance::Aws { imds, token }
+            }
+            Provider::Azure => {
+                let compute = imds
+                    .request(
+                        "GET",
+                        "/metadata/instance/compute?api-version=2021-02-01",
+                        &[("Metadata", "true")],
+                    )?
+                    .ok_or_else(|| anyhow!("no compute metadata"))?;
+                let compute = serde_json::from_str(&compute).context("invalid compute metadata")?;
+                Instance::Azure { compute }
+            }
+            Provider::Gcp => Instance::Gcp { imds },
+        })
+    }
+
+    /// The instance tag or attribute `name`.
+    fn tag(&self, name: &str) -> Result<Option<String>> {
+        match self {
+            Instance::Aws { imds, token } => imds.request(
+                "GET",
+                &format!("/latest/meta-data/tags/instance/{}", name),
+                &[("X-aws-ec2-metadata-token", token)],
+            ),
+            Instance::Azure { compute } => Ok(azure_tag(compute, name)),
+            Instance::Gcp { imds } => imds.request(
+                "GET",
+                &format!("/computeMetadata/v1/instance/attributes/{}", name),
+                &[("Metadata-Flavor", "Google")],
+            ),
+        }
+    }
+
+    fn user_data(&self) -> Result<Option<String>> {
+        match self {
+            Instance::Aws { imds, token } => imds.request(
+                "GET",
+                "/latest/user-data",
+                &[("X-aws-ec2-metadata-token", token)],
+            ),
+            Instance::Azure { compute } => {
+                let Some(user_data) = compute.get("userData").and_then(Value::as_str) else {
+                    return Ok(None);
+                };
+                let user_data = STANDARD
+                    .decode(user_data)
+                    .context("invalid base64 in userData")?;
+                Ok(Some(String::from_utf8_lossy(&user_data).into_owned()))
+            }
+            Instance::Gcp { imds } => imds.request(
+                "GET",
+                "/computeMetadata/v1/instance/attributes/user-data",
+                &[("Metadata-Flavor", "Google")],
+            ),
+        }
+    }
+}
+
+/// Look up the server URI and policy ID in the instance metadata.
+pub fn discover(config: &DiscoveryConfig) -> Result<Discovered> {
+    let provider = match config.provider {
+        Provider::Auto => detect()?,
+        provider => provider,
+    };
+    let imds = Imds {
+        addr: IMDS_ADDR.parse().expect("valid metadata service address"),
+        timeout: Duration::from_secs(config.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS)),
+    };
+    let instance = Instance::connect(provider, imds)?;
+    let names = [
+        config
+            .server_uri_key
+            .as_deref()
+            .unwrap_or(DEFAULT_SERVER_URI_KEY),
+        config
+            .policy_id_key
+            .as_deref()
+            .unwrap_or(DEFAULT_POLICY_ID_KEY),
+    ];
+
+    let mut values = Vec::new();
+    for name in names {
+        values.push(instance.tag(name)?.map(|value| value.trim().to_string()));
+    }
+    if values.iter().any(Option::is_none) {
+        if let Some(user_data) = instance.user_data()? {
+            for (value, name) in values.iter_mut().zip(names) {
+                if value.is_none() {
+                    *value = find_value(&user_data, name);
+                }
+            }
+        }
+    }
+
+    let [server_uri, policy_id]: [Option<String>; 2] =
+        values.try_into().expect("one value per name");
+    if let Some(server_uri) = &server_uri {
+        info!(
+            "Discovered TAS server {} from {:?} metadata",
+            server_uri, provider
+        );
+    }
+    if let Some(policy_id) = &policy_id {
+        info!(
+            "Discovered policy ID {} from {:?} metadata",
+            policy_id, provider
+        );
+    }
+    Ok(Discovered {
+        server_uri,
+        policy_id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_find_value() {
+        let user_data = "#cloud-config\n\
+                         tas-server-uri: \"https://tas:5000\"\n\
+                         packages: [cryptsetup]\n";
+        assert_eq!(
+            find_value(user_data, "tas-server-uri").as_deref(),
+            Some("https://tas:5000")
+        );
+        let script = "#!/bin/sh\n# tas-key-id=luks\nexec /sbin/init\n";
+        assert_eq!(find_value(script, "tas-key-id").as_deref(), Some("luks"));
+        assert_eq!(find_value("tas-key-idx: luks\n", "tas-key-id"), None);
+        assert_eq!(find_value("tas-key-id:\n", "tas-key-id"), None);
+    }
+
+    #[test]
+    fn test_azure_tag() {
+        let compute = json!({"tagsList": [
+            {"name": "env", "value": "prod"},
+            {"name": "tas-server-uri", "value": "https://tas:5000"}
+        ]});
+        assert_eq!(
+            azure_tag(&compute, "tas-server-uri").as_deref(),
+            Some("https://tas:5000")
+        );
+        assert_eq!(azure_tag(&compute, "tas-key-id"), None);
+        assert_eq!(
+            provider_of("Microsoft Corporation\n"),
+            Some(Provider::Azure)
+        );
+        assert_eq!(provider_of("QEMU\n"), None);
+    }
+
+    #[test]
+    fn test_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let imds = Imds {
+            addr: listener.local_addr().unwrap(),
+            timeout: Duration::from_secs(5),
+        };
+        let server = std::thread::spawn(move || {
+            for response in [
+                "HTTP/1.0 200 OK\r\n\r\nluks",
+                "HTTP/1.0 404 Not Found\r\n\r\n",
+            ] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0u8; 512];
+                let n = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..n]).into_owned();
+                assert!(
+                    request.contains("Metadata-Flavor: Google\r\n"),
+                    "{}",
+                    request
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        let headers = [("Metadata-Flavor", "Google")];
+        let path = "/computeMetadata/v1/instance/attributes/tas-key-id";
+        assert_eq!(
+            imds.request("GET", path, &headers).unwrap().as_deref(),
+            Some("luks")
+        );
+        assert_eq!(imds.request("GET", path, &headers).unwrap(), None);
+        server.join().unwrap();
+
+        assert!(parse_response(b"HTTP/1.1 500 Internal Server Error\r\n\r\n").is_err());
+    }
+}
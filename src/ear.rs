@@ -0,0 +1,276 @@
+// TEE Attestation Service Agent — EAR verification
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With an `[ear]` section, the agent requires the TAS server to return an
+// EAT Attestation Result (EAR, draft-ietf-rats-ear) as `attestation-result`
+// next to the secret, and checks it before the secret is handed to
+// consumers:
+//
+//   - the JWT signature verifies against `verifier_key` (ES256, RS256 or
+//     PS256; PEM or DER SubjectPublicKeyInfo),
+//   - `exp`, `nbf` and `iat`, if present, hold within the clock skew
+//     tolerance (see clock.rs),
+//   - `eat_nonce` is present and is the nonce of this attestation, so a
+//     result recorded for an earlier attestation cannot be replayed, and
+//   - every submodule's `ear.status` is at least `min_status`, where
+//     affirming > warning > none > contraindicated (default: affirming).
+//
+// A secret whose result fails any check is discarded.
+//
+// No unsafe code.
+
+use crate::clock;
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use log::debug;
+use p256::ecdsa;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::signature::Verifier;
+use rsa::RsaPublicKey;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+
+/// EAR trust tier, ordered from worst to best.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Contraindicated,
+    #[serde(rename = "none")]
+    NoClaim,
+    Warning,
+    #[default]
+    Affirming,
+}
+
+impl Status {
+    fn parse(status: &str) -> Option<Self> {
+        match status {
+            "affirming" => Some(Status::Affirming),
+            "warning" => Some(Status::Warning),
+            "none" => Some(Status::NoClaim),
+            "contraindicated" => Some(Status::Contraindicated),
+            _ => None,
+        }
+    }
+}
+
+/// `[ear]` section of the config file.
+#[derive(Deserialize, Clone)]
+pub struct EarConfig {
+    /// Public key of the verifier that signs attestation results
+    pub verifier_key: PathBuf,
+    /// Lowest acceptable `ear.status` (default: affirming)
+    #[serde(default)]
+    pub min_status: Status,
+}
+
+fn decode_json(part: &str) -> Result<Value> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(part)
+        .context("invalid base64url in attestation result")?;
+    serde_json::from_slice(&bytes).context("invalid JSON in attestation result")
+}
+
+/// Parse a PEM or DER SubjectPublicKeyInfo with `pem` or `der`.
+fn load_key<K>(
+    path: &Path,
+    pem: impl Fn(&str) -> Result<K, String>,
+    der: impl Fn(&[u8]) -> Result<K, String>,
+) -> Result<K> {
+    let data =
+        std::fs::read(path).with_context(|| format!("unable to read verifier key {:?}", path))?;
+    let key = match std::str::from_utf8(&data) {
+        Ok(text) if text.trim_start().starts_with("-----BEGIN") => pem(text),
+        _ => der(&data),
+    };
+    key.map_err(|e| anyhow!("invalid verifier key {:?}: {}", path, e))
+}
+
+/// Check the JWS signature of `signing_input` with the key at `key_path`.
+fn verify_signature(
+    alg: &str,
+    key_path: &Path,
+    signing_input: &[u8],
+    signature: &[u8],
+) -> Result<()> {
+    let rsa_key = || {
+        load_key(
+            key_path,
+            |pem| RsaPublicKey::from_public_key_pem(pem).map_err(|e| e.to_string()),
+            |der| RsaPublicKey::from_public_key_der(der).map_err(|e| e.to_string()),
+        )
+    };
+    let verified = match alg {
+        "ES256" => {
+            type EcKey = ecdsa::VerifyingKey;
+            let key = load_key(
+                key_path,
+                |pem| {
+                    <EcKey as p256::pkcs8::DecodePublicKey>::from_public_key_pem(pem)
+                        .map_err(|e| e.to_string())
+                },
+                |der| {
+                    <EcKey as p256::pkcs8::DecodePublicKey>::from_public_key_der(der)
+                        .map_err(|e| e.to_string())
+                },
+            )?;
+            let signature = ecdsa::Signature::from_slice(signature)
+                .map_err(|e| anyhow!("invalid ES256 signature: {}", e))?;
+            ecdsa::signature::Verifier::verify(&key, signing_input, &signature).is_ok()
+        }
+        "RS256" => {
+            let key = rsa::pkcs1v15::VerifyingKey::<Sha256>::new(rsa_key()?);
+            let signature = rsa::pkcs1v15::Signature::try_from(signature)
+                .map_err(|e| anyhow!("invalid RS256 signature: {}", e))?;
+            key.verify(signing_input, &signature).is_ok()
+        }
+        "PS256" => {
+            let key = rsa::pss::VerifyingKey::<Sha256>::new(rsa_key()?);
+            let signature = rsa::pss::Signature::try_from(signature)
+                .map_err(|e| anyhow!("invalid PS256 signature: {}", e))?;
+            key.verify(signing_input, &signature).is_ok()
+        }
+        other => return Err(anyhow!("unsupported signature algorithm {:?}", other)),
+    };
+    if !verified {
+        return Err(anyhow!("signature does not verify"));
+    }
+    Ok(())
+}
+
+/// Check the claims of a verified result against `config`.
+fn check_claims(config: &EarConfig, claims: &Value, nonce: &str, now: i64) -> Result<()> {
+    clock::check_times(claims, now)?;
+    let eat_nonce = claims
+        .get("eat_nonce")
+        .ok_or_else(|| anyhow!("no eat_nonce claim"))?;
+    if eat_nonce.as_str() != Some(nonce) {
+        return Err(anyhow!("eat_nonce does not match the attestation nonce"));
+    }
+    let submods = claims
+        .get("submods")
+        .and_then(Value::as_object)
+        .filter(|submods| !submods.is_empty())
+        .ok_or_else(|| anyhow!("no submods claim"))?;
+    for (name, submod) in submods {
+        let status = submod
+            .get("ear.status")
+            .and_then(Value::as_str)
+            .and_then(Status::parse)
+            .ok_or_else(|| anyhow!("submod {:?} has no valid ear.status", name))?;
+        debug!("EAR submod {}: {:?}", name, status);
+        if status < config.min_status {
+            return Err(anyhow!(
+                "submod {:?} is {:?}, below {:?}",
+                name,
+                status,
+                config.min_status
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check the signature of the JWT `token` with the key at `key_path` and
+/// return its claims.
+pub fn verify_jwt(key_path: &Path, token: &str) -> Result<Value> {
+    let parts: Vec<&str> = token.split('.').collect();
+    let [header, payload, signature] = parts[..] else {
+        return Err(anyhow!("attestation result is not a JWT"));
+    };
+    let alg = decode_json(header)?
+        .get("alg")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("no alg in attestation result header"))?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .context("invalid base64url in attestation result")?;
+    let signing_input = format!("{}.{}", header, payload);
+    verify_signature(&alg, key_path, signing_input.as_bytes(), &signature)?;
+    decode_json(payload)
+}
+
+/// Verify the EAR JWT `token` issued for the attestation with `nonce`.
+pub fn verify(config: &EarConfig, token: &str, nonce: &str) -> Result<()> {
+    check_claims(
+        config,
+        &verify_jwt(&config.verifier_key, token)?,
+        nonce.trim_matches('"'),
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::generate_wrapping_key;
+    use serde_json::json;
+
+    fn config(verifier_key: PathBuf, min_status: Status) -> EarConfig {
+        EarConfig {
+            verifier_key,
+            min_status,
+        }
+    }
+
+    #[test]
+    fn test_status_order() {
+        assert!(Status::Affirming > Status::Warning);
+        assert!(Status::Warning > Status::NoClaim);
+        assert!(Status::NoClaim > Status::Contraindicated);
+        assert_eq!(Status::parse("none"), Some(Status::NoClaim));
+        assert_eq!(Status::parse("bogus"), None);
+    }
+
+    #[test]
+    fn test_check_claims() {
+        let cfg = config(PathBuf::new(), Status::Warning);
+        let claims = |status: &str| {
+            json!({
+                "eat_nonce": "n0nce",
+                "exp": 2000,
+                "submods": {"cpu": {"ear.status": "affirming"}, "gpu": {"ear.status": status}}
+            })
+        };
+        assert!(check_claims(&cfg, &claims("warning"), "n0nce", 1000).is_ok());
+        assert!(check_claims(&cfg, &claims("none"), "n0nce", 1000).is_err());
+        assert!(check_claims(&cfg, &claims("warning"), "other", 1000).is_err());
+        assert!(check_claims(&cfg, &claims("warning"), "n0nce", 3000).is_err());
+        assert!(check_claims(&cfg, &json!({"submods": {}}), "n0nce", 1000).is_err());
+        let mut no_nonce = claims("warning");
+        no_nonce.as_object_mut().unwrap().remove("eat_nonce");
+        assert!(check_claims(&cfg, &no_nonce, "n0nce", 1000).is_err());
+
+        let strict = config(PathBuf::new(), Status::default());
+        assert!(check_claims(&strict, &claims("warning"), "n0nce", 1000).is_err());
+    }
+
+    #[test]
+    fn test_verify_rs256() {
+        let key = generate_wrapping_key().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join("verifier.der");
+        std::fs::write(&key_path, key.public_key_to_spki_der().unwrap()).unwrap();
+
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(
+            json!({"eat_nonce": "n0nce", "submods": {"cpu": {"ear.status": "affirming"}}})
+                .to_string(),
+        );
+        let signing_input = format!("{}.{}", header, payload);
+        let signature = URL_SAFE_NO_PAD.encode(key.sign(signing_input.as_bytes()));
+        let token = format!("{}.{}", signing_input, signature);
+
+        let cfg = config(key_path, Status::Affirming);
+        assert!(verify(&cfg, &token, "\"n0nce\"").is_ok());
+
+        let tampered = format!("{}.{}.{}", header, URL_SAFE_NO_PAD.encode("{}"), signature);
+        assert!(verify(&cfg, &tampered, "n0nce").is_err());
+        assert!(verify(&cfg, "not-a-jwt", "n0nce").is_err());
+    }
+}
@@ -0,0 +1,309 @@
+// TEE Attestation Service Agent — Entity Attestation Tokens
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With an `[eat]` section, collected evidence and agent claims are packaged
+// as an Entity Attestation Token (RFC 9711): a CBOR Web Token claims set,
+// signed as a tagged COSE_Sign1 message with RS256. Verifier backends that
+// accept EAT evidence submit the token instead of the raw TEE report.
+//
+// Claims:
+//
+//   6   iat                  issue time
+//   10  eat_nonce            SHA-512 of the runtime data, i.e. the report
+//                            data of the TEE evidence
+//   270 swname               "tas_agent"
+//   271 swversion            [agent version]
+//   "tee-type"               TEE type, e.g. "amd-sev-snp"
+//   "tee-evidence"           raw TEE report or quote
+//   "runtime-data"           TAS nonce || wrapping key DER || component hashes
+//   "signing-key"            SubjectPublicKeyInfo DER of the signing key
+//
+// The token is signed either with the ephemeral wrapping key, which the
+// evidence already binds through its report data, or with a TPM-resident
+// key (see tpm::sign) that the verifier has enrolled. The key ID header is
+// SHA-256 of the signing key's SubjectPublicKeyInfo.
+//
+// No unsafe code.
+
+use crate::crypto::RsaKey;
+use crate::tpm;
+use crate::verifier::Evidence;
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose, Engine};
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha512};
+
+/// COSE algorithm ID of RSASSA-PKCS1-v1_5 with SHA-256.
+const ALG_RS256: i64 = -257;
+/// CBOR tag of a COSE_Sign1 message.
+const TAG_COSE_SIGN1: u64 = 18;
+
+/// Key the token is signed with.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningKey {
+    /// The ephemeral wrapping key of the attestation
+    #[default]
+    Ephemeral,
+    /// The TPM's owner-hierarchy signing primary
+    Tpm,
+}
+
+/// `[eat]` section of the config file.
+#[derive(Deserialize, Clone, Default)]
+pub struct EatConfig {
+    /// Key to sign the token with (default: ephemeral)
+    #[serde(default)]
+    pub signing_key: SigningKey,
+}
+
+/// The subset of CBOR (RFC 8949) needed to encode a token.
+#[derive(Debug, Clone, PartialEq)]
+enum Cbor {
+    Int(i64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Tag(u64, Box<Cbor>),
+}
+
+/// Append a CBOR item head with major type `major` and argument `n`.
+fn head(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    if n < 24 {
+        out.push(major | n as u8);
+    } else if n <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(n as u8);
+    } else if n <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend((n as u16).to_be_bytes());
+    } else if n <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend((n as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend(n.to_be_bytes());
+    }
+}
+
+impl Cbor {
+    fn text(s: &str) -> Self {
+        Cbor::Text(s.to_string())
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Cbor::Int(n) if *n >= 0 => head(out, 0, *n as u64),
+            Cbor::Int(n) => head(out, 1, !(*n) as u64),
+            Cbor::Bytes(b) => {
+                head(out, 2, b.len() as u64);
+                out.extend(b);
+            }
+            Cbor::Text(s) => {
+                head(out, 3, s.len() as u64);
+                out.extend(s.as_bytes());
+            }
+            Cbor::Array(items) => {
+                head(out, 4, items.len() as u64);
+                for item in items {
+                    item.encode(out);
+                }
+            }
+            Cbor::Map(entries) => {
+                head(out, 5, entries.len() as u64);
+                for (key, value) in entries {
+                    key.encode(out);
+                    value.encode(out);
+                }
+            }
+            Cbor::Tag(tag, item) => {
+                head(out, 6, *tag);
+                item.encode(out);
+            }
+        }
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+}
+
+/// The EAT claims set for `evidence`, signed by `signing_key`.
+fn claims(evidence: &Evidence<'_>, signing_key: &[u8], iat: i64) -> Result<Cbor> {
+    let report = general_purpose::STANDARD
+        .decode(evidence.evidence)
+        .context("evidence is not valid base64")?;
+    Ok(Cbor::Map(vec![
+        (Cbor::Int(6), Cbor::Int(iat)),
+        (
+            Cbor::Int(10),
+            Cbor::Bytes(Sha512::digest(evidence.runtime_data).to_vec()),
+        ),
+        (Cbor::Int(270), Cbor::text(env!("CARGO_PKG_NAME"))),
+        (
+            Cbor::Int(271),
+            Cbor::Array(vec![Cbor::text(env!("CARGO_PKG_VERSION"))]),
+        ),
+        (Cbor::text("tee-type"), Cbor::text(evidence.tee_type)),
+        (Cbor::text("tee-evidence"), Cbor::Bytes(report)),
+        (
+            Cbor::text("runtime-data"),
+            Cbor::Bytes(evidence.runtime_data.to_vec()),
+        ),
+        (Cbor::text("signing-key"), Cbor::Bytes(signing_key.to_vec())),
+    ]))
+}
+
+/// Build a COSE_Sign1 message over `payload`, signed by `sign`.
+fn sign1(
+    payload: Vec<u8>,
+    key_id: Vec<u8>,
+    sign: impl FnOnce(&[u8]) -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    let protected = Cbor::Map(vec![(Cbor::Int(1), Cbor::Int(ALG_RS256))]).to_vec();
+    let to_be_signed = Cbor::Array(vec![
+        Cbor::text("Signature1"),
+        Cbor::Bytes(protected.clone()),
+        Cbor::Bytes(Vec::new()),
+        Cbor::Bytes(payload.clone()),
+    ])
+    .to_vec();
+    let signature = sign(&to_be_signed)?;
+
+    Ok(Cbor::Tag(
+        TAG_COSE_SIGN1,
+        Box::new(Cbor::Array(vec![
+            Cbor::Bytes(protected),
+            Cbor::Map(vec![(Cbor::Int(4), Cbor::Bytes(key_id))]),
+            Cbor::Bytes(payload),
+            Cbor::Bytes(signature),
+        ])),
+    )
+    .to_vec())
+}
+
+/// Package `evidence` as a signed EAT. `wrapping_key` is the ephemeral key
+/// of the attestation.
+pub fn build(
+    config: &EatConfig,
+    evidence: &Evidence<'_>,
+    wrapping_key: &RsaKey,
+) -> Result<Vec<u8>> {
+    let iat = chrono::Utc::now().timestamp();
+    match config.signing_key {
+        SigningKey::Ephemeral => {
+            let public = wrapping_key
+                .public_key_to_spki_der()
+                .map_err(|e| anyhow!("{}", e))?;
+            let payload = claims(evidence, &public, iat)?.to_vec();
+            sign1(payload, Sha256::digest(&public).to_vec(), |message| {
+                Ok(wrapping_key.sign(message))
+            })
+        }
+        SigningKey::Tpm => {
+            let public = tpm::signing_key().context("unable to load TPM signing key")?;
+            let payload = claims(evidence, &public, iat)?.to_vec();
+            sign1(payload, Sha256::digest(&public).to_vec(), |message| {
+                tpm::sign(message).context("unable to sign with TPM key")
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cbor_encoding() {
+        // RFC 8949 Appendix A
+        assert_eq!(Cbor::Int(10).to_vec(), [0x0a]);
+        assert_eq!(Cbor::Int(24).to_vec(), [0x18, 0x18]);
+        assert_eq!(Cbor::Int(1000).to_vec(), [0x19, 0x03, 0xe8]);
+        assert_eq!(Cbor::Int(1000000).to_vec(), [0x1a, 0x00, 0x0f, 0x42, 0x40]);
+        assert_eq!(Cbor::Int(-1).to_vec(), [0x20]);
+        assert_eq!(Cbor::Int(-257).to_vec(), [0x39, 0x01, 0x00]);
+        assert_eq!(Cbor::text("IETF").to_vec(), b"\x64IETF");
+        assert_eq!(Cbor::Bytes(vec![1, 2, 3, 4]).to_vec(), [0x44, 1, 2, 3, 4]);
+        assert_eq!(
+            Cbor::Array(vec![Cbor::Int(1), Cbor::Int(2)]).to_vec(),
+            [0x82, 0x01, 0x02]
+        );
+        assert_eq!(
+            Cbor::Map(vec![(Cbor::Int(1), Cbor::Int(2))]).to_vec(),
+            [0xa1, 0x01, 0x02]
+        );
+        assert_eq!(
+            Cbor::Tag(1, Box::new(Cbor::Int(1363896240))).to_vec(),
+            [0xc1, 0x1a, 0x51, 0x4b, 0x67, 0xb0]
+        );
+    }
+
+    #[test]
+    fn test_sign1_structure() {
+        let mut signed = Vec::new();
+        let token = sign1(b"payload".to_vec(), vec![0xaa], |message| {
+            signed = message.to_vec();
+            Ok(vec![0x5a; 4])
+        })
+        .unwrap();
+
+        // Sig_structure: ["Signature1", h'A1013901 00', h'', h'payload']
+        let mut expected = vec![0x84, 0x6a];
+        expected.extend(b"Signature1");
+        expected.extend([0x45, 0xa1, 0x01, 0x39, 0x01, 0x00, 0x40, 0x47]);
+        expected.extend(b"payload");
+        assert_eq!(signed, expected);
+
+        // 18([protected, {4: h'AA'}, payload, signature])
+        assert_eq!(&token[..3], [0xd2, 0x84, 0x45]);
+        assert!(token.ends_with(&[0x44, 0x5a, 0x5a, 0x5a, 0x5a]));
+        assert!(token
+            .windows(4)
+            .any(|window| window == [0xa1, 0x04, 0x41, 0xaa]));
+    }
+
+    #[test]
+    fn test_claims() {
+        let evidence = Evidence {
+            tee_type: "amd-sev-snp",
+            evidence: "AQID",
+            runtime_data: b"nonce",
+            eat: None,
+        };
+        let Cbor::Map(entries) = claims(&evidence, b"key", 1700000000).unwrap() else {
+            panic!("claims are not a map");
+        };
+        let claim = |key: Cbor| {
+            entries
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.clone())
+        };
+        assert_eq!(claim(Cbor::Int(6)), Some(Cbor::Int(1700000000)));
+        assert_eq!(
+            claim(Cbor::Int(10)),
+            Some(Cbor::Bytes(Sha512::digest(b"nonce").to_vec()))
+        );
+        assert_eq!(
+            claim(Cbor::text("tee-evidence")),
+            Some(Cbor::Bytes(vec![1, 2, 3]))
+        );
+        assert_eq!(
+            claim(Cbor::text("signing-key")),
+            Some(Cbor::Bytes(b"key".to_vec()))
+        );
+
+        let invalid = Evidence {
+            evidence: "not base64!",
+            ..evidence
+        };
+        assert!(claims(&invalid, b"key", 0).is_err());
+    }
+}
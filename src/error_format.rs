@@ -0,0 +1,132 @@
+// TEE Attestation Service Agent — machine-readable errors
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With `--error-format json`, the error the agent exits with is printed to
+// stderr as one JSON object instead of English text, so that wrapper scripts
+// and provisioning systems can react to it without matching messages:
+//
+//   {"code": "network", "phase": "attestation", "retryable": true,
+//    "message": "TAS Version Error: Error making request: ...",
+//    "hint": "check that the TAS server is reachable and trusted"}
+//
+// `code` is the error class also given to hooks (config, network,
+// clock-skew, evidence, release, crypto, other) or "output" when delivering
+// the secret failed; `phase` is where the agent was: startup, attestation or
+// output. Network and clock-skew failures are `retryable`, the latter once
+// the clock is synchronized. Log lines still go to stderr as text; the error
+// object is the last line.
+//
+// No unsafe code.
+
+use crate::hooks;
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+/// How the final error is printed.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ErrorFormat {
+    /// The error chain as text
+    #[default]
+    Text,
+    /// One JSON object
+    Json,
+}
+
+static FORMAT: OnceLock<ErrorFormat> = OnceLock::new();
+
+/// Print all later errors in `format`.
+pub fn configure(format: ErrorFormat) {
+    let _ = FORMAT.set(format);
+}
+
+/// Where the agent was when it failed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    /// Config, sandboxes and subcommands that do not attest
+    Startup,
+    /// Evidence collection and key release
+    Attestation,
+    /// Delivery of the released secret
+    Output,
+}
+
+impl Phase {
+    fn as_str(self) -> &'static str {
+        match self {
+            Phase::Startup => "startup",
+            Phase::Attestation => "attestation",
+            Phase::Output => "output",
+        }
+    }
+}
+
+/// What to check for an error of class `code`.
+fn hint(code: &str) -> Option<&'static str> {
+    match code {
+        "config" => Some("check the config file and command-line options"),
+        "network" => Some("check that the TAS server is reachable and trusted"),
+        "clock-skew" => Some("check that the system clock is synchronized"),
+        "evidence" => Some("check that the TEE and its configfs-tsm provider are available"),
+        "release" => Some("check the key policy on the TAS server for this TEE"),
+        "crypto" => Some("check that the TAS server wraps secrets in a supported format"),
+        "output" => Some("check the output path and its permissions"),
+        _ => None,
+    }
+}
+
+/// The JSON object of `err`.
+fn object(phase: Phase, err: &anyhow::Error) -> Value {
+    let code = match phase {
+        Phase::Output => "output",
+        _ => hooks::error_class(err).as_str(),
+    };
+    json!({
+        "code": code,
+        "phase": phase.as_str(),
+        "message": format!("{:#}", err),
+        "retryable": matches!(code, "network" | "clock-skew"),
+        "hint": hint(code),
+    })
+}
+
+/// Print `err` in the configured format and exit with status 1.
+pub fn exit(phase: Phase, err: &anyhow::Error) -> ! {
+    match FORMAT.get().copied().unwrap_or_default() {
+        ErrorFormat::Text => eprintln!("{:#}", err),
+        ErrorFormat::Json => eprintln!("{}", object(phase, err)),
+    }
+    std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_object() {
+        let err =
+            hooks::ErrorClass::Network.error("TAS Version Error: Error making request: refused");
+        let error = object(Phase::Attestation, &err);
+        assert_eq!(error["code"], "network");
+        assert_eq!(error["phase"], "attestation");
+        assert_eq!(error["retryable"], true);
+        assert_eq!(error["message"], err.to_string());
+        assert!(error["hint"].is_string());
+
+        let err = hooks::ErrorClass::Release.error("TAS Secret Error: denied");
+        assert_eq!(object(Phase::Attestation, &err)["retryable"], false);
+        let err = hooks::ErrorClass::ClockSkew.error("clock skew: token iat is 2000");
+        let error = object(Phase::Attestation, &err);
+        assert_eq!(error["code"], "clock-skew");
+        assert_eq!(error["retryable"], true);
+        let error = object(Phase::Output, &anyhow!("permission denied"));
+        assert_eq!(error["code"], "output");
+        let error = object(Phase::Startup, &anyhow!("something else"));
+        assert_eq!(error["code"], "other");
+        assert_eq!(error["hint"], Value::Null);
+    }
+}
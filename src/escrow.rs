@@ -0,0 +1,196 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Offline escrow of the released secret.
+//
+// After a successful release the encrypted `SecretsPayload` returned by TAS is
+// persisted together with a copy of the AES key that decrypts it, sealed to
+// the TPM and the current values of the PCR selection `escrow_pcrs`. If the
+// TAS server cannot be reached on a later boot, and the operator has
+// explicitly opted in with `offline_fallback = true`, the agent unseals the
+// AES key and decrypts the escrowed payload instead.
+//
+// The payload on disk is useless without the TPM of the machine that stored
+// it, booted into the same measured state: the RSA-wrapped key inside it was
+// wrapped to an ephemeral agent key that no longer exists, and the TPM only
+// unseals the AES key while the selected PCRs are unchanged.
+
+use crate::audit;
+use crate::tpm::{self, SealedObject};
+use crate::utils::SecretsPayload;
+use anyhow::{Context, Result};
+use log::debug;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+const PAYLOAD_FILE: &str = "payload.json";
+const KEY_PUB_FILE: &str = "key.pub";
+const KEY_PRIV_FILE: &str = "key.priv";
+
+/// Where the escrow is kept and the PCRs its key is sealed to.
+#[derive(Clone)]
+pub struct EscrowConfig {
+    pub dir: PathBuf,
+    /// PCR selection, e.g. "sha256:0,4,7,8,9"
+    pub pcrs: String,
+}
+
+/// Write `data` to `path` with 0600 permissions, replacing any existing file
+/// atomically.
+pub(crate) fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)
+        .with_context(|| format!("unable to create {:?}", tmp))?;
+    file.write_all(data)
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("unable to write {:?}", tmp))?;
+    fs::rename(&tmp, path).with_context(|| format!("unable to rename {:?} to {:?}", tmp, path))
+}
+
+/// Persist the encrypted payload and a TPM-sealed copy of its AES key.
+fn store_inner(config: &EscrowConfig, secret_json: &str, aes_key: &[u8]) -> Result<()> {
+    let dir = &config.dir;
+    fs::create_dir_all(dir).with_context(|| format!("unable to create {:?}", dir))?;
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+        .with_context(|| format!("unable to set permissions on {:?}", dir))?;
+
+    let sealed = tpm::seal_to_pcrs(aes_key, &config.pcrs).context("unable to seal escrow key")?;
+    write_private(&dir.join(KEY_PUB_FILE), &sealed.public)?;
+    write_private(&dir.join(KEY_PRIV_FILE), &sealed.private)?;
+    write_private(&dir.join(PAYLOAD_FILE), secret_json.as_bytes())?;
+    Ok(())
+}
+
+/// Escrow the secret payload returned by TAS as `config` asks.
+pub fn store(config: &EscrowConfig, secret_json: &str, aes_key: &[u8]) -> Result<()> {
+    let result = store_inner(config, secret_json, aes_key);
+    let dir_str = config.dir.display().to_string();
+    match &result {
+        Ok(()) => {
+            debug!("Escrowed secret payload in {:?}", config.dir);
+            audit::record(
+                "escrow-store",
+                &[("dir", &dir_str), ("pcrs", &config.pcrs), ("result", "ok")],
+            );
+        }
+        Err(e) => {
+            let error = format!("{:#}", e);
+            audit::record(
+                "escrow-store",
+                &[("dir", &dir_str), ("result", "failed"), ("error", &error)],
+            );
+        }
+    }
+    result
+}
+
+/// Load the escrowed payload and sealed key from `dir`.
+fn load(dir: &Path) -> Result<(SecretsPayload, SealedObject)> {
+    let payload_path = dir.join(PAYLOAD_FILE);
+    let payload = fs::read_to_string(&payload_path)
+        .with_context(|| format!("no escrowed payload at {:?}", payload_path))?;
+    let secret: SecretsPayload =
+        serde_json::from_str(&payload).context("escrowed payload is malformed")?;
+
+    let sealed = SealedObject {
+        public: fs::read(dir.join(KEY_PUB_FILE)).context("unable to read escrowed key")?,
+        private: fs::read(dir.join(KEY_PRIV_FILE)).context("unable to read escrowed key")?,
+    };
+    Ok((secret, sealed))
+}
+
+/// Recover the secret from the escrow `config` describes. `reason` is
+/// recorded in the audit log.
+pub fn recover(config: &EscrowConfig, reason: &str) -> Result<Vec<u8>> {
+    let dir = &config.dir;
+    let result = load(dir).and_then(|(mut secret, sealed)| {
+        let mut aes_key = tpm::unseal_with_pcrs(&sealed, &config.pcrs)
+            .context("unable to unseal escrow key (PCRs changed?)")?;
+        let decrypted = crate::decrypt_payload(&aes_key, &mut secret);
+        aes_key.zeroize();
+        decrypted
+    });
+
+    let dir_str = dir.display().to_string();
+    match &result {
+        Ok(_) => audit::record(
+            "escrow-fallback",
+            &[("dir", &dir_str), ("reason", reason), ("result", "ok")],
+        ),
+        Err(e) => {
+            let error = format!("{:#}", e);
+            audit::record(
+                "escrow-fallback",
+                &[
+                    ("dir", &dir_str),
+                    ("reason", reason),
+                    ("result", "failed"),
+                    ("error", &error),
+                ],
+            );
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_private_sets_mode_and_replaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PAYLOAD_FILE);
+
+        write_private(&path, b"first").unwrap();
+        write_private(&path, b"second").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn test_load_missing_escrow() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = load(dir.path()).err().unwrap();
+        assert!(err.to_string().contains("no escrowed payload"), "{err}");
+    }
+
+    #[test]
+    fn test_load_reads_payload_and_sealed_key() {
+        let dir = tempfile::tempdir().unwrap();
+        // base64 of "k", "b", "i", "t"
+        let payload = r#"{"wrapped_key":"aw==","blob":"Yg==","iv":"aQ==","tag":"dA=="}"#;
+        write_private(&dir.path().join(PAYLOAD_FILE), payload.as_bytes()).unwrap();
+        write_private(&dir.path().join(KEY_PUB_FILE), b"pub").unwrap();
+        write_private(&dir.path().join(KEY_PRIV_FILE), b"priv").unwrap();
+
+        let (secret, sealed) = load(dir.path()).unwrap();
+        assert_eq!(*secret.blob, b"b");
+        assert_eq!(secret.algorithm, "AES-GCM");
+        assert_eq!(sealed.public, b"pub");
+        assert_eq!(sealed.private, b"priv");
+    }
+
+    #[test]
+    fn test_recover_fails_closed_without_escrow() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = EscrowConfig {
+            dir: dir.path().to_path_buf(),
+            pcrs: "sha256:0,4,7,8,9".to_string(),
+        };
+        assert!(recover(&config, "test").is_err());
+    }
+}
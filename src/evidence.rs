@@ -0,0 +1,216 @@
+// TEE Attestation Service Agent — TEE evidence
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// The evidence a backend collects, as a typed value: the TEE type, the raw
+// report or quote, auxiliary data the verifier needs with it (the services
+// manifest of SVSM evidence) and claims describing how the report was
+// requested (the SVSM service GUID and manifest version). `encode` turns it
+// into the base64 `tee-evidence` of the get_secret request:
+//
+//   - the report, for plain SEV-SNP reports and TDX quotes,
+//   - base64 JSON of {"report", "manifest", "service_guid",
+//     "manifest_version"}, for evidence with auxiliary data.
+//
+// Evidence crosses the privilege separation socket as JSON, with the
+// report and auxiliary data in base64.
+//
+// No unsafe code.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+/// TEE type of SEV-SNP evidence obtained through an SVSM.
+pub const SVSM_TEE_TYPE: &str = "amd-sev-snp-svsm";
+
+/// The kind of TEE evidence comes from, as named to TAS.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum TeeType {
+    /// An AMD SEV-SNP attestation report
+    AmdSevSnp,
+    /// An SEV-SNP report requested through an SVSM, with its manifest
+    AmdSevSnpSvsm,
+    /// An Intel TDX quote
+    IntelTdx,
+    /// A TEE type from `[tsm.providers]` or `--tee-type`
+    Other(String),
+}
+
+impl TeeType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TeeType::AmdSevSnp => "amd-sev-snp",
+            TeeType::AmdSevSnpSvsm => SVSM_TEE_TYPE,
+            TeeType::IntelTdx => "intel-tdx",
+            TeeType::Other(name) => name,
+        }
+    }
+}
+
+impl From<&str> for TeeType {
+    fn from(name: &str) -> Self {
+        match name {
+            "amd-sev-snp" => TeeType::AmdSevSnp,
+            SVSM_TEE_TYPE => TeeType::AmdSevSnpSvsm,
+            "intel-tdx" => TeeType::IntelTdx,
+            other => TeeType::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for TeeType {
+    fn from(name: String) -> Self {
+        TeeType::from(name.as_str())
+    }
+}
+
+impl From<TeeType> for String {
+    fn from(tee_type: TeeType) -> Self {
+        tee_type.as_str().to_string()
+    }
+}
+
+impl fmt::Display for TeeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Evidence collected from a TEE.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Evidence {
+    pub tee_type: TeeType,
+    /// The raw report or quote
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    pub report: Vec<u8>,
+    /// Data the verifier needs besides the report, e.g. the SVSM manifest
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_base64_optional",
+        deserialize_with = "deserialize_base64_optional"
+    )]
+    pub aux: Option<Vec<u8>>,
+    /// How the report was requested, e.g. the SVSM service GUID
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub claims: Map<String, Value>,
+}
+
+impl Evidence {
+    /// Evidence of a bare report or quote.
+    pub fn new(tee_type: TeeType, report: Vec<u8>) -> Self {
+        Evidence {
+            tee_type,
+            report,
+            aux: None,
+            claims: Map::new(),
+        }
+    }
+
+    /// The base64 `tee-evidence` of a get_secret request.
+    pub fn encode(&self) -> String {
+        let Some(manifest) = &self.aux else {
+            return STANDARD.encode(&self.report);
+        };
+        let claim = |name: &str| self.claims.get(name).cloned().unwrap_or(Value::Null);
+        let envelope = json!({
+            "report": STANDARD.encode(&self.report),
+            "manifest": STANDARD.encode(manifest),
+            "service_guid": claim("service_guid"),
+            "manifest_version": claim("manifest_version"),
+        });
+        STANDARD.encode(envelope.to_string())
+    }
+}
+
+fn serialize_base64<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&STANDARD.encode(bytes))
+}
+
+fn serialize_base64_optional<S: Serializer>(
+    bytes: &Option<Vec<u8>>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    match bytes {
+        Some(bytes) => serialize_base64(bytes, s),
+        None => s.serialize_none(),
+    }
+}
+
+fn deserialize_base64<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+    let encoded = String::deserialize(d)?;
+    STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_base64_optional<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<Vec<u8>>, D::Error> {
+    deserialize_base64(d).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tee_type() {
+        for name in ["amd-sev-snp", SVSM_TEE_TYPE, "intel-tdx", "arm-cca"] {
+            assert_eq!(TeeType::from(name).as_str(), name);
+        }
+        assert_eq!(TeeType::from("intel-tdx"), TeeType::IntelTdx);
+        assert_eq!(
+            TeeType::from("arm-cca"),
+            TeeType::Other("arm-cca".to_string())
+        );
+        assert_eq!(
+            serde_json::to_string(&TeeType::AmdSevSnpSvsm).unwrap(),
+            "\"amd-sev-snp-svsm\""
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let evidence = Evidence::new(TeeType::AmdSevSnp, b"report".to_vec());
+        assert_eq!(evidence.encode(), STANDARD.encode("report"));
+
+        let mut evidence = Evidence::new(TeeType::AmdSevSnpSvsm, b"report".to_vec());
+        evidence.aux = Some(b"manifest".to_vec());
+        evidence.claims.insert(
+            "service_guid".to_string(),
+            "c476f1eb-0123-45a5-9641-b4e7dde5bfe3".into(),
+        );
+        let envelope: Value =
+            serde_json::from_slice(&STANDARD.decode(evidence.encode()).unwrap()).unwrap();
+        assert_eq!(envelope["report"], STANDARD.encode("report"));
+        assert_eq!(envelope["manifest"], STANDARD.encode("manifest"));
+        assert_eq!(
+            envelope["service_guid"],
+            "c476f1eb-0123-45a5-9641-b4e7dde5bfe3"
+        );
+        assert!(envelope["manifest_version"].is_null());
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let mut evidence = Evidence::new(TeeType::Other("arm-cca".to_string()), vec![0, 1, 2]);
+        evidence.aux = Some(vec![3]);
+        evidence
+            .claims
+            .insert("manifest_version".to_string(), 0.into());
+        let json = serde_json::to_string(&evidence).unwrap();
+        assert!(json.contains(r#""report":"AAEC""#), "{}", json);
+        assert_eq!(serde_json::from_str::<Evidence>(&json).unwrap(), evidence);
+
+        let plain = Evidence::new(TeeType::IntelTdx, vec![4]);
+        let json = serde_json::to_string(&plain).unwrap();
+        assert_eq!(json, r#"{"tee_type":"intel-tdx","report":"BA=="}"#);
+        assert_eq!(serde_json::from_str::<Evidence>(&json).unwrap(), plain);
+    }
+}
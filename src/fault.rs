@@ -0,0 +1,138 @@
+// TEE Attestation Service Agent — fault injection
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Deliberate failures for resilience testing, selected with the hidden
+// `--inject-fault <FAULT>` flag (repeatable) or a comma-separated list in
+// TAS_AGENT_INJECT_FAULT:
+//
+//   nonce-timeout    fetching the TAS nonce times out
+//   release-500      TAS answers the key release with HTTP 500
+//   corrupt-tag      the AES-GCM tag (or AES-KWP / AES-GCM-STREAM blob) of
+//                    the released payload is corrupted, so decryption fails
+//   missing-outblob  configfs-tsm produces no outblob
+//
+// Each fault surfaces as the error the real failure would, at the phase it
+// names, after the HTTP client's own retries: operators can check that
+// hooks, audit records, daemon retries and init container reporting behave
+// as intended without breaking a server or a TEE. Every injection is logged
+// and recorded as a "fault-injected" audit event.
+//
+// No unsafe code.
+
+use crate::audit;
+use log::warn;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Environment variable listing the faults to inject.
+pub const FAULT_ENV: &str = "TAS_AGENT_INJECT_FAULT";
+
+static FAULTS: OnceLock<Vec<Fault>> = OnceLock::new();
+
+/// A failure that can be injected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    NonceTimeout,
+    Release500,
+    CorruptTag,
+    MissingOutblob,
+}
+
+impl Fault {
+    const ALL: [Fault; 4] = [
+        Fault::NonceTimeout,
+        Fault::Release500,
+        Fault::CorruptTag,
+        Fault::MissingOutblob,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Fault::NonceTimeout => "nonce-timeout",
+            Fault::Release500 => "release-500",
+            Fault::CorruptTag => "corrupt-tag",
+            Fault::MissingOutblob => "missing-outblob",
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Fault {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Fault::ALL
+            .into_iter()
+            .find(|fault| fault.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Fault::ALL.iter().map(|fault| fault.name()).collect();
+                format!(
+                    "unknown fault {:?} (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Parse a comma-separated list of faults, warning about unknown names.
+fn parse_list(list: &str) -> Vec<Fault> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| match name.parse() {
+            Ok(fault) => Some(fault),
+            Err(e) => {
+                warn!("{}: {}", FAULT_ENV, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Enable the faults given on the command line and in TAS_AGENT_INJECT_FAULT.
+pub fn init(cli: &[Fault]) {
+    let mut faults = cli.to_vec();
+    if let Ok(list) = std::env::var(FAULT_ENV) {
+        faults.extend(parse_list(&list));
+    }
+    for fault in &faults {
+        warn!("Fault injection enabled: {}", fault);
+    }
+    let _ = FAULTS.set(faults);
+}
+
+/// Whether `fault` is enabled. Records the injection when it is.
+pub fn fire(fault: Fault) -> bool {
+    let enabled = FAULTS.get().is_some_and(|faults| faults.contains(&fault));
+    if enabled {
+        warn!("Injecting fault: {}", fault);
+        audit::record("fault-injected", &[("fault", fault.name())]);
+    }
+    enabled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fault_names() {
+        for fault in Fault::ALL {
+            assert_eq!(fault.to_string().parse::<Fault>(), Ok(fault));
+        }
+        assert!("release-404".parse::<Fault>().is_err());
+        assert_eq!(
+            parse_list(" nonce-timeout,, corrupt-tag ,bogus"),
+            vec![Fault::NonceTimeout, Fault::CorruptTag]
+        );
+    }
+}
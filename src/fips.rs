@@ -0,0 +1,90 @@
+// TEE Attestation Service Agent — FIPS-validated cryptography
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// In a build with the `fips` feature, the cryptography of the key-release
+// path runs in the AWS-LC FIPS 140-3 module (aws-lc-rs with its `fips`
+// feature) instead of the RustCrypto crates:
+//
+//   - generation of the ephemeral RSA wrapping key
+//   - RSA-OAEP (SHA-256) unwrap of the AES key
+//   - AES-256-GCM decryption of the secret payload, including chunked
+//     payloads
+//   - AES Key Wrap with Padding unwrap of AES-KWP payloads
+//
+// The agent refuses to start if the module is not operating in FIPS mode.
+// Key encodings, evidence hashing and the test-only encryption helpers stay
+// on the RustCrypto crates; they protect no released secret.
+//
+// No unsafe code.
+
+use anyhow::{anyhow, Result};
+use aws_lc_rs::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
+use aws_lc_rs::encoding::AsDer;
+use aws_lc_rs::key_wrap::{AesKek, KeyWrapPadded, AES_256};
+use aws_lc_rs::rsa::{
+    KeySize, OaepPrivateDecryptingKey, PrivateDecryptingKey, OAEP_SHA256_MGF1SHA256,
+};
+use std::error::Error;
+use zeroize::Zeroizing;
+
+/// Fail unless the AWS-LC module runs in FIPS mode, which includes its
+/// power-on self tests.
+pub fn check_mode() -> Result<()> {
+    aws_lc_rs::try_fips_mode().map_err(|e| anyhow!("FIPS mode is not available: {}", e))
+}
+
+/// Generate an RSA private key of `bits` bits, as PKCS#8 DER.
+pub fn generate_rsa_pkcs8(bits: usize) -> Result<Zeroizing<Vec<u8>>, Box<dyn Error>> {
+    let size = match bits {
+        2048 => KeySize::Rsa2048,
+        3072 => KeySize::Rsa3072,
+        4096 => KeySize::Rsa4096,
+        _ => return Err("Key bits must be 2048, 3072 or 4096".into()),
+    };
+    let key = PrivateDecryptingKey::generate(size).map_err(|_| "FIPS RSA key generation failed")?;
+    let der = key.as_der().map_err(|_| "Failed to encode FIPS RSA key")?;
+    Ok(Zeroizing::new(der.as_ref().to_vec()))
+}
+
+/// RSA-OAEP (SHA-256, MGF1-SHA-256) decryption with a PKCS#8 DER private key.
+pub fn rsa_oaep_decrypt(pkcs8: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let key = PrivateDecryptingKey::from_pkcs8(pkcs8)
+        .map_err(|e| format!("Failed to load private key: {}", e))?;
+    let key = OaepPrivateDecryptingKey::new(key).map_err(|_| "Unsupported RSA key")?;
+    let mut output = Zeroizing::new(vec![0u8; key.min_output_size()]);
+    let plaintext = key
+        .decrypt(&OAEP_SHA256_MGF1SHA256, ciphertext, &mut output, None)
+        .map_err(|_| "RSA-OAEP decryption error")?;
+    Ok(plaintext.to_vec())
+}
+
+/// AES-256-GCM decryption of `buf` in place with a detached `tag`.
+pub fn aes_gcm_open(
+    aes_key: &[u8],
+    nonce: &[u8],
+    aad: &[u8],
+    buf: &mut [u8],
+    tag: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let key = LessSafeKey::new(
+        UnboundKey::new(&AES_256_GCM, aes_key).map_err(|_| "Invalid AES-256-GCM key")?,
+    );
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Invalid AES-GCM nonce")?;
+    // The ciphertext is copied out so the plaintext lands in `buf` itself
+    let ciphertext = buf.to_vec();
+    key.open_separate_gather(nonce, Aad::from(aad), &ciphertext, tag, buf)
+        .map_err(|_| "Decryption error: aead::Error")?;
+    Ok(())
+}
+
+/// Unwrap an AES Key Wrap with Padding (RFC 5649) secret.
+pub fn aes_kwp_unwrap(aes_key: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let kek = AesKek::new(&AES_256, aes_key).map_err(|_| "Invalid AES Key Wrap key")?;
+    let mut output = Zeroizing::new(vec![0u8; wrapped.len()]);
+    let secret = kek
+        .unwrap_with_padding(wrapped, &mut output)
+        .map_err(|_| "AES Key Wrap unwrapping failed")?;
+    Ok(secret.to_vec())
+}
@@ -0,0 +1,49 @@
+// TEE Attestation Service Agent — golden-file assertions
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Tests compare wire formats (request bodies, key encodings) against files
+// under testdata/golden, so that unintended drift breaks the build instead
+// of server compatibility. When a change is intended, regenerate the files
+// with `UPDATE_GOLDEN=1 cargo test` and review the diff.
+//
+// No unsafe code.
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Environment variable that makes tests rewrite the golden files.
+const UPDATE_ENV: &str = "UPDATE_GOLDEN";
+
+/// Path of the golden file `name`.
+pub fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("testdata/golden")
+        .join(name)
+}
+
+/// Contents of the golden file `name`, without the trailing newline.
+pub fn read(name: &str) -> String {
+    let path = path(name);
+    let data = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("unable to read {:?}: {} (set {})", path, e, UPDATE_ENV));
+    data.strip_suffix('\n').unwrap_or(&data).to_string()
+}
+
+/// Assert that `actual` matches the golden file `name`, or rewrite the file
+/// when UPDATE_GOLDEN is set.
+pub fn assert_golden(name: &str, actual: &str) {
+    if std::env::var_os(UPDATE_ENV).is_some() {
+        fs::write(path(name), format!("{}\n", actual)).unwrap();
+        return;
+    }
+    assert_eq!(
+        actual,
+        read(name),
+        "{} no longer matches its golden file; if the change is intended, rerun with {}=1 \
+         and review the diff",
+        name,
+        UPDATE_ENV
+    );
+}
@@ -0,0 +1,277 @@
+// TEE Attestation Service Agent — gRPC façade
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Serves a subset of the Confidential Containers attestation-agent and
+// confidential-data-hub gRPC APIs in daemon mode, so components that expect
+// an attestation-agent endpoint can use tas_agent as a drop-in backend:
+//
+//   attestation_agent.AttestationAgentService/GetEvidence
+//     Returns fresh TEE evidence whose report data is SHA-512(RuntimeData),
+//     as JSON: {"tee-type": "...", "evidence": "<base64>"}.
+//   attestation_agent.AttestationAgentService/GetToken
+//     Not supported: TAS releases secrets rather than attestation tokens.
+//   api.GetResourceService/GetResource
+//     Returns the latest secret the daemon holds for the policy ID named by
+//     ResourcePath (a leading '/' is ignored).
+//
+// The server listens on a unix socket. Like the local API (see local_api.rs),
+// callers are authenticated with SO_PEERCRED against `allowed_uids` /
+// `allowed_gids` before any RPC is served.
+
+use crate::audit;
+use crate::daemon::SecretStore;
+use crate::local_api::{self, AccessControl};
+use crate::privsep;
+use anyhow::{Context, Result};
+use log::info;
+use sha2::{Digest, Sha512};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::UnixListener;
+use tokio_stream::wrappers::UnixListenerStream;
+use tonic::transport::server::UdsConnectInfo;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+pub mod pb {
+    pub mod attestation_agent {
+        tonic::include_proto!("attestation_agent");
+    }
+    pub mod api {
+        tonic::include_proto!("api");
+    }
+}
+
+use pb::api::get_resource_service_server::{GetResourceService, GetResourceServiceServer};
+use pb::api::{GetResourceRequest, GetResourceResponse};
+use pb::attestation_agent::attestation_agent_service_server::{
+    AttestationAgentService, AttestationAgentServiceServer,
+};
+use pb::attestation_agent::{
+    GetEvidenceRequest, GetEvidenceResponse, GetTokenRequest, GetTokenResponse,
+};
+
+/// Reject callers whose peer credentials are not allowed.
+#[allow(clippy::result_large_err)] // tonic handlers return Status unboxed
+fn authorize<T>(request: &Request<T>, access: &AccessControl) -> Result<(), Status> {
+    let cred = request
+        .extensions()
+        .get::<UdsConnectInfo>()
+        .and_then(|info| info.peer_cred);
+    match cred {
+        Some(cred) if access.is_authorized(cred.uid(), cred.gid()) => Ok(()),
+        _ => {
+            let uid = cred.map_or_else(|| "unknown".to_string(), |c| c.uid().to_string());
+            audit::record("grpc-request", &[("uid", &uid), ("result", "denied")]);
+            Err(Status::permission_denied("peer is not allowed"))
+        }
+    }
+}
+
+/// Collect TEE evidence bound to `runtime_data`.
+fn collect_evidence(runtime_data: &[u8]) -> Result<Vec<u8>, String> {
+    let report_data = Sha512::digest(runtime_data);
+    // Evidence collection requires a 64-character nonce even though only the
+    // report data is written to the inblob.
+    let nonce = hex::encode(&report_data[..32]);
+    // Through the evidence helper when running as `[daemon] user`
+    let evidence = privsep::get_evidence(&nonce, Some(&report_data))?;
+    Ok(serde_json::json!({
+        "tee-type": evidence.tee_type.as_str(),
+        "evidence": evidence.encode(),
+    })
+    .to_string()
+    .into_bytes())
+}
+
+struct AttestationAgent {
+    access: Arc<AccessControl>,
+}
+
+#[tonic::async_trait]
+impl AttestationAgentService for AttestationAgent {
+    async fn get_evidence(
+        &self,
+        request: Request<GetEvidenceRequest>,
+    ) -> Result<Response<GetEvidenceResponse>, Status> {
+        authorize(&request, &self.access)?;
+        let runtime_data = request.into_inner().runtime_data;
+        let evidence = tokio::task::spawn_blocking(move || collect_evidence(&runtime_data))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(Status::internal)?;
+        Ok(Response::new(GetEvidenceResponse { evidence }))
+    }
+
+    async fn get_token(
+        &self,
+        request: Request<GetTokenRequest>,
+    ) -> Result<Response<GetTokenResponse>, Status> {
+        Err(Status::unimplemented(format!(
+            "token type {:?} is not supported: TAS does not issue attestation tokens",
+            request.into_inner().token_type
+        )))
+    }
+}
+
+struct ResourceService {
+    store: Arc<SecretStore>,
+    access: Arc<AccessControl>,
+}
+
+#[tonic::async_trait]
+impl GetResourceService for ResourceService {
+    async fn get_resource(
+        &self,
+        request: Request<GetResourceRequest>,
+    ) -> Result<Response<GetResourceResponse>, Status> {
+        authorize(&request, &self.access)?;
+        let resource_path = request.into_inner().resource_path;
+        let policy_id = resource_path.trim_start_matches('/');
+        let resource = self.store.get(policy_id).await;
+        let result = if resource.is_some() {
+            "ok"
+        } else {
+            "not found"
+        };
+        audit::record(
+            "grpc-get-resource",
+            &[("resource", &resource_path), ("result", result)],
+        );
+        match resource {
+            Some(resource) => Ok(Response::new(GetResourceResponse { resource })),
+            None => Err(Status::not_found(format!(
+                "no secret available for {:?}",
+                resource_path
+            ))),
+        }
+    }
+}
+
+/// Bind the gRPC socket at `path`, replacing a stale socket from a previous
+/// run.
+pub fn bind(path: &Path) -> Result<UnixListener> {
+    local_api::bind(path)
+}
+
+/// Serve the gRPC API on `listener` until the task is cancelled.
+pub async fn serve(
+    listener: UnixListener,
+    store: Arc<SecretStore>,
+    access: Arc<AccessControl>,
+) -> Result<()> {
+    info!("Serving gRPC API on {:?}", listener.local_addr()?);
+    Server::builder()
+        .add_service(AttestationAgentServiceServer::new(AttestationAgent {
+            access: access.clone(),
+        }))
+        .add_service(GetResourceServiceServer::new(ResourceService {
+            store,
+            access,
+        }))
+        .serve_with_incoming(UnixListenerStream::new(listener))
+        .await
+        .context("gRPC server failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local_api::Access;
+    use tokio::net::unix::UCred;
+
+    fn access() -> Arc<AccessControl> {
+        Arc::new(AccessControl::new(Access {
+            allowed_uids: vec![0],
+            allowed_gids: vec![],
+        }))
+    }
+
+    /// A request as the server hands it over from a unix socket peer.
+    fn from_peer<T>(message: T, cred: Option<UCred>) -> Request<T> {
+        let mut request = Request::new(message);
+        request.extensions_mut().insert(UdsConnectInfo {
+            peer_addr: None,
+            peer_cred: cred,
+        });
+        request
+    }
+
+    /// Credentials of this test process.
+    async fn own_cred() -> UCred {
+        let (a, _b) = tokio::net::UnixStream::pair().unwrap();
+        a.peer_cred().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_token_unimplemented() {
+        let status = AttestationAgent { access: access() }
+            .get_token(Request::new(GetTokenRequest {
+                token_type: "kbs".to_string(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unimplemented);
+    }
+
+    #[tokio::test]
+    async fn test_get_resource() {
+        let cred = own_cred().await;
+        let store = Arc::new(SecretStore::default());
+        store.insert("luks", b"secret".to_vec()).await;
+        let access = Arc::new(AccessControl::new(Access {
+            allowed_uids: vec![cred.uid()],
+            allowed_gids: vec![],
+        }));
+        let service = ResourceService { store, access };
+
+        let response = service
+            .get_resource(from_peer(
+                GetResourceRequest {
+                    resource_path: "/luks".to_string(),
+                },
+                Some(cred),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().resource, b"secret");
+
+        let status = service
+            .get_resource(from_peer(
+                GetResourceRequest {
+                    resource_path: "other".to_string(),
+                },
+                Some(cred),
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_get_resource_requires_allowed_peer() {
+        let cred = own_cred().await;
+        let store = Arc::new(SecretStore::default());
+        store.insert("luks", b"secret".to_vec()).await;
+        let access = Arc::new(AccessControl::new(Access {
+            allowed_uids: vec![cred.uid().wrapping_add(1)],
+            allowed_gids: vec![],
+        }));
+        let service = ResourceService { store, access };
+
+        for cred in [Some(cred), None] {
+            let status = service
+                .get_resource(from_peer(
+                    GetResourceRequest {
+                        resource_path: "/luks".to_string(),
+                    },
+                    cred,
+                ))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+// TEE Attestation Service Agent — process hardening
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Called first thing in main, before any key material exists: the core file
+// size limit is set to zero and the process is marked non-dumpable
+// (PR_SET_DUMPABLE=0), which also stops other processes of the same user
+// from attaching with ptrace or reading its memory through /proc. A crash
+// therefore never writes the wrapping key, the unwrapped AES key or a
+// decrypted payload to a core file, whatever the system's core_pattern.
+//
+// The one-shot modes continue with a warning if this fails; the daemon,
+// which holds secrets for its whole lifetime, refuses to start. The flag is
+// reset by execve, so the privilege-separated daemon process sets it again
+// when it starts.
+//
+// No unsafe code.
+
+use anyhow::{Context, Result};
+use rustix::process::{set_dumpable_behavior, setrlimit, DumpableBehavior, Resource, Rlimit};
+
+/// Disable core dumps and ptrace attachment for this process.
+pub fn disable_core_dumps() -> Result<()> {
+    let no_core = Rlimit {
+        current: Some(0),
+        maximum: Some(0),
+    };
+    setrlimit(Resource::Core, no_core).context("unable to set RLIMIT_CORE to 0")?;
+    set_dumpable_behavior(DumpableBehavior::NotDumpable)
+        .context("unable to mark the process non-dumpable")?;
+    Ok(())
+}
+
+/// Whether `disable_core_dumps` is in effect.
+#[cfg(any(test, feature = "daemon"))]
+pub fn core_dumps_disabled() -> bool {
+    rustix::process::dumpable_behavior().is_ok_and(|b| b == DumpableBehavior::NotDumpable)
+        && rustix::process::getrlimit(Resource::Core).maximum == Some(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disable_core_dumps() {
+        disable_core_dumps().unwrap();
+        assert!(core_dumps_disabled());
+    }
+}
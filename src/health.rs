@@ -0,0 +1,227 @@
+// TEE Attestation Service Agent — health endpoints
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Serves `/healthz` and `/readyz` on a loopback TCP address in daemon mode,
+// so node health checks can incorporate attestation state. Both return a
+// JSON report and never expose secrets:
+//
+//   {"status": "ok", "last_attempt": "<rfc3339>", "last_success": "<rfc3339>",
+//    "last_error_class": null, "tas_reachable": true, "failure_streaks": []}
+//
+// The endpoints are unauthenticated, so failures are reported by their error
+// class (see hooks.rs) only. Messages can name policy IDs, server URIs, file
+// paths and server responses, and stay in the log.
+//
+// `/healthz` answers 200 while the daemon is running. `/readyz` answers 200
+// only when the most recent key release succeeded, and 503 otherwise.
+// TAS connectivity is checked with a version probe whose result is cached for
+// PROBE_TTL, so frequent health checks do not load the server.
+// `failure_streaks` lists the policy IDs whose releases keep failing, by
+// their index in the configured list, with the number of failures and when
+// they are retried (see streak.rs).
+//
+// No unsafe code.
+
+use crate::daemon::Releaser;
+use crate::hooks::{self, ErrorClass};
+use crate::local_api::{read_request, write_response, Request, Response, MAX_REQUEST_BYTES};
+use crate::streak::Streaks;
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use log::{debug, info, warn};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{Duration, Instant};
+
+/// How long a TAS connectivity probe result is reused.
+const PROBE_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct Status {
+    last_attempt: Option<String>,
+    last_success: Option<String>,
+    last_error: Option<ErrorClass>,
+}
+
+/// Outcome of the daemon's key releases, for the health endpoints.
+#[derive(Default)]
+pub struct Health {
+    status: Mutex<Status>,
+    probe: tokio::sync::Mutex<Option<(Instant, bool)>>,
+    /// Consecutive failures of each policy ID
+    pub streaks: Streaks,
+}
+
+impl Health {
+    /// Record the result of a key release.
+    pub fn record<T>(&self, result: &Result<T>) {
+        let mut status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Utc::now().to_rfc3339();
+        match result {
+            Ok(_) => {
+                status.last_success = Some(now.clone());
+                status.last_error = None;
+            }
+            Err(e) => status.last_error = Some(hooks::error_class(e)),
+        }
+        status.last_attempt = Some(now);
+    }
+
+    /// Record the result of a TAS connectivity probe made elsewhere.
+    pub async fn record_probe(&self, reachable: bool) {
+        *self.probe.lock().await = Some((Instant::now(), reachable));
+    }
+
+    fn is_ready(&self) -> bool {
+        let status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        status.last_success.is_some() && status.last_error.is_none()
+    }
+
+    fn report(&self, tas_reachable: bool, policy_ids: &[String]) -> serde_json::Value {
+        let ready = self.is_ready();
+        let status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        serde_json::json!({
+            "status": if ready { "ok" } else { "unavailable" },
+            "last_attempt": status.last_attempt,
+            "last_success": status.last_success,
+            "last_error_class": status.last_error.map(ErrorClass::as_str),
+            "tas_reachable": tas_reachable,
+            "failure_streaks": self.streaks.report(policy_ids),
+        })
+    }
+}
+
+/// Whether TAS answers a version probe, using the cached result if recent.
+async fn tas_reachable(releaser: &Releaser) -> bool {
+    let mut probe = releaser.health.probe.lock().await;
+    if let Some((checked, reachable)) = *probe {
+        if checked.elapsed() < PROBE_TTL {
+            return reachable;
+        }
+    }
+    let reachable = match releaser.probe_tas().await {
+        Ok(_) => true,
+        Err(e) => {
+            debug!("TAS connectivity probe failed: {:#}", e);
+            false
+        }
+    };
+    *probe = Some((Instant::now(), reachable));
+    reachable
+}
+
+async fn route(request: &Request, releaser: &Releaser) -> Response {
+    let readiness = match request.path.as_str() {
+        "/healthz" => false,
+        "/readyz" => true,
+        _ => return Response::text(404, "Not Found"),
+    };
+    if request.method != "GET" {
+        return Response::text(405, "Method Not Allowed");
+    }
+
+    let report = releaser
+        .health
+        .report(tas_reachable(releaser).await, &releaser.policy_ids());
+    if readiness && !releaser.health.is_ready() {
+        Response::json(503, "Service Unavailable", &report)
+    } else {
+        Response::json(200, "OK", &report)
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, releaser: Arc<Releaser>) -> Result<()> {
+    let (read_half, mut write_half) = stream.split();
+    let mut reader = BufReader::new(read_half.take(MAX_REQUEST_BYTES));
+    let response = match read_request(&mut reader).await {
+        Ok(request) => route(&request, &releaser).await,
+        Err(e) => {
+            debug!("Bad health request: {:#}", e);
+            Response::text(400, "Bad Request")
+        }
+    };
+    write_response(&mut write_half, &response).await
+}
+
+/// Serve the health endpoints on `addr` until the task is cancelled.
+pub async fn serve(addr: SocketAddr, releaser: Arc<Releaser>) -> Result<()> {
+    if !addr.ip().is_loopback() {
+        return Err(anyhow!("health address {} is not a loopback address", addr));
+    }
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("unable to bind health endpoint on {}", addr))?;
+    info!("Serving health endpoints on {}", addr);
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let releaser = releaser.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream, releaser).await {
+                        debug!("Health connection error: {:#}", e);
+                    }
+                });
+            }
+            Err(e) => warn!("Health endpoint accept failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health_record() {
+        let health = Health::default();
+        let policy_ids = ["root".to_string(), "luks".to_string()];
+        assert!(!health.is_ready());
+
+        health.record(&Ok(()));
+        assert!(health.is_ready());
+        let report = health.report(true, &policy_ids);
+        assert_eq!(report["status"], "ok");
+        assert!(report["last_success"].is_string());
+        assert!(report["last_error_class"].is_null());
+
+        health.record::<()>(&Err(
+            ErrorClass::Release.error("TAS Secret Error: policy luks denied")
+        ));
+        assert!(!health.is_ready());
+        let report = health.report(false, &policy_ids);
+        assert_eq!(report["status"], "unavailable");
+        assert_eq!(report["last_error_class"], "release");
+        assert!(!report.to_string().contains("luks"), "{report}");
+        assert_eq!(report["tas_reachable"], false);
+        assert_eq!(report["failure_streaks"], serde_json::json!([]));
+
+        health.streaks.record::<()>(
+            "luks",
+            &Err(anyhow!("TAS Nonce Error: refused")),
+            Duration::from_secs(60),
+        );
+        let report = health.report(false, &policy_ids);
+        assert_eq!(report["failure_streaks"][0]["policy"], 1);
+        assert_eq!(report["failure_streaks"][0]["failures"], 1);
+        assert!(!report.to_string().contains("luks"), "{report}");
+    }
+
+    #[tokio::test]
+    async fn test_serve_rejects_non_loopback() {
+        let releaser = Arc::new(Releaser::new(
+            None,
+            crate::CliOverrides::default(),
+            Arc::default(),
+            vec![],
+            Duration::ZERO,
+        ));
+        let err = serve("0.0.0.0:8099".parse().unwrap(), releaser)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("loopback"), "{err}");
+    }
+}
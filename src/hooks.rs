@@ -0,0 +1,275 @@
+// TEE Attestation Service Agent — exec hooks
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Runs operator-supplied programs after each key release, so notification,
+// quarantine or remediation logic can be wired in without patching the
+// agent. `on_success` runs after a secret is released and `on_failure` after
+// an attempt fails. Hooks are executed directly (no shell) with a minimal
+// environment:
+//
+//   PATH                     /usr/sbin:/usr/bin:/sbin:/bin
+//   TAS_AGENT_RESULT         "success" or "failure"
+//   TAS_AGENT_KEY_ID         policy ID of the key that was requested
+//   TAS_AGENT_TEE_TYPE       TEE type (e.g. "amd-sev-snp"), if evidence was
+//                            collected
+//   TAS_AGENT_ERROR_CLASS    on failure: config, network, clock-skew,
+//                            evidence, release, crypto or other
+//   TAS_AGENT_ERROR          on failure: the error message
+//   TAS_AGENT_REQUEST_ID     ID of the attestation attempt, as sent to TAS
+//                            (see request_id.rs)
+//
+// The secret itself is never passed to a hook. A hook that exits non-zero or
+// outlives `timeout_secs` (default 30) is logged and killed; it never changes
+// the outcome of the release.
+//
+// No unsafe code.
+
+use crate::request_id;
+use anyhow::Result;
+use log::{debug, warn};
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+/// Default number of seconds a hook may run.
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// `[hooks]` section of the config file.
+#[derive(Deserialize, Default, Clone)]
+pub struct HooksConfig {
+    /// Program to run after a secret is released
+    pub on_success: Option<PathBuf>,
+    /// Program to run after a release attempt fails
+    pub on_failure: Option<PathBuf>,
+    /// Seconds a hook may run before it is killed (default: 30)
+    pub timeout_secs: Option<u64>,
+}
+
+/// Stage an error failed at, given to hooks as TAS_AGENT_ERROR_CLASS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorClass {
+    Config,
+    Network,
+    ClockSkew,
+    Evidence,
+    Release,
+    Crypto,
+    Other,
+}
+
+impl ErrorClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorClass::Config => "config",
+            ErrorClass::Network => "network",
+            ErrorClass::ClockSkew => "clock-skew",
+            ErrorClass::Evidence => "evidence",
+            ErrorClass::Release => "release",
+            ErrorClass::Crypto => "crypto",
+            ErrorClass::Other => "other",
+        }
+    }
+
+    /// An error with `message` that failed at this stage.
+    pub fn error(self, message: impl fmt::Display) -> anyhow::Error {
+        anyhow::Error::new(self.context(message))
+    }
+
+    /// Context with `message` for an error that failed at this stage.
+    pub fn context(self, message: impl fmt::Display) -> Classified {
+        Classified {
+            class: self,
+            message: message.to_string(),
+        }
+    }
+
+    /// An error with `message` describing `cause`, which failed at this
+    /// stage. A network or clock skew failure behind `cause` keeps its class,
+    /// as the stage that ran into it is not at fault.
+    pub fn wrap(self, cause: &anyhow::Error, message: impl fmt::Display) -> anyhow::Error {
+        match error_class(cause) {
+            class @ (ErrorClass::Network | ErrorClass::ClockSkew) => class.error(message),
+            _ => self.error(message),
+        }
+    }
+}
+
+/// An error message tagged with the stage that failed.
+#[derive(Debug)]
+pub struct Classified {
+    class: ErrorClass,
+    message: String,
+}
+
+impl fmt::Display for Classified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Classified {}
+
+/// Classify an error for TAS_AGENT_ERROR_CLASS by the stage its failure site
+/// tagged it with; untagged errors are `Other`.
+pub fn error_class(err: &anyhow::Error) -> ErrorClass {
+    err.downcast_ref::<Classified>()
+        .map_or(ErrorClass::Other, |classified| classified.class)
+}
+
+/// Environment passed to a hook.
+fn hook_env<T>(
+    key_id: &str,
+    tee_type: Option<&str>,
+    result: &Result<T>,
+) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("PATH", "/usr/sbin:/usr/bin:/sbin:/bin".to_string()),
+        ("TAS_AGENT_KEY_ID", key_id.to_string()),
+    ];
+    if let Some(tee_type) = tee_type {
+        env.push(("TAS_AGENT_TEE_TYPE", tee_type.to_string()));
+    }
+    if let Some(id) = request_id::current() {
+        env.push(("TAS_AGENT_REQUEST_ID", id));
+    }
+    match result {
+        Ok(_) => env.push(("TAS_AGENT_RESULT", "success".to_string())),
+        Err(e) => {
+            env.push(("TAS_AGENT_RESULT", "failure".to_string()));
+            env.push(("TAS_AGENT_ERROR_CLASS", error_class(e).as_str().to_string()));
+            env.push(("TAS_AGENT_ERROR", format!("{:#}", e)));
+        }
+    }
+    env
+}
+
+/// Run the hook matching `result`, if one is configured.
+pub async fn run<T>(
+    hooks: Option<&HooksConfig>,
+    key_id: &str,
+    tee_type: Option<&str>,
+    result: &Result<T>,
+) {
+    let Some(hooks) = hooks else {
+        return;
+    };
+    let hook = match result {
+        Ok(_) => &hooks.on_success,
+        Err(_) => &hooks.on_failure,
+    };
+    let Some(hook) = hook else {
+        return;
+    };
+    let limit = Duration::from_secs(hooks.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+
+    let mut child = match Command::new(hook)
+        .env_clear()
+        .envs(hook_env(key_id, tee_type, result))
+        .current_dir("/")
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("unable to run hook {:?}: {}", hook, e);
+            return;
+        }
+    };
+    match timeout(limit, child.wait()).await {
+        Ok(Ok(status)) if status.success() => debug!("Hook {:?} completed", hook),
+        Ok(Ok(status)) => warn!("Hook {:?} failed: {}", hook, status),
+        Ok(Err(e)) => warn!("Hook {:?} failed: {}", hook, e),
+        Err(_) => {
+            warn!("Hook {:?} timed out after {}s", hook, limit.as_secs());
+            let _ = child.kill().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_error_class() {
+        let denied = ErrorClass::Release.error("TAS Secret Error: Error: Received HTTP 403");
+        assert_eq!(error_class(&denied), ErrorClass::Release);
+        // Tags survive context added further up
+        let denied = denied.context("daemon error");
+        assert_eq!(error_class(&denied), ErrorClass::Release);
+
+        let parse = "x"
+            .parse::<u32>()
+            .context(ErrorClass::Crypto.context("JSON Deserialize Error"))
+            .unwrap_err();
+        assert_eq!(error_class(&parse), ErrorClass::Crypto);
+        assert_eq!(parse.to_string(), "JSON Deserialize Error");
+
+        // A network or clock skew cause keeps its class when wrapped
+        let skew = ErrorClass::ClockSkew.error("clock skew: token expired at 1000");
+        let wrapped =
+            ErrorClass::Release.wrap(&skew, format!("Attestation Result Error: {:#}", skew));
+        assert_eq!(error_class(&wrapped), ErrorClass::ClockSkew);
+        let request = ErrorClass::Network.error("Error making request: timed out");
+        let wrapped = ErrorClass::Release.wrap(&request, "Verifier Error");
+        assert_eq!(error_class(&wrapped), ErrorClass::Network);
+        let wrapped = ErrorClass::Release.wrap(&denied, "KMS Release Error");
+        assert_eq!(error_class(&wrapped), ErrorClass::Release);
+
+        // Matching messages are not enough
+        assert_eq!(
+            error_class(&anyhow!("TAS Nonce Error: Error making request")),
+            ErrorClass::Other
+        );
+        assert_eq!(ErrorClass::ClockSkew.as_str(), "clock-skew");
+    }
+
+    #[test]
+    fn test_hook_env() {
+        let env = hook_env("luks", Some("amd-sev-snp"), &Ok(()));
+        assert!(env.contains(&("TAS_AGENT_RESULT", "success".to_string())));
+        assert!(env.contains(&("TAS_AGENT_TEE_TYPE", "amd-sev-snp".to_string())));
+        assert!(!env.iter().any(|(k, _)| *k == "TAS_AGENT_ERROR"));
+
+        let failed: Result<()> =
+            Err(ErrorClass::Release.error("TAS Secret Error: Error: Received HTTP 403"));
+        let env = hook_env("luks", None, &failed);
+        assert!(env.contains(&("TAS_AGENT_RESULT", "failure".to_string())));
+        assert!(env.contains(&("TAS_AGENT_ERROR_CLASS", "release".to_string())));
+        assert!(!env.iter().any(|(k, _)| *k == "TAS_AGENT_TEE_TYPE"));
+    }
+
+    #[tokio::test]
+    async fn test_run_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("ran");
+        let script = dir.path().join("hook.sh");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"$TAS_AGENT_RESULT $TAS_AGENT_KEY_ID\" > {}\n",
+                marker.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let hooks = HooksConfig {
+            on_success: Some(script),
+            ..Default::default()
+        };
+        run(Some(&hooks), "luks", None, &Ok(())).await;
+        assert_eq!(std::fs::read_to_string(&marker).unwrap(), "success luks\n");
+
+        // No failure hook configured: nothing runs.
+        std::fs::remove_file(&marker).unwrap();
+        run::<()>(Some(&hooks), "luks", None, &Err(anyhow!("x"))).await;
+        assert!(!marker.exists());
+    }
+}
@@ -0,0 +1,312 @@
+// TEE Attestation Service Agent — Kubernetes init container mode
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// `tas_agent init` runs as an init container on a confidential node. It
+// attests once, releases every secret listed in a mounted spec file (usually
+// a ConfigMap) and writes each into `--out-dir`, an emptyDir or tmpfs volume
+// shared with the application containers:
+//
+//   [[secrets]]
+//   policy_id = "luks-data"     # policy ID or KBS resource URI
+//   path = "db/key"             # relative to --out-dir
+//   mode = "0440"               # octal, default 0400
+//   uid = 1000                  # owner, default unchanged
+//   gid = 1000
+//
+// Secrets are released concurrently, at most `max_parallel_releases` (from
+// the config file) at a time, all sharing the one attestation. Files are
+// written atomically. The outcome is written as a JSON termination
+// message to `--termination-log` (by default the kubelet's
+// /dev/termination-log), and the agent exits non-zero on failure so the pod
+// does not start without its secrets.
+//
+// No unsafe code.
+
+use crate::{attest_prepared, hooks, prepare, release, request_id, resolve_session, CliOverrides};
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use zeroize::Zeroize;
+
+/// Default file mode of released secrets.
+const DEFAULT_MODE: u32 = 0o400;
+/// Longest termination message the kubelet keeps.
+const TERMINATION_MESSAGE_MAX: usize = 4096;
+
+/// A secret to release, from `[[secrets]]` in the spec file.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SecretSpec {
+    /// Policy ID or KBS resource URI of the secret
+    pub policy_id: String,
+    /// File to write, relative to the output directory
+    pub path: PathBuf,
+    /// Octal file mode (default: 0400)
+    #[serde(skip_serializing)]
+    pub mode: Option<String>,
+    /// Owner user ID
+    #[serde(skip_serializing)]
+    pub uid: Option<u32>,
+    /// Owner group ID
+    #[serde(skip_serializing)]
+    pub gid: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct Spec {
+    #[serde(default)]
+    secrets: Vec<SecretSpec>,
+}
+
+impl SecretSpec {
+    fn mode(&self) -> Result<u32> {
+        match &self.mode {
+            Some(mode) => u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                .ok()
+                .filter(|mode| *mode <= 0o777)
+                .ok_or_else(|| anyhow!("invalid mode {:?} for {:?}", mode, self.path)),
+            None => Ok(DEFAULT_MODE),
+        }
+    }
+}
+
+/// Load and validate the spec file at `path`.
+fn load_spec(path: &Path) -> Result<Vec<SecretSpec>> {
+    let data = fs::read_to_string(path).with_context(|| format!("unable to read {:?}", path))?;
+    let spec: Spec = toml::from_str(&data).with_context(|| format!("unable to load {:?}", path))?;
+    if spec.secrets.is_empty() {
+        return Err(anyhow!("{:?} lists no secrets", path));
+    }
+    for secret in &spec.secrets {
+        let relative = secret
+            .path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+        if !relative || secret.path.as_os_str().is_empty() {
+            return Err(anyhow!(
+                "secret path {:?} must be relative and stay inside the output directory",
+                secret.path
+            ));
+        }
+        secret.mode()?;
+    }
+    Ok(spec.secrets)
+}
+
+/// Atomically write `data` to `out_dir`/`spec.path` with the spec's mode and
+/// owner.
+fn write_secret(out_dir: &Path, spec: &SecretSpec, data: &[u8]) -> Result<()> {
+    let path = out_dir.join(&spec.path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("unable to create {:?}", parent))?;
+    }
+    let tmp = path.with_extension("tas-tmp");
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(spec.mode()?)
+        .open(&tmp)
+        .with_context(|| format!("unable to create {:?}", tmp))?;
+    file.write_all(data)
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("unable to write {:?}", tmp))?;
+    if spec.uid.is_some() || spec.gid.is_some() {
+        std::os::unix::fs::chown(&tmp, spec.uid, spec.gid)
+            .with_context(|| format!("unable to change the owner of {:?}", tmp))?;
+    }
+    fs::rename(&tmp, &path).with_context(|| format!("unable to rename {:?} to {:?}", tmp, path))
+}
+
+/// Build the termination message for `result`, given the secrets written.
+fn termination_message(result: &Result<String>, written: &[SecretSpec]) -> String {
+    let message = match result {
+        Ok(tee_type) => json!({
+            "result": "success",
+            "tee_type": tee_type,
+            "secrets": written,
+        }),
+        Err(e) => {
+            let mut error = format!("{:#}", e);
+            // Leave room for the rest of the message
+            if error.len() > TERMINATION_MESSAGE_MAX / 2 {
+                let mut end = TERMINATION_MESSAGE_MAX / 2;
+                while !error.is_char_boundary(end) {
+                    end -= 1;
+                }
+                error.truncate(end);
+            }
+            json!({
+                "result": "failure",
+                "error_class": hooks::error_class(e).as_str(),
+                "error": error,
+                "secrets": written,
+            })
+        }
+    };
+    message.to_string()
+}
+
+/// Attest and write every secret of `specs`, recording them in `written`.
+/// Returns the TEE type.
+async fn release_all(
+    config_path: Option<PathBuf>,
+    overrides: CliOverrides,
+    specs: &[SecretSpec],
+    out_dir: &Path,
+    written: &mut Vec<SecretSpec>,
+) -> Result<String> {
+    let mut session = resolve_session(config_path, Some(overrides))?;
+    let (version, preparation) = tokio::join!(session.cached_server_version(), prepare(&session));
+    let version = version
+        .map_err(|e| hooks::ErrorClass::Network.error(format!("TAS Version Error: {}", e)))?;
+    debug!("TEE Attestation Server Version: {}", version.version);
+    session.negotiate(&version);
+
+    let permits = Arc::new(Semaphore::new(session.max_parallel_releases));
+    let attestation = Arc::new(attest_prepared(session, preparation?).await?);
+
+    // Release the secrets concurrently with the one attestation; an error
+    // drops the set, cancelling the releases still in flight
+    let mut releases = JoinSet::new();
+    for spec in specs {
+        let attestation = attestation.clone();
+        let permits = permits.clone();
+        let spec = spec.clone();
+        releases.spawn(request_id::inherit(request_id::current(), async move {
+            let _permit = permits.acquire_owned().await;
+            let secret = release(&attestation, &spec.policy_id).await;
+            (spec, secret)
+        }));
+    }
+    while let Some(joined) = releases.join_next().await {
+        let (spec, secret) = joined.map_err(|e| anyhow!("secret release task failed: {}", e))?;
+        let mut secret = secret?;
+        let result = write_secret(out_dir, &spec, &secret);
+        secret.zeroize();
+        result?;
+        info!("Wrote {} to {:?}", spec.policy_id, spec.path);
+        written.push(spec);
+    }
+    Ok(attestation.tee_type().to_string())
+}
+
+/// Run the init container: release the secrets listed in `spec_path` into
+/// `out_dir` and report the outcome to `termination_log`.
+pub async fn run(
+    config_path: Option<PathBuf>,
+    overrides: CliOverrides,
+    spec_path: &Path,
+    out_dir: &Path,
+    termination_log: &Path,
+) -> Result<()> {
+    let mut written = Vec::new();
+    let result = match load_spec(spec_path) {
+        Ok(specs) => {
+            let release = release_all(config_path, overrides, &specs, out_dir, &mut written);
+            request_id::scope(release).await
+        }
+        Err(e) => Err(e),
+    };
+
+    let message = termination_message(&result, &written);
+    if let Err(e) = fs::write(termination_log, message) {
+        // Not running under the kubelet; the log has the details
+        debug!("unable to write {:?}: {}", termination_log, e);
+    }
+    result.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn spec(path: &str, mode: Option<&str>) -> SecretSpec {
+        SecretSpec {
+            policy_id: "luks".to_string(),
+            path: PathBuf::from(path),
+            mode: mode.map(str::to_string),
+            uid: None,
+            gid: None,
+        }
+    }
+
+    #[test]
+    fn test_load_spec() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.toml");
+        fs::write(
+            &path,
+            "[[secrets]]\npolicy_id = \"luks\"\npath = \"db/key\"\nmode = \"0440\"\n",
+        )
+        .unwrap();
+        let specs = load_spec(&path).unwrap();
+        assert_eq!(specs.len(), 1);
+        assert_eq!(specs[0].mode().unwrap(), 0o440);
+
+        for bad in ["/etc/shadow", "../key", "db/../../key"] {
+            fs::write(
+                &path,
+                format!("[[secrets]]\npolicy_id = \"luks\"\npath = \"{}\"\n", bad),
+            )
+            .unwrap();
+            assert!(load_spec(&path).is_err(), "{bad}");
+        }
+        fs::write(
+            &path,
+            "[[secrets]]\npolicy_id = \"luks\"\npath = \"key\"\nmode = \"999\"\n",
+        )
+        .unwrap();
+        assert!(load_spec(&path).is_err());
+        fs::write(&path, "").unwrap();
+        assert!(load_spec(&path).is_err());
+    }
+
+    #[test]
+    fn test_write_secret() {
+        let dir = tempfile::tempdir().unwrap();
+        write_secret(dir.path(), &spec("db/key", Some("0440")), b"s3cret").unwrap();
+        let path = dir.path().join("db/key");
+        assert_eq!(fs::read(&path).unwrap(), b"s3cret");
+        let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o440);
+
+        write_secret(dir.path(), &spec("plain", None), b"x").unwrap();
+        let mode = fs::metadata(dir.path().join("plain"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, DEFAULT_MODE);
+    }
+
+    #[test]
+    fn test_termination_message() {
+        let written = vec![spec("db/key", Some("0440"))];
+        let ok: serde_json::Value = serde_json::from_str(&termination_message(
+            &Ok("amd-sev-snp".to_string()),
+            &written,
+        ))
+        .unwrap();
+        assert_eq!(ok["result"], "success");
+        assert_eq!(ok["secrets"][0]["path"], "db/key");
+        assert!(ok["secrets"][0].get("mode").is_none());
+
+        let failed =
+            Err(hooks::ErrorClass::Release
+                .error(format!("TAS Secret Error: {}", "x".repeat(10000))));
+        let message = termination_message(&failed, &[]);
+        assert!(message.len() <= TERMINATION_MESSAGE_MAX);
+        let failed: serde_json::Value = serde_json::from_str(&message).unwrap();
+        assert_eq!(failed["error_class"], "release");
+    }
+}
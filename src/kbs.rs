@@ -0,0 +1,153 @@
+// TEE Attestation Service Agent — KBS resource URIs
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Confidential Containers components name secrets with KBS resource URIs:
+//
+//   kbs://<host[:port]>/<repository>/<type>/<tag>
+//   kbs:///<repository>/<type>/<tag>
+//
+// Wherever a policy ID is accepted, such a URI may be given instead. The
+// resource path `<repository>/<type>/<tag>` is requested from TAS as the key
+// ID. An empty authority means the configured server; a host names the TAS
+// server to use, which becomes the server URI (`https://<host>`) when none is
+// configured, and must otherwise match the configured one.
+//
+// No unsafe code.
+
+use crate::hooks::ErrorClass;
+use anyhow::Result;
+
+const SCHEME: &str = "kbs://";
+
+/// A parsed KBS resource URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceUri {
+    pub host: Option<String>,
+    pub repository: String,
+    pub kind: String,
+    pub tag: String,
+}
+
+impl ResourceUri {
+    /// Parse `id` if it is a KBS resource URI. Returns Ok(None) for plain
+    /// policy IDs.
+    pub fn parse(id: &str) -> Result<Option<Self>> {
+        let Some(rest) = id.strip_prefix(SCHEME) else {
+            return Ok(None);
+        };
+        let invalid = || ErrorClass::Config.error(format!("invalid KBS resource URI {:?}", id));
+        let (host, path) = rest.split_once('/').ok_or_else(invalid)?;
+        let segments: Vec<&str> = path.split('/').collect();
+        let [repository, kind, tag] = segments[..] else {
+            return Err(invalid());
+        };
+        for segment in [repository, kind, tag] {
+            if segment.is_empty()
+                || segment == "."
+                || segment == ".."
+                || segment.contains(['?', '#', '\0'])
+            {
+                return Err(invalid());
+            }
+        }
+        Ok(Some(ResourceUri {
+            host: (!host.is_empty()).then(|| host.to_string()),
+            repository: repository.to_string(),
+            kind: kind.to_string(),
+            tag: tag.to_string(),
+        }))
+    }
+
+    /// The resource path, requested from TAS as the key ID.
+    pub fn path(&self) -> String {
+        format!("{}/{}/{}", self.repository, self.kind, self.tag)
+    }
+}
+
+/// Host (and port) part of an http(s) server URI.
+fn server_host(server_uri: &str) -> &str {
+    let rest = server_uri
+        .split_once("://")
+        .map_or(server_uri, |(_, rest)| rest);
+    rest.split('/').next().unwrap_or(rest)
+}
+
+/// Server URI implied by `policy_id` when none is configured.
+pub fn server_uri(policy_id: &str) -> Result<Option<String>> {
+    Ok(ResourceUri::parse(policy_id)?
+        .and_then(|uri| uri.host)
+        .map(|host| format!("https://{}", host)))
+}
+
+/// The key ID to request from `server_uri` for `policy_id`.
+pub fn key_id(policy_id: &str, server_uri: &str) -> Result<String> {
+    let Some(uri) = ResourceUri::parse(policy_id)? else {
+        return Ok(policy_id.to_string());
+    };
+    if let Some(host) = &uri.host {
+        if host != server_host(server_uri) {
+            return Err(ErrorClass::Config.error(format!(
+                "KBS resource URI {:?} names a different server than {}",
+                policy_id, server_uri
+            )));
+        }
+    }
+    Ok(uri.path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ResourceUri::parse("luks-root").unwrap(), None);
+        let uri = ResourceUri::parse("kbs:///default/key/luks")
+            .unwrap()
+            .unwrap();
+        assert_eq!(uri.host, None);
+        assert_eq!(uri.path(), "default/key/luks");
+        let uri = ResourceUri::parse("kbs://tas.example.com:8443/repo/cert/web")
+            .unwrap()
+            .unwrap();
+        assert_eq!(uri.host.as_deref(), Some("tas.example.com:8443"));
+        assert_eq!(uri.kind, "cert");
+
+        for bad in [
+            "kbs://",
+            "kbs:///default/key",
+            "kbs:///default/key/luks/extra",
+            "kbs:///default//luks",
+            "kbs:///default/../luks",
+        ] {
+            assert!(ResourceUri::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_key_id() {
+        let server = "https://tas.example.com:8443";
+        assert_eq!(key_id("luks-root", server).unwrap(), "luks-root");
+        assert_eq!(
+            key_id("kbs:///default/key/luks", server).unwrap(),
+            "default/key/luks"
+        );
+        assert_eq!(
+            key_id("kbs://tas.example.com:8443/default/key/luks", server).unwrap(),
+            "default/key/luks"
+        );
+        assert!(key_id("kbs://other.example.com/default/key/luks", server).is_err());
+    }
+
+    #[test]
+    fn test_server_uri() {
+        assert_eq!(server_uri("luks-root").unwrap(), None);
+        assert_eq!(server_uri("kbs:///default/key/luks").unwrap(), None);
+        assert_eq!(
+            server_uri("kbs://tas.example.com/default/key/luks").unwrap(),
+            Some("https://tas.example.com".to_string())
+        );
+    }
+}
@@ -0,0 +1,245 @@
+// TEE Attestation Service Agent — pre-generated wrapping keys
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With a `[key_pool]` section, RSA wrapping keys are generated ahead of time
+// and kept under `dir`, each encrypted (AES Key Wrap with Padding, RFC 5649)
+// with a random key sealed to the TPM — and, if `pcrs` is set, to the
+// current values of that PCR selection. Attestation then takes a key from
+// the pool instead of generating one, taking RSA key generation (up to
+// seconds on a small VM) off the boot critical path.
+//
+// Pooled keys are single-use: an entry's files are removed before its key is
+// unsealed, so a key backs at most one attestation even if the agent dies
+// half-way. An empty or unusable pool falls back to generating a key.
+//
+// `tas_agent key-pool` fills the pool up to `size` keys, e.g. from an
+// initramfs preparation step or late in the previous boot; the daemon tops
+// it up after every round of releases.
+//
+// No unsafe code.
+
+use crate::audit;
+use crate::crypto::{
+    generate_wrapping_key, unwrap_secret_with_aes_key_wrap, wrap_secret_with_aes_key_wrap, RsaKey,
+};
+use crate::escrow::write_private;
+use crate::rng::rng;
+use crate::tpm::{self, SealedObject};
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use rand::RngCore;
+use serde::Deserialize;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+/// Default number of keys kept in the pool.
+const DEFAULT_SIZE: usize = 2;
+
+/// `[key_pool]` section of the config file.
+#[derive(Deserialize, Clone)]
+pub struct KeyPoolConfig {
+    /// Directory the sealed keys are kept in
+    pub dir: PathBuf,
+    /// Number of keys to keep ready (default: 2)
+    pub size: Option<usize>,
+    /// PCR selection to also seal the keys to (default: none)
+    pub pcrs: Option<String>,
+}
+
+impl KeyPoolConfig {
+    fn size(&self) -> usize {
+        self.size.unwrap_or(DEFAULT_SIZE)
+    }
+}
+
+/// Complete pool entries in `dir`, without extension, oldest name first.
+///
+/// The `.blob` file is written last, so an entry without one is incomplete.
+fn entries(dir: &Path) -> Result<Vec<PathBuf>> {
+    let read = match fs::read_dir(dir) {
+        Ok(read) => read,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("unable to read {:?}", dir)),
+    };
+    let mut entries = Vec::new();
+    for file in read {
+        let path = file
+            .with_context(|| format!("unable to read {:?}", dir))?
+            .path();
+        if path.extension().is_some_and(|ext| ext == "blob") {
+            entries.push(path.with_extension(""));
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+fn seal(config: &KeyPoolConfig, key: &[u8]) -> Result<SealedObject> {
+    match &config.pcrs {
+        Some(pcrs) => tpm::seal_to_pcrs(key, pcrs),
+        None => tpm::seal(key),
+    }
+}
+
+fn unseal(config: &KeyPoolConfig, sealed: &SealedObject) -> Result<Vec<u8>> {
+    match &config.pcrs {
+        Some(pcrs) => tpm::unseal_with_pcrs(sealed, pcrs),
+        None => tpm::unseal(sealed),
+    }
+}
+
+/// Generate a wrapping key and add it to the pool.
+fn add(config: &KeyPoolConfig) -> Result<()> {
+    let wrapping_key =
+        generate_wrapping_key().map_err(|e| anyhow!("unable to generate wrapping key: {}", e))?;
+    let mut der = wrapping_key
+        .private_key_to_der()
+        .map_err(|e| anyhow!("unable to encode wrapping key: {}", e))?;
+
+    let mut key = [0u8; 32];
+    rng().fill_bytes(&mut key);
+    let wrapped = wrap_secret_with_aes_key_wrap(&key, &der)
+        .map_err(|e| anyhow!("unable to encrypt wrapping key: {}", e));
+    der.zeroize();
+    let sealed = seal(config, &key).context("unable to seal pool key");
+    key.zeroize();
+    let (wrapped, sealed) = (wrapped?, sealed?);
+
+    let mut id = [0u8; 8];
+    rng().fill_bytes(&mut id);
+    let entry = config.dir.join(format!(
+        "{}-{}",
+        chrono::Utc::now().format("%Y%m%d%H%M%S"),
+        hex::encode(id)
+    ));
+    write_private(&entry.with_extension("pub"), &sealed.public)?;
+    write_private(&entry.with_extension("priv"), &sealed.private)?;
+    write_private(&entry.with_extension("blob"), &wrapped)?;
+    Ok(())
+}
+
+/// Generate keys until the pool holds `size` of them. Returns the number
+/// added.
+pub fn fill(config: &KeyPoolConfig) -> Result<usize> {
+    fs::create_dir_all(&config.dir)
+        .with_context(|| format!("unable to create {:?}", config.dir))?;
+    fs::set_permissions(&config.dir, fs::Permissions::from_mode(0o700))
+        .with_context(|| format!("unable to set permissions on {:?}", config.dir))?;
+
+    let missing = config.size().saturating_sub(entries(&config.dir)?.len());
+    let mut added = 0;
+    let mut result = Ok(());
+    while added < missing {
+        if let Err(e) = add(config) {
+            result = Err(e);
+            break;
+        }
+        added += 1;
+    }
+
+    let dir = config.dir.display().to_string();
+    let added_str = added.to_string();
+    match &result {
+        Ok(()) => audit::record(
+            "key-pool-fill",
+            &[("dir", &dir), ("added", &added_str), ("result", "ok")],
+        ),
+        Err(e) => {
+            let error = format!("{:#}", e);
+            audit::record(
+                "key-pool-fill",
+                &[
+                    ("dir", &dir),
+                    ("added", &added_str),
+                    ("result", "failed"),
+                    ("error", &error),
+                ],
+            );
+        }
+    }
+    result.map(|()| added)
+}
+
+/// Read and remove `entry`, then unseal its wrapping key.
+fn take_entry(config: &KeyPoolConfig, entry: &Path) -> Result<RsaKey> {
+    let wrapped = fs::read(entry.with_extension("blob")).context("unable to read pooled key")?;
+    let sealed = SealedObject {
+        public: fs::read(entry.with_extension("pub")).context("unable to read pooled key")?,
+        private: fs::read(entry.with_extension("priv")).context("unable to read pooled key")?,
+    };
+    // Never hand out the same key twice
+    for ext in ["blob", "pub", "priv"] {
+        let path = entry.with_extension(ext);
+        fs::remove_file(&path).with_context(|| format!("unable to remove {:?}", path))?;
+    }
+
+    let mut key = unseal(config, &sealed).context("unable to unseal pool key")?;
+    let der = unwrap_secret_with_aes_key_wrap(&key, &wrapped)
+        .map_err(|e| anyhow!("unable to decrypt pooled key: {}", e));
+    key.zeroize();
+    let mut der = der?;
+    let wrapping_key = RsaKey::from_private_key_der(&der)
+        .map_err(|e| anyhow!("unable to decode pooled key: {}", e));
+    der.zeroize();
+    wrapping_key
+}
+
+/// Take a wrapping key from the pool. Returns None if the pool is empty.
+pub fn take(config: &KeyPoolConfig) -> Result<Option<RsaKey>> {
+    let Some(entry) = entries(&config.dir)?.into_iter().next() else {
+        return Ok(None);
+    };
+    let result = take_entry(config, &entry);
+    let dir = config.dir.display().to_string();
+    match &result {
+        Ok(_) => {
+            debug!("Took wrapping key {:?} from the pool", entry);
+            audit::record("key-pool-take", &[("dir", &dir), ("result", "ok")]);
+        }
+        Err(e) => {
+            let error = format!("{:#}", e);
+            audit::record(
+                "key-pool-take",
+                &[("dir", &dir), ("result", "failed"), ("error", &error)],
+            );
+        }
+    }
+    result.map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_skip_incomplete() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(entries(&dir.path().join("missing")).unwrap().is_empty());
+
+        for name in [
+            "b.pub", "b.priv", "b.blob", "a.pub", "a.priv", "a.blob", "c.pub",
+        ] {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+        assert_eq!(
+            entries(dir.path()).unwrap(),
+            vec![dir.path().join("a"), dir.path().join("b")]
+        );
+    }
+
+    #[test]
+    fn test_take_from_empty_pool() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = KeyPoolConfig {
+            dir: dir.path().to_path_buf(),
+            size: None,
+            pcrs: None,
+        };
+        assert_eq!(config.size(), DEFAULT_SIZE);
+        assert!(take(&config).unwrap().is_none());
+    }
+}
@@ -0,0 +1,299 @@
+// TEE Attestation Service Agent — cloud KMS key release
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With a `[kms]` section, the key is released by a cloud key management
+// service instead of the TAS KBM, for hybrid deployments where the cloud KMS
+// is the key authority. TAS still issues the nonce the evidence is bound to,
+// and an external verifier (`[verifier]`) must appraise the evidence: its
+// attestation token is what the KMS release policy evaluates.
+//
+// Azure Key Vault / Managed HSM Secure Key Release (`backend = "azure"`):
+//
+//   POST <vault_url>/keys/<key_name>[/<key_version>]/release?api-version=7.4
+//     {"target": <attestation token>, "enc": "RSA_AES_KEY_WRAP_256"}
+//
+// Key Vault checks the token against the key's release policy and returns
+// the key wrapped to the encryption key carried in the token: an AES-256 key
+// wrapped with RSA-OAEP-256, followed by the key material wrapped with that
+// AES key (RFC 5649). The agent unwraps it with its ephemeral wrapping key,
+// so the token must carry that key as its runtime encryption key. The Azure
+// AD access token is read from `access_token` or requested from the instance
+// metadata service (managed identity).
+//
+// AWS KMS is not supported: its attestation-bound Decrypt and
+// GenerateDataKey calls only accept Nitro Enclaves attestation documents.
+//
+// No unsafe code.
+
+use crate::crypto::{unwrap_secret_with_aes_key_wrap, RsaKey};
+use crate::hooks::ErrorClass;
+use crate::tas_api::{create_client, RetryConfig};
+use crate::verifier::json_response;
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use log::debug;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::PathBuf;
+use zeroize::Zeroize;
+
+/// Key Vault API version of the release operation.
+const API_VERSION: &str = "7.4";
+/// Key wrapping algorithm requested from Key Vault.
+const WRAP_ALGORITHM: &str = "RSA_AES_KEY_WRAP_256";
+/// Managed identity token endpoint of the Azure instance metadata service.
+const IMDS_TOKEN_URL: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+
+/// `[kms]` section of the config file.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum KmsConfig {
+    /// Azure Key Vault or Managed HSM Secure Key Release
+    Azure(AzureConfig),
+}
+
+/// Azure settings under `[kms]` with `backend = "azure"`.
+#[derive(Deserialize, Clone)]
+pub struct AzureConfig {
+    /// Key Vault or Managed HSM URI, e.g. https://myvault.vault.azure.net
+    pub vault_url: String,
+    /// Name of the exportable key to release
+    pub key_name: String,
+    /// Key version (default: the current version)
+    pub key_version: Option<String>,
+    /// File holding an Azure AD access token (default: managed identity)
+    pub access_token: Option<PathBuf>,
+}
+
+/// Azure AD resource the access token is requested for.
+fn resource(vault_url: &str) -> &'static str {
+    if vault_url.contains(".managedhsm.") {
+        "https://managedhsm.azure.net"
+    } else {
+        "https://vault.azure.net"
+    }
+}
+
+/// Get an access token for Key Vault.
+async fn access_token(config: &AzureConfig, retry_config: &RetryConfig) -> Result<String> {
+    if let Some(path) = &config.access_token {
+        return Ok(std::fs::read_to_string(path)
+            .with_context(|| format!("unable to read access token from {:?}", path))?
+            .trim()
+            .to_string());
+    }
+    debug!("Requesting managed identity token");
+    let client =
+        create_client(IMDS_TOKEN_URL, PathBuf::new(), retry_config).map_err(|e| anyhow!(e))?;
+    let response = client
+        .get(IMDS_TOKEN_URL)
+        .query(&[
+            ("api-version", "2018-02-01"),
+            ("resource", resource(&config.vault_url)),
+        ])
+        .header("Metadata", "true")
+        .send()
+        .await;
+    json_response(response)
+        .await
+        .context("managed identity token request failed")?
+        .get("access_token")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Error: 'access_token' field not found in response"))
+}
+
+/// Decode the base64url JSON payload of the JWS `token`.
+fn jws_payload(token: &str) -> Result<Value> {
+    let payload = token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| anyhow!("key release response is not a JWS"))?;
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload)
+        .context("invalid base64url in key release response")?;
+    serde_json::from_slice(&payload).context("invalid JSON in key release response")
+}
+
+/// Unwrap the `key_hsm` blob of a released key with `wrapping_key`.
+fn unwrap_key_hsm(key_hsm: &str, wrapping_key: &RsaKey) -> Result<Vec<u8>> {
+    let blob = URL_SAFE_NO_PAD
+        .decode(key_hsm)
+        .context("invalid base64url in key_hsm")?;
+    let blob: Value = serde_json::from_slice(&blob).context("invalid JSON in key_hsm")?;
+    let ciphertext = blob
+        .get("ciphertext")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("key_hsm has no ciphertext"))?;
+    let ciphertext = URL_SAFE_NO_PAD
+        .decode(ciphertext)
+        .context("invalid base64url in key_hsm ciphertext")?;
+
+    let rsa_len = wrapping_key.size();
+    if ciphertext.len() <= rsa_len {
+        return Err(anyhow!("key_hsm ciphertext is truncated"));
+    }
+    let (wrapped_aes_key, wrapped_key) = ciphertext.split_at(rsa_len);
+    let mut aes_key = wrapping_key
+        .unwrap_key(wrapped_aes_key)
+        .map_err(|e| ErrorClass::Crypto.error(format!("Crypto Unwrap Error: {}", e)))?;
+    let key = unwrap_secret_with_aes_key_wrap(&aes_key, wrapped_key)
+        .map_err(|e| ErrorClass::Crypto.error(format!("AES Key Wrap Decrypt Error: {}", e)));
+    aes_key.zeroize();
+    key
+}
+
+/// Release the key from Azure Key Vault against the attestation `token`.
+async fn azure_release(
+    config: &AzureConfig,
+    token: &str,
+    wrapping_key: &RsaKey,
+    cert_path: PathBuf,
+    retry_config: &RetryConfig,
+) -> Result<Vec<u8>> {
+    let mut url = format!(
+        "{}/keys/{}",
+        config.vault_url.trim_end_matches('/'),
+        config.key_name
+    );
+    if let Some(version) = &config.key_version {
+        url = format!("{}/{}", url, version);
+    }
+    let url = format!("{}/release?api-version={}", url, API_VERSION);
+    let access_token = access_token(config, retry_config).await?;
+    debug!("Requesting key release from {}", url);
+
+    let client = create_client(&url, cert_path, retry_config).map_err(|e| anyhow!(e))?;
+    let response = client
+        .post(&url)
+        .bearer_auth(access_token)
+        .json(&serde_json::json!({"target": token, "enc": WRAP_ALGORITHM}))
+        .send()
+        .await;
+    let release = json_response(response).await?;
+    let value = release
+        .get("value")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("Error: 'value' field not found in response"))?;
+    let key_hsm = jws_payload(value)?
+        .pointer("/response/key/key/key_hsm")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("released key has no key_hsm"))?;
+    unwrap_key_hsm(&key_hsm, wrapping_key)
+}
+
+/// Release the key from the configured KMS against the attestation `token`.
+pub async fn release(
+    config: &KmsConfig,
+    token: &str,
+    wrapping_key: &RsaKey,
+    cert_path: PathBuf,
+    retry_config: &RetryConfig,
+) -> Result<Vec<u8>> {
+    match config {
+        KmsConfig::Azure(azure) => {
+            azure_release(azure, token, wrapping_key, cert_path, retry_config).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{generate_wrapping_key, wrap_secret_with_aes_key_wrap};
+    use mockito::Server;
+    use std::io::Write;
+
+    /// Build a key_hsm blob wrapping `key` to `wrapping_key`.
+    fn key_hsm(key: &[u8], wrapping_key: &RsaKey) -> String {
+        let aes_key = [7u8; 32];
+        let mut ciphertext = wrapping_key.encrypt(&aes_key).unwrap();
+        ciphertext.extend(wrap_secret_with_aes_key_wrap(&aes_key, key).unwrap());
+        let blob = serde_json::json!({
+            "schema_version": "1.0",
+            "header": {"alg": "dir", "enc": WRAP_ALGORITHM},
+            "ciphertext": URL_SAFE_NO_PAD.encode(ciphertext),
+        });
+        URL_SAFE_NO_PAD.encode(blob.to_string())
+    }
+
+    #[test]
+    fn test_resource() {
+        assert_eq!(
+            resource("https://myvault.vault.azure.net"),
+            "https://vault.azure.net"
+        );
+        assert_eq!(
+            resource("https://myhsm.managedhsm.azure.net"),
+            "https://managedhsm.azure.net"
+        );
+    }
+
+    #[test]
+    fn test_unwrap_key_hsm() {
+        let wrapping_key = generate_wrapping_key().unwrap();
+        let blob = key_hsm(b"0123456789abcdef0123456789abcdef", &wrapping_key);
+        assert_eq!(
+            unwrap_key_hsm(&blob, &wrapping_key).unwrap(),
+            b"0123456789abcdef0123456789abcdef"
+        );
+
+        let other_key = generate_wrapping_key().unwrap();
+        assert!(unwrap_key_hsm(&blob, &other_key).is_err());
+        assert!(unwrap_key_hsm("e30", &wrapping_key).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_azure_release() {
+        let wrapping_key = generate_wrapping_key().unwrap();
+        let payload = serde_json::json!({
+            "response": {"key": {"key": {"kty": "oct-HSM",
+                                         "key_hsm": key_hsm(b"disk-key", &wrapping_key)}}}
+        });
+        let jws = format!(
+            "eyJhbGciOiJSUzI1NiJ9.{}.c2ln",
+            URL_SAFE_NO_PAD.encode(payload.to_string())
+        );
+
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/keys/disk/release")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "api-version".into(),
+                API_VERSION.into(),
+            ))
+            .match_header("authorization", "Bearer aad-token")
+            .match_body(mockito::Matcher::PartialJsonString(format!(
+                r#"{{"target":"eyJ.maa.sig","enc":"{}"}}"#,
+                WRAP_ALGORITHM
+            )))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::json!({ "value": jws }).to_string())
+            .create_async()
+            .await;
+
+        let mut token_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(token_file, "aad-token").unwrap();
+        let config = KmsConfig::Azure(AzureConfig {
+            vault_url: server.url(),
+            key_name: "disk".to_string(),
+            key_version: None,
+            access_token: Some(token_file.path().to_path_buf()),
+        });
+        let key = release(
+            &config,
+            "eyJ.maa.sig",
+            &wrapping_key,
+            PathBuf::new(),
+            &RetryConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(key, b"disk-key");
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,69 @@
+// TEE Attestation Service Agent — response size limits
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// The agent runs as root during boot, so a misbehaving or malicious TAS
+// endpoint must not make it allocate without bound. TAS response bodies are
+// read up to a limit, checking Content-Length first, and base64 fields of
+// the secret payload are checked before their buffers are allocated:
+//
+//   [limits]
+//   response_bytes = 65536       # version and get_nonce responses
+//   secret_bytes = 67108864      # get_secret responses
+//   error_bytes = 4096           # error bodies, truncated beyond this
+//   decoded_bytes = 67108864     # each base64-decoded payload field
+//
+// The values shown are the defaults. A response over its limit fails the
+// request; an error body over its limit is cut short, as it only ends up in
+// the error message.
+//
+// No unsafe code.
+
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+const DEFAULT_RESPONSE_BYTES: usize = 64 * 1024;
+const DEFAULT_SECRET_BYTES: usize = 64 * 1024 * 1024;
+const DEFAULT_ERROR_BYTES: usize = 4 * 1024;
+const DEFAULT_DECODED_BYTES: usize = 64 * 1024 * 1024;
+
+/// `[limits]` section of the config file.
+#[derive(Deserialize, Clone, Default)]
+pub struct LimitsConfig {
+    /// Largest version or get_nonce response (default: 64 KiB)
+    pub response_bytes: Option<usize>,
+    /// Largest get_secret response (default: 64 MiB)
+    pub secret_bytes: Option<usize>,
+    /// Bytes of an error body kept for the error message (default: 4 KiB)
+    pub error_bytes: Option<usize>,
+    /// Largest base64-decoded field of a secret payload (default: 64 MiB)
+    pub decoded_bytes: Option<usize>,
+}
+
+static LIMITS: OnceLock<LimitsConfig> = OnceLock::new();
+
+/// Apply `config` to all later responses.
+pub fn configure(config: LimitsConfig) {
+    let _ = LIMITS.set(config);
+}
+
+fn limits() -> &'static LimitsConfig {
+    LIMITS.get_or_init(LimitsConfig::default)
+}
+
+pub fn response_bytes() -> usize {
+    limits().response_bytes.unwrap_or(DEFAULT_RESPONSE_BYTES)
+}
+
+pub fn secret_bytes() -> usize {
+    limits().secret_bytes.unwrap_or(DEFAULT_SECRET_BYTES)
+}
+
+pub fn error_bytes() -> usize {
+    limits().error_bytes.unwrap_or(DEFAULT_ERROR_BYTES)
+}
+
+pub fn decoded_bytes() -> usize {
+    limits().decoded_bytes.unwrap_or(DEFAULT_DECODED_BYTES)
+}
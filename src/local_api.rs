@@ -0,0 +1,412 @@
+// TEE Attestation Service Agent — local unix-socket API
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Lets other processes on the guest obtain attestation-gated secrets from the
+// daemon without holding TAS credentials or touching configfs-tsm themselves.
+//
+// The protocol is a minimal subset of HTTP/1.1, one request per connection:
+//
+//   GET /secret/<policy_id>[?force=1] HTTP/1.1
+//
+// A 200 response carries the raw secret as an application/octet-stream body.
+// Only policy IDs the daemon is configured to maintain can be requested; if
+// the daemon holds no secret for one yet it is released on demand, and a
+// failed release is reported as 503. `force=1` bypasses the daemon's stored
+// secret and cached attestation and releases the secret afresh. Connections
+// are served concurrently.
+//
+// Clients are authenticated with SO_PEERCRED: the connecting process's uid or
+// gid must appear in `allowed_uids` / `allowed_gids`. The socket itself is
+// world-connectable so that the credential check is the single gate.
+//
+// No unsafe code.
+
+use crate::audit;
+use crate::daemon::Releaser;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::{UnixListener, UnixStream};
+use zeroize::Zeroize;
+
+/// Upper bound on the size of a request (request line plus headers).
+pub const MAX_REQUEST_BYTES: u64 = 8192;
+
+/// Peers allowed to request secrets.
+#[derive(Clone)]
+pub struct Access {
+    pub allowed_uids: Vec<u32>,
+    pub allowed_gids: Vec<u32>,
+}
+
+/// The allowed peers, shared by every socket that serves secrets and
+/// replaced on configuration reload.
+pub struct AccessControl {
+    access: RwLock<Access>,
+}
+
+impl AccessControl {
+    pub fn new(access: Access) -> Self {
+        AccessControl {
+            access: RwLock::new(access),
+        }
+    }
+
+    /// Replace the allowed peers, e.g. after a configuration reload.
+    pub fn set(&self, access: Access) {
+        if let Ok(mut current) = self.access.write() {
+            *current = access;
+        }
+    }
+
+    pub fn is_authorized(&self, uid: u32, gid: u32) -> bool {
+        // A poisoned lock denies access.
+        self.access.read().is_ok_and(|access| {
+            access.allowed_uids.contains(&uid) || access.allowed_gids.contains(&gid)
+        })
+    }
+}
+
+/// Shared state for the request handlers.
+pub struct ApiState {
+    pub releaser: Arc<Releaser>,
+    access: Arc<AccessControl>,
+}
+
+impl ApiState {
+    pub fn new(releaser: Arc<Releaser>, access: Arc<AccessControl>) -> Self {
+        ApiState { releaser, access }
+    }
+}
+
+/// A parsed request line.
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+}
+
+pub struct Response {
+    status: u16,
+    reason: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    pub fn text(status: u16, reason: &'static str) -> Self {
+        Response {
+            status,
+            reason,
+            content_type: "text/plain",
+            body: format!("{}\n", reason).into_bytes(),
+        }
+    }
+
+    pub fn json(status: u16, reason: &'static str, body: &serde_json::Value) -> Self {
+        Response {
+            status,
+            reason,
+            content_type: "application/json",
+            body: format!("{}\n", body).into_bytes(),
+        }
+    }
+}
+
+impl Drop for Response {
+    fn drop(&mut self) {
+        self.body.zeroize();
+    }
+}
+
+/// Read the request line and skip the headers up to the blank line.
+pub async fn read_request<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Request> {
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .await
+        .context("failed to read request line")?;
+    if !line.ends_with('\n') {
+        return Err(anyhow!("request line too long or truncated"));
+    }
+
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(anyhow!("malformed request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(anyhow!("unsupported protocol {:?}", version));
+    }
+    let request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+    };
+
+    loop {
+        let mut header = String::new();
+        let n = reader
+            .read_line(&mut header)
+            .await
+            .context("failed to read request header")?;
+        if n == 0 || !header.ends_with('\n') {
+            return Err(anyhow!("request headers too long or truncated"));
+        }
+        if header.trim_end().is_empty() {
+            return Ok(request);
+        }
+    }
+}
+
+/// Whether the query string asks to bypass the caches.
+fn is_forced(query: Option<&str>) -> bool {
+    query.is_some_and(|query| {
+        query
+            .split('&')
+            .any(|param| matches!(param, "force" | "force=1" | "force=true"))
+    })
+}
+
+async fn route(request: &Request, releaser: &Releaser) -> Response {
+    let (path, query) = match request.path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (request.path.as_str(), None),
+    };
+    let Some(policy_id) = path.strip_prefix("/secret/") else {
+        return Response::text(404, "Not Found");
+    };
+    if request.method != "GET" {
+        return Response::text(405, "Method Not Allowed");
+    }
+    match releaser.get_or_release(policy_id, is_forced(query)).await {
+        Ok(Some(secret)) => Response {
+            status: 200,
+            reason: "OK",
+            content_type: "application/octet-stream",
+            body: secret,
+        },
+        Ok(None) => Response::text(404, "Not Found"),
+        Err(e) => {
+            // Failure streaks are alerted by the releaser
+            debug!("On-demand release failed for {}: {:#}", policy_id, e);
+            Response::text(503, "Service Unavailable")
+        }
+    }
+}
+
+pub async fn write_response<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    response: &Response,
+) -> Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.reason,
+        response.content_type,
+        response.body.len()
+    );
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(&response.body).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn handle_connection(mut stream: UnixStream, state: Arc<ApiState>) -> Result<()> {
+    let cred = stream
+        .peer_cred()
+        .context("failed to read peer credentials")?;
+    let uid = cred.uid().to_string();
+    let (read_half, mut write_half) = stream.split();
+
+    if !state.access.is_authorized(cred.uid(), cred.gid()) {
+        audit::record("secret-request", &[("uid", &uid), ("result", "denied")]);
+        return write_response(&mut write_half, &Response::text(403, "Forbidden")).await;
+    }
+
+    let mut reader = BufReader::new(read_half.take(MAX_REQUEST_BYTES));
+    let response = match read_request(&mut reader).await {
+        Ok(request) => {
+            let response = route(&request, &state.releaser).await;
+            let status = response.status.to_string();
+            audit::record(
+                "secret-request",
+                &[("uid", &uid), ("path", &request.path), ("status", &status)],
+            );
+            response
+        }
+        Err(e) => {
+            debug!("Bad request from uid {}: {:#}", uid, e);
+            Response::text(400, "Bad Request")
+        }
+    };
+    write_response(&mut write_half, &response).await
+}
+
+/// Bind a world-connectable socket at `path`, replacing a stale socket from a
+/// previous run. Access control is enforced per connection with SO_PEERCRED.
+pub fn bind_socket(path: &Path) -> Result<std::os::unix::net::UnixListener> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("unable to create {:?}", parent))?;
+    }
+    if path.exists() {
+        fs::remove_file(path).with_context(|| format!("unable to remove stale {:?}", path))?;
+    }
+    let listener = std::os::unix::net::UnixListener::bind(path)
+        .with_context(|| format!("unable to bind {:?}", path))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o666))
+        .with_context(|| format!("unable to set permissions on {:?}", path))?;
+    listener
+        .set_nonblocking(true)
+        .with_context(|| format!("unable to configure {:?}", path))?;
+    Ok(listener)
+}
+
+/// Bind the API socket at `path`.
+pub fn bind(path: &Path) -> Result<UnixListener> {
+    UnixListener::from_std(bind_socket(path)?)
+        .with_context(|| format!("unable to register {:?}", path))
+}
+
+/// Accept and serve connections until the task is cancelled.
+pub async fn serve(listener: UnixListener, state: Arc<ApiState>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream, state).await {
+                        debug!("Local API connection error: {:#}", e);
+                    }
+                });
+            }
+            Err(e) => warn!("Local API accept failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::SecretStore;
+    use crate::CliOverrides;
+    use std::time::Duration;
+
+    fn releaser(store: SecretStore) -> Releaser {
+        Releaser::new(
+            None,
+            CliOverrides::default(),
+            Arc::new(store),
+            vec!["luks".to_string()],
+            Duration::from_secs(30),
+        )
+    }
+
+    fn access() -> AccessControl {
+        AccessControl::new(Access {
+            allowed_uids: vec![0, 1000],
+            allowed_gids: vec![42],
+        })
+    }
+
+    #[tokio::test]
+    async fn test_read_request() {
+        let mut input: &[u8] = b"GET /secret/luks HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let req = read_request(&mut input).await.unwrap();
+        assert_eq!(
+            req,
+            Request {
+                method: "GET".to_string(),
+                path: "/secret/luks".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_request_rejects_malformed() {
+        let mut input: &[u8] = b"GET\r\n\r\n";
+        assert!(read_request(&mut input).await.is_err());
+
+        let mut input: &[u8] = b"GET /secret/luks SPDY/3\r\n\r\n";
+        assert!(read_request(&mut input).await.is_err());
+
+        // Headers never terminated
+        let mut input: &[u8] = b"GET /secret/luks HTTP/1.1\r\nHost: x\r\n";
+        assert!(read_request(&mut input).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_route() {
+        let store = SecretStore::default();
+        store.insert("luks", b"secret".to_vec()).await;
+        let releaser = releaser(store);
+
+        let get = |path: &str| Request {
+            method: "GET".to_string(),
+            path: path.to_string(),
+        };
+        let ok = route(&get("/secret/luks"), &releaser).await;
+        assert_eq!(ok.status, 200);
+        assert_eq!(ok.body, b"secret");
+
+        assert_eq!(route(&get("/secret/other"), &releaser).await.status, 404);
+        assert_eq!(
+            route(&get("/secret/other?force=1"), &releaser).await.status,
+            404
+        );
+        assert_eq!(route(&get("/healthz"), &releaser).await.status, 404);
+
+        let post = Request {
+            method: "POST".to_string(),
+            path: "/secret/luks".to_string(),
+        };
+        assert_eq!(route(&post, &releaser).await.status, 405);
+    }
+
+    #[test]
+    fn test_is_forced() {
+        assert!(is_forced(Some("force=1")));
+        assert!(is_forced(Some("a=b&force")));
+        assert!(!is_forced(Some("force=0")));
+        assert!(!is_forced(None));
+    }
+
+    #[tokio::test]
+    async fn test_write_response() {
+        let mut out = Vec::new();
+        write_response(&mut out, &Response::text(403, "Forbidden"))
+            .await
+            .unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+        assert!(text.contains("Content-Length: 10\r\n"));
+        assert!(text.ends_with("\r\n\r\nForbidden\n"));
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        let access = access();
+        assert!(access.is_authorized(0, 0));
+        assert!(access.is_authorized(1000, 1000));
+        assert!(access.is_authorized(1001, 42));
+        assert!(!access.is_authorized(1001, 1001));
+    }
+
+    #[test]
+    fn test_set_access() {
+        let access = access();
+        access.set(Access {
+            allowed_uids: vec![1001],
+            allowed_gids: vec![],
+        });
+        assert!(access.is_authorized(1001, 1001));
+        assert!(!access.is_authorized(0, 0));
+    }
+}
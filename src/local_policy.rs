@@ -0,0 +1,379 @@
+// TEE Attestation Service Agent — local policy gate
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With a `[local_policy]` section, the agent evaluates an operator policy
+// against the parsed evidence and the attestation results before it uses a
+// released secret, and refuses to deliver the secret if the policy fails,
+// even though the server accepted the evidence.
+//
+// Policy input:
+//
+//   {
+//     "tee_type": "amd-sev-snp",
+//     "measurements": {"MEASUREMENT": "<hex>"},
+//     "report": {<TEE report fields, see snp_report / tdx_report>},
+//     "attestation_token": {<claims of the verifier's token>},
+//     "attestation_result": {<claims of the server's EAR>}
+//   }
+//
+// `attestation_token` and `attestation_result` are present when a verifier
+// or server returned one; their signatures are checked elsewhere (`[ear]`).
+//
+// `rules` is a JSON policy keyed by JSON pointers into the input:
+//
+//   {
+//     "allow": {"/report/debug": [false], "/measurements/MEASUREMENT": ["..."]},
+//     "min": {"/report/current_tcb/snp": 8}
+//   }
+//
+// Every `allow` pointer must resolve to one of the listed values and every
+// `min` pointer to a number at least as large. `rego` is a Rego module
+// evaluated with `opa eval`; `query` (default `data.tas_agent.allow`) must
+// be true. When both are given, both must pass.
+//
+// No unsafe code.
+
+use crate::corim;
+use anyhow::{anyhow, Context, Result};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use log::debug;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Default Rego query.
+const DEFAULT_QUERY: &str = "data.tas_agent.allow";
+
+/// `[local_policy]` section of the config file.
+#[derive(Deserialize, Clone)]
+pub struct LocalPolicyConfig {
+    /// JSON policy file
+    pub rules: Option<PathBuf>,
+    /// Rego policy file, evaluated with opa
+    pub rego: Option<PathBuf>,
+    /// opa executable (default: opa from PATH)
+    pub opa: Option<PathBuf>,
+    /// Rego query that must be true (default: data.tas_agent.allow)
+    pub query: Option<String>,
+}
+
+/// A JSON policy.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct Rules {
+    /// Allowed values by JSON pointer
+    #[serde(default)]
+    allow: BTreeMap<String, Vec<Value>>,
+    /// Minimum numbers by JSON pointer
+    #[serde(default)]
+    min: BTreeMap<String, f64>,
+}
+
+fn le_u32(report: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(report[offset..offset + 4].try_into().unwrap())
+}
+
+fn le_u64(report: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(report[offset..offset + 8].try_into().unwrap())
+}
+
+fn hex_at(report: &[u8], offset: usize, len: usize) -> String {
+    hex::encode(&report[offset..offset + len])
+}
+
+/// SEV-SNP TCB_VERSION fields.
+fn snp_tcb(report: &[u8], offset: usize) -> Value {
+    json!({
+        "bootloader": report[offset],
+        "tee": report[offset + 1],
+        "snp": report[offset + 6],
+        "microcode": report[offset + 7],
+    })
+}
+
+/// Fields of an SEV-SNP attestation report.
+fn snp_report(report: &[u8]) -> Result<Value> {
+    if report.len() < corim::SNP_REPORT_LEN {
+        return Err(anyhow!("SEV-SNP report is truncated"));
+    }
+    let policy = le_u64(report, 0x08);
+    Ok(json!({
+        "version": le_u32(report, 0x00),
+        "guest_svn": le_u32(report, 0x04),
+        "policy": policy,
+        "debug": policy & (1 << 19) != 0,
+        "migrate_ma": policy & (1 << 18) != 0,
+        "smt": policy & (1 << 16) != 0,
+        "family_id": hex_at(report, 0x10, 16),
+        "image_id": hex_at(report, 0x20, 16),
+        "vmpl": le_u32(report, 0x30),
+        "current_tcb": snp_tcb(report, 0x38),
+        "platform_info": le_u64(report, 0x40),
+        "host_data": hex_at(report, 0xc0, 32),
+        "id_key_digest": hex_at(report, 0xe0, 48),
+        "author_key_digest": hex_at(report, 0x110, 48),
+        "reported_tcb": snp_tcb(report, 0x180),
+        "chip_id": hex_at(report, 0x1a0, 64),
+        "committed_tcb": snp_tcb(report, 0x1e0),
+        "current_version": format!("{}.{}.{}", report[0x1ea], report[0x1e9], report[0x1e8]),
+        "launch_tcb": snp_tcb(report, 0x1f8),
+    }))
+}
+
+/// Fields of the TD report body of a TDX quote.
+fn tdx_report(quote: &[u8]) -> Result<Value> {
+    let body = corim::tdx_body(quote)?;
+    // The body runs up to the end of REPORTDATA
+    if quote.len() < body + 584 {
+        return Err(anyhow!("TDX quote is truncated"));
+    }
+    let attributes = le_u64(quote, body + 120);
+    Ok(json!({
+        "version": u16::from_le_bytes([quote[0], quote[1]]),
+        "tee_tcb_svn": hex_at(quote, body, 16),
+        "mr_seam": hex_at(quote, body + 16, 48),
+        "mr_signer_seam": hex_at(quote, body + 64, 48),
+        "td_attributes": attributes,
+        "debug": attributes & 1 != 0,
+        "xfam": le_u64(quote, body + 128),
+        "mr_config_id": hex_at(quote, body + 184, 48),
+        "mr_owner": hex_at(quote, body + 232, 48),
+        "mr_owner_config": hex_at(quote, body + 280, 48),
+    }))
+}
+
+/// Claims of a JWT, without checking its signature.
+fn jwt_claims(token: &str) -> Result<Value> {
+    let payload = token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| anyhow!("not a JWT"))?;
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .context("invalid base64url in JWT")?;
+    serde_json::from_slice(&payload).context("invalid JSON in JWT")
+}
+
+/// Build the policy input for the base64 `evidence` of `tee_type` and the
+/// attestation results.
+pub fn input(
+    tee_type: &str,
+    evidence: &str,
+    attestation_token: Option<&str>,
+    attestation_result: Option<&str>,
+) -> Result<Value> {
+    let report = STANDARD
+        .decode(evidence)
+        .context("evidence is not valid base64")?;
+    let mut input = Map::new();
+    input.insert("tee_type".into(), tee_type.into());
+
+    let parsed = match tee_type {
+        "amd-sev-snp" => Some(snp_report(&report)?),
+        "intel-tdx" => Some(tdx_report(&report)?),
+        _ => None,
+    };
+    if let Some(parsed) = parsed {
+        let measurements: Map<String, Value> = corim::measurements(tee_type, &report)?
+            .into_iter()
+            .map(|(name, digest)| (name.to_string(), hex::encode(digest).into()))
+            .collect();
+        input.insert("measurements".into(), measurements.into());
+        input.insert("report".into(), parsed);
+    }
+    if let Some(token) = attestation_token {
+        let claims = jwt_claims(token).context("unable to read the attestation token")?;
+        input.insert("attestation_token".into(), claims);
+    }
+    if let Some(result) = attestation_result {
+        let claims = jwt_claims(result).context("unable to read the attestation result")?;
+        input.insert("attestation_result".into(), claims);
+    }
+    Ok(input.into())
+}
+
+/// Evaluate the JSON policy `rules` against `input`.
+fn check_rules(rules: &Rules, input: &Value) -> Result<()> {
+    for (pointer, allowed) in &rules.allow {
+        let value = input
+            .pointer(pointer)
+            .ok_or_else(|| anyhow!("{} is not present", pointer))?;
+        if !allowed.contains(value) {
+            return Err(anyhow!("{} is {}, which is not allowed", pointer, value));
+        }
+    }
+    for (pointer, min) in &rules.min {
+        let value = input
+            .pointer(pointer)
+            .and_then(Value::as_f64)
+            .ok_or_else(|| anyhow!("{} is not a number", pointer))?;
+        if value < *min {
+            return Err(anyhow!("{} is {}, below {}", pointer, value, min));
+        }
+    }
+    Ok(())
+}
+
+fn load_rules(path: &Path) -> Result<Rules> {
+    let data =
+        std::fs::read_to_string(path).with_context(|| format!("unable to read {:?}", path))?;
+    serde_json::from_str(&data).with_context(|| format!("unable to load {:?}", path))
+}
+
+/// Evaluate the Rego policy at `rego` against `input` with opa.
+fn check_rego(config: &LocalPolicyConfig, rego: &Path, input: &Value) -> Result<()> {
+    let opa = config.opa.as_deref().unwrap_or(Path::new("opa"));
+    let query = config.query.as_deref().unwrap_or(DEFAULT_QUERY);
+    debug!("Evaluating {} in {:?} with {:?}", query, rego, opa);
+    let mut child = Command::new(opa)
+        .args(["eval", "--format", "raw", "--stdin-input", "--data"])
+        .arg(rego)
+        .arg(query)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("unable to run {:?}", opa))?;
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("unable to open stdin of {:?}", opa))?
+        .write_all(input.to_string().as_bytes())
+        .with_context(|| format!("unable to write to {:?}", opa))?;
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("unable to wait for {:?}", opa))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{:?} exited with {}: {}",
+            opa,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "true" => Ok(()),
+        "" => Err(anyhow!("{} is undefined", query)),
+        other => Err(anyhow!("{} is {}", query, other)),
+    }
+}
+
+/// Evaluate the configured policies against `input`.
+pub fn check(config: &LocalPolicyConfig, input: &Value) -> Result<()> {
+    if config.rules.is_none() && config.rego.is_none() {
+        return Err(anyhow!("neither rules nor rego is set"));
+    }
+    if let Some(path) = &config.rules {
+        check_rules(&load_rules(path)?, input)?;
+    }
+    if let Some(rego) = &config.rego {
+        check_rego(config, rego, input)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn snp_evidence() -> String {
+        let mut report = vec![0u8; corim::SNP_REPORT_LEN];
+        report[0] = 2;
+        // Debug allowed
+        report[0x0a] = 0x08;
+        report[0x38 + 6] = 8;
+        report[0x38 + 7] = 115;
+        report[0x90..0x90 + 48].fill(0xab);
+        STANDARD.encode(report)
+    }
+
+    fn config(rules: Option<PathBuf>, rego: Option<PathBuf>) -> LocalPolicyConfig {
+        LocalPolicyConfig {
+            rules,
+            rego,
+            opa: None,
+            query: None,
+        }
+    }
+
+    #[test]
+    fn test_snp_input() {
+        let token = format!(
+            "eyJhbGciOiJub25lIn0.{}.",
+            URL_SAFE_NO_PAD.encode(r#"{"x-ms-attestation-type":"sevsnpvm"}"#)
+        );
+        let doc = input("amd-sev-snp", &snp_evidence(), Some(&token), None).unwrap();
+        let measurement = "ab".repeat(48);
+        assert_eq!(doc["report"]["version"], json!(2));
+        assert_eq!(doc["report"]["debug"], json!(true));
+        assert_eq!(doc["report"]["current_tcb"]["microcode"], json!(115));
+        assert_eq!(doc["measurements"]["MEASUREMENT"], json!(measurement));
+        assert_eq!(
+            doc["attestation_token"]["x-ms-attestation-type"],
+            json!("sevsnpvm")
+        );
+        assert!(doc.get("attestation_result").is_none());
+
+        assert!(input("amd-sev-snp", "AAAA", None, None).is_err());
+        let other = input("custom", "AAAA", None, None).unwrap();
+        assert!(other.get("report").is_none());
+    }
+
+    #[test]
+    fn test_check_rules() {
+        let doc = input("amd-sev-snp", &snp_evidence(), None, None).unwrap();
+        let rules = |json: Value| -> Rules { serde_json::from_value(json).unwrap() };
+
+        let measurement = "ab".repeat(48);
+        let pass = rules(json!({
+            "allow": {"/tee_type": ["amd-sev-snp"], "/measurements/MEASUREMENT": [measurement]},
+            "min": {"/report/current_tcb/snp": 8, "/report/current_tcb/microcode": 100}
+        }));
+        assert!(check_rules(&pass, &doc).is_ok());
+
+        let debug = rules(json!({"allow": {"/report/debug": [false]}}));
+        assert!(check_rules(&debug, &doc).is_err());
+        let old_tcb = rules(json!({"min": {"/report/current_tcb/snp": 9}}));
+        assert!(check_rules(&old_tcb, &doc).is_err());
+        let missing = rules(json!({"allow": {"/attestation_result/ear.status": ["affirming"]}}));
+        assert!(check_rules(&missing, &doc).is_err());
+        assert!(serde_json::from_value::<Rules>(json!({"deny": {}})).is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let doc = input("amd-sev-snp", &snp_evidence(), None, None).unwrap();
+
+        let rules = dir.path().join("policy.json");
+        std::fs::write(&rules, r#"{"allow": {"/report/debug": [true]}}"#).unwrap();
+        assert!(check(&config(Some(rules.clone()), None), &doc).is_ok());
+        assert!(check(&config(None, None), &doc).is_err());
+
+        // Stand-in for opa that allows only inputs with debug enabled
+        let opa = dir.path().join("opa");
+        std::fs::write(
+            &opa,
+            "#!/bin/sh\nif grep -q '\"debug\":true'; then echo true; else echo false; fi\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&opa, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut cfg = config(Some(rules), Some(dir.path().join("policy.rego")));
+        cfg.opa = Some(opa);
+        assert!(check(&cfg, &doc).is_ok());
+
+        let mut release = doc.clone();
+        release["report"]["debug"] = json!(false);
+        cfg.rules = None;
+        assert!(check(&cfg, &release).is_err());
+    }
+}
@@ -13,37 +13,122 @@
 //
 
 use chrono::Utc;
-use log::{debug, Level, LevelFilter, Metadata, Record};
+use log::{debug, info, warn, Level, LevelFilter, Metadata, Record};
+#[cfg(feature = "hex-dump")]
 use pretty_hex::PrettyHex;
-use std::fs::read_to_string;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 #[cfg(feature = "askpass")]
 mod askpass;
+mod audit;
+mod boot_claims;
+mod buildinfo;
+mod bundle;
+mod cassette;
+mod claims;
+mod clock;
+mod cmdline;
+mod conformance;
+mod corim;
 mod crypto;
+mod custody;
+#[cfg(feature = "daemon")]
+mod daemon;
+#[cfg(feature = "snp-derived-key")]
+mod derived_key;
+mod discovery;
 // Any component feature
 #[cfg(feature = "gpu-nvidia")]
 mod components;
+mod ear;
+mod eat;
+mod error_format;
+mod escrow;
+mod evidence;
+mod fault;
+#[cfg(feature = "fips")]
+mod fips;
+#[cfg(test)]
+mod golden;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod hardening;
+#[cfg(feature = "daemon")]
+mod health;
+mod hooks;
+mod k8s;
+mod kbs;
+mod key_pool;
+mod kms;
+mod limits;
+#[cfg(feature = "daemon")]
+mod local_api;
+mod local_policy;
+mod netif;
+mod output;
 #[cfg(feature = "passfifo")]
 mod passfifo;
+mod payload;
+mod platform;
+mod postprocess;
+#[cfg(feature = "daemon")]
+mod privsep;
+mod request_id;
+mod rng;
+#[cfg(feature = "landlock")]
+mod sandbox;
+#[cfg(feature = "daemon")]
+mod schedule;
+mod schema;
+mod scrub;
+#[cfg(feature = "daemon")]
+mod sd_notify;
+mod sealed_cache;
+#[cfg(feature = "seccomp")]
+mod seccomp;
+mod server_attestation;
+mod server_uri;
+mod shared_attestation;
+mod shutdown;
+mod spiffe;
+mod srv;
+#[cfg(feature = "daemon")]
+mod state;
+#[cfg(feature = "daemon")]
+mod streak;
 mod tas_api;
 mod tee_evidence;
+mod telemetry;
+mod tls;
+mod token;
+mod tpm;
+mod transcript;
 mod utils;
+mod vault;
+mod verifier;
+mod version_cache;
+mod wrap;
 use anyhow::{anyhow, Context, Result};
-use clap::Parser;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::{Parser, Subcommand};
+use error_format::Phase;
+use hooks::ErrorClass;
 use serde::Deserialize;
 
 use crypto::{
-    compute_report_data_binding, decrypt_secret_with_aes_key, generate_wrapping_key,
-    unwrap_secret_with_aes_key_wrap,
+    compute_report_data_binding, compute_report_data_binding_with_components,
+    decrypt_chunked_in_place, decrypt_secret_with_aes_key, generate_wrapping_key, payload_bound_to,
+    unwrap_secret_with_aes_key_wrap, RsaKey,
 };
-// Any component feature
-#[cfg(feature = "gpu-nvidia")]
-use crypto::compute_report_data_binding_with_components;
-use tas_api::{tas_get_nonce, tas_get_secret_key, tas_get_version, RetryConfig};
+use tas_api::{
+    tas_get_nonce, tas_get_secret_key, tas_get_version, EvidenceSignature, RetryConfig,
+    SecretRequest, SecretResponse, ServerVersion, TasError,
+};
+#[cfg(not(feature = "daemon"))]
 use tee_evidence::tee_get_evidence;
 use utils::SecretsPayload;
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 
 struct SimpleLogger;
 
@@ -55,7 +140,10 @@ impl log::Log for SimpleLogger {
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
             let now = Utc::now().to_rfc3339();
-            eprintln!("{} {} - {}", now, record.level(), record.args());
+            match request_id::current() {
+                Some(id) => eprintln!("{} {} [{}] - {}", now, record.level(), id, record.args()),
+                None => eprintln!("{} {} - {}", now, record.level(), record.args()),
+            }
         }
     }
 
@@ -77,6 +165,10 @@ struct Cli {
     #[arg(long, value_name = "URI")]
     server_uri: Option<String>,
 
+    /// Accept a plaintext http:// server URI, e.g. for a lab setup
+    #[arg(long)]
+    allow_insecure: bool,
+
     /// Path to the API key for the TAS REST service
     #[arg(long, value_name = "FILE")]
     api_key: Option<PathBuf>,
@@ -85,10 +177,21 @@ struct Cli {
     #[arg(long, value_name = "ID")]
     policy_id: Option<String>,
 
-    /// Path to the CA root certificate signing the TAS REST service cert
-    #[arg(long, value_name = "FILE")]
+    /// Path to the CA root certificate signing the TAS REST service cert: a
+    /// PEM bundle, or a directory of *.pem and *.crt files
+    #[arg(long, value_name = "PATH")]
     cert_path: Option<PathBuf>,
 
+    /// Override the config file with tas_agent.* parameters from
+    /// /proc/cmdline, e.g. in an initramfs
+    #[arg(long)]
+    kernel_cmdline: bool,
+
+    /// Submit the evidence as this TEE type instead of the one detected from
+    /// the configfs-tsm provider, e.g. for providers this agent does not know
+    #[arg(long, value_name = "TYPE")]
+    tee_type: Option<String>,
+
     /// Maximum number of retry attempts for HTTP requests (default: 3)
     #[arg(long, value_name = "N")]
     max_retries: Option<u32>,
@@ -101,6 +204,56 @@ struct Cli {
     #[arg(long, value_name = "SECS")]
     retry_max_backoff_secs: Option<u64>,
 
+    /// Write the key to FILE (mode 0600) instead of stdout
+    #[arg(long, value_name = "FILE", conflicts_with = "secret_fd")]
+    out: Option<PathBuf>,
+
+    /// After writing --out, wait for SIGUSR1 or SECS seconds, then overwrite and remove FILE
+    #[arg(long, value_name = "SECS", requires = "out")]
+    shred_after: Option<u64>,
+
+    /// Write the key to the inherited, already-open file descriptor N instead of stdout
+    #[arg(long, value_name = "N")]
+    secret_fd: Option<u32>,
+
+    /// Write the attestation token (verifier JWT, or a TPM-signed summary) to FILE
+    #[arg(long, value_name = "FILE")]
+    token_out: Option<PathBuf>,
+
+    /// Send the claim KEY=VALUE with the evidence, overriding [claims];
+    /// repeatable
+    #[arg(long = "claim", value_name = "KEY=VALUE", value_parser = claims::parse)]
+    claims: Vec<(String, String)>,
+
+    /// Save every artifact of the run, secrets redacted, into a timestamped
+    /// directory under DIR
+    #[arg(long, value_name = "DIR")]
+    transcript_dir: Option<PathBuf>,
+
+    /// Send TAS traffic from this source address
+    #[arg(long, value_name = "ADDR", conflicts_with = "bind_interface")]
+    bind_address: Option<std::net::IpAddr>,
+
+    /// Send TAS traffic from the global address of this network interface
+    #[arg(long, value_name = "NAME")]
+    bind_interface: Option<String>,
+
+    /// UNSAFE: append the TLS secrets of the TAS connection to FILE in
+    /// SSLKEYLOGFILE format, to decrypt packet captures when troubleshooting
+    #[cfg(feature = "tls-keylog")]
+    #[arg(long, value_name = "FILE")]
+    unsafe_tls_keylog: Option<PathBuf>,
+
+    /// Print the error the agent exits with as text or as a JSON object
+    /// (code, phase, message, retryable, hint)
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t)]
+    error_format: error_format::ErrorFormat,
+
+    /// Inject a failure for resilience testing (nonce-timeout, release-500,
+    /// corrupt-tag, missing-outblob); repeatable
+    #[arg(long, value_name = "FAULT", hide = true)]
+    inject_fault: Vec<fault::Fault>,
+
     /// Disable GPU attestation (enabled by default when built with GPU support)
     // Any GPU feature
     #[cfg(feature = "gpu-nvidia")]
@@ -116,17 +269,209 @@ struct Cli {
     #[cfg(feature = "passfifo")]
     #[arg(long)]
     passfifo: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Stay resident and re-attest periodically to keep secrets fresh
+    #[cfg(feature = "daemon")]
+    Daemon,
+    /// Print this machine's launch measurements as a CoMID reference value template
+    Corim,
+    /// Print the agent version
+    Version {
+        /// Print the git commit, features, TEE backends and algorithms as JSON
+        #[arg(long)]
+        verbose: bool,
+    },
+    /// Check a TAS server's compatibility with this agent and print a report
+    Conformance {
+        /// Seconds the server accepts a nonce for; enables the stale-nonce check
+        #[arg(long, value_name = "SECS")]
+        nonce_lifetime: Option<u64>,
+    },
+    /// Pre-generate wrapping keys into the [key_pool] directory
+    KeyPool,
+    /// Carry evidence from a guest without network access to TAS and back
+    Evidence {
+        #[command(subcommand)]
+        command: EvidenceCommand,
+    },
+    /// Unwrap and decrypt a saved secret payload locally, to reproduce
+    /// decryption failures, and output it like the normal mode
+    DecryptPayload {
+        /// The SecretsPayload JSON, or a get_secret response or submitted
+        /// bundle holding it
+        #[arg(long, value_name = "FILE")]
+        payload: PathBuf,
+        /// PKCS#1 DER private wrapping key
+        #[arg(long, value_name = "FILE")]
+        key: PathBuf,
+    },
+    /// Send an exported evidence bundle to TAS and add the response to it
+    Submit {
+        /// Bundle written by `evidence export`
+        #[arg(long, value_name = "FILE")]
+        bundle: PathBuf,
+    },
+    /// Release secrets into a directory as a Kubernetes init container
+    Init {
+        /// Secrets to release ([[secrets]] entries with policy_id, path and mode)
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = "/etc/tas_agent/secrets.toml"
+        )]
+        secrets: PathBuf,
+        /// Directory to write the secrets to (an emptyDir or tmpfs volume)
+        #[arg(long, value_name = "DIR")]
+        out_dir: PathBuf,
+        /// File to write the JSON termination message to
+        #[arg(long, value_name = "FILE", default_value = "/dev/termination-log")]
+        termination_log: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum EvidenceCommand {
+    /// Collect evidence bound to a nonce fetched elsewhere into a bundle
+    Export {
+        /// File holding the TAS nonce
+        #[arg(long, value_name = "FILE")]
+        nonce_file: PathBuf,
+        /// Bundle to write
+        #[arg(long, value_name = "FILE")]
+        out: PathBuf,
+        /// Where to keep the private wrapping key until import; never
+        /// carry it off the guest
+        #[arg(long, value_name = "FILE")]
+        key: PathBuf,
+    },
+    /// Unwrap the secret of a submitted bundle and output it like the
+    /// normal mode
+    Import {
+        /// Bundle returned by `submit`
+        #[arg(long, value_name = "FILE")]
+        bundle: PathBuf,
+        /// Private wrapping key written by `evidence export`
+        #[arg(long, value_name = "FILE")]
+        key: PathBuf,
+    },
+}
+
+/// Default number of key releases issued concurrently.
+const DEFAULT_MAX_PARALLEL_RELEASES: usize = 4;
+
+const DEFAULT_CONFIG_PATH: &str = "/etc/tas_agent/config.toml";
+const DEFAULT_API_KEY_PATH: &str = "/etc/tas_agent/api-key";
+const DEFAULT_CERT_PATH: &str = "/etc/tas_agent/root_cert.pem";
+
+/// `[keys."<policy ID>"]` section of the config file: the TAS server and
+/// credentials of one policy ID, where they differ from the defaults.
+#[derive(Deserialize, Clone, Default)]
+struct KeyConfig {
+    server_uri: Option<String>,
+    api_key: Option<PathBuf>,
+    cert_path: Option<PathBuf>,
+    /// Steps reshaping the decrypted secret before it is delivered
+    #[serde(default)]
+    postprocess: Vec<postprocess::Step>,
 }
 
 #[derive(Deserialize, Default)]
 struct Config {
     server_uri: Option<String>,
+    /// Accept a plaintext http:// server URI
+    allow_insecure: Option<bool>,
     api_key: Option<PathBuf>,
     policy_id: Option<String>,
     cert_path: Option<PathBuf>,
     max_retries: Option<u32>,
     retry_min_backoff_secs: Option<u64>,
     retry_max_backoff_secs: Option<u64>,
+    /// Minimum TLS version and cipher suites of the TAS connection
+    tls: Option<tls::TlsConfig>,
+    /// Find the TAS server through DNS SRV records when no URI is set
+    srv: Option<srv::SrvConfig>,
+    /// Look up an unset server URI or policy ID in the instance metadata
+    discovery: Option<discovery::DiscoveryConfig>,
+    /// Largest TAS responses and base64-decoded fields the agent accepts
+    limits: Option<limits::LimitsConfig>,
+    /// Most key releases in flight at once when releasing several secrets
+    /// (default: 4)
+    max_parallel_releases: Option<usize>,
+    /// TAS server and credentials per policy ID, for multi-tenant guests
+    #[serde(default)]
+    keys: BTreeMap<String, KeyConfig>,
+    /// Operator-defined claims sent with the evidence
+    claims: Option<BTreeMap<String, String>>,
+    /// Send the kernel command line, initrd digest and UKI identity as claims
+    boot_claims: Option<boot_claims::BootClaimsConfig>,
+    /// Directory holding the offline escrow of the last released secret
+    escrow_dir: Option<PathBuf>,
+    /// PCR selection the escrow key is sealed to (required with escrow_dir)
+    escrow_pcrs: Option<String>,
+    /// Allow unlocking from the escrow when the TAS server is unreachable
+    offline_fallback: Option<bool>,
+    /// Reject secret payloads not bound to the request nonce or evidence
+    require_payload_binding: Option<bool>,
+    /// Field names and encoding of secret payloads from other key brokers
+    payload_mapping: Option<utils::PayloadMapping>,
+    /// Accept TAS nonces that are not 64 bytes long by hashing them with
+    /// SHA-512
+    hash_nonce: Option<bool>,
+    /// Encoding of the TAS nonce: "raw" (default), "hex", "base64" or "auto"
+    nonce_encoding: Option<utils::NonceEncoding>,
+    /// Mix locally generated random bytes with the TAS nonce
+    local_entropy: Option<bool>,
+    /// Encoding of the public wrapping key: "base64-der" or "pem" (default:
+    /// whichever TAS advertises, else "base64-der")
+    wrapping_key_encoding: Option<crypto::WrappingKeyEncoding>,
+    /// Algorithm TAS wraps secrets with: "RSA-OAEP-256", "ECDH-ES" or "HPKE"
+    /// (default: the strongest both sides support)
+    wrap_algorithm: Option<wrap::WrapAlgorithm>,
+    /// Send the CPU model, microcode, kernel and TCB versions with the evidence
+    platform_metadata: Option<bool>,
+    /// Seal released secrets to the TPM's PCR state for later boots
+    sealed_cache: Option<sealed_cache::SealedCacheConfig>,
+    /// configfs-tsm settings, e.g. attestation through an SVSM
+    tsm: Option<tee_evidence::TsmConfig>,
+    /// Take wrapping keys from a pool generated ahead of time
+    key_pool: Option<key_pool::KeyPoolConfig>,
+    /// Reuse a recent TAS version probe across one-shot invocations
+    version_cache: Option<version_cache::VersionCacheConfig>,
+    /// Share one attestation between one-shot invocations
+    shared_attestation: Option<shared_attestation::SharedAttestationConfig>,
+    /// Append audit events as JSON lines to this file
+    audit_log: Option<PathBuf>,
+    /// Programs to run after each key release
+    hooks: Option<hooks::HooksConfig>,
+    /// External verifier to appraise evidence before key release
+    verifier: Option<verifier::VerifierConfig>,
+    /// Package evidence as a signed EAT for the verifier
+    eat: Option<eat::EatConfig>,
+    /// Sign the evidence with a key bound into the report data
+    evidence_signing: Option<custody::EvidenceSigningConfig>,
+    /// Require and verify an attestation result from TAS
+    ear: Option<ear::EarConfig>,
+    /// Verify the TAS server's own attestation before using a secret
+    server_attestation: Option<server_attestation::ServerAttestationConfig>,
+    /// Mix an SEV-SNP derived key into the released secret
+    #[cfg(feature = "snp-derived-key")]
+    snp_derived_key: Option<derived_key::DerivedKeyConfig>,
+    /// Publish attestation tokens to SPIRE and fetch SPIFFE X.509 SVIDs
+    spiffe: Option<spiffe::SpiffeConfig>,
+    /// Pull secrets from HashiCorp Vault with the attestation token
+    vault: Option<vault::VaultConfig>,
+    /// Release the key from a cloud KMS instead of the TAS KBM
+    kms: Option<kms::KmsConfig>,
+    /// Evaluate a local policy before using released secrets
+    local_policy: Option<local_policy::LocalPolicyConfig>,
+    /// Report failures to the TAS telemetry endpoint
+    telemetry: Option<telemetry::TelemetryConfig>,
     /// Set to true to disable GPU attestation
     // Any GPU feature
     #[cfg(feature = "gpu-nvidia")]
@@ -137,86 +482,638 @@ struct Config {
     /// Enable initramfs-tools passfifo watcher mode
     #[cfg(feature = "passfifo")]
     passfifo: Option<bool>,
+    /// Daemon mode settings
+    #[cfg(feature = "daemon")]
+    daemon: Option<daemon::DaemonConfig>,
+    /// Restrict the normal mode to a syscall allowlist before contacting TAS
+    #[cfg(feature = "seccomp")]
+    seccomp: Option<bool>,
+    /// Confine the normal mode's filesystem access with Landlock
+    #[cfg(feature = "landlock")]
+    landlock: Option<bool>,
+    /// Override these settings with tas_agent.* kernel parameters
+    kernel_cmdline: Option<bool>,
+    /// Seconds of clock skew tolerated in token time checks (default: 60)
+    clock_skew_secs: Option<u64>,
+}
+
+impl Config {
+    /// Configured integrations that run external programs, which the
+    /// seccomp and Landlock sandboxes forbid. `token_out` is whether
+    /// --token-out is given, which without a verifier token is signed by
+    /// tpm2-tools.
+    #[cfg(any(feature = "seccomp", feature = "landlock"))]
+    fn program_integrations(&self, token_out: bool) -> Vec<&'static str> {
+        [
+            ("[hooks]", self.hooks.is_some()),
+            (
+                "[eat] signing_key = \"tpm\"",
+                self.eat
+                    .as_ref()
+                    .is_some_and(|e| matches!(e.signing_key, eat::SigningKey::Tpm)),
+            ),
+            (
+                "[evidence_signing] key = \"tpm\"",
+                self.evidence_signing
+                    .as_ref()
+                    .is_some_and(|e| matches!(e.key, custody::CustodyKey::Tpm)),
+            ),
+            (
+                "--token-out without [verifier]",
+                token_out && self.verifier.is_none(),
+            ),
+            (
+                "[local_policy] rego",
+                self.local_policy.as_ref().is_some_and(|p| p.rego.is_some()),
+            ),
+            ("[spiffe]", self.spiffe.is_some()),
+            ("escrow_dir", self.escrow_dir.is_some()),
+            ("[sealed_cache]", self.sealed_cache.is_some()),
+            ("[key_pool]", self.key_pool.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(section, set)| set.then_some(section))
+        .collect()
+    }
+
+    /// Most key releases to issue concurrently, at least one.
+    fn max_parallel_releases(&self) -> usize {
+        self.max_parallel_releases
+            .unwrap_or(DEFAULT_MAX_PARALLEL_RELEASES)
+            .max(1)
+    }
+}
+
+/// Read the config file; a missing default config file reads as empty.
+fn read_config(path: Option<&std::path::Path>) -> Result<String> {
+    let config_path = path.unwrap_or(std::path::Path::new(DEFAULT_CONFIG_PATH));
+    if config_path.exists() {
+        std::fs::read_to_string(config_path).with_context(|| {
+            ErrorClass::Config.context(format!("unable to read {:?}", config_path))
+        })
+    } else if path.is_some() {
+        Err(ErrorClass::Config.error(format!("config file {:?} does not exist", config_path)))
+    } else {
+        Ok(String::new())
+    }
 }
 
 fn load_config(path: Option<PathBuf>) -> Result<Config> {
     let config_path = path
         .clone()
-        .unwrap_or_else(|| PathBuf::from("/etc/tas_agent/config.toml"));
-    if !config_path.exists() {
-        if path.is_some() {
-            return Err(anyhow!("config file {:?} does not exist", config_path));
-        }
-        return Ok(Config::default());
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+    // Under privilege separation the evidence helper reads the file
+    #[cfg(feature = "daemon")]
+    let data = privsep::config();
+    #[cfg(not(feature = "daemon"))]
+    let data = None;
+    let data = match data {
+        Some(data) => data,
+        None => read_config(path.as_deref())?,
+    };
+    let mut cfg: Config = toml::from_str(&data)
+        .with_context(|| ErrorClass::Config.context(format!("unable to load {:?}", config_path)))?;
+    if cmdline::enabled() || cfg.kernel_cmdline.unwrap_or(false) {
+        cmdline::apply(&mut cfg)?;
+    }
+    // configfs-tsm settings, the TLS policy, response size limits and the
+    // clock skew tolerance apply to the whole process
+    if let Some(tsm) = &cfg.tsm {
+        tee_evidence::configure(tsm.clone());
+    }
+    if let Some(tls) = &cfg.tls {
+        tls::configure(tls.clone());
     }
+    if let Some(limits) = &cfg.limits {
+        limits::configure(limits.clone());
+    }
+    if let Some(secs) = cfg.clock_skew_secs {
+        clock::configure(secs);
+    }
+    Ok(cfg)
+}
 
-    let data = std::fs::read_to_string(config_path.clone())
-        .with_context(|| format!("unable to read {:?}", config_path))?;
+/// Install the seccomp filter for the normal mode if the config enables it.
+#[cfg(feature = "seccomp")]
+fn apply_seccomp(config_path: Option<PathBuf>, token_out: bool) -> Result<()> {
+    let cfg = load_config(config_path)?;
+    if !cfg.seccomp.unwrap_or(false) {
+        return Ok(());
+    }
+    seccomp::install(&cfg.program_integrations(token_out))
+}
 
-    toml::from_str(&data).with_context(|| format!("unable to load {:?}", config_path))
+/// Confine the normal mode's filesystem access with Landlock if the config
+/// enables it. Runs before the tokio runtime starts, so that every thread
+/// inherits the restriction.
+#[cfg(feature = "landlock")]
+fn apply_landlock(cli: &Cli) -> Result<()> {
+    let cfg = load_config(cli.config.clone())?;
+    if !cfg.landlock.unwrap_or(false) || cli.command.is_some() {
+        return Ok(());
+    }
+    #[cfg(feature = "askpass")]
+    if cli.askpass || cfg.askpass.unwrap_or(false) {
+        return Ok(());
+    }
+    #[cfg(feature = "passfifo")]
+    if cli.passfifo || cfg.passfifo.unwrap_or(false) {
+        return Ok(());
+    }
+    let conflicts = cfg.program_integrations(cli.token_out.is_some());
+    if !conflicts.is_empty() {
+        return Err(anyhow!(
+            "landlock = true cannot be combined with {}, which run external programs",
+            conflicts.join(", ")
+        ));
+    }
+
+    sandbox::restrict(&landlock_rules(cli, &cfg))
+}
+
+/// The Landlock rules for a normal-mode run of `cli` with `cfg`: the system
+/// paths and every file or directory the config names.
+#[cfg(feature = "landlock")]
+fn landlock_rules(cli: &Cli, cfg: &Config) -> Vec<(PathBuf, sandbox::Rights)> {
+    use sandbox::{output_rule, Rights};
+
+    let mut rules = sandbox::system_rules();
+    let read = [
+        cli.config
+            .clone()
+            .unwrap_or_else(|| DEFAULT_CONFIG_PATH.into()),
+        api_key_path(cli.api_key.clone(), cfg.api_key.clone()),
+        cert_path(cli.cert_path.clone(), cfg.cert_path.clone()),
+    ];
+    rules.extend(read.into_iter().map(|path| (path, Rights::Read)));
+    for key in cfg.keys.values() {
+        let read = key.api_key.iter().chain(&key.cert_path);
+        rules.extend(read.map(|path| (path.clone(), Rights::Read)));
+    }
+    if let Some(out) = &cli.out {
+        // The directory, since --shred-after removes the file again
+        rules.push((sandbox::parent_dir(out), Rights::Manage));
+    }
+    if let Some(path) = &cli.token_out {
+        rules.push(output_rule(path));
+    }
+    if let Some(path) = &cfg.audit_log {
+        rules.push(output_rule(path));
+    }
+    if let Some(dir) = &cli.transcript_dir {
+        rules.push(if dir.is_dir() {
+            (dir.clone(), Rights::Manage)
+        } else {
+            output_rule(dir)
+        });
+    }
+    #[cfg(feature = "tls-keylog")]
+    if let Some(path) = &cli.unsafe_tls_keylog {
+        rules.push(output_rule(path));
+    }
+    if cfg.discovery.is_some() {
+        rules.push((discovery::DMI_VENDOR.into(), Rights::Read));
+    }
+    if let Some(config) = &cfg.boot_claims {
+        rules.push((cmdline::CMDLINE.into(), Rights::Read));
+        rules.push((boot_claims::EFIVARS.into(), Rights::Read));
+        if let Some(initrd) = &config.initrd {
+            rules.push((initrd.clone(), Rights::Read));
+        }
+    }
+    if cfg.platform_metadata.unwrap_or(false) {
+        rules.push((platform::CPUINFO.into(), Rights::Read));
+        rules.push((platform::OSRELEASE.into(), Rights::Read));
+    }
+    if cmdline::enabled() || cfg.kernel_cmdline.unwrap_or(false) {
+        rules.push((cmdline::CMDLINE.into(), Rights::Read));
+    }
+    // Files read while releasing, after the ruleset is in force
+    if let Some(ear) = &cfg.ear {
+        rules.push((ear.verifier_key.clone(), Rights::Read));
+    }
+    if let Some(server) = &cfg.server_attestation {
+        rules.push((server.verifier_key.clone(), Rights::Read));
+    }
+    if let Some(path) = cfg.local_policy.as_ref().and_then(|p| p.rules.as_ref()) {
+        rules.push((path.clone(), Rights::Read));
+    }
+    if let Some(path) = cfg.verifier.as_ref().and_then(verifier::credential_path) {
+        rules.push((path.to_path_buf(), Rights::Read));
+    }
+    if let Some(kms::KmsConfig::Azure(azure)) = &cfg.kms {
+        if let Some(path) = &azure.access_token {
+            rules.push((path.clone(), Rights::Read));
+        }
+    }
+    if let Some(vault) = &cfg.vault {
+        if let Some(path) = &vault.cert_path {
+            rules.push((path.clone(), Rights::Read));
+        }
+        rules.extend(vault.secrets.iter().map(|secret| output_rule(&secret.out)));
+    }
+    if let Some(dir) = cfg.version_cache.as_ref().and_then(|c| c.path().parent()) {
+        rules.push((dir.to_path_buf(), Rights::Manage));
+    }
+    if let Some(dir) = cfg
+        .shared_attestation
+        .as_ref()
+        .and_then(|c| c.path().parent())
+    {
+        rules.push((dir.to_path_buf(), Rights::Manage));
+    }
+    if let Some(dir) = cfg.tsm.as_ref().and_then(|t| t.report_dir.clone()) {
+        rules.push((dir, Rights::Manage));
+    }
+    let lock_path = cfg
+        .tsm
+        .as_ref()
+        .map_or(std::path::Path::new(tee_evidence::DEFAULT_LOCK_PATH), |t| {
+            t.lock_path()
+        });
+    rules.push(output_rule(lock_path));
+    if let Some(path) = std::env::var_os(cassette::RECORD_ENV) {
+        rules.push(output_rule(std::path::Path::new(&path)));
+    }
+    #[cfg(feature = "evidence-fixtures")]
+    if let Some(dir) = std::env::var_os(tee_evidence::FIXTURE_ENV) {
+        rules.push((dir.into(), Rights::Read));
+    }
+    #[cfg(feature = "snp-derived-key")]
+    if cfg.snp_derived_key.is_some() {
+        rules.push((derived_key::DEVICE.into(), Rights::Write));
+    }
+    rules
 }
 
 /// Optional CLI overrides for use when calling fetch_key() from askpass mode
 /// or other non-CLI contexts.
+#[derive(Clone, Default)]
 pub struct CliOverrides {
     pub server_uri: Option<String>,
+    pub allow_insecure: bool,
     pub api_key: Option<PathBuf>,
     pub policy_id: Option<String>,
     pub cert_path: Option<PathBuf>,
     pub max_retries: Option<u32>,
     pub retry_min_backoff_secs: Option<u64>,
     pub retry_max_backoff_secs: Option<u64>,
+    pub token_out: Option<PathBuf>,
+    pub claims: Vec<(String, String)>,
     #[cfg(feature = "gpu-nvidia")]
     pub no_gpu: bool,
 }
 
-/// Core key-fetch logic: loads config, contacts TAS, retrieves and decrypts key.
-///
-/// Returns the decrypted key as raw bytes. This function is used by both
-/// the normal stdout mode and the askpass watcher mode.
-pub async fn fetch_key(
-    config_path: Option<PathBuf>,
-    overrides: Option<CliOverrides>,
-) -> Result<Vec<u8>> {
-    let cfg = load_config(config_path)?;
-    let ovr = overrides.unwrap_or(CliOverrides {
-        server_uri: None,
-        api_key: None,
-        policy_id: None,
-        cert_path: None,
-        max_retries: None,
-        retry_min_backoff_secs: None,
-        retry_max_backoff_secs: None,
-        #[cfg(feature = "gpu-nvidia")]
-        no_gpu: false,
-    });
+/// Decrypt the secret payload with the unwrapped AES key, using the algorithm
+/// the secret was wrapped with.
+pub fn decrypt_payload(aes_key: &[u8], secret: &mut SecretsPayload) -> Result<Vec<u8>> {
+    debug!("Decrypting secret using algorithm: {}", secret.algorithm);
+    let aad: &[u8] = secret.aad.as_deref().map_or(b"", Vec::as_slice);
+    if secret.algorithm == "AES-KWP" {
+        if !aad.is_empty() {
+            return Err(ErrorClass::Crypto.error(
+                "AES Key Wrap Decrypt Error: AES-KWP payloads cannot carry authenticated data",
+            ));
+        }
+        debug!("Using AES Key Wrap to unwrap secret");
+        unwrap_secret_with_aes_key_wrap(aes_key, &secret.blob)
+            .map_err(|err| ErrorClass::Crypto.error(format!("AES Key Wrap Decrypt Error: {}", err)))
+    } else if secret.algorithm == "AES-GCM-STREAM" {
+        // Decrypted chunk by chunk in place; the plaintext is moved out of
+        // the blob buffer rather than copied
+        debug!("Using chunked AES-GCM to decrypt secret");
+        decrypt_chunked_in_place(aes_key, &secret.iv, &mut secret.blob, aad)
+            .map_err(|err| ErrorClass::Crypto.error(format!("AES-GCM Decrypt Error: {}", err)))?;
+        Ok(std::mem::take(&mut *secret.blob))
+    } else {
+        debug!("Using AES-GCM to decrypt secret");
+        decrypt_secret_with_aes_key(aes_key, &secret.iv, &mut secret.blob, &secret.tag, aad)
+            .map_err(|err| ErrorClass::Crypto.error(format!("AES-GCM Decrypt Error: {}", err)))
+    }
+}
+
+/// TAS server and credentials of a policy ID.
+#[derive(Clone, PartialEq)]
+struct Endpoint {
+    server_uri: String,
+    api_key: Zeroizing<String>,
+    cert_path: PathBuf,
+}
+
+/// Connection settings resolved from the config file and CLI overrides.
+#[derive(Clone)]
+pub struct Session {
+    server_uri: String,
+    api_key: Zeroizing<String>,
+    policy_id: Option<String>,
+    cert_path: PathBuf,
+    /// Endpoints of policy IDs served by another server or tenant
+    keys: BTreeMap<String, Endpoint>,
+    /// Post-processing steps of policy IDs under `[keys]`
+    postprocess: BTreeMap<String, Vec<postprocess::Step>>,
+    retry_config: RetryConfig,
+    max_parallel_releases: usize,
+    escrow: Option<escrow::EscrowConfig>,
+    offline_fallback: bool,
+    require_payload_binding: bool,
+    payload_mapping: Option<utils::PayloadMapping>,
+    hash_nonce: bool,
+    nonce_encoding: utils::NonceEncoding,
+    local_entropy: bool,
+    /// Configured wrapping key encoding, replaced by the negotiated one once
+    /// the server has been probed
+    wrapping_key_encoding: Option<crypto::WrappingKeyEncoding>,
+    /// Configured wrapping algorithm
+    wrap_algorithm: Option<wrap::WrapAlgorithm>,
+    /// Wrapping algorithm agreed with the server, RSA-OAEP-256 until it has
+    /// been probed
+    negotiated_wrap_algorithm: wrap::WrapAlgorithm,
+    platform_metadata: bool,
+    sealed_cache: Option<sealed_cache::SealedCacheConfig>,
+    key_pool: Option<key_pool::KeyPoolConfig>,
+    version_cache: Option<version_cache::VersionCacheConfig>,
+    shared_attestation: Option<shared_attestation::SharedAttestationConfig>,
+    gpu_enabled: bool,
+    hooks: Option<hooks::HooksConfig>,
+    verifier: Option<verifier::VerifierConfig>,
+    eat: Option<eat::EatConfig>,
+    evidence_signing: Option<custody::EvidenceSigningConfig>,
+    ear: Option<ear::EarConfig>,
+    server_attestation: Option<server_attestation::ServerAttestationConfig>,
+    #[cfg(feature = "snp-derived-key")]
+    snp_derived_key: Option<derived_key::DerivedKeyConfig>,
+    spiffe: Option<spiffe::SpiffeConfig>,
+    vault: Option<vault::VaultConfig>,
+    kms: Option<kms::KmsConfig>,
+    local_policy: Option<local_policy::LocalPolicyConfig>,
+    telemetry: Option<telemetry::TelemetryConfig>,
+    claims: Option<BTreeMap<String, String>>,
+    token_out: Option<PathBuf>,
+}
+
+impl Session {
+    /// A session with the endpoint of `policy_id`, if it differs from this
+    /// session's.
+    fn for_key(&self, policy_id: &str) -> Option<Session> {
+        let endpoint = self.keys.get(policy_id)?;
+        if endpoint.server_uri == self.server_uri
+            && endpoint.api_key == self.api_key
+            && endpoint.cert_path == self.cert_path
+        {
+            return None;
+        }
+        Some(Session {
+            server_uri: endpoint.server_uri.clone(),
+            api_key: endpoint.api_key.clone(),
+            cert_path: endpoint.cert_path.clone(),
+            ..self.clone()
+        })
+    }
+
+    /// Probe the TAS server version, which also checks connectivity.
+    pub async fn server_version(&self) -> Result<ServerVersion, TasError> {
+        tas_get_version(
+            &self.server_uri,
+            &self.api_key,
+            self.cert_path.clone(),
+            &self.retry_config,
+        )
+        .await
+    }
+
+    /// Like `server_version`, but answered from the `[version_cache]` while
+    /// a previous probe of the same server is fresh. Not used with
+    /// `offline_fallback`, which relies on the probe to detect an
+    /// unreachable server.
+    pub async fn cached_server_version(&self) -> Result<ServerVersion, TasError> {
+        let cache = match &self.version_cache {
+            Some(cache) if !self.offline_fallback => cache,
+            _ => return self.server_version().await,
+        };
+        if let Some(version) = version_cache::load(cache, &self.server_uri) {
+            debug!("Using cached TAS server version");
+            return Ok(version);
+        }
+        let version = self.server_version().await?;
+        if let Err(e) = version_cache::store(cache, &self.server_uri, &version) {
+            warn!("Failed to cache TAS server version: {:#}", e);
+        }
+        Ok(version)
+    }
+
+    /// Whether secrets must be wrapped to the RSA key: the EAT, the cloud KMS
+    /// and the shared attestation carry it, and FIPS builds only use RSA.
+    fn rsa_only(&self) -> bool {
+        self.eat.is_some()
+            || self.kms.is_some()
+            || self.shared_attestation.is_some()
+            || cfg!(feature = "fips")
+    }
+
+    /// Settle the wrapping key encoding and algorithm with those the server
+    /// advertised in its version probe.
+    pub fn negotiate(&mut self, version: &ServerVersion) {
+        let encoding = crypto::WrappingKeyEncoding::negotiate(
+            self.wrapping_key_encoding,
+            &version.wrapping_key_encodings,
+        );
+        if self.wrapping_key_encoding.is_some_and(|e| e != encoding) {
+            info!(
+                "TAS does not accept the configured wrapping key encoding; using {}",
+                encoding.as_str()
+            );
+        }
+        self.wrapping_key_encoding = Some(encoding);
 
-    let server_uri = ovr
-        .server_uri
-        .or(cfg.server_uri)
-        .ok_or_else(|| anyhow!("server URI is required"))?;
+        let algorithm = wrap::WrapAlgorithm::negotiate(
+            self.wrap_algorithm,
+            &version.wrap_algorithms,
+            !self.rsa_only(),
+        );
+        if self.wrap_algorithm.is_some_and(|a| a != algorithm) {
+            info!(
+                "Unable to use the configured wrapping algorithm; using {}",
+                algorithm.as_str()
+            );
+        }
+        self.negotiated_wrap_algorithm = algorithm;
+    }
+}
 
-    if !server_uri.starts_with("http://") && !server_uri.starts_with("https://") {
+/// Check the syntax of a policy ID: a key ID or a KBS resource URI.
+fn check_policy_id(policy_id: &str) -> Result<()> {
+    if policy_id.is_empty() {
+        return Err(anyhow!("policy ID must not be empty"));
+    }
+    if policy_id
+        .chars()
+        .any(|c| c.is_whitespace() || c.is_control())
+    {
         return Err(anyhow!(
-            "server URI must start with http:// or https:// (got {:?})",
+            "policy ID {:?} contains whitespace or control characters",
+            policy_id
+        ));
+    }
+    kbs::ResourceUri::parse(policy_id).map(|_| ())
+}
+
+/// Check that the CA certificates for an HTTPS `server_uri` exist.
+fn check_cert_path(server_uri: &str, cert_path: &std::path::Path) -> Result<()> {
+    if server_uri.starts_with("https://") && !cert_path.exists() {
+        return Err(anyhow!(
+            "CA certificates {:?} for {} do not exist",
+            cert_path,
             server_uri
         ));
     }
+    Ok(())
+}
 
-    let api_key_path = ovr
-        .api_key
-        .or(cfg.api_key)
-        .unwrap_or_else(|| PathBuf::from("/etc/tas_agent/api-key"));
+/// Settings problems found while resolving a session, reported together so
+/// that a broken config can be fixed in one pass. A network failure among
+/// them, e.g. of a DNS SRV lookup, keeps its class.
+#[derive(Default)]
+struct Problems(Vec<String>, Option<anyhow::Error>);
 
-    let policy_id = ovr
-        .policy_id
-        .or(cfg.policy_id)
-        .ok_or_else(|| anyhow!("server policy ID is required"))?;
+impl Problems {
+    fn push(&mut self, problem: &str) {
+        self.0.push(problem.to_string());
+    }
+
+    /// The value of `result`, recording its error, if any.
+    fn check<T>(&mut self, result: Result<T>) -> Option<T> {
+        result
+            .map_err(|e| {
+                self.0.push(format!("{:#}", e));
+                self.1.get_or_insert(e);
+            })
+            .ok()
+    }
+
+    fn into_result(self) -> Result<()> {
+        let class = |message: String| match &self.1 {
+            Some(cause) => ErrorClass::Config.wrap(cause, message),
+            None => ErrorClass::Config.error(message),
+        };
+        match self.0.as_slice() {
+            [] => Ok(()),
+            [problem] => Err(class(format!("invalid configuration: {}", problem))),
+            problems => Err(class(format!(
+                "invalid configuration ({} problems):\n  - {}",
+                problems.len(),
+                problems.join("\n  - ")
+            ))),
+        }
+    }
+}
+
+/// Environment variable naming the file to read the API key from.
+const API_KEY_FILE_ENV: &str = "TAS_SERVER_API_KEY_FILE";
+
+/// Name of the systemd credential (`LoadCredential=`) holding the API key.
+const API_KEY_CREDENTIAL: &str = "tas-api-key";
+
+/// Environment variable naming the CA certificate bundle or directory.
+const ROOT_CERT_ENV: &str = "TAS_SERVER_ROOT_CERT";
+
+/// The CA certificates to verify TAS with: `--cert-path`,
+/// TAS_SERVER_ROOT_CERT, the config file or the default path.
+fn cert_path(cli: Option<PathBuf>, config: Option<PathBuf>) -> PathBuf {
+    cli.or_else(|| std::env::var_os(ROOT_CERT_ENV).map(PathBuf::from))
+        .or(config)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CERT_PATH))
+}
+
+/// The file to read the API key from: `--api-key`, TAS_SERVER_API_KEY_FILE,
+/// the config file, the systemd credential if the unit passes one, or the
+/// default path.
+fn api_key_path(cli: Option<PathBuf>, config: Option<PathBuf>) -> PathBuf {
+    cli.or_else(|| std::env::var_os(API_KEY_FILE_ENV).map(PathBuf::from))
+        .or(config)
+        .or_else(|| {
+            let dir = std::env::var_os("CREDENTIALS_DIRECTORY")?;
+            let path = PathBuf::from(dir).join(API_KEY_CREDENTIAL);
+            path.exists().then_some(path)
+        })
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_API_KEY_PATH))
+}
+
+/// Read the API key at `path`, zeroizing the file contents once trimmed.
+fn read_api_key(path: &std::path::Path) -> Result<Zeroizing<String>> {
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path).with_context(|| {
+        ErrorClass::Config.context(format!("unable to read API key from {:?}", path))
+    })?;
+    // Sized up front so that reading never reallocates, leaving copies
+    let len = file.metadata().map_or(0, |m| m.len() as usize);
+    let mut contents = Zeroizing::new(Vec::with_capacity(len + 1));
+    file.read_to_end(&mut contents).with_context(|| {
+        ErrorClass::Config.context(format!("unable to read API key from {:?}", path))
+    })?;
+    let key = std::str::from_utf8(&contents).with_context(|| {
+        ErrorClass::Config.context(format!("API key in {:?} is not UTF-8", path))
+    })?;
+    Ok(Zeroizing::new(key.trim().to_string()))
+}
+
+/// Load the config, apply the overrides and read the API key.
+pub fn resolve_session(
+    config_path: Option<PathBuf>,
+    overrides: Option<CliOverrides>,
+) -> Result<Session> {
+    let cfg = load_config(config_path)?;
+    let ovr = overrides.unwrap_or_default();
+    let max_parallel_releases = cfg.max_parallel_releases();
+
+    let mut problems = Problems::default();
+
+    let mut discovered = discovery::Discovered::default();
+    // A failed lookup is reported rather than as a missing setting
+    let mut lookup_failed = false;
+    if let Some(discovery) = &cfg.discovery {
+        let unset = |ovr: &Option<String>, cfg: &Option<String>| ovr.is_none() && cfg.is_none();
+        let server_unset = unset(&ovr.server_uri, &cfg.server_uri) && cfg.srv.is_none();
+        if server_unset || unset(&ovr.policy_id, &cfg.policy_id) {
+            let found =
+                discovery::discover(discovery).context("instance metadata discovery failed");
+            lookup_failed = found.is_err();
+            discovered = problems.check(found).unwrap_or_default();
+        }
+    }
+
+    let policy_id = ovr.policy_id.or(cfg.policy_id).or(discovered.policy_id);
+    let implied_uri = match &policy_id {
+        Some(policy_id) => problems
+            .check(check_policy_id(policy_id).and_then(|()| kbs::server_uri(policy_id)))
+            .flatten(),
+        None => None,
+    };
+    let server_uri = match ovr.server_uri.or(cfg.server_uri).or(implied_uri) {
+        Some(server_uri) => Some(server_uri),
+        None => cfg.srv.as_ref().and_then(|config| {
+            let resolved = srv::resolve(config);
+            lookup_failed |= resolved.is_err();
+            problems.check(resolved)
+        }),
+    };
+    let allow_insecure = ovr.allow_insecure || cfg.allow_insecure.unwrap_or(false);
+    let server_uri = match server_uri.or(discovered.server_uri) {
+        Some(uri) => problems
+            .check(server_uri::normalize(&uri, allow_insecure))
+            .unwrap_or(uri),
+        None => {
+            if !lookup_failed {
+                problems.push("server URI is required");
+            }
+            String::new()
+        }
+    };
 
-    let cert_path = ovr
-        .cert_path
-        .or(cfg.cert_path)
-        .unwrap_or_else(|| PathBuf::from("/etc/tas_agent/root_cert.pem"));
+    let api_key_path = api_key_path(ovr.api_key, cfg.api_key);
+
+    let cert_path = cert_path(ovr.cert_path, cfg.cert_path);
+    problems.check(check_cert_path(&server_uri, &cert_path));
 
     let retry_config = RetryConfig {
         max_retries: ovr.max_retries.or(cfg.max_retries).unwrap_or(3),
@@ -231,55 +1128,312 @@ pub async fn fetch_key(
     };
     debug!("Retry config: {:?}", retry_config);
 
-    let api_key = read_to_string(api_key_path.clone())
-        .with_context(|| format!("unable to read API key from {:?}", api_key_path))?
-        .trim()
-        .to_string();
+    // Any GPU feature
+    #[cfg(feature = "gpu-nvidia")]
+    let gpu_enabled = !ovr.no_gpu && !cfg.no_gpu.unwrap_or(false);
+    #[cfg(not(feature = "gpu-nvidia"))]
+    let gpu_enabled = false;
+
+    audit::init(cfg.audit_log.as_deref());
 
-    // Generate a wrapping key for the HSM to wrap the secret key with
-    debug!("Generating wrapping key...");
-    let rsa_wrapping_key =
-        generate_wrapping_key().map_err(|e| anyhow!("failed to generate wrapping key: {}", e))?;
-    debug!("\nGenerated wrapping key: {}\n", rsa_wrapping_key);
+    if cfg.eat.is_some() && cfg.verifier.is_none() {
+        warn!("[eat] has no effect without a [verifier] section");
+    }
+    if cfg.spiffe.is_some() && cfg.verifier.is_none() {
+        warn!("[spiffe] has no effect without a [verifier] section");
+    }
+    if cfg.vault.is_some() && cfg.verifier.is_none() {
+        warn!("[vault] has no effect without a [verifier] section");
+    }
+    if cfg.kms.is_some() && cfg.verifier.is_none() {
+        problems.push("[kms] requires a [verifier] section");
+    }
 
-    let wrapping_key = rsa_wrapping_key
-        .public_key_to_base64()
-        .map_err(|e| anyhow!("failed to convert wrapping key to DER base64: {}", e))?;
-    debug!("Base64-encoded public wrapping key: {}\n", wrapping_key);
+    let api_key = problems.check(read_api_key(&api_key_path));
+
+    let mut claims = claims::merge(cfg.claims, &ovr.claims);
+    if let Some(config) = &cfg.boot_claims {
+        let boot =
+            problems.check(boot_claims::collect(config).context("unable to collect boot claims"));
+        claims
+            .get_or_insert_with(BTreeMap::new)
+            .extend(boot.unwrap_or_default());
+    }
 
-    // Call the function to get the TAS server version
-    match tas_get_version(&server_uri, &api_key, cert_path.clone(), &retry_config).await {
-        Ok(version) => debug!("TEE Attestation Server Version: {}", version),
-        Err(err) => {
-            return Err(anyhow!("TAS Version Error: {}", err));
+    let mut keys = BTreeMap::new();
+    let mut postprocess = BTreeMap::new();
+    for (policy_id, key) in cfg.keys {
+        problems.check(
+            check_policy_id(&policy_id).with_context(|| format!("invalid [keys.{:?}]", policy_id)),
+        );
+        problems.check(
+            postprocess::check(&key.postprocess)
+                .with_context(|| format!("invalid [keys.{:?}] postprocess", policy_id)),
+        );
+        if !key.postprocess.is_empty() {
+            postprocess.insert(policy_id.clone(), key.postprocess);
+        }
+        let server_uri = match key.server_uri {
+            Some(uri) => problems
+                .check(
+                    server_uri::normalize(&uri, allow_insecure)
+                        .with_context(|| format!("invalid [keys.{:?}] server_uri", policy_id)),
+                )
+                .unwrap_or(uri),
+            None => server_uri.clone(),
+        };
+        let cert_path = key.cert_path.unwrap_or_else(|| cert_path.clone());
+        problems.check(
+            check_cert_path(&server_uri, &cert_path)
+                .with_context(|| format!("invalid [keys.{:?}] cert_path", policy_id)),
+        );
+        let api_key = match &key.api_key {
+            Some(path) => problems.check(read_api_key(path)),
+            None => api_key.clone(),
+        };
+        if let Some(api_key) = api_key {
+            let endpoint = Endpoint {
+                server_uri,
+                api_key,
+                cert_path,
+            };
+            keys.insert(policy_id, endpoint);
         }
     }
 
+    let escrow = match (cfg.escrow_dir, cfg.escrow_pcrs) {
+        (Some(dir), Some(pcrs)) => Some(escrow::EscrowConfig { dir, pcrs }),
+        (Some(_), None) => {
+            problems.push("escrow_dir requires escrow_pcrs");
+            None
+        }
+        (None, _) => None,
+    };
+
+    problems.into_result()?;
+    let api_key = api_key.unwrap_or_default();
+
+    Ok(Session {
+        server_uri,
+        api_key,
+        policy_id,
+        cert_path,
+        keys,
+        postprocess,
+        retry_config,
+        max_parallel_releases,
+        escrow,
+        offline_fallback: cfg.offline_fallback.unwrap_or(false),
+        require_payload_binding: cfg.require_payload_binding.unwrap_or(false),
+        payload_mapping: cfg.payload_mapping,
+        hash_nonce: cfg.hash_nonce.unwrap_or(false),
+        nonce_encoding: cfg.nonce_encoding.unwrap_or_default(),
+        local_entropy: cfg.local_entropy.unwrap_or(false),
+        wrapping_key_encoding: cfg.wrapping_key_encoding,
+        wrap_algorithm: cfg.wrap_algorithm,
+        negotiated_wrap_algorithm: wrap::WrapAlgorithm::default(),
+        platform_metadata: cfg.platform_metadata.unwrap_or(false),
+        sealed_cache: cfg.sealed_cache,
+        key_pool: cfg.key_pool,
+        version_cache: cfg.version_cache,
+        shared_attestation: cfg.shared_attestation,
+        gpu_enabled,
+        hooks: cfg.hooks,
+        verifier: cfg.verifier,
+        eat: cfg.eat,
+        evidence_signing: cfg.evidence_signing,
+        ear: cfg.ear,
+        server_attestation: cfg.server_attestation,
+        #[cfg(feature = "snp-derived-key")]
+        snp_derived_key: cfg.snp_derived_key,
+        spiffe: cfg.spiffe,
+        vault: cfg.vault,
+        kms: cfg.kms,
+        local_policy: cfg.local_policy,
+        telemetry: cfg.telemetry,
+        claims,
+        token_out: ovr.token_out,
+    })
+}
+
+/// TEE evidence bound to a TAS nonce and an ephemeral wrapping key.
+///
+/// One attestation can back several key releases while TAS still accepts
+/// its nonce.
+pub struct Attestation {
+    session: Session,
+    nonce: String,
+    /// How the nonce was turned into 64 bytes, if it was not used as is
+    nonce_construction: Option<&'static str>,
+    /// Base64 of the local random bytes mixed into the nonce, if any
+    local_entropy: Option<String>,
+    /// Chain-of-custody signature over the request, if evidence is signed
+    evidence_signature: Option<EvidenceSignature>,
+    tee_evidence: String,
+    tee_type: String,
+    rsa_wrapping_key: RsaKey,
+    /// P-256 key secrets are wrapped to instead, if ECDH-ES or HPKE was
+    /// negotiated
+    ec_wrapping_key: Option<wrap::EcKey>,
+    wrapping_key: String,
+    key_binding_enabled: bool,
+    /// Report data bound into the evidence, if key binding is enabled
+    report_data: Option<Vec<u8>>,
+    component_evidence: Option<serde_json::Value>,
+    attestation_token: Option<String>,
+    /// Unmeasured platform description sent with the evidence, if enabled
+    platform_metadata: Option<serde_json::Value>,
+}
+
+impl Attestation {
+    /// The TEE type the evidence was collected from.
+    pub fn tee_type(&self) -> &str {
+        &self.tee_type
+    }
+
+    /// DER SubjectPublicKeyInfo of the key secrets are wrapped to, as bound
+    /// into the evidence.
+    fn wrapping_key_der(&self) -> Result<Vec<u8>> {
+        match &self.ec_wrapping_key {
+            Some(key) => key.public_key_to_der().map_err(|e| anyhow!("{}", e)),
+            None => self
+                .rsa_wrapping_key
+                .public_key_to_der()
+                .map_err(|e| anyhow!("{}", e)),
+        }
+    }
+}
+
+/// An ephemeral wrapping key and a TAS nonce, ready to be bound into TEE
+/// evidence.
+pub struct Preparation {
+    rsa_wrapping_key: RsaKey,
+    nonce: String,
+}
+
+/// Generate a wrapping key while fetching a nonce from TAS.
+///
+/// Key generation is CPU-bound and the nonce a network round trip, so they
+/// run concurrently; callers can also probe the server version alongside.
+pub async fn prepare(session: &Session) -> Result<Preparation> {
+    // Generate a wrapping key for the HSM to wrap the secret key with, unless
+    // one was generated ahead of time
+    let pool = session.key_pool.clone();
+    let keygen = tokio::task::spawn_blocking(move || {
+        if let Some(pool) = &pool {
+            match key_pool::take(pool) {
+                Ok(Some(key)) => return Ok(key),
+                Ok(None) => info!("Wrapping key pool is empty; generating a key"),
+                Err(e) => warn!("Unable to use pooled wrapping key: {:#}", e),
+            }
+        }
+        debug!("Generating wrapping key...");
+        generate_wrapping_key().map_err(|e| e.to_string())
+    });
+
     // Call the function to get the nonce from the TAS server
-    let nonce = tas_get_nonce(&server_uri, &api_key, cert_path.clone(), &retry_config)
+    let nonce = async {
+        if fault::fire(fault::Fault::NonceTimeout) {
+            return Err("Error making request: operation timed out (injected fault)".to_string());
+        }
+        tas_get_nonce(
+            &session.server_uri,
+            &session.api_key,
+            session.cert_path.clone(),
+            &session.retry_config,
+        )
         .await
-        .map_err(|e| anyhow!("TAS Nonce Error: {}", e))?;
+    };
+
+    let (rsa_wrapping_key, nonce) = tokio::join!(keygen, nonce);
+    let rsa_wrapping_key = rsa_wrapping_key
+        .map_err(|e| ErrorClass::Crypto.error(format!("failed to generate wrapping key: {}", e)))?
+        .map_err(|e| ErrorClass::Crypto.error(format!("failed to generate wrapping key: {}", e)))?;
+    debug!("\nGenerated wrapping key: {}\n", rsa_wrapping_key);
+    let nonce = nonce.map_err(|e| ErrorClass::Network.error(format!("TAS Nonce Error: {}", e)))?;
     debug!("Nonce: {}", nonce);
 
+    Ok(Preparation {
+        rsa_wrapping_key,
+        nonce,
+    })
+}
+
+/// Generate a wrapping key, fetch a nonce from TAS and collect TEE evidence
+/// binding both.
+pub async fn attest(session: Session) -> Result<Attestation> {
+    let preparation = prepare(&session).await?;
+    attest_prepared(session, preparation).await
+}
+
+/// Collect TEE evidence binding the wrapping key and nonce of `preparation`.
+pub async fn attest_prepared(session: Session, preparation: Preparation) -> Result<Attestation> {
+    let Preparation {
+        rsa_wrapping_key,
+        nonce,
+    } = preparation;
+
+    // An EC algorithm takes a P-256 key in place of the RSA key; it is
+    // cheap enough to generate here
+    let algorithm = session.negotiated_wrap_algorithm;
+    let ec_wrapping_key = algorithm.is_ec().then(|| wrap::EcKey::generate(algorithm));
+    if algorithm.is_ec() {
+        debug!("Wrapping with {}", algorithm.as_str());
+    }
+
+    let encoding = session.wrapping_key_encoding.unwrap_or_default();
+    let wrapping_key = match &ec_wrapping_key {
+        Some(key) => key.public_key_encoded(encoding).map_err(|e| e.to_string()),
+        None => rsa_wrapping_key
+            .public_key_encoded(encoding)
+            .map_err(|e| e.to_string()),
+    }
+    .map_err(|e| anyhow!("failed to encode wrapping key: {}", e))?;
+    // DER of the public key bound into the evidence
+    let pubkey_der = match &ec_wrapping_key {
+        Some(key) => key.public_key_to_der().map_err(|e| e.to_string()),
+        None => rsa_wrapping_key
+            .public_key_to_der()
+            .map_err(|e| e.to_string()),
+    }
+    .map_err(|e| anyhow!("Failed to get public key DER: {}", e))?;
+    debug!(
+        "Public wrapping key ({}): {}\n",
+        encoding.as_str(),
+        wrapping_key
+    );
+
     // Key binding is always enabled
     let key_binding_enabled = true;
 
-    // --- GPU attestation evidence collection ---
-    // Any GPU feature
-    #[cfg(feature = "gpu-nvidia")]
-    let gpu_enabled = !ovr.no_gpu && !cfg.no_gpu.unwrap_or(false);
-    #[cfg(not(feature = "gpu-nvidia"))]
-    let gpu_enabled = false;
+    // The challenge the nonce encodes, as bound into the evidence
+    let challenge = utils::decode_nonce(nonce.trim_matches('"'), session.nonce_encoding)
+        .map_err(|e| ErrorClass::Config.error(format!("Nonce Decode Error: {}", e)))?;
+    let (nonce_input, nonce_construction, local_entropy) = if session.local_entropy {
+        let entropy = crypto::local_entropy();
+        (
+            crypto::mix_local_entropy(&challenge, &entropy),
+            Some(crypto::NONCE_LOCAL_ENTROPY),
+            Some(STANDARD.encode(entropy)),
+        )
+    } else {
+        let (nonce_input, nonce_construction) = crypto::nonce_input(&challenge, session.hash_nonce)
+            .map_err(|e| ErrorClass::Config.error(format!("Nonce Decode Error: {}", e)))?;
+        (nonce_input, nonce_construction, None)
+    };
+    if let Some(construction) = nonce_construction {
+        debug!("Binding the {} of the nonce", construction);
+    }
 
-    let (component_evidence, _component_hashes) = if gpu_enabled {
+    // --- GPU attestation evidence collection ---
+    let (component_evidence, _component_hashes) = if session.gpu_enabled {
         #[cfg(feature = "gpu-nvidia")]
         {
             let nonce_trimmed = nonce.trim_matches('"');
             match components::gpu_nvidia::collect_and_hash_gpu_evidence(nonce_trimmed) {
                 Ok((evidence_json, hashes)) => (Some(evidence_json), hashes),
                 Err(e) => {
-                    eprintln!("GPU attestation error: {}", e);
-                    std::process::exit(1);
+                    return Err(ErrorClass::Evidence.error(format!("GPU attestation error: {}", e)))
                 }
             }
         }
@@ -293,26 +1447,30 @@ pub async fn fetch_key(
         (None, Vec::<u8>::new())
     };
 
+    // The evidence signing key is bound after the component hashes
+    let signer = session
+        .evidence_signing
+        .as_ref()
+        .map(custody::Signer::new)
+        .transpose()
+        .map_err(|e| {
+            ErrorClass::Evidence.error(format!(
+                "TEE evidence Error: unable to create signing key: {:#}",
+                e
+            ))
+        })?;
+    let mut bound_extra = _component_hashes.clone();
+    if let Some(signer) = &signer {
+        bound_extra.extend(signer.public_key());
+    }
+
     // --- Compute CPU report_data binding ---
     let report_data: Option<Vec<u8>> = if key_binding_enabled {
-        let pubkey_der = rsa_wrapping_key
-            .public_key_to_der()
-            .map_err(|e| anyhow!("Failed to get public key DER: {}", e))?;
-
-        let nonce_trimmed = nonce.trim_matches('"');
-        // Any component feature
-        #[cfg(feature = "gpu-nvidia")]
-        let binding = if _component_hashes.is_empty() {
-            compute_report_data_binding(nonce_trimmed.as_bytes(), &pubkey_der)
+        let binding = if bound_extra.is_empty() {
+            compute_report_data_binding(&nonce_input, &pubkey_der)
         } else {
-            compute_report_data_binding_with_components(
-                nonce_trimmed.as_bytes(),
-                &pubkey_der,
-                &_component_hashes,
-            )
+            compute_report_data_binding_with_components(&nonce_input, &pubkey_der, &bound_extra)
         };
-        #[cfg(not(feature = "gpu-nvidia"))]
-        let binding = compute_report_data_binding(nonce_trimmed.as_bytes(), &pubkey_der);
         debug!("Report data binding (hex): {}", hex::encode(&binding));
         Some(binding)
     } else {
@@ -320,69 +1478,591 @@ pub async fn fetch_key(
     };
 
     // Generate the TEE evidence with key binding
-    let (tee_evidence, tee_type) = tee_get_evidence(&nonce, report_data.as_deref())
-        .map_err(|err| anyhow!("TEE evidence Error: {}", err))?;
+    #[cfg(feature = "daemon")]
+    let evidence = privsep::get_evidence(&nonce, report_data.as_deref());
+    #[cfg(not(feature = "daemon"))]
+    let evidence = tee_get_evidence(&nonce, report_data.as_deref());
+    let evidence = if fault::fire(fault::Fault::MissingOutblob) {
+        Err("Failed to read outblob file: No such file or directory (injected fault)".to_string())
+    } else {
+        evidence
+    };
+    let evidence = evidence
+        .map_err(|err| ErrorClass::Evidence.error(format!("TEE evidence Error: {}", err)))?;
+    let tee_evidence = evidence.encode();
+    let tee_type = evidence.tee_type.to_string();
     debug!("Generated TEE Evidence (Base64-encoded): {}", tee_evidence);
     debug!("TEE Type: {}", tee_type);
+    if transcript::active() {
+        transcript::save("nonce.txt", nonce.as_bytes());
+        if let Some(report_data) = &report_data {
+            transcript::save("report-data.bin", report_data);
+        }
+        transcript::save("report.bin", &evidence.report);
+        if let Some(aux) = &evidence.aux {
+            transcript::save("report-aux.bin", aux);
+        }
+        match tas_api::read_ca_certs(&session.cert_path) {
+            Ok(certs) => transcript::save("tas-ca.pem", &certs),
+            Err(e) => warn!(
+                "unable to read the CA certificates for the transcript: {}",
+                e
+            ),
+        }
+    }
 
-    // Call the function to get the secret key
-    let secret_string = tas_get_secret_key(
-        &server_uri,
-        &api_key,
-        &nonce,
-        &tee_evidence,
-        &tee_type,
-        &policy_id,
-        &wrapping_key,
-        cert_path.clone(),
-        &retry_config,
+    let platform_metadata = session
+        .platform_metadata
+        .then(|| platform::collect(&tee_type, &tee_evidence));
+
+    // Have the external verifier appraise the evidence, if one is configured
+    let attestation_token = match &session.verifier {
+        Some(verifier) => {
+            // The report data is SHA-512 of this runtime data
+            let mut runtime_data = nonce_input.clone();
+            runtime_data.extend(&pubkey_der);
+            runtime_data.extend(&bound_extra);
+            let mut evidence = verifier::Evidence {
+                tee_type: &tee_type,
+                evidence: &tee_evidence,
+                runtime_data: &runtime_data,
+                eat: None,
+            };
+            let eat = session
+                .eat
+                .as_ref()
+                .map(|eat| eat::build(eat, &evidence, &rsa_wrapping_key))
+                .transpose()
+                .map_err(|e| {
+                    ErrorClass::Evidence
+                        .error(format!("TEE evidence Error: unable to build EAT: {:#}", e))
+                })?;
+            evidence.eat = eat.as_deref();
+            let token = verifier::appraise(
+                verifier,
+                &evidence,
+                session.cert_path.clone(),
+                &session.retry_config,
+            )
+            .await
+            .map_err(|e| ErrorClass::Release.wrap(&e, format!("Verifier Error: {:#}", e)))?;
+            debug!("Attestation token: {}", token);
+            Some(token)
+        }
+        None => None,
+    };
+
+    // Publish the attestation token to SPIRE and fetch the SVID
+    if let (Some(spiffe), Some(token)) = (&session.spiffe, &attestation_token) {
+        spiffe::fetch_svid(spiffe, token).await;
+    }
+
+    // Pull Vault secrets through the same attestation
+    if let (Some(vault), Some(token)) = (&session.vault, &attestation_token) {
+        vault::fetch_secrets(
+            vault,
+            token,
+            session.cert_path.clone(),
+            &session.retry_config,
+        )
+        .await;
+    }
+
+    // Sign everything sent along with the evidence
+    let evidence_signature = match &signer {
+        Some(signer) => {
+            let request = SecretRequest {
+                nonce: &nonce,
+                tee_evidence: &tee_evidence,
+                tee_type: &tee_type,
+                policy_id: "",
+                wrapping_key: &wrapping_key,
+                report_data_binding: key_binding_enabled,
+                component_evidence: component_evidence.as_ref(),
+                attestation_token: attestation_token.as_deref(),
+                nonce_construction,
+                claims: session.claims.as_ref(),
+                local_entropy: local_entropy.as_deref(),
+                platform_metadata: platform_metadata.as_ref(),
+                wrap_algorithm: ec_wrapping_key.as_ref().map(|key| key.algorithm().as_str()),
+                ..Default::default()
+            };
+            Some(signer.sign(&request).map_err(|e| {
+                ErrorClass::Evidence.error(format!(
+                    "TEE evidence Error: unable to sign evidence: {:#}",
+                    e
+                ))
+            })?)
+        }
+        None => None,
+    };
+
+    Ok(Attestation {
+        session,
+        nonce,
+        nonce_construction,
+        local_entropy,
+        evidence_signature,
+        tee_evidence,
+        tee_type,
+        rsa_wrapping_key,
+        ec_wrapping_key,
+        wrapping_key,
         key_binding_enabled,
-        component_evidence.as_ref(),
+        report_data,
+        component_evidence,
+        attestation_token,
+        platform_metadata,
+    })
+}
+
+/// Evaluate the local policy, if any, against the evidence of `attestation`
+/// and the server's `attestation_result`.
+fn check_local_policy(attestation: &Attestation, attestation_result: Option<&str>) -> Result<()> {
+    let Some(config) = &attestation.session.local_policy else {
+        return Ok(());
+    };
+    local_policy::input(
+        &attestation.tee_type,
+        &attestation.tee_evidence,
+        attestation.attestation_token.as_deref(),
+        attestation_result,
+    )
+    .and_then(|input| local_policy::check(config, &input))
+    .map_err(|e| ErrorClass::Release.error(format!("Local Policy Error: {:#}", e)))?;
+    debug!("Local policy passed");
+    Ok(())
+}
+
+/// Request the secret for `policy_id` with `attestation`, then unwrap and
+/// decrypt it. A policy ID with its own endpoint under `[keys]` is released
+/// with an attestation of its own against that endpoint.
+pub async fn release(attestation: &Attestation, policy_id: &str) -> Result<Vec<u8>> {
+    let session = &attestation.session;
+    let own;
+    let attestation = match session.for_key(policy_id) {
+        Some(session) => {
+            debug!("Attesting to {} for {}", session.server_uri, policy_id);
+            own = attest(session).await?;
+            &own
+        }
+        None => attestation,
+    };
+    let secret = release_attested(attestation, policy_id).await?;
+    let secret = postprocess(session, policy_id, secret)?;
+    // Only an attestation that released a secret is proven to local consumers
+    write_token(attestation, policy_id);
+    Ok(secret)
+}
+
+/// Write proof of `attestation`, which released the secret for `policy_id`,
+/// to `--token-out`.
+fn write_token(attestation: &Attestation, policy_id: &str) {
+    let Some(path) = &attestation.session.token_out else {
+        return;
+    };
+    let summary = token::Summary {
+        tee_type: &attestation.tee_type,
+        nonce: &attestation.nonce,
+        evidence: &attestation.tee_evidence,
+        report_data: attestation.report_data.as_deref(),
+        policy_id,
+    };
+    token::write(path, attestation.attestation_token.as_deref(), &summary);
+}
+
+async fn release_attested(attestation: &Attestation, policy_id: &str) -> Result<Vec<u8>> {
+    let session = &attestation.session;
+
+    // The cloud KMS is the key authority instead of the TAS KBM
+    if let Some(config) = &session.kms {
+        let token = attestation
+            .attestation_token
+            .as_deref()
+            .ok_or_else(|| ErrorClass::Release.error("KMS Release Error: no attestation token"))?;
+        check_local_policy(attestation, None)?;
+        let secret = kms::release(
+            config,
+            token,
+            &attestation.rsa_wrapping_key,
+            session.cert_path.clone(),
+            &session.retry_config,
+        )
+        .await
+        .map_err(|e| ErrorClass::Release.wrap(&e, format!("KMS Release Error: {:#}", e)))?;
+        return mix_derived_key(session, secret);
+    }
+
+    let key_id = kbs::key_id(policy_id, &session.server_uri)?;
+
+    // Call the function to get the secret key
+    if fault::fire(fault::Fault::Release500) {
+        return Err(ErrorClass::Release.error(format!(
+            "TAS Secret Error: Error: Received HTTP {} with message: injected fault",
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR
+        )));
+    }
+    let response = tas_get_secret_key(
+        &session.server_uri,
+        &session.api_key,
+        session.cert_path.clone(),
+        &session.retry_config,
+        &SecretRequest {
+            nonce: &attestation.nonce,
+            tee_evidence: &attestation.tee_evidence,
+            tee_type: &attestation.tee_type,
+            policy_id: &key_id,
+            wrapping_key: &attestation.wrapping_key,
+            report_data_binding: attestation.key_binding_enabled,
+            component_evidence: attestation.component_evidence.as_ref(),
+            attestation_token: attestation.attestation_token.as_deref(),
+            nonce_construction: attestation.nonce_construction,
+            evidence_signature: attestation.evidence_signature.as_ref(),
+            claims: session.claims.as_ref(),
+            local_entropy: attestation.local_entropy.as_deref(),
+            platform_metadata: attestation.platform_metadata.as_ref(),
+            wrap_algorithm: attestation
+                .ec_wrapping_key
+                .as_ref()
+                .map(|key| key.algorithm().as_str()),
+        },
     )
     .await
-    .map_err(|e| anyhow!("TAS Secret Error: {}", e))?;
+    .map_err(|e| {
+        let class = if e.request_failed {
+            ErrorClass::Network
+        } else {
+            ErrorClass::Release
+        };
+        class.error(format!("TAS Secret Error: {}", e))
+    })?;
+
+    open_response(attestation, response)
+}
+
+/// Check a get_secret response for `attestation` and unwrap the secret.
+fn open_response(attestation: &Attestation, response: SecretResponse) -> Result<Vec<u8>> {
+    let session = &attestation.session;
+
+    // Check the server's attestation result before using the secret
+    if let Some(ear) = &session.ear {
+        let token = response.attestation_result.as_deref().ok_or_else(|| {
+            ErrorClass::Release.error("Attestation Result Error: none returned by the server")
+        })?;
+        ear::verify(ear, token, &attestation.nonce).map_err(|e| {
+            ErrorClass::Release.wrap(&e, format!("Attestation Result Error: {:#}", e))
+        })?;
+        debug!("Attestation result verified");
+    }
+    // Only trust a secret from a key broker that is itself attested
+    if let Some(server) = &session.server_attestation {
+        let token = response.server_attestation.as_deref().ok_or_else(|| {
+            ErrorClass::Release.error("Server Attestation Error: none returned by the server")
+        })?;
+        attestation
+            .wrapping_key_der()
+            .and_then(|key| server_attestation::verify(server, token, &attestation.nonce, &key))
+            .map_err(|e| {
+                ErrorClass::Release.wrap(&e, format!("Server Attestation Error: {:#}", e))
+            })?;
+        debug!("Server attestation verified");
+    }
+    if let Some(result) = &response.attestation_result {
+        transcript::save("attestation-result.jwt", result.as_bytes());
+    }
+    check_local_policy(attestation, response.attestation_result.as_deref())?;
+
+    let secret_string = match &session.payload_mapping {
+        Some(mapping) => mapping
+            .apply(&response.secret_key)
+            .map_err(|e| ErrorClass::Crypto.error(format!("JSON Deserialize Error: {}", e)))?,
+        None => response.secret_key,
+    };
     debug!("Secret Key/Payload: {}", secret_string);
+    let payload: serde_json::Value = serde_json::from_str(&secret_string)
+        .context(ErrorClass::Crypto.context("JSON Deserialize Error"))?;
+    schema::SECRETS_PAYLOAD
+        .validate(&payload)
+        .map_err(|e| ErrorClass::Crypto.error(format!("JSON Deserialize Error: {}", e)))?;
 
     // Deserialize the base64-encoded secret payload
-    let mut secret: SecretsPayload =
-        serde_json::from_str(&secret_string).context("JSON Deserialize Error")?;
+    let mut secret: SecretsPayload = serde_json::from_str(&secret_string)
+        .context(ErrorClass::Crypto.context("JSON Deserialize Error"))?;
     debug!("Deserialized secret payload: {:?}", secret);
 
+    // A payload carrying authenticated data must have been sealed for this
+    // attestation; the data is then also authenticated by the decryption
+    match secret.aad.as_deref() {
+        Some(aad) if payload_bound_to(aad, &attestation.nonce, &attestation.tee_evidence) => {
+            debug!("Secret payload is bound to this attestation");
+        }
+        Some(_) => {
+            return Err(ErrorClass::Release
+                .error("Payload Binding Error: payload is bound to a different nonce or evidence"))
+        }
+        None if session.require_payload_binding => {
+            return Err(ErrorClass::Release
+                .error("Payload Binding Error: payload is not bound to the request nonce"))
+        }
+        None => debug!("Secret payload carries no nonce binding"),
+    }
+
+    if fault::fire(fault::Fault::CorruptTag) {
+        let field = match secret.algorithm.as_str() {
+            "AES-KWP" | "AES-GCM-STREAM" => &mut secret.blob,
+            _ => &mut secret.tag,
+        };
+        if let Some(byte) = field.first_mut() {
+            *byte ^= 0xff;
+        }
+    }
+
     // Unwrap the secret key using the wrapping key
     debug!("Unwrapping secret key...");
-    let aes_key = rsa_wrapping_key
-        .unwrap_key(&secret.wrapped_key)
-        .map_err(|err| anyhow!("Crypto Unwrap Error: {}", err))?;
-    debug!("Unwrapped secret key: {:?}", aes_key.hex_dump());
+    // Zeroized when dropped, or by the panic hook
+    let aes_key = match &attestation.ec_wrapping_key {
+        Some(key) => key
+            .unwrap_key(&secret.wrapped_key)
+            .map_err(|err| ErrorClass::Crypto.error(format!("Crypto Unwrap Error: {:#}", err)))?,
+        None => attestation
+            .rsa_wrapping_key
+            .unwrap_key(&secret.wrapped_key)
+            .map_err(|err| ErrorClass::Crypto.error(format!("Crypto Unwrap Error: {}", err)))?,
+    };
+    let aes_key = scrub::Sensitive::new(aes_key);
+    #[cfg(feature = "hex-dump")]
+    debug!(
+        "Unwrapped secret key: {:?}",
+        aes_key.lock().as_slice().hex_dump()
+    );
 
-    // Decrypt the secret using the algorithm that was used to wrap it
-    debug!("Decrypting secret using algorithm: {}", secret.algorithm);
-    let decrypted_payload = if secret.algorithm == "AES-KWP" {
-        debug!("Using AES Key Wrap to unwrap secret");
-        unwrap_secret_with_aes_key_wrap(&aes_key, &secret.blob)
-            .map_err(|err| anyhow!("AES Key Wrap Decrypt Error: {}", err))?
-    } else {
-        debug!("Using AES-GCM to decrypt secret");
-        decrypt_secret_with_aes_key(&aes_key, &secret.iv, &mut secret.blob, &secret.tag)
-            .map_err(|err| anyhow!("AES-GCM Decrypt Error: {}", err))?
+    let decrypted_payload = decrypt_payload(&aes_key.lock(), &mut secret)?;
+
+    // Keep an offline copy for use when TAS is unreachable; failure to
+    // escrow must not prevent this unlock.
+    if let Some(escrow) = &session.escrow {
+        if let Err(e) = escrow::store(escrow, &secret_string, &aes_key.lock()) {
+            warn!("unable to escrow secret payload: {:#}", e);
+        }
+    }
+
+    mix_derived_key(session, decrypted_payload)
+}
+
+/// Mix the SEV-SNP derived key into a released or recovered secret, when
+/// `[snp_derived_key]` is configured.
+#[cfg(feature = "snp-derived-key")]
+fn mix_derived_key(session: &Session, secret: Vec<u8>) -> Result<Vec<u8>> {
+    match &session.snp_derived_key {
+        Some(config) => derived_key::apply(config, secret)
+            .map_err(|e| ErrorClass::Evidence.error(format!("SNP Derived Key Error: {:#}", e))),
+        None => Ok(secret),
+    }
+}
+
+#[cfg(not(feature = "snp-derived-key"))]
+fn mix_derived_key(_session: &Session, secret: Vec<u8>) -> Result<Vec<u8>> {
+    Ok(secret)
+}
+
+/// Reshape a released or recovered secret for `policy_id` as its `[keys]`
+/// entry asks.
+fn postprocess(session: &Session, policy_id: &str, secret: Vec<u8>) -> Result<Vec<u8>> {
+    match session.postprocess.get(policy_id) {
+        Some(steps) => postprocess::apply(steps, secret)
+            .map_err(|e| ErrorClass::Crypto.error(format!("Post-processing Error: {:#}", e))),
+        None => Ok(secret),
+    }
+}
+
+/// Core key-fetch logic: loads config, contacts TAS, retrieves and decrypts key.
+///
+/// Returns the decrypted key as raw bytes. This function is used by both
+/// the normal stdout mode and the askpass watcher mode.
+pub async fn fetch_key(
+    config_path: Option<PathBuf>,
+    overrides: Option<CliOverrides>,
+) -> Result<Vec<u8>> {
+    request_id::scope(fetch_key_attempt(config_path, overrides)).await
+}
+
+/// One attempt of [`fetch_key`], under its own request ID.
+async fn fetch_key_attempt(
+    config_path: Option<PathBuf>,
+    overrides: Option<CliOverrides>,
+) -> Result<Vec<u8>> {
+    let session = resolve_session(config_path, overrides)?;
+    let policy_id = session
+        .policy_id
+        .clone()
+        .ok_or_else(|| ErrorClass::Config.error("server policy ID is required"))?;
+    // Attest straight to the policy ID's own endpoint, if it has one
+    let mut session = session.for_key(&policy_id).unwrap_or(session);
+
+    // Skip remote attestation while the PCRs match the sealed copy
+    let sealed_cache = session.sealed_cache.clone();
+    if let Some(cache) = &sealed_cache {
+        match sealed_cache::load(cache, &policy_id) {
+            Ok(secret) => {
+                info!("Unsealed cached secret; PCRs unchanged");
+                return Ok(secret);
+            }
+            Err(e) => info!("Attesting to TAS: {:#}", e),
+        }
+    }
+
+    let hooks = session.hooks.clone();
+    let reporter = telemetry::Reporter::new(&session);
+
+    // Release with the attestation of an invocation just before this one
+    let shared = match &session.shared_attestation {
+        Some(config) => Some(shared_attestation::lock(config).await?),
+        None => None,
+    };
+    let reused = match shared.as_ref().and_then(|shared| shared.load(&session)) {
+        Some(attestation) => match release(&attestation, &policy_id).await {
+            Ok(secret) => Some((
+                Ok(secret),
+                Some((attestation.tee_type, attestation.tee_evidence)),
+            )),
+            Err(e) => {
+                info!("Shared attestation not accepted, attesting afresh: {:#}", e);
+                None
+            }
+        },
+        None => None,
     };
 
-    // Zeroize sensitive material from memory
-    let mut aes_key_mut = aes_key;
-    aes_key_mut.zeroize();
-    secret.wrapped_key.zeroize();
-    secret.iv.zeroize();
-    secret.blob.zeroize();
-    secret.tag.zeroize();
+    let (result, evidence) = match reused {
+        Some(reused) => reused,
+        None => {
+            // Probe the TAS server version while generating the wrapping key
+            // and fetching the nonce
+            let (version, preparation) =
+                tokio::join!(session.cached_server_version(), prepare(&session));
+            match version {
+                Ok(version) => {
+                    debug!("TEE Attestation Server Version: {}", version.version);
+                    session.negotiate(&version);
+                }
+                Err(err) => {
+                    // The version probe is the first contact with the server.
+                    // Only a failure to reach it falls back to the escrow: an
+                    // answer, even an error, means TAS is up and decides.
+                    let reason = err.to_string();
+                    if let Some(escrow) = session.escrow.as_ref().filter(|_| err.unreachable) {
+                        if session.offline_fallback {
+                            warn!("TAS server unreachable, using offline escrow: {}", err);
+                            return escrow::recover(escrow, &reason)
+                                .context("offline escrow fallback failed")
+                                .and_then(|secret| mix_derived_key(&session, secret))
+                                .and_then(|secret| postprocess(&session, &policy_id, secret));
+                        }
+                        audit::record(
+                            "escrow-fallback",
+                            &[("reason", &reason), ("result", "denied by policy")],
+                        );
+                    }
+                    let result =
+                        Err(ErrorClass::Network.error(format!("TAS Version Error: {}", err)));
+                    hooks::run(hooks.as_ref(), &policy_id, None, &result).await;
+                    return result;
+                }
+            }
 
-    Ok(decrypted_payload)
+            let attestation = match preparation {
+                Ok(preparation) => attest_prepared(session, preparation).await,
+                Err(e) => Err(e),
+            };
+            match attestation {
+                Ok(attestation) => {
+                    let result = release(&attestation, &policy_id).await;
+                    // Share only an attestation TAS has accepted
+                    if let (Some(shared), Ok(_)) = (&shared, &result) {
+                        if let Err(e) = shared.store(&attestation) {
+                            warn!("unable to share the attestation: {:#}", e);
+                        }
+                    }
+                    (
+                        result,
+                        Some((attestation.tee_type, attestation.tee_evidence)),
+                    )
+                }
+                Err(e) => (Err(e), None),
+            }
+        }
+    };
+    // Let the next invocation in
+    drop(shared);
+    let tee_type = evidence.as_ref().map(|(tee_type, _)| tee_type.as_str());
+    if let (Some(reporter), Err(e)) = (&reporter, &result) {
+        let tee_evidence = evidence.as_ref().map(|(_, evidence)| evidence.as_str());
+        reporter.report(e, tee_type, tee_evidence).await;
+    }
+    hooks::run(hooks.as_ref(), &policy_id, tee_type, &result).await;
+
+    // Failure to seal must not prevent this unlock
+    if let (Some(cache), Ok(secret)) = (&sealed_cache, &result) {
+        if let Err(e) = sealed_cache::store(cache, &policy_id, secret) {
+            warn!("unable to seal secret to the TPM: {:#}", e);
+        }
+    }
+    result
 }
 
 static LOGGER: SimpleLogger = SimpleLogger;
 
-#[tokio::main]
-async fn main() {
+/// Send TAS traffic from `--bind-address` or out of `--bind-interface`.
+fn bind_source(cli: &Cli) -> Result<()> {
+    match (cli.bind_address, &cli.bind_interface) {
+        (Some(address), _) => {
+            info!("Sending TAS traffic from {}", address);
+            tas_api::bind(address);
+        }
+        (None, Some(interface)) => {
+            let address = netif::listen(interface)?;
+            info!("Sending TAS traffic out of {} ({})", interface, address);
+        }
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+/// Record an interrupted run in the audit log, flush it and exit with the
+/// signal's exit status.
+fn exit_interrupted(interrupted: shutdown::Interrupted) -> ! {
+    audit::record("interrupted", &[("signal", interrupted.signal)]);
+    audit::flush();
+    std::process::exit(interrupted.exit_code);
+}
+
+/// Build the tokio runtime; a single thread keeps the minimal build's
+/// memory footprint small.
+fn runtime() -> std::io::Result<tokio::runtime::Runtime> {
+    #[cfg(feature = "minimal")]
+    let mut builder = tokio::runtime::Builder::new_current_thread();
+    #[cfg(not(feature = "minimal"))]
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all().build()
+}
+
+fn main() {
     let cli = Cli::parse();
+    error_format::configure(cli.error_format);
+    // Before the agent opens any descriptor of its own
+    let secret_fd = match cli.secret_fd.map(output::claim_fd).transpose() {
+        Ok(file) => file,
+        Err(e) => error_format::exit(Phase::Startup, &e),
+    };
+
+    if let Some(Command::Version { verbose }) = cli.command {
+        if verbose {
+            println!("{:#}", buildinfo::report());
+        } else {
+            println!("{}", buildinfo::version());
+        }
+        return;
+    }
 
     // Always initialise the logger; -d bumps the level from INFO to DEBUG
     let level = if cli.debug {
@@ -391,6 +2071,97 @@ async fn main() {
         LevelFilter::Info
     };
     let _ = log::set_logger(&LOGGER).map(|()| log::set_max_level(level));
+    if cli.kernel_cmdline {
+        cmdline::enable();
+    }
+    // The daemon survives a panicking task; everything else exits
+    #[cfg(feature = "daemon")]
+    let resident = matches!(cli.command, Some(Command::Daemon));
+    #[cfg(not(feature = "daemon"))]
+    let resident = false;
+    scrub::install(!resident);
+
+    // Before any key material exists
+    if let Err(e) = hardening::disable_core_dumps() {
+        warn!("Core dumps remain enabled: {:#}", e);
+    }
+
+    #[cfg(feature = "fips")]
+    if let Err(e) = fips::check_mode() {
+        error_format::exit(Phase::Startup, &e);
+    }
+
+    // Opening the --bind-interface connector is not possible once sandboxed
+    if let Err(e) = bind_source(&cli) {
+        error_format::exit(Phase::Startup, &e);
+    }
+
+    #[cfg(feature = "landlock")]
+    if let Err(e) = apply_landlock(&cli) {
+        error_format::exit(Phase::Startup, &e);
+    }
+
+    match runtime() {
+        Ok(runtime) => runtime.block_on(run(cli, secret_fd)),
+        Err(e) => {
+            let e = anyhow!(e).context("unable to start the tokio runtime");
+            error_format::exit(Phase::Startup, &e);
+        }
+    }
+}
+
+async fn run(cli: Cli, secret_fd: Option<std::fs::File>) {
+    cassette::init();
+    if let Err(e) = netif::spawn() {
+        error_format::exit(Phase::Startup, &e);
+    }
+    if let Some(tee_type) = &cli.tee_type {
+        tee_evidence::force_tee_type(tee_type.clone());
+    }
+    if let Some(dir) = &cli.transcript_dir {
+        if let Err(e) = transcript::init(dir) {
+            error_format::exit(Phase::Startup, &e);
+        }
+    }
+    #[cfg(feature = "tls-keylog")]
+    if let Some(path) = &cli.unsafe_tls_keylog {
+        #[cfg(feature = "daemon")]
+        if let Some(Command::Daemon) = cli.command {
+            let e = anyhow!("--unsafe-tls-keylog is refused in daemon mode");
+            error_format::exit(Phase::Startup, &e);
+        }
+        if let Err(e) = tls::enable_keylog(path) {
+            error_format::exit(Phase::Startup, &e);
+        }
+        audit::record("tls-keylog", &[("path", &path.display().to_string())]);
+    }
+    fault::init(&cli.inject_fault);
+
+    if let Some(Command::Corim) = cli.command {
+        match corim::export() {
+            Ok(comid) => println!("{:#}", comid),
+            Err(e) => {
+                error_format::exit(Phase::Startup, &e);
+            }
+        }
+        return;
+    }
+
+    if let Some(Command::KeyPool) = cli.command {
+        let filled = load_config(cli.config.clone()).and_then(|cfg| {
+            let pool = cfg
+                .key_pool
+                .ok_or_else(|| anyhow!("no [key_pool] section in the config file"))?;
+            key_pool::fill(&pool)
+        });
+        match filled {
+            Ok(added) => println!("Added {} wrapping key(s) to the pool", added),
+            Err(e) => {
+                error_format::exit(Phase::Startup, &e);
+            }
+        }
+        return;
+    }
 
     // In askpass mode, dispatch to the askpass watcher and exit
     #[cfg(feature = "askpass")]
@@ -398,8 +2169,7 @@ async fn main() {
         let cfg = match load_config(cli.config.clone()) {
             Ok(cfg) => cfg,
             Err(e) => {
-                eprintln!("{:#}", e);
-                std::process::exit(1);
+                error_format::exit(Phase::Startup, &e);
             }
         };
         if cli.askpass || cfg.askpass.unwrap_or(false) {
@@ -417,8 +2187,7 @@ async fn main() {
         let cfg = match load_config(cli.config.clone()) {
             Ok(cfg) => cfg,
             Err(e) => {
-                eprintln!("{:#}", e);
-                std::process::exit(1);
+                error_format::exit(Phase::Startup, &e);
             }
         };
         if cli.passfifo || cfg.passfifo.unwrap_or(false) {
@@ -430,30 +2199,148 @@ async fn main() {
         }
     }
 
-    // --- Normal (stdout) mode ---
     let overrides = CliOverrides {
         server_uri: cli.server_uri,
+        allow_insecure: cli.allow_insecure,
         api_key: cli.api_key,
         policy_id: cli.policy_id,
         cert_path: cli.cert_path,
         max_retries: cli.max_retries,
         retry_min_backoff_secs: cli.retry_min_backoff_secs,
         retry_max_backoff_secs: cli.retry_max_backoff_secs,
+        token_out: cli.token_out,
+        claims: cli.claims,
         #[cfg(feature = "gpu-nvidia")]
         no_gpu: cli.no_gpu,
     };
 
-    match fetch_key(cli.config, Some(overrides)).await {
+    if let Some(Command::Init {
+        secrets,
+        out_dir,
+        termination_log,
+    }) = &cli.command
+    {
+        let run = k8s::run(cli.config, overrides, secrets, out_dir, termination_log);
+        match shutdown::cancel_on_signal(run).await {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => {
+                error_format::exit(Phase::Attestation, &e);
+            }
+            Err(interrupted) => exit_interrupted(interrupted),
+        }
+    }
+
+    if let Some(Command::Evidence {
+        command:
+            EvidenceCommand::Export {
+                nonce_file,
+                out,
+                key,
+            },
+    }) = &cli.command
+    {
+        let run = bundle::export(cli.config, overrides, nonce_file, out, key);
+        match shutdown::cancel_on_signal(run).await {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => {
+                error_format::exit(Phase::Attestation, &e);
+            }
+            Err(interrupted) => exit_interrupted(interrupted),
+        }
+    }
+
+    if let Some(Command::Submit { bundle }) = &cli.command {
+        let run = bundle::submit(cli.config, overrides, bundle);
+        match shutdown::cancel_on_signal(run).await {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => {
+                error_format::exit(Phase::Attestation, &e);
+            }
+            Err(interrupted) => exit_interrupted(interrupted),
+        }
+    }
+
+    if let Some(Command::Conformance { nonce_lifetime }) = cli.command {
+        let run = conformance::run(cli.config, overrides, nonce_lifetime);
+        match shutdown::cancel_on_signal(run).await {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => {
+                error_format::exit(Phase::Attestation, &e);
+            }
+            Err(interrupted) => exit_interrupted(interrupted),
+        }
+    }
+
+    #[cfg(feature = "daemon")]
+    if let Some(Command::Daemon) = cli.command {
+        if let Err(e) = daemon::run_daemon(cli.config, overrides).await {
+            error_format::exit(Phase::Attestation, &e.context("daemon error"));
+        }
+        return;
+    }
+
+    // --- Normal (stdout) mode ---
+    #[cfg(feature = "seccomp")]
+    if let Err(e) = apply_seccomp(cli.config.clone(), overrides.token_out.is_some()) {
+        error_format::exit(Phase::Startup, &e);
+    }
+    let command = cli.command;
+    let config = cli.config;
+    let fetch = async {
+        match command {
+            Some(Command::Evidence {
+                command: EvidenceCommand::Import { bundle, key },
+            }) => bundle::import(config, overrides, &bundle, &key).await,
+            Some(Command::DecryptPayload { payload, key }) => payload::decrypt(&payload, &key),
+            _ => fetch_key(config, Some(overrides)).await,
+        }
+    };
+    let fetched = match shutdown::cancel_on_signal(fetch).await {
+        Ok(fetched) => fetched,
+        Err(interrupted) => exit_interrupted(interrupted),
+    };
+    transcript::outcome(fetched.as_ref().err());
+    match fetched {
         Ok(decrypted_payload) => {
+            let decrypted_payload = scrub::Sensitive::new(decrypted_payload);
+            if let Some(out) = cli.out {
+                let written = output::create_secret_file(&out, &decrypted_payload.lock());
+                decrypted_payload.lock().zeroize();
+                let file = match written {
+                    Ok(file) => file,
+                    Err(e) => error_format::exit(Phase::Output, &e),
+                };
+                if let Some(secs) = cli.shred_after {
+                    let timeout = std::time::Duration::from_secs(secs);
+                    match output::shred_after(&out, file, timeout).await {
+                        Ok(Some(interrupted)) => exit_interrupted(interrupted),
+                        Ok(None) => {}
+                        Err(e) => {
+                            error_format::exit(Phase::Output, &e);
+                        }
+                    }
+                }
+                return;
+            }
+
+            if let Some(file) = &secret_fd {
+                let written = output::write_secret_fd(file, &decrypted_payload.lock());
+                decrypted_payload.lock().zeroize();
+                if let Err(e) = written {
+                    error_format::exit(Phase::Output, &e);
+                }
+                return;
+            }
+
             use std::io::Write;
-            if let Err(e) = std::io::stdout().write_all(&decrypted_payload) {
-                eprintln!("failed to write key to stdout: {:#}", e);
-                std::process::exit(1);
+            let written = std::io::stdout().write_all(&decrypted_payload.lock());
+            if let Err(e) = written {
+                let e = anyhow!(e).context("failed to write key to stdout");
+                error_format::exit(Phase::Output, &e);
             }
         }
         Err(e) => {
-            eprintln!("{:#}", e);
-            std::process::exit(1);
+            error_format::exit(Phase::Attestation, &e);
         }
     }
 }
@@ -0,0 +1,312 @@
+// TEE Attestation Service Agent — network path of TAS traffic
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// In segmented networks key-release traffic must leave through the
+// management network. `--bind-address ADDR` makes connections to TAS use ADDR
+// as their source address, which leaves the choice of interface to (policy)
+// routing. `--bind-interface NAME` sends them out of interface NAME whatever
+// the routing table says, with sockets bound to the device (SO_BINDTODEVICE).
+//
+// reqwest cannot bind its sockets to a device, so with `--bind-interface` TAS
+// clients go through a connector in the agent: an HTTP CONNECT proxy on a
+// loopback port that opens each connection with a socket bound to NAME and
+// relays the bytes. TLS stays end to end between the client and TAS. The
+// connector only connects to the TAS endpoints the agent built clients for
+// (`proxy_for`), so it cannot be used to reach anything else through NAME.
+// TAS server URIs must be https://.
+//
+// At startup, before the sandboxes are applied, NAME is checked to have a
+// global address with getifaddrs(3), and the connector's listening socket is
+// opened; no external program is run. Binding to a device may need
+// CAP_NET_RAW.
+//
+// getifaddrs(3) is called through libc; the unsafe blocks only read the list
+// it returns, before freeing it.
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener as StdTcpListener};
+use std::sync::{Mutex, OnceLock};
+use tokio::io::{copy_bidirectional, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{lookup_host, TcpListener, TcpSocket, TcpStream};
+
+/// Largest CONNECT request head accepted.
+const MAX_HEAD_BYTES: usize = 8 * 1024;
+
+/// The addresses of `interface`.
+fn addresses(interface: &str) -> Result<Vec<IpAddr>> {
+    let mut list: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs only writes the head of the list it allocates to
+    // `list`, which is freed below.
+    if unsafe { libc::getifaddrs(&mut list) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("unable to list interfaces");
+    }
+    let mut addresses = Vec::new();
+    let mut cursor = list;
+    while !cursor.is_null() {
+        // SAFETY: `cursor` is an entry of the list, valid until freeifaddrs;
+        // a non-null ifa_addr points to a sockaddr of the family it gives.
+        unsafe {
+            let entry = &*cursor;
+            cursor = entry.ifa_next;
+            if entry.ifa_addr.is_null()
+                || CStr::from_ptr(entry.ifa_name).to_bytes() != interface.as_bytes()
+            {
+                continue;
+            }
+            match i32::from((*entry.ifa_addr).sa_family) {
+                libc::AF_INET => {
+                    let sin = &*(entry.ifa_addr as *const libc::sockaddr_in);
+                    addresses.push(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                        sin.sin_addr.s_addr,
+                    ))));
+                }
+                libc::AF_INET6 => {
+                    let sin6 = &*(entry.ifa_addr as *const libc::sockaddr_in6);
+                    addresses.push(IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr)));
+                }
+                _ => {}
+            }
+        }
+    }
+    // SAFETY: `list` came from getifaddrs and is not used after this.
+    unsafe { libc::freeifaddrs(list) };
+    Ok(addresses)
+}
+
+/// Whether `address` is reachable beyond the host and link.
+fn is_global(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(v4) => !(v4.is_loopback() || v4.is_link_local() || v4.is_unspecified()),
+        IpAddr::V6(v6) => {
+            !(v6.is_loopback() || v6.is_unspecified() || (v6.segments()[0] & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// The first global address of `addresses`, IPv4 before IPv6.
+fn first_global(mut addresses: Vec<IpAddr>) -> Option<IpAddr> {
+    addresses.retain(is_global);
+    addresses.sort_by_key(IpAddr::is_ipv6);
+    addresses.into_iter().next()
+}
+
+/// The first global address of `interface`, IPv4 before IPv6.
+pub fn address(interface: &str) -> Result<IpAddr> {
+    first_global(addresses(interface)?)
+        .ok_or_else(|| anyhow!("interface {:?} has no global address", interface))
+}
+
+/// The connector's listening socket and interface, until `spawn` takes them.
+static PENDING: Mutex<Option<(StdTcpListener, String)>> = Mutex::new(None);
+
+/// URL of the connector, once `listen` opened it.
+static PROXY_URL: OnceLock<String> = OnceLock::new();
+
+/// TAS endpoints the connector may connect to.
+static ALLOWED: Mutex<Option<HashSet<(String, u16)>>> = Mutex::new(None);
+
+/// Open the connector for sending TAS traffic out of `interface`, after
+/// checking that the interface has a global address.
+pub fn listen(interface: &str) -> Result<IpAddr> {
+    let address = address(interface)?;
+    let listener = StdTcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .context("unable to open the interface connector")?;
+    listener.set_nonblocking(true)?;
+    let local = listener.local_addr()?;
+    debug!("Interface connector for {} on {}", interface, local);
+    let _ = PROXY_URL.set(format!("http://{}", local));
+    *PENDING.lock().unwrap_or_else(|e| e.into_inner()) = Some((listener, interface.to_string()));
+    Ok(address)
+}
+
+/// Start serving the connector opened by `listen`, if any.
+pub fn spawn() -> Result<()> {
+    let Some((listener, interface)) = PENDING.lock().unwrap_or_else(|e| e.into_inner()).take()
+    else {
+        return Ok(());
+    };
+    let listener = TcpListener::from_std(listener).context("unable to serve the connector")?;
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let interface = interface.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = relay(stream, &interface).await {
+                            warn!("Interface connector: {:#}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Interface connector accept failed: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// The proxy URL to send TAS requests to `host`:`port` through, after
+/// allowing the connector to connect there. None without `--bind-interface`.
+pub fn proxy_for(host: &str, port: u16) -> Option<&'static str> {
+    let url = PROXY_URL.get()?;
+    ALLOWED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashSet::new)
+        .insert((host.to_ascii_lowercase(), port));
+    Some(url)
+}
+
+fn is_allowed(host: &str, port: u16) -> bool {
+    ALLOWED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .is_some_and(|allowed| allowed.contains(&(host.to_ascii_lowercase(), port)))
+}
+
+/// The host and port of a CONNECT request head.
+fn parse_connect(head: &str) -> Option<(String, u16)> {
+    let mut words = head.lines().next()?.split(' ');
+    if words.next()? != "CONNECT" {
+        return None;
+    }
+    let authority = words.next()?;
+    let (host, port) = authority.rsplit_once(':')?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Some((host.to_string(), port.parse().ok()?))
+}
+
+/// Read a request head, up to the blank line, from `stream`.
+async fn read_head(stream: &mut TcpStream) -> Result<String> {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    // Byte by byte, so that nothing after the head is consumed
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_HEAD_BYTES || stream.read(&mut byte).await? == 0 {
+            return Err(anyhow!("incomplete CONNECT request"));
+        }
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).context("CONNECT request is not UTF-8")
+}
+
+/// Connect to `host`:`port` with a socket bound to `interface`.
+async fn connect(host: &str, port: u16, interface: &str) -> Result<TcpStream> {
+    let mut last_error = None;
+    for target in lookup_host((host, port)).await? {
+        let socket = match target {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        socket
+            .bind_device(Some(interface.as_bytes()))
+            .with_context(|| format!("unable to bind to interface {:?}", interface))?;
+        match socket.connect(target).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(match last_error {
+        Some(e) => anyhow!(e).context(format!("unable to connect to {}:{}", host, port)),
+        None => anyhow!("{} has no address", host),
+    })
+}
+
+/// Serve one CONNECT request from a TAS client.
+async fn relay(mut client: TcpStream, interface: &str) -> Result<()> {
+    let head = read_head(&mut client).await?;
+    let Some((host, port)) = parse_connect(&head) else {
+        client
+            .write_all(b"HTTP/1.1 405 Method Not Allowed\r\n\r\n")
+            .await?;
+        return Err(anyhow!("not a CONNECT request"));
+    };
+    if !is_allowed(&host, port) {
+        client.write_all(b"HTTP/1.1 403 Forbidden\r\n\r\n").await?;
+        return Err(anyhow!("refused to connect to {}:{}", host, port));
+    }
+    let mut upstream = match connect(&host, port, interface).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            client
+                .write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n")
+                .await?;
+            return Err(e);
+        }
+    };
+    debug!("Connected to {}:{} through {}", host, port, interface);
+    client
+        .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+        .await?;
+    copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_global() {
+        let addresses = vec![
+            "fe80::1".parse().unwrap(),
+            "fd00::5".parse().unwrap(),
+            "169.254.1.1".parse().unwrap(),
+            "10.20.0.5".parse().unwrap(),
+        ];
+        assert_eq!(first_global(addresses), Some("10.20.0.5".parse().unwrap()));
+        let addresses = vec!["fe80::1".parse().unwrap(), "fd00::5".parse().unwrap()];
+        assert_eq!(first_global(addresses), Some("fd00::5".parse().unwrap()));
+        assert_eq!(first_global(vec!["127.0.0.1".parse().unwrap()]), None);
+    }
+
+    #[test]
+    fn test_addresses() {
+        let loopback = addresses("lo").unwrap();
+        assert!(
+            loopback.contains(&IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            "{loopback:?}"
+        );
+        assert!(address("lo").is_err());
+        assert!(addresses("no-such-if0").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_connect() {
+        assert_eq!(
+            parse_connect("CONNECT tas.example.com:5001 HTTP/1.1\r\nHost: x\r\n\r\n"),
+            Some(("tas.example.com".to_string(), 5001))
+        );
+        assert_eq!(
+            parse_connect("CONNECT [fd00::5]:443 HTTP/1.1\r\n\r\n"),
+            Some(("fd00::5".to_string(), 443))
+        );
+        assert_eq!(parse_connect("GET / HTTP/1.1\r\n\r\n"), None);
+        assert_eq!(parse_connect("CONNECT tas HTTP/1.1\r\n\r\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_relay_refuses_unregistered_target() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let proxy = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            relay(stream, "lo").await
+        });
+
+        let mut client = TcpStream::connect(proxy).await.unwrap();
+        client
+            .write_all(b"CONNECT elsewhere.example.com:22 HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 403"), "{response}");
+        assert!(server.await.unwrap().is_err());
+    }
+}
@@ -0,0 +1,302 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Delivery of the released secret to a file or an inherited file descriptor,
+// and secure removal of delivered files once their consumer is done with them.
+//
+// Files are opened without following a symlink at the path and are set to
+// 0600 permissions on the open descriptor, whether they are created or
+// already existed. Shredding likewise refuses a symlink or anything but a
+// regular file, zero-fills through the descriptor and only unlinks the path
+// if it still names the file that was zeroed. When a cleanup timeout is given
+// the agent stays running after writing the file and overwrites and unlinks
+// it as soon as the consumer signals completion with SIGUSR1, or when the
+// timeout expires, whichever comes first. SIGTERM and SIGINT also trigger
+// the cleanup so the plaintext never outlives the agent.
+//
+// An inherited descriptor is claimed first thing in main, before the agent
+// opens any file or socket of its own, so that a number the parent did not
+// pass can never alias the audit log or a TAS connection.
+
+use crate::shutdown::{cancel_on_signal, Interrupted};
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use rustix::fs::{fcntl_getfl, OFlags};
+use rustix::io::{fcntl_getfd, fcntl_setfd, FdFlags};
+use std::fs::{self, File};
+use std::io::{Seek, SeekFrom, Write};
+use std::os::fd::{BorrowedFd, FromRawFd, RawFd};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::{sleep, Duration};
+
+/// Write the secret to `path` with 0600 permissions. A symlink at `path` is
+/// refused rather than followed, and an existing file's permissions are
+/// tightened before the secret is written.
+pub fn write_secret_file(path: &Path, secret: &[u8]) -> Result<()> {
+    create_secret_file(path, secret).map(drop)
+}
+
+/// Like [`write_secret_file`], returning the open file for [`shred`].
+pub fn create_secret_file(path: &Path, secret: &[u8]) -> Result<File> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .custom_flags(OFlags::NOFOLLOW.bits() as i32)
+        .open(path)
+        .with_context(|| format!("unable to create {:?}", path))?;
+    // fchmod, for a file that existed with wider permissions
+    file.set_permissions(fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("unable to set permissions on {:?}", path))?;
+    file.write_all(secret)
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("unable to write secret to {:?}", path))?;
+    Ok(file)
+}
+
+/// Take ownership of the file descriptor `fd` inherited from the parent
+/// process, checking that it is open for writing.
+///
+/// Must be called before the agent opens any descriptor of its own: an open
+/// `fd` is then one the parent passed, and no other handle in the process
+/// refers to it. The descriptor is marked close-on-exec so that hooks and
+/// helper programs do not inherit it.
+pub fn claim_fd(fd: u32) -> Result<File> {
+    let raw = RawFd::try_from(fd).map_err(|_| anyhow!("invalid file descriptor {}", fd))?;
+    // SAFETY: only used for the fcntl calls below; a descriptor that is not
+    // open makes them fail with EBADF.
+    let borrowed = unsafe { BorrowedFd::borrow_raw(raw) };
+    let fd_flags = fcntl_getfd(borrowed)
+        .with_context(|| format!("file descriptor {} was not inherited", fd))?;
+    let flags = fcntl_getfl(borrowed)
+        .with_context(|| format!("file descriptor {} was not inherited", fd))?;
+    if flags & OFlags::RWMODE == OFlags::RDONLY {
+        return Err(anyhow!("file descriptor {} is not open for writing", fd));
+    }
+    fcntl_setfd(borrowed, fd_flags | FdFlags::CLOEXEC)
+        .with_context(|| format!("unable to set close-on-exec on file descriptor {}", fd))?;
+    // SAFETY: the descriptor is open and, claimed before the agent opens
+    // anything, owned by nothing else in the process.
+    Ok(unsafe { File::from_raw_fd(raw) })
+}
+
+/// Write the secret to `file`, a descriptor claimed with [`claim_fd`].
+///
+/// The descriptor itself is written to, at its current offset and with the
+/// access the parent opened it with, so sockets work as well as pipes, FIFOs
+/// and regular files.
+pub fn write_secret_fd(mut file: &File, secret: &[u8]) -> Result<()> {
+    file.write_all(secret)
+        .and_then(|_| file.flush())
+        .context("unable to write secret to the inherited file descriptor")
+}
+
+/// Overwrite the contents of `path` with zeros, flush them to storage and
+/// unlink the file. A symlink at `path` or a file that is not a regular file
+/// is refused.
+#[cfg_attr(not(feature = "daemon"), allow(dead_code))]
+pub fn shred_file(path: &Path) -> Result<()> {
+    // O_NONBLOCK so that a FIFO swapped in cannot block the open
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .custom_flags((OFlags::NOFOLLOW | OFlags::NONBLOCK).bits() as i32)
+        .open(path)
+        .with_context(|| format!("unable to open {:?} for cleanup", path))?;
+    shred(path, file)
+}
+
+/// Overwrite `file`, created at `path`, with zeros, flush them to storage
+/// and unlink `path` if it still names `file`.
+pub fn shred(path: &Path, mut file: File) -> Result<()> {
+    let metadata = file
+        .metadata()
+        .with_context(|| format!("unable to stat {:?}", path))?;
+    if !metadata.file_type().is_file() {
+        return Err(anyhow!(
+            "{:?} is not a regular file, not shredding it",
+            path
+        ));
+    }
+    let len = metadata.len();
+
+    let zeros = [0u8; 4096];
+    let mut remaining = len;
+    file.seek(SeekFrom::Start(0))
+        .with_context(|| format!("unable to seek {:?}", path))?;
+    while remaining > 0 {
+        let n = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..n])
+            .with_context(|| format!("unable to overwrite {:?}", path))?;
+        remaining -= n as u64;
+    }
+    file.sync_all()
+        .with_context(|| format!("unable to sync {:?}", path))?;
+    drop(file);
+
+    let current =
+        fs::symlink_metadata(path).with_context(|| format!("unable to stat {:?}", path))?;
+    if (current.dev(), current.ino()) != (metadata.dev(), metadata.ino()) {
+        return Err(anyhow!("{:?} was replaced, not removing it", path));
+    }
+    fs::remove_file(path).with_context(|| format!("unable to remove {:?}", path))?;
+    debug!("Shredded {:?} ({} bytes)", path, len);
+    Ok(())
+}
+
+/// Wait for the consumer to signal completion (SIGUSR1), a termination
+/// signal, or `timeout`, then shred `file`, created at `path`. Returns the
+/// termination signal, if one cut the wait short.
+pub async fn shred_after(
+    path: &Path,
+    file: File,
+    timeout: Duration,
+) -> Result<Option<Interrupted>> {
+    let mut sigusr1 =
+        signal(SignalKind::user_defined1()).context("failed to register SIGUSR1 handler")?;
+
+    let waited = cancel_on_signal(async {
+        tokio::select! {
+            _ = sigusr1.recv() => info!("Consumer signalled completion, removing {:?}", path),
+            _ = sleep(timeout) => info!("Cleanup timeout reached, removing {:?}", path),
+        }
+    })
+    .await;
+
+    shred(path, file)?;
+    Ok(waited.err())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_secret_file_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        write_secret_file(&path, b"secret").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"secret");
+        let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+    }
+
+    #[test]
+    fn test_write_secret_file_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        fs::write(&path, b"old contents").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_secret_file(&path, b"secret").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"secret");
+        let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+    }
+
+    #[test]
+    fn test_write_secret_file_refuses_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        fs::write(&target, b"").unwrap();
+        let link = dir.path().join("key");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        assert!(write_secret_file(&link, b"secret").is_err());
+        assert_eq!(fs::read(&target).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_claim_fd_not_open() {
+        let err = claim_fd(987654).unwrap_err();
+        assert!(err.to_string().contains("987654"), "{err}");
+    }
+
+    #[test]
+    fn test_write_secret_fd_socket() {
+        use std::io::Read;
+        use std::os::fd::IntoRawFd;
+
+        let (a, mut b) = std::os::unix::net::UnixStream::pair().unwrap();
+        let file = claim_fd(a.into_raw_fd() as u32).unwrap();
+        let flags = fcntl_getfd(&file).unwrap();
+        assert!(flags.contains(FdFlags::CLOEXEC));
+        write_secret_fd(&file, b"secret").unwrap();
+        drop(file);
+        let mut received = Vec::new();
+        b.read_to_end(&mut received).unwrap();
+        assert_eq!(received, b"secret");
+    }
+
+    #[test]
+    fn test_claim_fd_read_only() {
+        use std::os::fd::{AsRawFd, IntoRawFd};
+
+        let file = tempfile::tempfile().unwrap();
+        let read_only = File::open(format!("/proc/self/fd/{}", file.as_raw_fd())).unwrap();
+        let raw = read_only.into_raw_fd();
+        let err = claim_fd(raw as u32).unwrap_err();
+        // SAFETY: not claimed, still owned here
+        drop(unsafe { File::from_raw_fd(raw) });
+        assert!(err.to_string().contains("not open for writing"), "{err}");
+    }
+
+    #[test]
+    fn test_shred_file_removes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        write_secret_file(&path, &[0xAA; 10000]).unwrap();
+
+        shred_file(&path).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_shred_file_refuses_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        fs::write(&target, b"keep").unwrap();
+        let link = dir.path().join("key");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        assert!(shred_file(&link).is_err());
+        assert_eq!(fs::read(&target).unwrap(), b"keep");
+        assert!(link.exists());
+    }
+
+    #[test]
+    fn test_shred_replaced_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        let file = create_secret_file(&path, b"secret").unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::write(&path, b"other").unwrap();
+
+        assert!(shred(&path, file).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"other");
+    }
+
+    #[test]
+    fn test_shred_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(shred_file(&dir.path().join("missing")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shred_after_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        let file = create_secret_file(&path, b"secret").unwrap();
+
+        let interrupted = shred_after(&path, file, Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert!(interrupted.is_none());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,129 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Offline decryption of saved secret payloads.
+//
+// `tas_agent decrypt-payload --payload FILE --key FILE` runs the unwrap and
+// decrypt steps of a release without TAS or a TEE, so that decryption
+// failures reported from the field can be reproduced. FILE holds the
+// `SecretsPayload`, or a get_secret response or submitted evidence bundle
+// carrying it; the key is the PKCS#1 DER private wrapping key, as written by
+// `evidence export`. Nothing else about the release is checked: the payload's
+// binding to a nonce and evidence is only authenticated by the decryption.
+
+use crate::crypto::RsaKey;
+use crate::decrypt_payload;
+use crate::hooks::ErrorClass;
+use crate::utils::SecretsPayload;
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// The `SecretsPayload` in `value`: the payload itself, the `secret_key` of a
+/// get_secret response, or the response of a bundle.
+fn find_payload(value: &Value) -> Option<Value> {
+    if value.get("wrapped_key").is_some() {
+        return Some(value.clone());
+    }
+    match value.get("secret_key") {
+        // A bundle keeps the payload serialized as JSON text
+        Some(Value::String(text)) => serde_json::from_str(text)
+            .ok()
+            .and_then(|inner| find_payload(&inner)),
+        Some(inner) => find_payload(inner),
+        None => value.get("response").and_then(find_payload),
+    }
+}
+
+/// Unwrap and decrypt the payload saved at `payload_path` with the private
+/// wrapping key at `key_path`.
+pub fn decrypt(payload_path: &Path, key_path: &Path) -> Result<Vec<u8>> {
+    let json = fs::read_to_string(payload_path)
+        .with_context(|| format!("unable to read {:?}", payload_path))?;
+    let value: Value = serde_json::from_str(&json)
+        .context(ErrorClass::Crypto.context("JSON Deserialize Error"))?;
+    let payload =
+        find_payload(&value).ok_or_else(|| anyhow!("no secret payload in {:?}", payload_path))?;
+    let mut secret: SecretsPayload = serde_json::from_value(payload)
+        .context(ErrorClass::Crypto.context("JSON Deserialize Error"))?;
+    debug!(
+        "Payload: algorithm {}, {} byte wrapped key, {} byte blob, {}",
+        secret.algorithm,
+        secret.wrapped_key.len(),
+        secret.blob.len(),
+        if secret.aad.is_some() {
+            "bound to an attestation"
+        } else {
+            "no binding"
+        }
+    );
+
+    let der = Zeroizing::new(
+        fs::read(key_path).with_context(|| format!("unable to read {:?}", key_path))?,
+    );
+    let rsa_wrapping_key = RsaKey::from_private_key_der(&der)
+        .map_err(|e| anyhow!("invalid wrapping key {:?}: {}", key_path, e))?;
+    let aes_key = Zeroizing::new(
+        rsa_wrapping_key
+            .unwrap_key(&secret.wrapped_key)
+            .map_err(|err| ErrorClass::Crypto.error(format!("Crypto Unwrap Error: {}", err)))?,
+    );
+    decrypt_payload(&aes_key, &mut secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{encrypt_secret_with_aes_key, generate_wrapping_key};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_find_payload() {
+        let payload = json!({"wrapped_key": "a2V5", "blob": "", "iv": "", "tag": ""});
+        assert_eq!(find_payload(&payload), Some(payload.clone()));
+        let response = json!({"secret_key": payload});
+        assert_eq!(find_payload(&response), Some(payload.clone()));
+        let bundle = json!({"response": {"secret_key": payload.to_string()}});
+        assert_eq!(find_payload(&bundle), Some(payload));
+        assert_eq!(find_payload(&json!({"secret_key": "<redacted>"})), None);
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let dir = tempdir().unwrap();
+        let key = generate_wrapping_key().unwrap();
+        let aes_key = [7u8; 32];
+        let iv = [1u8; 12];
+        let mut plaintext = b"disk key".to_vec();
+        let (blob, tag) = encrypt_secret_with_aes_key(&aes_key, &iv, &mut plaintext, b"").unwrap();
+        let payload = json!({
+            "wrapped_key": STANDARD.encode(key.encrypt(&aes_key).unwrap()),
+            "blob": STANDARD.encode(blob),
+            "iv": STANDARD.encode(iv),
+            "tag": STANDARD.encode(tag),
+        });
+        let payload_path = dir.path().join("response.json");
+        fs::write(&payload_path, json!({"secret_key": payload}).to_string()).unwrap();
+        let key_path = dir.path().join("wrapping.key");
+        fs::write(&key_path, key.private_key_to_der().unwrap()).unwrap();
+
+        assert_eq!(decrypt(&payload_path, &key_path).unwrap(), b"disk key");
+
+        // Another key cannot unwrap it
+        let other = generate_wrapping_key().unwrap();
+        fs::write(&key_path, other.private_key_to_der().unwrap()).unwrap();
+        let err = decrypt(&payload_path, &key_path).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("Crypto Unwrap Error"),
+            "{:#}",
+            err
+        );
+    }
+}
@@ -0,0 +1,115 @@
+// TEE Attestation Service Agent — platform metadata
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// When TAS rejects evidence, the operator has to find out which firmware the
+// host runs. With `platform_metadata = true` at the top level of the config
+// file, the get_secret request carries a description of the platform next
+// to the evidence:
+//
+//   "platform-metadata": {"cpu-model": "AMD EPYC 9454 48-Core Processor",
+//    "microcode": "0xa101148", "kernel": "6.8.0-45-generic",
+//    "tcb": {"current_tcb": {...}, "reported_tcb": {...}}}
+//
+// The CPU model and microcode revision come from /proc/cpuinfo, the kernel
+// release from /proc/sys/kernel/osrelease and the TCB versions from the
+// evidence, as in failure telemetry. Values that cannot be read are left
+// out. The metadata is reported by the guest and is not measured: it is for
+// correlating rejections with firmware levels, never for policy decisions.
+//
+// No unsafe code.
+
+use crate::telemetry;
+use log::debug;
+use serde_json::{Map, Value};
+
+/// CPU description of the kernel.
+pub const CPUINFO: &str = "/proc/cpuinfo";
+
+/// Release of the running kernel.
+pub const OSRELEASE: &str = "/proc/sys/kernel/osrelease";
+
+/// The value of field `name` of the first processor in `cpuinfo`.
+fn cpuinfo_field(cpuinfo: &str, name: &str) -> Option<String> {
+    cpuinfo
+        .lines()
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(field, _)| field.trim() == name)
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// The metadata object from the contents of /proc/cpuinfo and
+/// /proc/sys/kernel/osrelease and the TCB summary of the evidence.
+fn metadata(cpuinfo: Option<&str>, osrelease: Option<&str>, tcb: Option<Value>) -> Value {
+    let mut metadata = Map::new();
+    if let Some(cpuinfo) = cpuinfo {
+        if let Some(model) = cpuinfo_field(cpuinfo, "model name") {
+            metadata.insert("cpu-model".to_string(), model.into());
+        }
+        if let Some(microcode) = cpuinfo_field(cpuinfo, "microcode") {
+            metadata.insert("microcode".to_string(), microcode.into());
+        }
+    }
+    if let Some(release) = osrelease.map(str::trim).filter(|r| !r.is_empty()) {
+        metadata.insert("kernel".to_string(), release.into());
+    }
+    if let Some(tcb) = tcb {
+        metadata.insert("tcb".to_string(), tcb);
+    }
+    Value::Object(metadata)
+}
+
+fn read(path: &str) -> Option<String> {
+    std::fs::read_to_string(path)
+        .map_err(|e| debug!("unable to read {}: {}", path, e))
+        .ok()
+}
+
+/// Describe the platform that produced `evidence` of `tee_type`.
+pub fn collect(tee_type: &str, evidence: &str) -> Value {
+    metadata(
+        read(CPUINFO).as_deref(),
+        read(OSRELEASE).as_deref(),
+        telemetry::tcb_summary(tee_type, evidence),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const CPUINFO_SAMPLE: &str = "\
+processor\t: 0
+vendor_id\t: AuthenticAMD
+model name\t: AMD EPYC 9454 48-Core Processor
+microcode\t: 0xa101148
+flags\t\t: fpu vme sev_snp
+
+processor\t: 1
+model name\t: Other
+microcode\t: 0x1
+";
+
+    #[test]
+    fn test_metadata() {
+        let tcb = json!({"tee_tcb_svn": "0300"});
+        let metadata = metadata(Some(CPUINFO_SAMPLE), Some("6.8.0-45-generic\n"), Some(tcb));
+        assert_eq!(
+            metadata,
+            json!({
+                "cpu-model": "AMD EPYC 9454 48-Core Processor",
+                "microcode": "0xa101148",
+                "kernel": "6.8.0-45-generic",
+                "tcb": {"tee_tcb_svn": "0300"},
+            })
+        );
+
+        // Fields that cannot be read are left out
+        let metadata = super::metadata(Some("processor\t: 0\nmicrocode\t:\n"), None, None);
+        assert_eq!(metadata, json!({}));
+    }
+}
@@ -0,0 +1,184 @@
+// TEE Attestation Service Agent — secret post-processing
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Consumers expect secrets in different shapes: cryptsetup takes the bytes
+// as released, while a key broker may store a passphrase as hex or base64
+// text with a trailing newline, and an application may want a fixed-length
+// key. A `postprocess` list in the `[keys]` entry of a policy ID reshapes
+// the decrypted secret of that ID before it is delivered, one step after
+// the other:
+//
+//   [keys."app-token"]
+//   postprocess = ["trim", "base64-decode", { kdf = { info = "app", length = 32 } }]
+//
+//   "trim"           remove leading and trailing ASCII whitespace
+//   "hex-decode"     decode hex text
+//   "base64-decode"  decode base64 text, standard or URL-safe, padded or not
+//   { kdf = {...} }  HKDF-SHA256 of the secret, with the optional `salt`
+//                    and `info` strings, to `length` bytes (default: 32)
+//
+// The steps apply to every release of the policy ID, after an SNP derived
+// key has been mixed in, and to secrets recovered from the offline escrow.
+// The PCR-sealed cache keeps the post-processed secret. Intermediate
+// values are zeroized.
+//
+// No unsafe code.
+
+use crate::utils;
+use anyhow::{anyhow, Result};
+use hkdf::Hkdf;
+use serde::Deserialize;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// Output length of the KDF when `length` is not set.
+const DEFAULT_KDF_LENGTH: usize = 32;
+
+/// Longest HKDF-SHA256 output: 255 blocks of the hash length.
+const MAX_KDF_LENGTH: usize = 255 * 32;
+
+/// One step of a `postprocess` list.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Step {
+    Trim,
+    HexDecode,
+    Base64Decode,
+    Kdf(KdfConfig),
+}
+
+/// Parameters of the `kdf` step.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct KdfConfig {
+    /// HKDF salt (default: none)
+    pub salt: Option<String>,
+    /// HKDF info, separating keys derived from the same secret
+    pub info: Option<String>,
+    /// Bytes of output (default: 32)
+    pub length: Option<usize>,
+}
+
+impl KdfConfig {
+    fn length(&self) -> usize {
+        self.length.unwrap_or(DEFAULT_KDF_LENGTH)
+    }
+}
+
+/// Check `steps` before any secret is released.
+pub fn check(steps: &[Step]) -> Result<()> {
+    for step in steps {
+        if let Step::Kdf(kdf) = step {
+            if !(1..=MAX_KDF_LENGTH).contains(&kdf.length()) {
+                return Err(anyhow!(
+                    "kdf length must be between 1 and {} bytes",
+                    MAX_KDF_LENGTH
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn apply_step(step: &Step, secret: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let output = match step {
+        Step::Trim => secret.trim_ascii().to_vec(),
+        Step::HexDecode => hex::decode(secret).map_err(|e| anyhow!("hex-decode: {}", e))?,
+        Step::Base64Decode => std::str::from_utf8(secret)
+            .ok()
+            .and_then(utils::base64)
+            .ok_or_else(|| anyhow!("base64-decode: secret is not valid base64"))?,
+        Step::Kdf(kdf) => {
+            let mut okm = vec![0; kdf.length()];
+            Hkdf::<Sha256>::new(kdf.salt.as_deref().map(str::as_bytes), secret)
+                .expand(kdf.info.as_deref().unwrap_or("").as_bytes(), &mut okm)
+                .map_err(|e| anyhow!("kdf: {}", e))?;
+            okm
+        }
+    };
+    Ok(Zeroizing::new(output))
+}
+
+/// Pass `secret` through `steps` in order.
+pub fn apply(steps: &[Step], secret: Vec<u8>) -> Result<Vec<u8>> {
+    let mut secret = Zeroizing::new(secret);
+    for step in steps {
+        secret = apply_step(step, &secret)?;
+    }
+    Ok(std::mem::take(&mut *secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Key {
+        postprocess: Vec<Step>,
+    }
+
+    #[test]
+    fn test_parse_steps() {
+        let key: Key = toml::from_str(
+            r#"postprocess = ["trim", "hex-decode", "base64-decode", { kdf = { info = "app", length = 16 } }]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            key.postprocess,
+            [
+                Step::Trim,
+                Step::HexDecode,
+                Step::Base64Decode,
+                Step::Kdf(KdfConfig {
+                    salt: None,
+                    info: Some("app".to_string()),
+                    length: Some(16),
+                }),
+            ]
+        );
+        assert!(toml::from_str::<Key>(r#"postprocess = ["rot13"]"#).is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let steps = [Step::Trim, Step::HexDecode];
+        assert_eq!(
+            apply(&steps, b" 736563726574\n".to_vec()).unwrap(),
+            b"secret"
+        );
+
+        let steps = [Step::Trim, Step::Base64Decode];
+        assert_eq!(apply(&steps, b"c2VjcmV0\n".to_vec()).unwrap(), b"secret");
+
+        assert!(apply(&[Step::HexDecode], b"secret".to_vec()).is_err());
+        assert!(apply(&[Step::Base64Decode], vec![0xff]).is_err());
+        assert_eq!(apply(&[], b"secret".to_vec()).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn test_kdf() {
+        let kdf = |info: &str, length| {
+            Step::Kdf(KdfConfig {
+                salt: Some("salt".to_string()),
+                info: Some(info.to_string()),
+                length,
+            })
+        };
+        let key = apply(&[kdf("a", None)], b"secret".to_vec()).unwrap();
+        assert_eq!(key.len(), DEFAULT_KDF_LENGTH);
+        assert_eq!(apply(&[kdf("a", None)], b"secret".to_vec()).unwrap(), key);
+        assert_ne!(apply(&[kdf("b", None)], b"secret".to_vec()).unwrap(), key);
+        assert_eq!(
+            apply(&[kdf("a", Some(64))], b"secret".to_vec())
+                .unwrap()
+                .len(),
+            64
+        );
+
+        assert!(check(&[kdf("a", Some(0))]).is_err());
+        assert!(check(&[kdf("a", Some(MAX_KDF_LENGTH + 1))]).is_err());
+        assert!(check(&[Step::Trim, kdf("a", Some(MAX_KDF_LENGTH))]).is_ok());
+    }
+}
@@ -0,0 +1,400 @@
+// TEE Attestation Service Agent — privilege separation
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With `[daemon] user` set, a daemon started as root splits in two:
+//
+//   - The root process becomes the evidence helper. It only writes
+//     configfs-tsm reports on request and forwards signals; it never talks
+//     to the network or handles secrets.
+//   - It re-executes the agent as `user` to run the daemon proper: HTTP to
+//     TAS, key unwrapping, publishing and the local API.
+//
+// The two are connected by a socketpair passed as the child's standard input.
+// Each request is one JSON line, answered by one JSON line or an error:
+//
+//   - an evidence request carries the TAS nonce and the hex-encoded report
+//     data, and is answered with the base64 evidence and TEE type;
+//   - a config request is answered with the text of the config file, which
+//     the helper reads and checks. The daemon process asks once at start-up
+//     and again on SIGHUP, and never opens the config file itself, so it is
+//     parsed from the same text for the whole time between reloads.
+//
+// A compromised network path can therefore obtain evidence over report data
+// of its choosing — as it could before — but no longer holds root or the TSM
+// interface itself.
+//
+// SIGTERM, SIGINT, SIGHUP and SIGUSR1 sent to the helper (systemd's main
+// PID) are forwarded to the daemon process, and the helper exits once it
+// does. The daemon process sends the systemd notifications, so the unit needs
+// NotifyAccess=all. The helper creates nothing on the daemon's behalf; it
+// hands the unit's RuntimeDirectory and StateDirectory (from systemd's
+// RUNTIME_DIRECTORY and STATE_DIRECTORY) to `user`, and the daemon process
+// creates `secrets_dir` and its other paths itself. NVIDIA GPU evidence, when enabled, is still collected by
+// the daemon process.
+//
+// No unsafe code.
+
+use crate::evidence::Evidence;
+use crate::hooks::ErrorClass;
+use crate::tee_evidence::tee_get_evidence;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use rustix::process::{kill_process, Pid, Signal};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::os::fd::{AsFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
+use tokio::process::Command;
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Set in the daemon process's environment by the helper.
+const CHILD_ENV: &str = "TAS_AGENT_PRIVSEP";
+
+/// Largest request or response line accepted.
+const MAX_LINE_BYTES: u64 = 64 * 1024;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "lowercase")]
+enum Request {
+    Evidence {
+        nonce: String,
+        report_data: Option<String>,
+    },
+    Config,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Response {
+    Evidence { evidence: Evidence },
+    Config { config: String },
+    Error { error: String },
+}
+
+/// Connection to the evidence helper, if this is the daemon process.
+static HELPER: OnceLock<Mutex<BufReader<UnixStream>>> = OnceLock::new();
+
+/// Config file text last received from the evidence helper.
+static CONFIG: Mutex<Option<String>> = Mutex::new(None);
+
+/// Look up the UID and GID of `user` in passwd(5) formatted `passwd`.
+fn find_user(passwd: &str, user: &str) -> Option<(u32, u32)> {
+    passwd.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != user {
+            return None;
+        }
+        let uid = fields.nth(1)?.parse().ok()?;
+        let gid = fields.next()?.parse().ok()?;
+        Some((uid, gid))
+    })
+}
+
+fn lookup_user(user: &str) -> Result<(u32, u32)> {
+    let passwd = std::fs::read_to_string("/etc/passwd").context("unable to read /etc/passwd")?;
+    find_user(&passwd, user).ok_or_else(|| anyhow!("unknown user {:?}", user))
+}
+
+/// Give the directories systemd created for the unit, listed in `dirs` as in
+/// RUNTIME_DIRECTORY, to `uid` and `gid`.
+fn hand_over_dirs(dirs: &str, uid: u32, gid: u32) -> Result<()> {
+    for dir in dirs.split(':').filter(|dir| !dir.is_empty()) {
+        std::os::unix::fs::chown(dir, Some(uid), Some(gid))
+            .with_context(|| format!("unable to hand {:?} to uid {}", dir, uid))?;
+        debug!("Handed {:?} to uid {}", dir, uid);
+    }
+    Ok(())
+}
+
+/// Read one line of at most MAX_LINE_BYTES. Returns None at end of stream.
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Option<String>> {
+    let mut line = String::new();
+    if reader.take(MAX_LINE_BYTES).read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with('\n') {
+        return Err(anyhow!("privsep message too long or truncated"));
+    }
+    Ok(Some(line))
+}
+
+fn write_line<T: Serialize>(writer: impl Write, message: &T) -> Result<()> {
+    // Serialize straight into the socket buffer rather than through an
+    // intermediate copy of the (possibly large) evidence
+    let mut writer = BufWriter::new(writer);
+    serde_json::to_writer(&mut writer, message)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Produce the evidence for one request.
+fn evidence(nonce: &str, report_data: Option<String>) -> Response {
+    let report_data = match report_data.map(hex::decode).transpose() {
+        Ok(report_data) => report_data,
+        Err(e) => {
+            return Response::Error {
+                error: format!("invalid report data: {}", e),
+            }
+        }
+    };
+    match tee_get_evidence(nonce, report_data.as_deref()) {
+        Ok(evidence) => Response::Evidence { evidence },
+        Err(error) => Response::Error { error },
+    }
+}
+
+/// Read the config file and check that it parses.
+fn checked_config(config_path: Option<&Path>) -> Response {
+    let checked = crate::read_config(config_path).and_then(|config| {
+        toml::from_str::<crate::Config>(&config).context("unable to load the config")?;
+        Ok(config)
+    });
+    match checked {
+        Ok(config) => Response::Config { config },
+        Err(e) => Response::Error {
+            error: format!("{:#}", e),
+        },
+    }
+}
+
+fn handle(request: Request, config_path: Option<&Path>) -> Response {
+    match request {
+        Request::Evidence { nonce, report_data } => evidence(&nonce, report_data),
+        Request::Config => checked_config(config_path),
+    }
+}
+
+/// Answer requests on `stream` until the daemon process closes it.
+fn serve(stream: UnixStream, config_path: Option<&Path>) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    while let Some(line) = read_line(&mut reader)? {
+        let response = match serde_json::from_str(&line) {
+            Ok(request) => handle(request, config_path),
+            Err(e) => Response::Error {
+                error: format!("invalid request: {}", e),
+            },
+        };
+        write_line(&stream, &response)?;
+    }
+    Ok(())
+}
+
+fn forward(pid: Option<Pid>, sig: Signal, name: &str) {
+    let Some(pid) = pid else {
+        return;
+    };
+    debug!("Forwarding {} to the daemon process", name);
+    if let Err(e) = kill_process(pid, sig) {
+        warn!("unable to forward {}: {}", name, e);
+    }
+}
+
+/// Run as the evidence helper: start the daemon process as `user` and serve
+/// it evidence and the config at `config_path` until it exits.
+pub async fn run_helper(user: &str, config_path: Option<PathBuf>) -> Result<()> {
+    let (uid, gid) = lookup_user(user)?;
+    for var in ["RUNTIME_DIRECTORY", "STATE_DIRECTORY"] {
+        if let Some(dirs) = std::env::var_os(var) {
+            hand_over_dirs(&dirs.to_string_lossy(), uid, gid)?;
+        }
+    }
+    let (helper_end, child_end) = UnixStream::pair().context("unable to create socketpair")?;
+    let exe = std::env::current_exe().context("unable to locate the agent executable")?;
+
+    let mut sigterm =
+        signal(SignalKind::terminate()).context("failed to register SIGTERM handler")?;
+    let mut sigint =
+        signal(SignalKind::interrupt()).context("failed to register SIGINT handler")?;
+    let mut sighup = signal(SignalKind::hangup()).context("failed to register SIGHUP handler")?;
+    let mut sigusr1 =
+        signal(SignalKind::user_defined1()).context("failed to register SIGUSR1 handler")?;
+
+    let mut child = Command::new(exe)
+        .args(std::env::args_os().skip(1))
+        .env(CHILD_ENV, "1")
+        // The daemon process feeds the watchdog.
+        .env_remove("WATCHDOG_PID")
+        .stdin(Stdio::from(OwnedFd::from(child_end)))
+        .uid(uid)
+        .gid(gid)
+        .kill_on_drop(true)
+        .spawn()
+        .context("unable to start the unprivileged daemon process")?;
+    let pid = child.id().and_then(|id| Pid::from_raw(id as i32));
+    info!("Started daemon process as {} (uid {})", user, uid);
+
+    // Ends when the daemon process exits and its end of the socket closes.
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = serve(helper_end, config_path.as_deref()) {
+            warn!("Evidence helper failed: {:#}", e);
+        }
+    });
+
+    let status = loop {
+        tokio::select! {
+            status = child.wait() => {
+                break status.context("unable to wait for the daemon process")?;
+            }
+            _ = sigterm.recv() => {
+                forward(pid, Signal::TERM, "SIGTERM");
+            }
+            _ = sigint.recv() => {
+                forward(pid, Signal::INT, "SIGINT");
+            }
+            _ = sighup.recv() => {
+                forward(pid, Signal::HUP, "SIGHUP");
+            }
+            _ = sigusr1.recv() => {
+                forward(pid, Signal::USR1, "SIGUSR1");
+            }
+        }
+    };
+    if !status.success() {
+        return Err(anyhow!("daemon process exited with {}", status));
+    }
+    Ok(())
+}
+
+/// If this process was started by the evidence helper, connect to it, fetch
+/// the config and return true.
+pub fn connect() -> Result<bool> {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return Ok(false);
+    }
+    let fd = std::io::stdin()
+        .as_fd()
+        .try_clone_to_owned()
+        .context("unable to take over the evidence helper socket")?;
+    let stream = UnixStream::from(fd);
+    HELPER
+        .set(Mutex::new(BufReader::new(stream)))
+        .map_err(|_| anyhow!("already connected to the evidence helper"))?;
+    debug!("Connected to the evidence helper");
+    reload_config()?;
+    Ok(true)
+}
+
+fn exchange(helper: &Mutex<BufReader<UnixStream>>, request: &Request) -> Result<Response> {
+    let mut helper = helper.lock().unwrap_or_else(|e| e.into_inner());
+    write_line(helper.get_ref(), request)?;
+    let line = read_line(&mut *helper)?.ok_or_else(|| anyhow!("evidence helper exited"))?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+fn request(
+    helper: &Mutex<BufReader<UnixStream>>,
+    nonce: &str,
+    report_data: Option<&[u8]>,
+) -> Result<Evidence> {
+    let request = Request::Evidence {
+        nonce: nonce.to_string(),
+        report_data: report_data.map(hex::encode),
+    };
+    match exchange(helper, &request)? {
+        Response::Evidence { evidence } => Ok(evidence),
+        Response::Error { error } => Err(anyhow!(error)),
+        Response::Config { .. } => Err(anyhow!("unexpected response from the evidence helper")),
+    }
+}
+
+fn request_config(helper: &Mutex<BufReader<UnixStream>>) -> Result<String> {
+    match exchange(helper, &Request::Config)? {
+        Response::Config { config } => Ok(config),
+        Response::Error { error } => Err(ErrorClass::Config.error(error)),
+        Response::Evidence { .. } => Err(anyhow!("unexpected response from the evidence helper")),
+    }
+}
+
+/// Fetch the config again from the evidence helper, when connected to one.
+pub fn reload_config() -> Result<()> {
+    let Some(helper) = HELPER.get() else {
+        return Ok(());
+    };
+    let config =
+        request_config(helper).context("unable to get the config from the evidence helper")?;
+    *CONFIG.lock().unwrap_or_else(|e| e.into_inner()) = Some(config);
+    Ok(())
+}
+
+/// The config text received from the evidence helper, if connected to one.
+pub fn config() -> Option<String> {
+    CONFIG.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Collect TEE evidence, through the evidence helper when connected to one.
+pub fn get_evidence(nonce: &str, report_data: Option<&[u8]>) -> Result<Evidence, String> {
+    match HELPER.get() {
+        Some(helper) => request(helper, nonce, report_data).map_err(|e| format!("{:#}", e)),
+        None => tee_get_evidence(nonce, report_data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hand_over_dirs() {
+        let a = tempfile::tempdir().unwrap();
+        let b = tempfile::tempdir().unwrap();
+        let dirs = format!("{}:{}", a.path().display(), b.path().display());
+        let uid = rustix::process::getuid().as_raw();
+        let gid = rustix::process::getgid().as_raw();
+        hand_over_dirs(&dirs, uid, gid).unwrap();
+        assert!(hand_over_dirs("/nonexistent/tas_agent", uid, gid).is_err());
+    }
+
+    #[test]
+    fn test_find_user() {
+        let passwd = "root:x:0:0:root:/root:/bin/bash\n\
+                      tas-agent:x:998:997::/var/lib/tas_agent:/usr/sbin/nologin\n";
+        assert_eq!(find_user(passwd, "tas-agent"), Some((998, 997)));
+        assert_eq!(find_user(passwd, "root"), Some((0, 0)));
+        assert_eq!(find_user(passwd, "tas"), None);
+    }
+
+    #[test]
+    fn test_serve_round_trip() {
+        let (helper_end, client_end) = UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || serve(helper_end, None));
+
+        let helper = Mutex::new(BufReader::new(client_end));
+        // tee_get_evidence rejects the short nonce before touching configfs.
+        let err = request(&helper, "short", None).unwrap_err();
+        assert!(err.to_string().contains("64 bytes"), "{err}");
+
+        write_line(helper.lock().unwrap().get_ref(), &"not a request").unwrap();
+        let line = read_line(&mut *helper.lock().unwrap()).unwrap().unwrap();
+        assert!(line.contains("invalid request"), "{line}");
+
+        drop(helper);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_serve_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "policy_id = \"p\"\n").unwrap();
+        let (helper_end, client_end) = UnixStream::pair().unwrap();
+        let config_path = path.clone();
+        let server = std::thread::spawn(move || serve(helper_end, Some(&config_path)));
+
+        let helper = Mutex::new(BufReader::new(client_end));
+        assert_eq!(request_config(&helper).unwrap(), "policy_id = \"p\"\n");
+
+        // A config that does not parse is refused by the helper
+        std::fs::write(&path, "policy_id = [").unwrap();
+        let err = request_config(&helper).unwrap_err();
+        assert!(err.to_string().contains("unable to load"), "{err}");
+
+        drop(helper);
+        server.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+// TEE Attestation Service Agent — request IDs
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Every attestation attempt gets a random version 4 UUID: a one-shot key
+// fetch, a daemon release of one policy ID, the secrets of a Kubernetes init
+// container. The ID is sent to TAS as an `X-Request-ID` header on each call
+// of the attempt (version probe, nonce, secret and telemetry), and added to
+// the log lines, audit records and hook environment the attempt produces:
+//
+//   2026-10-16T09:12:44+00:00 WARN [1b4e28ba-2fa1-41d2-883f-0016d3cca427] - ...
+//   {"timestamp": ..., "event": "escrow-fallback",
+//    "request_id": "1b4e28ba-2fa1-41d2-883f-0016d3cca427", ...}
+//
+// so the agent's and the server's logs of an incident can be joined on it.
+// The ID is held in a Tokio task-local; tasks spawned by an attempt inherit
+// it through `inherit`.
+//
+// No unsafe code.
+
+use crate::rng::rng;
+use log::debug;
+use rand::RngCore;
+use std::future::Future;
+
+/// Header carrying the request ID on TAS calls.
+pub const HEADER: &str = "X-Request-ID";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Format 16 random bytes as a version 4 UUID.
+pub fn uuid_v4(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// A fresh request ID.
+pub fn generate() -> String {
+    let mut bytes = [0u8; 16];
+    rng().fill_bytes(&mut bytes);
+    uuid_v4(bytes)
+}
+
+/// The ID of the attempt the current task belongs to, if any.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(String::clone).ok()
+}
+
+/// Run `attempt` under a fresh request ID.
+pub async fn scope<F: Future>(attempt: F) -> F::Output {
+    let id = generate();
+    debug!("Request ID: {}", id);
+    REQUEST_ID.scope(id, attempt).await
+}
+
+/// Run `task`, spawned by an attempt, under the ID of that attempt.
+pub async fn inherit<F: Future>(id: Option<String>, task: F) -> F::Output {
+    match id {
+        Some(id) => REQUEST_ID.scope(id, task).await,
+        None => task.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid_v4() {
+        let uuid = uuid_v4([0xff; 16]);
+        assert_eq!(uuid, "ffffffff-ffff-4fff-bfff-ffffffffffff");
+    }
+
+    #[tokio::test]
+    async fn test_scope() {
+        assert_eq!(current(), None);
+        let id = scope(async { current() }).await.unwrap();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert_ne!(scope(async { current() }).await.unwrap(), id);
+
+        let inherited = scope(async {
+            let id = current();
+            let task = tokio::spawn(inherit(id.clone(), async { current() }));
+            (id, task.await.unwrap())
+        })
+        .await;
+        assert_eq!(inherited.0, inherited.1);
+        assert_eq!(current(), None);
+    }
+}
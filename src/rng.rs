@@ -0,0 +1,114 @@
+// TEE Attestation Service Agent — random number source
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Every random value the agent draws (wrapping keys, OAEP padding, sealed
+// cache keys, CoRIM tag IDs) comes from `rng()`. Normal builds use the
+// thread-local, OS-seeded generator of `rand`.
+//
+// Builds with the `deterministic-rng` feature instead draw from a single
+// generator seeded from TAS_AGENT_RNG_SEED (an unsigned integer, default 0).
+// Combined with `evidence-fixtures`, a run against the mock server then
+// sends byte-identical requests every time, so the whole pipeline can be
+// compared against golden output. Keys are predictable in such builds:
+// never enable `deterministic-rng` in production.
+//
+// No unsafe code.
+
+use rand::{CryptoRng, RngCore};
+
+/// Environment variable holding the seed of deterministic builds.
+#[cfg(feature = "deterministic-rng")]
+pub const SEED_ENV: &str = "TAS_AGENT_RNG_SEED";
+
+#[cfg(feature = "deterministic-rng")]
+static SEEDED: std::sync::Mutex<Option<rand::rngs::StdRng>> = std::sync::Mutex::new(None);
+
+/// Handle to the agent's random number source.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AgentRng;
+
+/// The agent's random number source.
+pub fn rng() -> AgentRng {
+    AgentRng
+}
+
+#[cfg(not(feature = "deterministic-rng"))]
+fn with<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    f(&mut rand::thread_rng())
+}
+
+/// A generator seeded from `seed`, or from 0 if it is missing or invalid.
+#[cfg(feature = "deterministic-rng")]
+fn seeded(seed: Option<&str>) -> rand::rngs::StdRng {
+    use rand::SeedableRng;
+
+    let seed = match seed.map(|s| s.trim().parse::<u64>()) {
+        Some(Ok(seed)) => seed,
+        Some(Err(_)) => {
+            log::warn!("invalid {}, using seed 0", SEED_ENV);
+            0
+        }
+        None => 0,
+    };
+    log::warn!(
+        "Using deterministic randomness (seed {}); keys are predictable",
+        seed
+    );
+    rand::rngs::StdRng::seed_from_u64(seed)
+}
+
+#[cfg(feature = "deterministic-rng")]
+fn with<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    let mut rng = SEEDED
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let rng = rng.get_or_insert_with(|| seeded(std::env::var(SEED_ENV).ok().as_deref()));
+    f(rng)
+}
+
+impl RngCore for AgentRng {
+    fn next_u32(&mut self) -> u32 {
+        with(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        with(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        with(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        with(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
+// Both sources are cryptographically secure generators; only the seeding
+// of deterministic builds is not.
+impl CryptoRng for AgentRng {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_fills_bytes() {
+        let mut a = [0u8; 32];
+        let mut b = [0u8; 32];
+        rng().fill_bytes(&mut a);
+        rng().fill_bytes(&mut b);
+        assert_ne!(a, b);
+    }
+
+    #[cfg(feature = "deterministic-rng")]
+    #[test]
+    fn test_seeded_keygen_is_reproducible() {
+        let key = |seed| rsa::RsaPrivateKey::new(&mut seeded(seed), 2048).unwrap();
+        assert_eq!(key(Some("42")), key(Some("42")));
+        assert_ne!(key(Some("42")), key(Some("43")));
+        assert_eq!(key(None), key(Some("not a number")));
+    }
+}
@@ -0,0 +1,288 @@
+// TEE Attestation Service Agent — Landlock filesystem sandbox
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With `landlock = true` in a build with the `landlock` feature, the normal
+// (stdout, --out, --secret-fd) mode confines its own filesystem access with
+// Landlock before the tokio runtime starts, so every thread inherits the
+// restriction. Only the paths the run needs stay reachable:
+//
+//   - read:    the config file, the API keys and root certificates, every
+//              verifier key, policy and credential file the config names,
+//              the resolver and TLS configuration under /etc, the SEV VMPL
+//              file
+//   - execute: the system library directories, for NSS modules loaded by
+//              the resolver
+//   - manage:  /sys/kernel/config/tsm or the `[tsm] report_dir` (report
+//              directories are created and removed), the directory of --out,
+//              --token-out, the Vault secret outputs and the version cache,
+//              and the audit log
+//
+// Paths that do not exist when the rules are built are skipped. Kernels
+// without Landlock (before 5.13, or with it disabled) run unrestricted with
+// a warning; older Landlock ABIs enforce the subset of rights they know.
+//
+// Integrations that run external programs cannot work under the sandbox,
+// so enabling it together with any of them is a configuration error. The
+// askpass, passfifo and daemon modes are not sandboxed.
+//
+// No unsafe code.
+
+use anyhow::{Context, Result};
+use landlock::{
+    Access, AccessFs, BitFlags, PathBeneath, PathFd, Ruleset, RulesetAttr, RulesetCreatedAttr,
+    RulesetStatus, ABI,
+};
+use log::{debug, warn};
+use std::path::{Path, PathBuf};
+
+/// Newest Landlock ABI the rules are written for.
+const LANDLOCK_ABI: ABI = ABI::V3;
+
+/// How a path may be accessed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rights {
+    /// Read files and list directories
+    Read,
+    /// Read, and map or run files as code
+    Execute,
+    /// Read and write existing files
+    Write,
+    /// Read and write, and create or remove entries
+    Manage,
+}
+
+impl Rights {
+    fn access(self) -> BitFlags<AccessFs> {
+        let read = AccessFs::ReadFile | AccessFs::ReadDir;
+        let write = read | AccessFs::WriteFile | AccessFs::Truncate;
+        match self {
+            Rights::Read => read,
+            Rights::Execute => read | AccessFs::Execute,
+            Rights::Write => write,
+            Rights::Manage => {
+                write
+                    | AccessFs::MakeReg
+                    | AccessFs::MakeDir
+                    | AccessFs::RemoveFile
+                    | AccessFs::RemoveDir
+            }
+        }
+    }
+}
+
+/// Directory a file at `path` is created in.
+pub fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Rule for a file the agent writes: the file itself if it exists,
+/// otherwise its directory, where it is created.
+pub fn output_rule(path: &Path) -> (PathBuf, Rights) {
+    if path.is_file() {
+        return (path.to_path_buf(), Rights::Write);
+    }
+    (parent_dir(path), Rights::Manage)
+}
+
+/// Rules every normal-mode run needs besides its configured paths.
+pub fn system_rules() -> Vec<(PathBuf, Rights)> {
+    let read = [
+        "/etc/resolv.conf",
+        "/etc/hosts",
+        "/etc/nsswitch.conf",
+        "/etc/host.conf",
+        "/etc/gai.conf",
+        "/etc/localtime",
+        "/etc/ssl",
+        "/etc/pki",
+        "/dev/urandom",
+        "/sys/devices/system/cpu/sev",
+    ];
+    let execute = ["/lib", "/lib64", "/usr/lib", "/usr/lib64"];
+    read.iter()
+        .map(|path| (PathBuf::from(path), Rights::Read))
+        .chain(
+            execute
+                .iter()
+                .map(|path| (PathBuf::from(path), Rights::Execute)),
+        )
+        .chain([(PathBuf::from("/sys/kernel/config/tsm"), Rights::Manage)])
+        .collect()
+}
+
+/// Restrict the calling thread, and threads it starts later, to `rules`.
+pub fn restrict(rules: &[(PathBuf, Rights)]) -> Result<()> {
+    let mut ruleset = Ruleset::default()
+        .handle_access(AccessFs::from_all(LANDLOCK_ABI))
+        .context("unable to set up Landlock ruleset")?
+        .create()
+        .context("unable to create Landlock ruleset")?;
+    for (path, rights) in rules {
+        let fd = match PathFd::new(path) {
+            Ok(fd) => fd,
+            Err(e) => {
+                debug!("Not granting access to {:?}: {}", path, e);
+                continue;
+            }
+        };
+        let mut access = rights.access();
+        if !path.is_dir() {
+            access &= AccessFs::from_file(LANDLOCK_ABI);
+        }
+        debug!("Landlock: {:?} {:?}", rights, path);
+        ruleset = ruleset
+            .add_rule(PathBeneath::new(fd, access))
+            .with_context(|| format!("unable to add Landlock rule for {:?}", path))?;
+    }
+    let status = ruleset
+        .restrict_self()
+        .context("unable to enforce Landlock ruleset")?;
+    match status.ruleset {
+        RulesetStatus::FullyEnforced => debug!("Landlock ruleset enforced"),
+        RulesetStatus::PartiallyEnforced => {
+            debug!("Landlock ruleset partially enforced by this kernel")
+        }
+        RulesetStatus::NotEnforced => {
+            warn!("Landlock is not supported by this kernel; filesystem access is not restricted")
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_rule() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("key");
+        std::fs::write(&existing, b"").unwrap();
+        assert_eq!(output_rule(&existing), (existing.clone(), Rights::Write));
+        assert_eq!(
+            output_rule(&dir.path().join("new")),
+            (dir.path().to_path_buf(), Rights::Manage)
+        );
+        assert_eq!(
+            output_rule(Path::new("key")),
+            (PathBuf::from("."), Rights::Manage)
+        );
+    }
+
+    #[test]
+    fn test_rights_are_cumulative() {
+        assert!(Rights::Execute.access().contains(Rights::Read.access()));
+        assert!(Rights::Manage.access().contains(Rights::Write.access()));
+        assert!(!Rights::Write.access().contains(AccessFs::MakeReg));
+    }
+
+    /// Every absolute path string anywhere in `value`.
+    fn config_paths(value: &toml::Value, paths: &mut Vec<PathBuf>) {
+        match value {
+            toml::Value::String(s) if s.starts_with('/') => paths.push(s.into()),
+            toml::Value::Array(values) => values.iter().for_each(|v| config_paths(v, paths)),
+            toml::Value::Table(table) => table.values().for_each(|v| config_paths(v, paths)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_rules_cover_every_config_path() {
+        // Every section that can be combined with `landlock = true`; the
+        // program integrations are rejected before the rules are built.
+        let common = r#"
+            server_uri = "https://tas.example.com:5000"
+            api_key = "/etc/tas_agent/api-key"
+            cert_path = "/etc/tas_agent/root_cert.pem"
+            audit_log = "/var/log/tas_agent/audit.log"
+            landlock = true
+            kernel_cmdline = true
+            platform_metadata = true
+
+            [keys.data]
+            api_key = "/etc/tas_agent/data-api-key"
+            cert_path = "/etc/tas_agent/data-root.pem"
+
+            [boot_claims]
+            initrd = "/boot/initrd.img"
+
+            [discovery]
+
+            [tsm]
+            report_dir = "/run/tas_agent/tsm"
+            lock_path = "/run/tas_agent/tsm.lock"
+
+            [version_cache]
+            path = "/var/cache/tas_agent/version.json"
+
+            [shared_attestation]
+            path = "/run/tas_agent/shared/attestation.json"
+
+            [ear]
+            verifier_key = "/etc/tas_agent/ear-verifier.pem"
+
+            [server_attestation]
+            verifier_key = "/etc/tas_agent/server-verifier.pem"
+            reference_values = {}
+
+            [local_policy]
+            rules = "/etc/tas_agent/policy.json"
+
+            [vault]
+            address = "https://vault.example.com:8200"
+            role = "tas-agent"
+            cert_path = "/etc/tas_agent/vault_ca.pem"
+            [[vault.secrets]]
+            path = "secret/data/app/db"
+            out = "/run/tas_agent/db-password"
+
+            [kms]
+            backend = "azure"
+            vault_url = "https://myvault.vault.azure.net"
+            key_name = "luks"
+            access_token = "/etc/tas_agent/azure-token"
+        "#;
+        let verifiers = [
+            "backend = \"ita\"\napi_key = \"/etc/tas_agent/ita-api-key\"",
+            "backend = \"maa\"\nurl = \"https://maa.example.com\"\n\
+             bearer_token = \"/etc/tas_agent/maa-token\"",
+            "backend = \"veraison\"\nurl = \"https://veraison.example.com\"\n\
+             media_type = \"application/eat+cwt\"\n\
+             bearer_token = \"/etc/tas_agent/veraison-token\"",
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config.toml");
+        let out = dir.path().join("secret");
+        let token_out = dir.path().join("token");
+        for verifier in verifiers {
+            let data = format!("{common}\n[verifier]\n{verifier}\n");
+            let cfg: crate::Config = toml::from_str(&data).unwrap();
+            let mut paths = Vec::new();
+            config_paths(&toml::from_str(&data).unwrap(), &mut paths);
+            let cli = <crate::Cli as clap::Parser>::parse_from([
+                "tas_agent".as_ref(),
+                "--config".as_ref(),
+                config.as_os_str(),
+                "--out".as_ref(),
+                out.as_os_str(),
+                "--token-out".as_ref(),
+                token_out.as_os_str(),
+            ]);
+            paths.extend([config.clone(), out.clone(), token_out.clone()]);
+
+            let rules = crate::landlock_rules(&cli, &cfg);
+            for path in paths {
+                assert!(
+                    rules.iter().any(|(rule, _)| path.starts_with(rule)),
+                    "no rule covers {:?}",
+                    path
+                );
+            }
+        }
+    }
+}
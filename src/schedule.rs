@@ -0,0 +1,529 @@
+// TEE Attestation Service Agent — re-attestation scheduler
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Decides when the daemon re-attests. Each schedule profile covers a set of
+// policy IDs and any combination of triggers:
+//
+//   interval_secs  re-attest at a fixed period
+//   cron           five-field cron expression ("minute hour day-of-month
+//                  month day-of-week", evaluated in UTC) for fleets with a
+//                  mandated attestation cadence
+//   on_resume      re-attest as soon as the system resumes from suspend or
+//                  hibernation
+//
+// Cron matches are kept as wall-clock times and compared with the system
+// clock, not turned into monotonic deadlines: the monotonic clock stops
+// while the system is suspended and ignores clock corrections, so a cron
+// deadline derived from it would fire late. The daemon wakes at least every
+// CRON_RECHECK to compare the clocks again.
+//
+// A resume is detected by comparing the boot-time clock (/proc/uptime, which
+// keeps counting while the system is suspended) with the monotonic clock,
+// which stops. After a kexec the daemon is started afresh and attests on
+// start-up, so no separate trigger is needed.
+//
+// No unsafe code.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use log::{debug, info};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use tokio::time::{Duration, Instant};
+
+/// How far ahead a cron expression is searched for its next match.
+const MAX_CRON_SEARCH_DAYS: u32 = 366 * 5;
+
+/// Longest the daemon sleeps towards a cron match before checking the system
+/// clock again.
+const CRON_RECHECK: Duration = Duration::from_secs(60);
+
+/// Suspensions shorter than this are not treated as a resume, so scheduling
+/// jitter does not trigger re-attestation.
+const RESUME_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// How often the clocks are compared to detect a resume.
+pub const RESUME_CHECK_PERIOD: Duration = Duration::from_secs(10);
+
+/// `[daemon.schedules.<profile>]` section of the config file.
+#[derive(Deserialize, Default, Clone)]
+pub struct ScheduleConfig {
+    /// Policy IDs this profile re-attests (default: all daemon policy IDs)
+    pub policy_ids: Option<Vec<String>>,
+    /// Seconds between re-attestations
+    pub interval_secs: Option<u64>,
+    /// Cron expression, evaluated in UTC
+    pub cron: Option<String>,
+    /// Re-attest after resume from suspend or hibernation
+    pub on_resume: Option<bool>,
+}
+
+/// A parsed five-field cron expression. Each field is a bit set of the values
+/// it matches.
+#[derive(Debug, PartialEq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Day-of-month or day-of-week is `*`; otherwise a day matching either
+    /// field matches, as in cron(8).
+    any_day: bool,
+}
+
+/// Parse one cron field into a bit set of values in `min..=max`. Returns the
+/// set and whether the field was `*`.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<(u64, bool)> {
+    let mut set = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|&s| s > 0)
+                    .ok_or_else(|| anyhow!("invalid step in {:?}", item))?;
+                (range, step)
+            }
+            None => (item, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, min, max)?, parse_value(b, min, max)?)
+        } else {
+            let start = parse_value(range, min, max)?;
+            // "a/n" runs from a to the end of the range.
+            (start, if step > 1 { max } else { start })
+        };
+        if start > end {
+            return Err(anyhow!("invalid range {:?}", item));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok((set, field == "*"))
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32> {
+    value
+        .parse()
+        .ok()
+        .filter(|v| (min..=max).contains(v))
+        .ok_or_else(|| anyhow!("value {:?} is not in {}-{}", value, min, max))
+}
+
+impl Cron {
+    /// Parse a five-field cron expression.
+    pub fn parse(expr: &str) -> Result<Self> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            return Err(anyhow!(
+                "cron expression {:?} must have 5 fields, found {}",
+                expr,
+                fields.len()
+            ));
+        };
+        let parse = |field, min, max| {
+            parse_field(field, min, max)
+                .with_context(|| format!("invalid cron expression {:?}", expr))
+        };
+
+        let (mut days_of_week, dow_any) = parse(dow, 0, 7)?;
+        // Both 0 and 7 are Sunday.
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        let (days_of_month, dom_any) = parse(dom, 1, 31)?;
+        Ok(Cron {
+            minutes: parse(minute, 0, 59)?.0,
+            hours: parse(hour, 0, 23)?.0,
+            days_of_month,
+            months: parse(month, 1, 12)?.0,
+            days_of_week,
+            any_day: dom_any || dow_any,
+        })
+    }
+
+    fn matches_date<D: Datelike>(&self, date: &D) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let dom = self.days_of_month & (1 << date.day()) != 0;
+        let dow = self.days_of_week & (1 << date.weekday().num_days_from_sunday()) != 0;
+        if self.any_day {
+            dom && dow
+        } else {
+            dom || dow
+        }
+    }
+
+    /// The first time strictly after `after` that matches.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut date = after.date_naive();
+        let mut first_minute = after.hour() * 60 + after.minute() + 1;
+        for _ in 0..MAX_CRON_SEARCH_DAYS {
+            if self.matches_date(&date) {
+                for minute_of_day in first_minute..24 * 60 {
+                    let (hour, minute) = (minute_of_day / 60, minute_of_day % 60);
+                    if self.hours & (1 << hour) != 0 && self.minutes & (1 << minute) != 0 {
+                        return date.and_hms_opt(hour, minute, 0).map(|t| t.and_utc());
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+            first_minute = 0;
+        }
+        None
+    }
+}
+
+/// One schedule profile and its next due times.
+struct Profile {
+    name: String,
+    policy_ids: Vec<String>,
+    interval: Option<Duration>,
+    cron: Option<Cron>,
+    on_resume: bool,
+    /// When the interval fires next
+    next: Option<Instant>,
+    /// When the cron expression matches next, by the system clock
+    next_cron: Option<DateTime<Utc>>,
+}
+
+impl Profile {
+    /// Set the timed triggers to fire next after `now` and `wall`.
+    fn reschedule(&mut self, now: Instant, wall: DateTime<Utc>) {
+        self.next = self.interval.map(|interval| now + interval);
+        self.next_cron = self.cron.as_ref().and_then(|cron| cron.next_after(wall));
+    }
+
+    fn is_due(&self, now: Instant, wall: DateTime<Utc>) -> bool {
+        self.next.is_some_and(|next| next <= now) || self.next_cron.is_some_and(|next| next <= wall)
+    }
+
+    /// When to wake next for this profile, as of `now` and `wall`.
+    fn deadline(&self, now: Instant, wall: DateTime<Utc>) -> Option<Instant> {
+        let by_cron = self.next_cron.map(|next| {
+            let wait = (next - wall).to_std().unwrap_or_default();
+            now + wait.min(CRON_RECHECK)
+        });
+        match (self.next, by_cron) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// Tracks the boot-time and monotonic clocks to detect a resume.
+struct ResumeDetector {
+    uptime: Duration,
+    instant: Instant,
+}
+
+/// Read the boot-time clock, which includes time spent suspended.
+fn read_uptime() -> Option<Duration> {
+    let uptime = fs::read_to_string("/proc/uptime").ok()?;
+    let secs: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    Some(Duration::from_secs_f64(secs))
+}
+
+/// Time spent suspended, given how far the boot-time and monotonic clocks
+/// advanced over the same period.
+fn suspended_for(boot_elapsed: Duration, monotonic_elapsed: Duration) -> Option<Duration> {
+    boot_elapsed
+        .checked_sub(monotonic_elapsed)
+        .filter(|&suspended| suspended >= RESUME_THRESHOLD)
+}
+
+impl ResumeDetector {
+    fn new() -> Option<Self> {
+        Some(ResumeDetector {
+            uptime: read_uptime()?,
+            instant: Instant::now(),
+        })
+    }
+
+    /// Return how long the system was suspended since the last check, if it
+    /// was.
+    fn check(&mut self) -> Option<Duration> {
+        let uptime = read_uptime()?;
+        let instant = Instant::now();
+        let suspended = suspended_for(uptime.saturating_sub(self.uptime), instant - self.instant);
+        self.uptime = uptime;
+        self.instant = instant;
+        suspended
+    }
+}
+
+/// Tracks when each schedule profile is next due.
+pub struct Scheduler {
+    profiles: Vec<Profile>,
+    resume: Option<ResumeDetector>,
+}
+
+impl Scheduler {
+    /// Build the scheduler. Without profiles, all policy IDs are re-attested
+    /// every `default_interval`.
+    pub fn new(
+        schedules: &BTreeMap<String, ScheduleConfig>,
+        policy_ids: &[String],
+        default_interval: Duration,
+    ) -> Result<Self> {
+        let mut profiles = Vec::new();
+        for (name, schedule) in schedules {
+            let ids = schedule
+                .policy_ids
+                .clone()
+                .unwrap_or_else(|| policy_ids.to_vec());
+            if let Some(id) = ids.iter().find(|id| !policy_ids.contains(id)) {
+                return Err(anyhow!(
+                    "schedule {:?} names policy ID {:?}, which the daemon does not maintain",
+                    name,
+                    id
+                ));
+            }
+            let cron = schedule
+                .cron
+                .as_deref()
+                .map(Cron::parse)
+                .transpose()
+                .with_context(|| format!("invalid schedule {:?}", name))?;
+            let interval = schedule
+                .interval_secs
+                .map(|secs| Duration::from_secs(secs.max(1)));
+            let on_resume = schedule.on_resume.unwrap_or(false);
+            if interval.is_none() && cron.is_none() && !on_resume {
+                return Err(anyhow!("schedule {:?} has no trigger", name));
+            }
+            profiles.push(Profile {
+                name: name.clone(),
+                policy_ids: ids,
+                interval,
+                cron,
+                on_resume,
+                next: None,
+                next_cron: None,
+            });
+        }
+        if profiles.is_empty() {
+            profiles.push(Profile {
+                name: "default".to_string(),
+                policy_ids: policy_ids.to_vec(),
+                interval: Some(default_interval),
+                cron: None,
+                on_resume: false,
+                next: None,
+                next_cron: None,
+            });
+        }
+
+        let (now, wall) = (Instant::now(), Utc::now());
+        for profile in &mut profiles {
+            profile.reschedule(now, wall);
+        }
+        let resume = if profiles.iter().any(|p| p.on_resume) {
+            ResumeDetector::new()
+        } else {
+            None
+        };
+        Ok(Scheduler { profiles, resume })
+    }
+
+    /// When to wake next: the earliest time a timed trigger fires, or
+    /// sooner to check the system clock for a cron match.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let (now, wall) = (Instant::now(), Utc::now());
+        self.profiles
+            .iter()
+            .filter_map(|p| p.deadline(now, wall))
+            .min()
+    }
+
+    /// Reschedule the profiles due at `now` and return their policy IDs.
+    pub fn take_due(&mut self, now: Instant) -> Vec<String> {
+        self.take_due_at(now, Utc::now())
+    }
+
+    fn take_due_at(&mut self, now: Instant, wall: DateTime<Utc>) -> Vec<String> {
+        let mut due = Vec::new();
+        for profile in &mut self.profiles {
+            if profile.is_due(now, wall) {
+                debug!("Schedule {:?} is due", profile.name);
+                profile.reschedule(now, wall);
+                extend_unique(&mut due, &profile.policy_ids);
+            }
+        }
+        due
+    }
+
+    /// Whether any profile re-attests on resume.
+    pub fn watches_resume(&self) -> bool {
+        self.resume.is_some()
+    }
+
+    /// If the system resumed since the last check, return the policy IDs to
+    /// re-attest.
+    pub fn check_resume(&mut self) -> Vec<String> {
+        let Some(suspended) = self.resume.as_mut().and_then(ResumeDetector::check) else {
+            return Vec::new();
+        };
+        info!("System resumed after {}s suspended", suspended.as_secs());
+        let mut due = Vec::new();
+        for profile in self.profiles.iter().filter(|p| p.on_resume) {
+            extend_unique(&mut due, &profile.policy_ids);
+        }
+        due
+    }
+}
+
+fn extend_unique(ids: &mut Vec<String>, more: &[String]) {
+    for id in more {
+        if !ids.contains(id) {
+            ids.push(id.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeDelta};
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        NaiveDate::from_ymd_opt(y, mo, d)
+            .unwrap()
+            .and_hms_opt(h, mi, 0)
+            .unwrap()
+            .and_utc()
+    }
+
+    #[test]
+    fn test_parse_field() {
+        assert_eq!(parse_field("*", 0, 3).unwrap(), (0b1111, true));
+        assert_eq!(parse_field("1,3", 0, 5).unwrap(), (0b1010, false));
+        assert_eq!(parse_field("2-4", 0, 5).unwrap(), (0b11100, false));
+        assert_eq!(parse_field("*/2", 0, 5).unwrap(), (0b10101, false));
+        assert_eq!(parse_field("1/2", 0, 5).unwrap(), (0b101010, false));
+        assert!(parse_field("6", 0, 5).is_err());
+        assert!(parse_field("4-2", 0, 5).is_err());
+        assert!(parse_field("*/0", 0, 5).is_err());
+        assert!(parse_field("mon", 0, 7).is_err());
+    }
+
+    #[test]
+    fn test_cron_parse_rejects_bad_expressions() {
+        assert!(Cron::parse("* * * *").is_err());
+        assert!(Cron::parse("60 * * * *").is_err());
+        assert!(Cron::parse("* * 0 * *").is_err());
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        let every_6h = Cron::parse("0 */6 * * *").unwrap();
+        assert_eq!(
+            every_6h.next_after(at(2026, 3, 1, 5, 59)),
+            Some(at(2026, 3, 1, 6, 0))
+        );
+        // Strictly after: a matching time moves to the next match.
+        assert_eq!(
+            every_6h.next_after(at(2026, 3, 1, 6, 0)),
+            Some(at(2026, 3, 1, 12, 0))
+        );
+        assert_eq!(
+            every_6h.next_after(at(2026, 3, 1, 23, 59)),
+            Some(at(2026, 3, 2, 0, 0))
+        );
+
+        // 2026-03-01 is a Sunday; 7 is also Sunday.
+        let sundays = Cron::parse("30 2 * * 7").unwrap();
+        assert_eq!(
+            sundays.next_after(at(2026, 3, 1, 3, 0)),
+            Some(at(2026, 3, 8, 2, 30))
+        );
+
+        // Day-of-month and day-of-week both restricted: either matches.
+        let either = Cron::parse("0 0 15 * 1").unwrap();
+        assert_eq!(
+            either.next_after(at(2026, 3, 1, 0, 0)),
+            Some(at(2026, 3, 2, 0, 0))
+        );
+
+        let never = Cron::parse("0 0 31 2 *").unwrap();
+        assert_eq!(never.next_after(at(2026, 3, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_suspended_for() {
+        let secs = Duration::from_secs;
+        assert_eq!(suspended_for(secs(100), secs(40)), Some(secs(60)));
+        assert_eq!(suspended_for(secs(12), secs(10)), None);
+        assert_eq!(suspended_for(secs(10), secs(12)), None);
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_profiles() {
+        let ids = vec!["a".to_string(), "b".to_string()];
+        let default = Scheduler::new(&BTreeMap::new(), &ids, Duration::from_secs(60)).unwrap();
+        assert!(default.next_deadline().is_some());
+        assert!(!default.watches_resume());
+
+        let mut schedules = BTreeMap::new();
+        schedules.insert(
+            "audit".to_string(),
+            ScheduleConfig {
+                policy_ids: Some(vec!["a".to_string()]),
+                interval_secs: Some(3600),
+                ..Default::default()
+            },
+        );
+        let mut scheduler = Scheduler::new(&schedules, &ids, Duration::from_secs(60)).unwrap();
+        let deadline = scheduler.next_deadline().unwrap();
+        assert!(scheduler.take_due(Instant::now()).is_empty());
+        assert_eq!(scheduler.take_due(deadline), vec!["a"]);
+
+        // A cron match is found by the system clock, however long the
+        // monotonic clock was stopped
+        schedules.insert(
+            "compliance".to_string(),
+            ScheduleConfig {
+                policy_ids: Some(vec!["b".to_string()]),
+                cron: Some("0 */6 * * *".to_string()),
+                ..Default::default()
+            },
+        );
+        let mut scheduler = Scheduler::new(&schedules, &ids, Duration::from_secs(60)).unwrap();
+        let now = Instant::now();
+        let deadline = scheduler.next_deadline().unwrap();
+        assert!(deadline <= now + CRON_RECHECK + Duration::from_secs(1));
+        let next = scheduler.profiles[1].next_cron.unwrap();
+        assert!(scheduler
+            .take_due_at(now, next - TimeDelta::minutes(1))
+            .is_empty());
+        assert_eq!(scheduler.take_due_at(now, next), vec!["b"]);
+        assert_eq!(
+            scheduler.profiles[1].next_cron,
+            Some(next + TimeDelta::hours(6))
+        );
+        schedules.remove("compliance");
+
+        schedules.insert("empty".to_string(), ScheduleConfig::default());
+        assert!(Scheduler::new(&schedules, &ids, Duration::from_secs(60)).is_err());
+
+        schedules.remove("empty");
+        schedules.insert(
+            "unknown".to_string(),
+            ScheduleConfig {
+                policy_ids: Some(vec!["c".to_string()]),
+                on_resume: Some(true),
+                ..Default::default()
+            },
+        );
+        assert!(Scheduler::new(&schedules, &ids, Duration::from_secs(60)).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// JSON Schema validation of TAS responses.
+//
+// The schemas in schemas/ describe the responses of the version, get_nonce
+// and get_secret endpoints, and the secret payload within the latter, and
+// are embedded in the binary. Responses are validated before use, so that a
+// server of a mismatched version fails with the field at fault:
+//
+//   Error: 'iv' field not found in response
+//   Error: 'version' field is number, expected string
+//
+// rather than a generic deserialization error further down. Only the
+// keywords the schemas use are supported: `type` (one or a list), `required`,
+// `properties` and `items`. Members not described are accepted, so servers
+// may add fields.
+
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// An embedded schema, parsed on first use.
+pub struct Schema {
+    source: &'static str,
+    parsed: OnceLock<Value>,
+}
+
+/// Response of GET /version.
+pub static VERSION: Schema = Schema::new(include_str!("../schemas/version.schema.json"));
+
+/// Response of GET /kb/v0/get_nonce.
+pub static GET_NONCE: Schema = Schema::new(include_str!("../schemas/get_nonce.schema.json"));
+
+/// Response of POST /kb/v0/get_secret.
+pub static GET_SECRET: Schema = Schema::new(include_str!("../schemas/get_secret.schema.json"));
+
+/// The secret payload of a get_secret response, after any field mapping.
+pub static SECRETS_PAYLOAD: Schema =
+    Schema::new(include_str!("../schemas/secrets_payload.schema.json"));
+
+impl Schema {
+    const fn new(source: &'static str) -> Self {
+        Schema {
+            source,
+            parsed: OnceLock::new(),
+        }
+    }
+
+    /// Check `response` against the schema.
+    pub fn validate(&self, response: &Value) -> Result<(), String> {
+        let schema = self
+            .parsed
+            .get_or_init(|| serde_json::from_str(self.source).expect("embedded schema is valid"));
+        check(schema, response, "")
+    }
+}
+
+/// JSON Schema name of the type of `value`.
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "number" => value.is_number(),
+        name => type_name(value) == name,
+    }
+}
+
+/// How `path` is named in diagnostics.
+fn describe(path: &str) -> String {
+    if path.is_empty() {
+        "response".to_string()
+    } else {
+        format!("'{}' field", path)
+    }
+}
+
+fn member(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    let types: Vec<&str> = match schema.get("type") {
+        Some(Value::String(name)) => vec![name.as_str()],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|name| has_type(value, name)) {
+        return Err(format!(
+            "Error: {} is {}, expected {}",
+            describe(path),
+            type_name(value),
+            types.join(" or ")
+        ));
+    }
+
+    match value {
+        Value::Object(members) => {
+            let required = schema.get("required").and_then(Value::as_array);
+            for name in required.into_iter().flatten().filter_map(Value::as_str) {
+                if !members.contains_key(name) {
+                    return Err(format!(
+                        "Error: {} not found in response",
+                        describe(&member(path, name))
+                    ));
+                }
+            }
+            if let Some(Value::Object(properties)) = schema.get("properties") {
+                for (name, property) in properties {
+                    if let Some(value) = members.get(name) {
+                        check(property, value, &member(path, name))?;
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{}[{}]", path, index))?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_embedded_schemas_parse() {
+        for schema in [&VERSION, &GET_NONCE, &GET_SECRET, &SECRETS_PAYLOAD] {
+            assert!(serde_json::from_str::<Value>(schema.source).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_validate_diagnostics() {
+        assert!(VERSION.validate(&json!({"version": "1.2.3"})).is_ok());
+        assert_eq!(
+            VERSION.validate(&json!({"version": 2})).unwrap_err(),
+            "Error: 'version' field is integer, expected string"
+        );
+        assert_eq!(
+            GET_NONCE.validate(&json!(["nonce"])).unwrap_err(),
+            "Error: response is array, expected object"
+        );
+
+        let payload = json!({"wrapped_key": "a2V5", "blob": "", "iv": "", "tag": ""});
+        assert!(GET_SECRET.validate(&json!({"secret_key": payload})).is_ok());
+        assert!(SECRETS_PAYLOAD.validate(&payload).is_ok());
+        // Recorded responses carry the payload as a string
+        assert!(GET_SECRET.validate(&json!({"secret_key": "s"})).is_ok());
+        assert_eq!(
+            SECRETS_PAYLOAD
+                .validate(&json!({"wrapped_key": "a2V5", "blob": "", "tag": ""}))
+                .unwrap_err(),
+            "Error: 'iv' field not found in response"
+        );
+        assert_eq!(
+            GET_SECRET
+                .validate(&json!({"secret_key": "s", "attestation-result": {}}))
+                .unwrap_err(),
+            "Error: 'attestation-result' field is object, expected string or null"
+        );
+    }
+
+    #[test]
+    fn test_validate_items() {
+        let schema = json!({"type": "array", "items": {"type": "number"}});
+        assert!(check(&schema, &json!([1, 2.5]), "").is_ok());
+        assert_eq!(
+            check(&schema, &json!([1, "2"]), "").unwrap_err(),
+            "Error: '[1]' field is string, expected number"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+// TEE Attestation Service Agent — scrubbing key material on panic
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// A panic must not leave key material behind or print it. Buffers holding
+// unwrapped AES keys and decrypted secrets are kept in `Sensitive`, which
+// registers them here. The panic hook installed at startup
+//
+//   - in one-shot modes, zeroizes every registered buffer that is still
+//     alive,
+//   - prints only the source location of the panic, never a backtrace, and
+//     the panic message only in debug mode (`-d`), since messages from
+//     `unwrap` and `expect` can embed the data being processed,
+//   - flushes the audit log and, in one-shot modes, exits with status 70
+//     (EX_SOFTWARE), so a crash can be told apart from an attestation
+//     failure.
+//
+// In one-shot modes the process exits from the hook, so nothing unwinds past
+// live key material. The daemon instead lets the panic unwind, so that a
+// panicking connection or re-attestation task fails on its own and the
+// daemon keeps serving. It leaves the registry alone, since other tasks'
+// buffers are still in use, and relies on the unwind dropping, and so
+// zeroizing, the panicking task's `Sensitive` buffers. A buffer that the
+// panicking code was itself reading through `Sensitive::lock` cannot be
+// reached by the hook; such reads are kept to single statements.
+//
+// No unsafe code.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError, Weak};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Exit status after a panic (EX_SOFTWARE).
+pub const EXIT_PANIC: i32 = 70;
+
+type Slot = Mutex<Zeroizing<Vec<u8>>>;
+
+/// Buffers to zeroize on panic.
+struct Registry(Mutex<Vec<Weak<Slot>>>);
+
+static REGISTRY: Registry = Registry::new();
+
+impl Registry {
+    const fn new() -> Self {
+        Registry(Mutex::new(Vec::new()))
+    }
+
+    fn register(&self, bytes: Vec<u8>) -> Sensitive {
+        let slot = Arc::new(Mutex::new(Zeroizing::new(bytes)));
+        let mut slots = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        // Forget buffers that have been dropped, and zeroized, since
+        slots.retain(|slot| slot.strong_count() > 0);
+        slots.push(Arc::downgrade(&slot));
+        Sensitive(slot)
+    }
+
+    /// Zeroize all live buffers; returns how many were.
+    fn scrub(&self) -> usize {
+        let slots = match self.0.try_lock() {
+            Ok(slots) => slots,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return 0,
+        };
+        let mut scrubbed = 0;
+        for slot in slots.iter().filter_map(Weak::upgrade) {
+            let mut bytes = match slot.try_lock() {
+                Ok(bytes) => bytes,
+                Err(TryLockError::Poisoned(e)) => e.into_inner(),
+                Err(TryLockError::WouldBlock) => continue,
+            };
+            bytes.zeroize();
+            scrubbed += 1;
+        }
+        scrubbed
+    }
+}
+
+/// Key material that the panic hook zeroizes. It is also zeroized when
+/// dropped.
+pub struct Sensitive(Arc<Slot>);
+
+impl Sensitive {
+    /// Take ownership of `bytes` without copying them.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        REGISTRY.register(bytes)
+    }
+
+    /// Access the bytes; hold the guard only as long as needed.
+    pub fn lock(&self) -> MutexGuard<'_, Zeroizing<Vec<u8>>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Replace the default panic hook, which prints the message and possibly a
+/// backtrace. With `exit`, the process zeroizes all key material and exits
+/// from the hook instead of letting the panic unwind.
+pub fn install(exit: bool) {
+    std::panic::set_hook(Box::new(move |info| {
+        // Other tasks of a resident process may still be using theirs
+        if exit {
+            REGISTRY.scrub();
+        }
+        match info.location() {
+            Some(location) => eprintln!(
+                "tas_agent: internal error at {}:{}",
+                location.file(),
+                location.line()
+            ),
+            None => eprintln!("tas_agent: internal error"),
+        }
+        if log::log_enabled!(log::Level::Debug) {
+            let payload = info.payload();
+            let message = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str));
+            if let Some(message) = message {
+                eprintln!("tas_agent: panic: {}", message);
+            }
+        }
+        crate::audit::record("panic", &[]);
+        crate::audit::flush();
+        if exit {
+            std::process::exit(EXIT_PANIC);
+        }
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrub_zeroizes_live_buffers() {
+        let registry = Registry::new();
+        let key = registry.register(vec![0xaa; 32]);
+        let dropped = registry.register(vec![0xbb; 16]);
+        drop(dropped);
+        let busy = registry.register(vec![0xcc; 8]);
+        let guard = busy.lock();
+
+        assert_eq!(registry.scrub(), 1);
+        assert!(key.lock().iter().all(|&b| b == 0));
+        assert_eq!(**guard, vec![0xcc; 8]);
+        drop(guard);
+
+        // Dropped buffers are forgotten at the next registration
+        let _other = registry.register(vec![0xdd; 4]);
+        assert_eq!(registry.0.lock().unwrap().len(), 3);
+    }
+}
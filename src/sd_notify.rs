@@ -0,0 +1,111 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Minimal sd_notify(3) client for daemon mode.
+//
+// Sends state strings such as READY=1 and WATCHDOG=1 as datagrams to the
+// socket named by $NOTIFY_SOCKET, without linking libsystemd. Both filesystem
+// and abstract-namespace ('@'-prefixed) socket addresses are supported. When
+// the agent is not started by systemd, notifications are silently skipped.
+//
+// No unsafe code.
+
+use anyhow::{Context, Result};
+use std::ffi::OsStr;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+/// Send `state` to the socket at `socket`. Returns Ok(false) when `socket`
+/// is unset or empty.
+fn notify_to(socket: Option<&OsStr>, state: &str) -> Result<bool> {
+    let Some(socket) = socket.filter(|s| !s.is_empty()) else {
+        return Ok(false);
+    };
+
+    let addr = match socket.as_bytes().strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name),
+        None => SocketAddr::from_pathname(socket),
+    }
+    .with_context(|| format!("invalid NOTIFY_SOCKET {:?}", socket))?;
+
+    let sock = UnixDatagram::unbound().context("failed to create notify socket")?;
+    sock.send_to_addr(state.as_bytes(), &addr)
+        .with_context(|| format!("failed to notify {:?}", socket))?;
+    Ok(true)
+}
+
+/// Send `state` to the service manager. Returns Ok(false) when not running
+/// under systemd.
+pub fn notify(state: &str) -> Result<bool> {
+    notify_to(std::env::var_os("NOTIFY_SOCKET").as_deref(), state)
+}
+
+/// Parse the watchdog timeout from WATCHDOG_USEC / WATCHDOG_PID values.
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok()? != own_pid {
+            return None;
+        }
+    }
+    let usec = usec?.parse::<u64>().ok().filter(|&u| u > 0)?;
+    Some(Duration::from_micros(usec))
+}
+
+/// How often to send WATCHDOG=1: half the configured watchdog timeout, or
+/// None if the service has no watchdog enabled.
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec = std::env::var("WATCHDOG_USEC").ok();
+    let pid = std::env::var("WATCHDOG_PID").ok();
+    parse_watchdog(usec.as_deref(), pid.as_deref(), std::process::id()).map(|d| d / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_without_socket() {
+        assert!(!notify_to(None, "READY=1").unwrap());
+        assert!(!notify_to(Some(OsStr::new("")), "READY=1").unwrap());
+    }
+
+    #[test]
+    fn test_notify_to_path_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let receiver = UnixDatagram::bind(&path).unwrap();
+
+        assert!(notify_to(Some(path.as_os_str()), "READY=1").unwrap());
+        let mut buf = [0u8; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+    }
+
+    #[test]
+    fn test_notify_to_missing_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing");
+        assert!(notify_to(Some(path.as_os_str()), "READY=1").is_err());
+    }
+
+    #[test]
+    fn test_parse_watchdog() {
+        assert_eq!(
+            parse_watchdog(Some("30000000"), None, 1),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog(Some("30000000"), Some("7"), 7),
+            Some(Duration::from_secs(30))
+        );
+        // Watchdog addressed to another process
+        assert_eq!(parse_watchdog(Some("30000000"), Some("8"), 7), None);
+        assert_eq!(parse_watchdog(None, None, 7), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 7), None);
+        assert_eq!(parse_watchdog(Some("abc"), None, 7), None);
+    }
+}
@@ -0,0 +1,167 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// PCR-sealed cache of released secrets.
+//
+// With a `[sealed_cache]` section, each secret released by TAS is kept under
+// `dir`, encrypted (AES Key Wrap with Padding, RFC 5649) with a random key
+// that is sealed to the TPM and the current values of the PCR selection
+// `pcrs`. On later runs the agent first tries to unseal the cached secret:
+// as long as the machine booted with identical measurements the TPM releases
+// the key and no remote attestation is needed. Once any selected PCR
+// changes, unsealing fails, the agent attests to TAS as usual and re-seals
+// the fresh secret to the new PCR values.
+//
+// The cache trades a remote check on every boot for local trust in the
+// measured boot chain; the PCR selection must cover everything that has to
+// be unchanged for the secret to be released. There is no default: firmware
+// and Secure Boot policy alone (PCRs 0 and 7) would let any OS signed for the
+// same platform unseal it, so the operator names the boot loader, kernel,
+// initrd and command line PCRs their boot chain measures into. It is used by the one-shot
+// modes (stdout, --out, askpass, passfifo), not by the daemon.
+
+use crate::audit;
+use crate::crypto::{unwrap_secret_with_aes_key_wrap, wrap_secret_with_aes_key_wrap};
+use crate::escrow::write_private;
+use crate::rng::rng;
+use crate::tpm::{self, SealedObject};
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+/// `[sealed_cache]` section of the config file.
+#[derive(Deserialize, Clone)]
+pub struct SealedCacheConfig {
+    /// Directory the sealed secrets are kept in
+    pub dir: PathBuf,
+    /// PCR selection the secrets are sealed to, e.g. "sha256:0,4,7,8,9,11"
+    pub pcrs: String,
+}
+
+/// Path of the cache entry for `policy_id`, without extension.
+fn entry(dir: &Path, policy_id: &str) -> PathBuf {
+    dir.join(hex::encode(Sha256::digest(policy_id.as_bytes())))
+}
+
+fn store_inner(config: &SealedCacheConfig, policy_id: &str, secret: &[u8]) -> Result<()> {
+    fs::create_dir_all(&config.dir)
+        .with_context(|| format!("unable to create {:?}", config.dir))?;
+    fs::set_permissions(&config.dir, fs::Permissions::from_mode(0o700))
+        .with_context(|| format!("unable to set permissions on {:?}", config.dir))?;
+
+    let mut key = [0u8; 32];
+    rng().fill_bytes(&mut key);
+    let wrapped = wrap_secret_with_aes_key_wrap(&key, secret)
+        .map_err(|e| anyhow!("unable to encrypt secret: {}", e));
+    let sealed = tpm::seal_to_pcrs(&key, &config.pcrs).context("unable to seal cache key");
+    key.zeroize();
+    let (wrapped, sealed) = (wrapped?, sealed?);
+
+    let entry = entry(&config.dir, policy_id);
+    write_private(&entry.with_extension("pub"), &sealed.public)?;
+    write_private(&entry.with_extension("priv"), &sealed.private)?;
+    write_private(&entry.with_extension("blob"), &wrapped)?;
+    Ok(())
+}
+
+/// Seal the released `secret` for `policy_id` into the cache.
+pub fn store(config: &SealedCacheConfig, policy_id: &str, secret: &[u8]) -> Result<()> {
+    let result = store_inner(config, policy_id, secret);
+    let dir = config.dir.display().to_string();
+    match &result {
+        Ok(()) => {
+            debug!("Sealed secret to {} in {:?}", &config.pcrs, config.dir);
+            audit::record(
+                "sealed-cache-store",
+                &[("dir", &dir), ("pcrs", &config.pcrs), ("result", "ok")],
+            );
+        }
+        Err(e) => {
+            let error = format!("{:#}", e);
+            audit::record(
+                "sealed-cache-store",
+                &[("dir", &dir), ("result", "failed"), ("error", &error)],
+            );
+        }
+    }
+    result
+}
+
+fn load_inner(config: &SealedCacheConfig, policy_id: &str) -> Result<Vec<u8>> {
+    let entry = entry(&config.dir, policy_id);
+    let blob_path = entry.with_extension("blob");
+    let wrapped =
+        fs::read(&blob_path).with_context(|| format!("no sealed secret at {:?}", blob_path))?;
+    let sealed = SealedObject {
+        public: fs::read(entry.with_extension("pub")).context("unable to read sealed key")?,
+        private: fs::read(entry.with_extension("priv")).context("unable to read sealed key")?,
+    };
+
+    let mut key = tpm::unseal_with_pcrs(&sealed, &config.pcrs)
+        .context("unable to unseal cache key (PCRs changed?)")?;
+    let secret = unwrap_secret_with_aes_key_wrap(&key, &wrapped)
+        .map_err(|e| anyhow!("unable to decrypt sealed secret: {}", e));
+    key.zeroize();
+    secret
+}
+
+/// Unseal the cached secret for `policy_id`. Fails if there is none or the
+/// PCRs no longer match.
+pub fn load(config: &SealedCacheConfig, policy_id: &str) -> Result<Vec<u8>> {
+    let result = load_inner(config, policy_id);
+    let dir = config.dir.display().to_string();
+    match &result {
+        Ok(_) => audit::record(
+            "sealed-cache-unseal",
+            &[("dir", &dir), ("pcrs", &config.pcrs), ("result", "ok")],
+        ),
+        Err(e) => {
+            let error = format!("{:#}", e);
+            audit::record(
+                "sealed-cache-unseal",
+                &[("dir", &dir), ("result", "failed"), ("error", &error)],
+            );
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_is_per_policy() {
+        let dir = Path::new("/var/lib/tas_agent/sealed");
+        let luks = entry(dir, "luks");
+        assert_eq!(luks, entry(dir, "luks"));
+        assert_ne!(luks, entry(dir, "kbs:///default/key/luks"));
+        assert_eq!(luks.parent(), Some(dir));
+    }
+
+    #[test]
+    fn test_load_missing_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SealedCacheConfig {
+            dir: dir.path().to_path_buf(),
+            pcrs: "sha256:0,4,7,8,9,11".to_string(),
+        };
+        let err = load(&config, "luks").unwrap_err();
+        assert!(err.to_string().contains("no sealed secret"), "{err}");
+    }
+
+    #[test]
+    fn test_pcrs_required() {
+        assert!(
+            toml::from_str::<SealedCacheConfig>(r#"dir = "/var/lib/tas_agent/sealed""#).is_err()
+        );
+    }
+}
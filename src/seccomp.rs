@@ -0,0 +1,287 @@
+// TEE Attestation Service Agent — seccomp filter for the one-shot path
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With `seccomp = true` in a build with the `seccomp` feature, the normal
+// (stdout, --out, --secret-fd) mode installs a syscall allowlist on every
+// thread before contacting TAS. Everything the agent does from there on —
+// file I/O (config, API key, root certificate, configfs-tsm, the output
+// file), IPv4/IPv6 sockets to TAS and DNS, memory management, timers and the
+// tokio runtime's threads — stays available; anything else, notably execve,
+// ptrace and mount, fails with EPERM. A bug in parsing a TAS response can
+// then no longer trivially spawn a shell or reach further into the system.
+//
+// A few allowed syscalls are only allowed with restricted arguments:
+//
+//   - ioctl:  FIONBIO and FIOCLEX (the standard library sets non-blocking
+//             and close-on-exec mode on sockets), TCGETS and TIOCGWINSZ
+//             (terminal detection for log output), and SNP_GET_DERIVED_KEY
+//             with `[snp_derived_key]`. TIOCSTI and device ioctls stay out.
+//   - prctl:  PR_SET_NAME and PR_GET_NAME, for naming runtime threads.
+//   - clone:  only with CLONE_THREAD, i.e. new threads, never processes.
+//   - clone3: its flags live in memory the filter cannot inspect, so it
+//             fails with ENOSYS, on which the C library falls back to clone.
+//
+// Integrations that run external programs (exec hooks, opa, spire-agent,
+// tpm2-tools for the escrow, sealed cache, key pool and TPM signing keys)
+// cannot work under the filter, so enabling it together with any of them is
+// a configuration error. The askpass, passfifo and daemon modes are not
+// filtered.
+//
+// No unsafe code.
+
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use seccompiler::{
+    apply_filter_all_threads, BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp,
+    SeccompCondition, SeccompFilter, SeccompRule, TargetArch,
+};
+use std::collections::BTreeMap;
+
+/// Syscalls allowed with any arguments.
+const ALLOWED: &[i64] = &[
+    // File I/O
+    libc::SYS_read,
+    libc::SYS_write,
+    libc::SYS_readv,
+    libc::SYS_writev,
+    libc::SYS_pread64,
+    libc::SYS_pwrite64,
+    libc::SYS_openat,
+    libc::SYS_close,
+    libc::SYS_lseek,
+    libc::SYS_fstat,
+    libc::SYS_newfstatat,
+    libc::SYS_statx,
+    libc::SYS_statfs,
+    libc::SYS_fstatfs,
+    libc::SYS_getdents64,
+    libc::SYS_fcntl,
+    libc::SYS_flock,
+    libc::SYS_mkdirat,
+    libc::SYS_unlinkat,
+    libc::SYS_renameat2,
+    libc::SYS_readlinkat,
+    libc::SYS_faccessat,
+    libc::SYS_faccessat2,
+    libc::SYS_fchmod,
+    libc::SYS_fchmodat,
+    libc::SYS_ftruncate,
+    libc::SYS_fsync,
+    libc::SYS_fdatasync,
+    libc::SYS_getcwd,
+    libc::SYS_umask,
+    libc::SYS_pipe2,
+    libc::SYS_dup,
+    libc::SYS_dup3,
+    // Memory
+    libc::SYS_brk,
+    libc::SYS_mmap,
+    libc::SYS_munmap,
+    libc::SYS_mremap,
+    libc::SYS_mprotect,
+    libc::SYS_madvise,
+    // Threads, signals and the tokio runtime
+    libc::SYS_futex,
+    libc::SYS_set_robust_list,
+    libc::SYS_set_tid_address,
+    libc::SYS_rseq,
+    libc::SYS_sched_yield,
+    libc::SYS_sched_getaffinity,
+    libc::SYS_getpid,
+    libc::SYS_gettid,
+    libc::SYS_tgkill,
+    libc::SYS_exit,
+    libc::SYS_exit_group,
+    libc::SYS_rt_sigaction,
+    libc::SYS_rt_sigprocmask,
+    libc::SYS_rt_sigreturn,
+    libc::SYS_sigaltstack,
+    libc::SYS_epoll_create1,
+    libc::SYS_epoll_ctl,
+    libc::SYS_epoll_pwait,
+    libc::SYS_eventfd2,
+    libc::SYS_ppoll,
+    // Time
+    libc::SYS_clock_gettime,
+    libc::SYS_clock_nanosleep,
+    libc::SYS_nanosleep,
+    libc::SYS_gettimeofday,
+    // Sockets to TAS and the resolver
+    libc::SYS_connect,
+    libc::SYS_bind,
+    libc::SYS_getsockopt,
+    libc::SYS_setsockopt,
+    libc::SYS_getsockname,
+    libc::SYS_getpeername,
+    libc::SYS_sendto,
+    libc::SYS_recvfrom,
+    libc::SYS_sendmsg,
+    libc::SYS_recvmsg,
+    libc::SYS_sendmmsg,
+    libc::SYS_shutdown,
+    // The --bind-interface connector, listening since before the sandbox
+    libc::SYS_accept4,
+    // TLS libraries and key generation
+    libc::SYS_getrandom,
+    libc::SYS_uname,
+    libc::SYS_getuid,
+    libc::SYS_geteuid,
+    libc::SYS_getgid,
+    libc::SYS_getegid,
+    libc::SYS_prlimit64,
+];
+
+/// Legacy syscalls still used by the C library on x86_64.
+#[cfg(target_arch = "x86_64")]
+const ALLOWED_X86_64: &[i64] = &[
+    libc::SYS_open,
+    libc::SYS_stat,
+    libc::SYS_lstat,
+    libc::SYS_access,
+    libc::SYS_readlink,
+    libc::SYS_rename,
+    libc::SYS_unlink,
+    libc::SYS_mkdir,
+    libc::SYS_rmdir,
+    libc::SYS_poll,
+    libc::SYS_epoll_wait,
+    libc::SYS_pipe,
+    libc::SYS_dup2,
+    libc::SYS_arch_prctl,
+];
+
+#[cfg(not(target_arch = "x86_64"))]
+const ALLOWED_X86_64: &[i64] = &[];
+
+/// Address families `socket` may create: local sockets for the resolver,
+/// IPv4 and IPv6.
+const SOCKET_FAMILIES: &[libc::c_int] = &[libc::AF_UNIX, libc::AF_INET, libc::AF_INET6];
+
+/// `ioctl` requests the agent issues.
+#[allow(clippy::unnecessary_cast)] // c_int rather than c_ulong on musl
+const IOCTL_REQUESTS: &[u64] = &[
+    libc::FIONBIO as u64,
+    libc::FIOCLEX as u64,
+    libc::TCGETS as u64,
+    libc::TIOCGWINSZ as u64,
+    #[cfg(feature = "snp-derived-key")]
+    SNP_GET_DERIVED_KEY,
+];
+
+/// `_IOWR('S', 0x1, struct snp_guest_request_ioctl)` from linux/sev-guest.h
+#[cfg(feature = "snp-derived-key")]
+const SNP_GET_DERIVED_KEY: u64 = 0xc020_5301;
+
+/// `prctl` options the agent uses.
+const PRCTL_OPTIONS: &[u64] = &[libc::PR_SET_NAME as u64, libc::PR_GET_NAME as u64];
+
+/// One rule per value: argument `arg` equals `value`.
+fn arg_in(arg: u8, values: &[u64]) -> Result<Vec<SeccompRule>> {
+    values
+        .iter()
+        .map(|&value| {
+            SeccompRule::new(vec![SeccompCondition::new(
+                arg,
+                SeccompCmpArgLen::Dword,
+                SeccompCmpOp::Eq,
+                value,
+            )?])
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("invalid seccomp rule: {}", e))
+}
+
+/// Compile `rules` for the running architecture.
+fn compile(
+    rules: BTreeMap<i64, Vec<SeccompRule>>,
+    mismatch: SeccompAction,
+    matched: SeccompAction,
+) -> Result<BpfProgram> {
+    let arch = TargetArch::try_from(std::env::consts::ARCH)
+        .map_err(|e| anyhow!("seccomp is not supported on this architecture: {}", e))?;
+    let filter = SeccompFilter::new(rules, mismatch, matched, arch)
+        .map_err(|e| anyhow!("invalid seccomp filter: {}", e))?;
+    filter
+        .try_into()
+        .map_err(|e| anyhow!("unable to compile seccomp filter: {}", e))
+}
+
+/// Build the allowlist for the running architecture.
+fn program() -> Result<BpfProgram> {
+    let mut rules: BTreeMap<i64, Vec<SeccompRule>> = ALLOWED
+        .iter()
+        .chain(ALLOWED_X86_64)
+        .map(|&nr| (nr, Vec::new()))
+        .collect();
+
+    let families: Vec<u64> = SOCKET_FAMILIES.iter().map(|&f| f as u64).collect();
+    rules.insert(libc::SYS_socket, arg_in(0, &families)?);
+    rules.insert(libc::SYS_ioctl, arg_in(1, IOCTL_REQUESTS)?);
+    rules.insert(libc::SYS_prctl, arg_in(0, PRCTL_OPTIONS)?);
+    let thread = SeccompCondition::new(
+        0,
+        SeccompCmpArgLen::Dword,
+        SeccompCmpOp::MaskedEq(libc::CLONE_THREAD as u64),
+        libc::CLONE_THREAD as u64,
+    )
+    .and_then(|condition| SeccompRule::new(vec![condition]))
+    .map_err(|e| anyhow!("invalid seccomp rule: {}", e))?;
+    rules.insert(libc::SYS_clone, vec![thread]);
+    // Answered with ENOSYS by clone3_program()
+    rules.insert(libc::SYS_clone3, Vec::new());
+
+    compile(
+        rules,
+        SeccompAction::Errno(libc::EPERM as u32),
+        SeccompAction::Allow,
+    )
+}
+
+/// Make clone3 fail with ENOSYS, so that the C library falls back to clone
+/// when it creates threads. Installed before the allowlist, which lets
+/// clone3 through: an errno from any filter takes precedence over allow.
+fn clone3_program() -> Result<BpfProgram> {
+    compile(
+        BTreeMap::from([(libc::SYS_clone3, Vec::new())]),
+        SeccompAction::Allow,
+        SeccompAction::Errno(libc::ENOSYS as u32),
+    )
+}
+
+/// Install the allowlist on all threads of the process. `conflicts` names
+/// the configured integrations that run external programs; if there are
+/// any, nothing is installed and an error is returned.
+pub fn install(conflicts: &[&str]) -> Result<()> {
+    if !conflicts.is_empty() {
+        return Err(anyhow!(
+            "seccomp = true cannot be combined with {}, which run external programs",
+            conflicts.join(", ")
+        ));
+    }
+    let program = program()?;
+    let clone3 = clone3_program()?;
+    apply_filter_all_threads(&clone3).context("unable to install seccomp filter")?;
+    apply_filter_all_threads(&program).context("unable to install seccomp filter")?;
+    debug!("Installed seccomp filter ({} instructions)", program.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program_compiles() {
+        let program = program().unwrap();
+        assert!(!program.is_empty());
+        assert!(!clone3_program().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_conflicts_rejected() {
+        let err = install(&["[hooks]", "escrow_dir"]).unwrap_err();
+        assert!(err.to_string().contains("[hooks], escrow_dir"));
+    }
+}
@@ -0,0 +1,224 @@
+// TEE Attestation Service Agent — mutual attestation of the TAS server
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With a `[server_attestation]` section, the agent also requires the TAS
+// server to prove that it runs in an attested TEE: the get_secret response
+// must carry a `server-attestation` JWT, issued by a verifier the operator
+// trusts for the key broker's own evidence, and the secret is only used if
+//
+//   - the JWT signature verifies against `verifier_key` (ES256, RS256 or
+//     PS256; PEM or DER SubjectPublicKeyInfo),
+//   - `exp`, `nbf` and `iat`, if present, hold within the clock skew
+//     tolerance (see clock.rs),
+//   - `eat_nonce` is the nonce of this attestation, and
+//     `wrapping_key_sha256` the hex SHA-256 of the DER SubjectPublicKeyInfo
+//     of this attestation's wrapping key. TAS issues the nonce, so a rogue
+//     broker could hand out the nonce of a token it captured earlier; the
+//     wrapping key is generated by the agent for each attestation, so a
+//     token bound to it was produced for this exchange and cannot be
+//     replayed by an impostor,
+//   - every JSON pointer in `reference_values` resolves to one of its listed
+//     values, e.g.
+//
+//       [server_attestation.reference_values]
+//       "/submods/tas/measurement" = ["8a3f..."]
+//       "/tee_type" = ["amd-sev-snp"]
+//
+// This protects against rogue key-broker instances that hold a valid TLS
+// certificate and API key but do not run the measured TAS image. The check
+// happens before the secret payload is unwrapped; a secret from a server
+// that fails it is discarded.
+//
+// No unsafe code.
+
+use crate::{clock, ear};
+use anyhow::{anyhow, Result};
+use log::debug;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// `[server_attestation]` section of the config file.
+#[derive(Deserialize, Clone)]
+pub struct ServerAttestationConfig {
+    /// Public key of the verifier that attests the TAS server
+    pub verifier_key: PathBuf,
+    /// Allowed values of the server's claims, by JSON pointer
+    pub reference_values: BTreeMap<String, Vec<Value>>,
+}
+
+/// Check the claims of a verified server attestation against `config`.
+fn check_claims(
+    config: &ServerAttestationConfig,
+    claims: &Value,
+    nonce: &str,
+    wrapping_key_der: &[u8],
+    now: i64,
+) -> Result<()> {
+    clock::check_times(claims, now)?;
+    match claims.get("eat_nonce").and_then(Value::as_str) {
+        Some(eat_nonce) if eat_nonce == nonce => {}
+        Some(_) => return Err(anyhow!("eat_nonce does not match the attestation nonce")),
+        None => return Err(anyhow!("no eat_nonce claim")),
+    }
+    let key_digest = hex::encode(Sha256::digest(wrapping_key_der));
+    match claims.get("wrapping_key_sha256").and_then(Value::as_str) {
+        Some(digest) if digest.eq_ignore_ascii_case(&key_digest) => {}
+        Some(_) => {
+            return Err(anyhow!(
+                "wrapping_key_sha256 does not match the attestation's wrapping key"
+            ))
+        }
+        None => return Err(anyhow!("no wrapping_key_sha256 claim")),
+    }
+    if config.reference_values.is_empty() {
+        return Err(anyhow!("no reference values configured"));
+    }
+    for (pointer, allowed) in &config.reference_values {
+        let value = claims
+            .pointer(pointer)
+            .ok_or_else(|| anyhow!("no claim at {:?}", pointer))?;
+        if !allowed.contains(value) {
+            return Err(anyhow!(
+                "claim {:?} is {}, not a reference value",
+                pointer,
+                value
+            ));
+        }
+        debug!("Server claim {} matches", pointer);
+    }
+    Ok(())
+}
+
+/// Verify the server attestation `token` returned for the attestation with
+/// `nonce` and the wrapping key `wrapping_key_der` (DER SubjectPublicKeyInfo).
+pub fn verify(
+    config: &ServerAttestationConfig,
+    token: &str,
+    nonce: &str,
+    wrapping_key_der: &[u8],
+) -> Result<()> {
+    check_claims(
+        config,
+        &ear::verify_jwt(&config.verifier_key, token)?,
+        nonce.trim_matches('"'),
+        wrapping_key_der,
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::generate_wrapping_key;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use serde_json::json;
+
+    fn config(verifier_key: PathBuf) -> ServerAttestationConfig {
+        let mut reference_values = BTreeMap::new();
+        reference_values.insert(
+            "/submods/tas/measurement".to_string(),
+            vec![json!("aa"), json!("bb")],
+        );
+        ServerAttestationConfig {
+            verifier_key,
+            reference_values,
+        }
+    }
+
+    #[test]
+    fn test_check_claims() {
+        let cfg = config(PathBuf::new());
+        let key_digest = hex::encode(Sha256::digest(b"spki"));
+        let claims = |measurement: &str| {
+            json!({
+                "eat_nonce": "n0nce",
+                "wrapping_key_sha256": key_digest,
+                "exp": 2000,
+                "submods": {"tas": {"measurement": measurement}}
+            })
+        };
+        assert!(check_claims(&cfg, &claims("bb"), "n0nce", b"spki", 1000).is_ok());
+        assert!(check_claims(&cfg, &claims("cc"), "n0nce", b"spki", 1000).is_err());
+        assert!(check_claims(&cfg, &claims("aa"), "other", b"spki", 1000).is_err());
+        assert!(check_claims(&cfg, &claims("aa"), "n0nce", b"spki", 3000).is_err());
+        assert!(check_claims(
+            &cfg,
+            &json!({"eat_nonce": "n0nce", "wrapping_key_sha256": key_digest}),
+            "n0nce",
+            b"spki",
+            1000
+        )
+        .is_err());
+        assert!(check_claims(
+            &cfg,
+            &json!({"wrapping_key_sha256": key_digest, "submods": {"tas": {"measurement": "aa"}}}),
+            "n0nce",
+            b"spki",
+            1000
+        )
+        .is_err());
+
+        let unconfigured = ServerAttestationConfig {
+            verifier_key: PathBuf::new(),
+            reference_values: BTreeMap::new(),
+        };
+        assert!(check_claims(&unconfigured, &claims("aa"), "n0nce", b"spki", 1000).is_err());
+    }
+
+    #[test]
+    fn test_check_claims_requires_wrapping_key_binding() {
+        let cfg = config(PathBuf::new());
+        let claims = |digest: Option<String>| {
+            let mut claims = json!({
+                "eat_nonce": "n0nce",
+                "submods": {"tas": {"measurement": "aa"}}
+            });
+            if let Some(digest) = digest {
+                claims["wrapping_key_sha256"] = json!(digest);
+            }
+            claims
+        };
+
+        // A token replayed with the nonce it was issued for, but from an
+        // earlier exchange with another wrapping key
+        let old_key = hex::encode(Sha256::digest(b"earlier spki"));
+        assert!(check_claims(&cfg, &claims(Some(old_key)), "n0nce", b"spki", 1000).is_err());
+        assert!(check_claims(&cfg, &claims(None), "n0nce", b"spki", 1000).is_err());
+
+        let upper = hex::encode_upper(Sha256::digest(b"spki"));
+        assert!(check_claims(&cfg, &claims(Some(upper)), "n0nce", b"spki", 1000).is_ok());
+    }
+
+    #[test]
+    fn test_verify_signed_token() {
+        let key = generate_wrapping_key().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join("verifier.der");
+        std::fs::write(&key_path, key.public_key_to_spki_der().unwrap()).unwrap();
+
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(
+            json!({
+                "eat_nonce": "n0nce",
+                "wrapping_key_sha256": hex::encode(Sha256::digest(b"spki")),
+                "submods": {"tas": {"measurement": "aa"}}
+            })
+            .to_string(),
+        );
+        let signing_input = format!("{}.{}", header, payload);
+        let signature = URL_SAFE_NO_PAD.encode(key.sign(signing_input.as_bytes()));
+        let token = format!("{}.{}", signing_input, signature);
+
+        let cfg = config(key_path);
+        assert!(verify(&cfg, &token, "\"n0nce\"", b"spki").is_ok());
+        assert!(verify(&cfg, &token, "other", b"spki").is_err());
+        assert!(verify(&cfg, &token, "n0nce", b"other spki").is_err());
+        let forged = format!("{}.{}.{}", header, payload, URL_SAFE_NO_PAD.encode("sig"));
+        assert!(verify(&cfg, &forged, "n0nce", b"spki").is_err());
+    }
+}
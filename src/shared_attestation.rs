@@ -0,0 +1,301 @@
+// TEE Attestation Service Agent — attestation shared between invocations
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// One-shot invocations started close together, e.g. one crypttab entry per
+// volume, each attest from scratch: a nonce, fresh evidence and possibly a
+// verifier round trip per volume. With a `[shared_attestation]` section they
+// share one attestation the way the daemon's releases do:
+//
+//   [shared_attestation]
+//   path = "/run/tas_agent/attestation.json"   # the default
+//   ttl_secs = 30                              # the default
+//
+// Each invocation takes an exclusive flock(2) on the `.lock` file next to
+// `path` (attestation.lock) before it attests, so concurrent invocations
+// queue up instead of attesting in parallel. Once TAS has released a secret
+// for the first one, its nonce, evidence, wrapping key pair and verifier
+// token are left in `path`; the next ones, while the entry is younger than
+// `ttl_secs` and for the same server, release their secrets with it. When
+// TAS does not accept the shared attestation, the invocation attests afresh
+// and replaces it. The daemon shares attestations in memory instead.
+//
+// The file holds the private wrapping key, which unwraps every secret
+// released with the attestation, so it is written with mode 0600 and
+// belongs on a tmpfs such as /run; `ttl_secs` should not exceed the time
+// TAS accepts a nonce for.
+//
+// No unsafe code.
+
+use crate::crypto::{RsaKey, NONCE_LOCAL_ENTROPY, NONCE_SHA512};
+use crate::escrow::write_private;
+use crate::tas_api::EvidenceSignature;
+use crate::{Attestation, Session};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use rustix::fs::{flock, FlockOperation};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File, OpenOptions};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Default location of the shared attestation; /run is cleared at every boot.
+const DEFAULT_PATH: &str = "/run/tas_agent/attestation.json";
+
+/// Default lifetime of a shared attestation.
+const DEFAULT_TTL_SECS: u64 = 30;
+
+/// `[shared_attestation]` section of the config file.
+#[derive(Deserialize, Clone)]
+pub struct SharedAttestationConfig {
+    /// File the attestation is kept in (default: /run/tas_agent/attestation.json)
+    pub path: Option<PathBuf>,
+    /// Seconds an attestation is shared for (default: 30)
+    pub ttl_secs: Option<u64>,
+}
+
+impl SharedAttestationConfig {
+    pub fn path(&self) -> &Path {
+        self.path.as_deref().unwrap_or(Path::new(DEFAULT_PATH))
+    }
+
+    fn ttl_secs(&self) -> u64 {
+        self.ttl_secs.unwrap_or(DEFAULT_TTL_SECS)
+    }
+}
+
+/// Contents of the shared attestation file.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    server_uri: String,
+    created_at: String,
+    nonce: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nonce_construction: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    local_entropy: Option<String>,
+    tee_type: String,
+    tee_evidence: String,
+    wrapping_key: String,
+    /// Base64 PKCS#1 DER of the private wrapping key
+    private_key: String,
+    key_binding_enabled: bool,
+    /// Base64 report data bound into the evidence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    report_data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    component_evidence: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attestation_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    evidence_signature: Option<EvidenceSignature>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    platform_metadata: Option<Value>,
+}
+
+impl Entry {
+    /// Whether the entry is for `server_uri` and younger than `ttl_secs` at
+    /// `now`.
+    fn fresh_at(&self, server_uri: &str, ttl_secs: u64, now: DateTime<Utc>) -> bool {
+        if self.server_uri != server_uri {
+            return false;
+        }
+        DateTime::parse_from_rfc3339(&self.created_at)
+            .ok()
+            .and_then(|created_at| (now - created_at.with_timezone(&Utc)).to_std().ok())
+            .is_some_and(|age| age.as_secs() < ttl_secs)
+    }
+
+    fn from_attestation(attestation: &Attestation) -> Result<Entry> {
+        let der = Zeroizing::new(
+            attestation
+                .rsa_wrapping_key
+                .private_key_to_der()
+                .map_err(|e| anyhow!("unable to encode the wrapping key: {}", e))?,
+        );
+        Ok(Entry {
+            server_uri: attestation.session.server_uri.clone(),
+            created_at: Utc::now().to_rfc3339(),
+            nonce: attestation.nonce.clone(),
+            nonce_construction: attestation.nonce_construction.map(str::to_string),
+            local_entropy: attestation.local_entropy.clone(),
+            tee_type: attestation.tee_type.clone(),
+            tee_evidence: attestation.tee_evidence.clone(),
+            wrapping_key: attestation.wrapping_key.clone(),
+            private_key: STANDARD.encode(&*der),
+            key_binding_enabled: attestation.key_binding_enabled,
+            report_data: attestation
+                .report_data
+                .as_ref()
+                .map(|data| STANDARD.encode(data)),
+            component_evidence: attestation.component_evidence.clone(),
+            attestation_token: attestation.attestation_token.clone(),
+            evidence_signature: attestation.evidence_signature.clone(),
+            platform_metadata: attestation.platform_metadata.clone(),
+        })
+    }
+
+    fn into_attestation(self, session: Session) -> Result<Attestation> {
+        let der = Zeroizing::new(
+            STANDARD
+                .decode(&self.private_key)
+                .context("wrapping key is not valid base64")?,
+        );
+        let rsa_wrapping_key = RsaKey::from_private_key_der(&der)
+            .map_err(|e| anyhow!("unable to read the wrapping key: {}", e))?;
+        let nonce_construction = match self.nonce_construction.as_deref() {
+            None => None,
+            Some(NONCE_SHA512) => Some(NONCE_SHA512),
+            Some(NONCE_LOCAL_ENTROPY) => Some(NONCE_LOCAL_ENTROPY),
+            Some(other) => return Err(anyhow!("unknown nonce construction {:?}", other)),
+        };
+        Ok(Attestation {
+            session,
+            nonce: self.nonce,
+            nonce_construction,
+            local_entropy: self.local_entropy,
+            evidence_signature: self.evidence_signature,
+            tee_evidence: self.tee_evidence,
+            tee_type: self.tee_type,
+            rsa_wrapping_key,
+            ec_wrapping_key: None,
+            wrapping_key: self.wrapping_key,
+            key_binding_enabled: self.key_binding_enabled,
+            report_data: self
+                .report_data
+                .map(|data| STANDARD.decode(data))
+                .transpose()
+                .context("report data is not valid base64")?,
+            component_evidence: self.component_evidence,
+            attestation_token: self.attestation_token,
+            platform_metadata: self.platform_metadata,
+        })
+    }
+}
+
+/// Exclusive hold on the shared attestation; released when dropped.
+pub struct SharedAttestation {
+    config: SharedAttestationConfig,
+    _lock: File,
+}
+
+/// Wait for the other invocations to finish with the shared attestation.
+pub async fn lock(config: &SharedAttestationConfig) -> Result<SharedAttestation> {
+    let path = config.path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("unable to create {:?}", dir))?;
+    }
+    let lock_path = path.with_extension("lock");
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(&lock_path)
+        .with_context(|| format!("unable to open {:?}", lock_path))?;
+    let file = tokio::task::spawn_blocking(move || {
+        flock(&file, FlockOperation::LockExclusive).map(|()| file)
+    })
+    .await
+    .context("lock task failed")?
+    .with_context(|| format!("unable to lock {:?}", lock_path))?;
+    debug!("Holding {:?}", lock_path);
+    Ok(SharedAttestation {
+        config: config.clone(),
+        _lock: file,
+    })
+}
+
+impl SharedAttestation {
+    /// The shared attestation, if one is fresh, for use with `session`.
+    pub fn load(&self, session: &Session) -> Option<Attestation> {
+        let path = self.config.path();
+        let json = Zeroizing::new(fs::read(path).ok()?);
+        let entry: Entry = match serde_json::from_slice(&json) {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Ignoring the shared attestation in {:?}: {}", path, e);
+                return None;
+            }
+        };
+        if !entry.fresh_at(&session.server_uri, self.config.ttl_secs(), Utc::now()) {
+            debug!("Shared attestation in {:?} is stale", path);
+            return None;
+        }
+        match entry.into_attestation(session.clone()) {
+            Ok(attestation) => {
+                debug!("Reusing the shared attestation in {:?}", path);
+                Some(attestation)
+            }
+            Err(e) => {
+                warn!("Ignoring the shared attestation in {:?}: {:#}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Share `attestation` with the next invocations.
+    pub fn store(&self, attestation: &Attestation) -> Result<()> {
+        let entry = Entry::from_attestation(attestation)?;
+        let json = Zeroizing::new(
+            serde_json::to_vec(&entry).context("unable to serialize the attestation")?,
+        );
+        write_private(self.config.path(), &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn entry(created_at: DateTime<Utc>) -> Entry {
+        Entry {
+            server_uri: "https://tas:5001".to_string(),
+            created_at: created_at.to_rfc3339(),
+            nonce: "\"abc\"".to_string(),
+            nonce_construction: Some(NONCE_SHA512.to_string()),
+            local_entropy: None,
+            tee_type: "amd-sev-snp".to_string(),
+            tee_evidence: "cmVwb3J0".to_string(),
+            wrapping_key: "a2V5".to_string(),
+            private_key: String::new(),
+            key_binding_enabled: true,
+            report_data: None,
+            component_evidence: None,
+            attestation_token: None,
+            evidence_signature: None,
+            platform_metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_entry_freshness() {
+        let now = Utc::now();
+        let created = entry(now - TimeDelta::seconds(10));
+        assert!(created.fresh_at("https://tas:5001", 30, now));
+        assert!(!created.fresh_at("https://tas:5001", 10, now));
+        assert!(!created.fresh_at("https://other:5001", 30, now));
+        assert!(!entry(now + TimeDelta::seconds(10)).fresh_at("https://tas:5001", 30, now));
+    }
+
+    #[test]
+    fn test_entry_wrapping_key() {
+        let key = crate::crypto::generate_wrapping_key().unwrap();
+        let mut shared = entry(Utc::now());
+        shared.private_key = STANDARD.encode(key.private_key_to_der().unwrap());
+        let json = serde_json::to_vec(&shared).unwrap();
+        let shared: Entry = serde_json::from_slice(&json).unwrap();
+        let der = STANDARD.decode(&shared.private_key).unwrap();
+        let restored = RsaKey::from_private_key_der(&der).unwrap();
+        assert_eq!(
+            restored.public_key_to_base64().unwrap(),
+            key.public_key_to_base64().unwrap()
+        );
+    }
+}
@@ -0,0 +1,74 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Graceful handling of SIGTERM and SIGINT in one-shot mode.
+//
+// Work that may be interrupted is run under `cancel_on_signal`. When a
+// termination signal arrives the in-flight future is dropped at its next
+// await point: pending HTTP requests are cancelled, configfs-tsm report
+// directories are removed by their TempDir guards, and key material held by
+// the future is released (and zeroized where the type does so on drop). The
+// caller then flushes the audit log and exits with the conventional
+// 128 + signal number status, so an interrupted run can be told apart from
+// an attestation failure (exit status 1).
+//
+// No unsafe code.
+
+use log::{info, warn};
+use std::future::Future;
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Exit status after SIGINT (128 + 2).
+pub const EXIT_SIGINT: i32 = 130;
+/// Exit status after SIGTERM (128 + 15).
+pub const EXIT_SIGTERM: i32 = 143;
+
+/// A termination signal that interrupted the agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupted {
+    pub signal: &'static str,
+    pub exit_code: i32,
+}
+
+/// Run `fut` to completion unless SIGTERM or SIGINT arrives first, in which
+/// case `fut` is dropped and the signal is returned.
+///
+/// If the signal handlers cannot be registered, `fut` runs uninterrupted.
+pub async fn cancel_on_signal<F: Future>(fut: F) -> Result<F::Output, Interrupted> {
+    let (mut sigterm, mut sigint) = match (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) {
+        (Ok(sigterm), Ok(sigint)) => (sigterm, sigint),
+        (Err(e), _) | (_, Err(e)) => {
+            warn!("unable to register termination signal handlers: {}", e);
+            return Ok(fut.await);
+        }
+    };
+
+    let interrupted = tokio::select! {
+        output = fut => return Ok(output),
+        _ = sigterm.recv() => Interrupted {
+            signal: "SIGTERM",
+            exit_code: EXIT_SIGTERM,
+        },
+        _ = sigint.recv() => Interrupted {
+            signal: "SIGINT",
+            exit_code: EXIT_SIGINT,
+        },
+    };
+    info!("Received {}, cancelling in-flight work", interrupted.signal);
+    Err(interrupted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancel_on_signal_completes() {
+        assert_eq!(cancel_on_signal(async { 42 }).await, Ok(42));
+    }
+}
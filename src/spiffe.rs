@@ -0,0 +1,182 @@
+// TEE Attestation Service Agent — SPIFFE SVIDs
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With a `[spiffe]` section, every successful attestation hands the
+// verifier's token to the SPIRE agent and fetches the workload's X.509 SVID:
+//
+//   1. The attestation token from the external verifier is written to
+//      `token_path` (mode 0600).
+//   2. `spire-agent api fetch x509` is run against the Workload API socket
+//      and writes the SVID, its key and the trust bundle to `svid_dir`
+//      (svid.0.pem, svid.0.key, bundle.0.pem).
+//
+// The fetch does not attest the node: the Workload API serves SVIDs of an
+// agent that has already attested, and fetching again does not present the
+// token anew. The token only becomes node attestation material with an
+// out-of-tree node attestor plugin, on the SPIRE agent and server, that reads
+// `token_path` when the agent attests or renews, and a server plugin that
+// validates the verifier's token. Without one the SVID says nothing about
+// this attestation. A failed SVID fetch is logged and never fails the key
+// release; in daemon mode it is repeated at every re-attestation.
+//
+// No unsafe code.
+
+use crate::output::write_secret_file;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+/// Default SPIRE agent Workload API socket.
+const DEFAULT_SOCKET_PATH: &str = "/tmp/spire-agent/public/api.sock";
+
+/// Default number of seconds to wait for the SVID.
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+/// `[spiffe]` section of the config file.
+#[derive(Deserialize, Clone)]
+pub struct SpiffeConfig {
+    /// File the attestation token is written to for the node attestor
+    pub token_path: PathBuf,
+    /// Directory the SVID, key and bundle are written to
+    pub svid_dir: PathBuf,
+    /// SPIRE agent Workload API socket
+    pub socket_path: Option<PathBuf>,
+    /// spire-agent executable (default: spire-agent from PATH)
+    pub spire_agent: Option<PathBuf>,
+    /// Seconds to wait for the SVID (default: 60)
+    pub timeout_secs: Option<u64>,
+}
+
+/// Arguments to `spire-agent` for fetching the SVID.
+fn fetch_args(config: &SpiffeConfig, limit: Duration) -> Vec<OsString> {
+    let socket_path = config
+        .socket_path
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_SOCKET_PATH));
+    vec![
+        "api".into(),
+        "fetch".into(),
+        "x509".into(),
+        "-socketPath".into(),
+        socket_path.into(),
+        "-timeout".into(),
+        format!("{}s", limit.as_secs()).into(),
+        "-write".into(),
+        config.svid_dir.clone().into(),
+    ]
+}
+
+/// Publish `token` and fetch the SVID into `svid_dir`.
+async fn try_fetch(config: &SpiffeConfig, token: &str) -> Result<()> {
+    write_secret_file(&config.token_path, token.as_bytes())
+        .context("unable to write attestation token")?;
+    std::fs::create_dir_all(&config.svid_dir)
+        .with_context(|| format!("unable to create {:?}", config.svid_dir))?;
+
+    let limit = Duration::from_secs(config.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    let spire_agent = config
+        .spire_agent
+        .as_deref()
+        .unwrap_or(Path::new("spire-agent"));
+    debug!("Fetching X.509 SVID with {:?}", spire_agent);
+    let mut command = Command::new(spire_agent);
+    command.args(fetch_args(config, limit)).kill_on_drop(true);
+    let output = command.output();
+    // Allow the agent's own timeout to fire first
+    let output = timeout(limit + Duration::from_secs(5), output)
+        .await
+        .map_err(|_| anyhow!("timed out after {}s", limit.as_secs()))?
+        .with_context(|| format!("unable to run {:?}", spire_agent))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{:?} exited with {}: {}",
+            spire_agent,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Publish the attestation `token` for the node attestor and fetch the
+/// X.509 SVID. Failures are logged.
+pub async fn fetch_svid(config: &SpiffeConfig, token: &str) {
+    match try_fetch(config, token).await {
+        Ok(()) => info!("Wrote X.509 SVID to {:?}", config.svid_dir),
+        Err(e) => warn!("unable to fetch X.509 SVID: {:#}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn config(dir: &Path) -> SpiffeConfig {
+        SpiffeConfig {
+            token_path: dir.join("token"),
+            svid_dir: dir.join("svid"),
+            socket_path: None,
+            spire_agent: None,
+            timeout_secs: None,
+        }
+    }
+
+    #[test]
+    fn test_fetch_args() {
+        let cfg = config(Path::new("/run/tas_agent"));
+        let args = fetch_args(&cfg, Duration::from_secs(60));
+        assert_eq!(
+            args,
+            [
+                "api",
+                "fetch",
+                "x509",
+                "-socketPath",
+                DEFAULT_SOCKET_PATH,
+                "-timeout",
+                "60s",
+                "-write",
+                "/run/tas_agent/svid"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_fetch() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("spire-agent");
+        // Write the token the node attestor would have presented as the "SVID"
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\nfor last; do :; done\ncat {} > \"$last/svid.0.pem\"\n",
+                dir.path().join("token").display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut cfg = config(dir.path());
+        cfg.spire_agent = Some(script);
+        try_fetch(&cfg, "eyJ.token.sig").await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("svid/svid.0.pem")).unwrap(),
+            "eyJ.token.sig"
+        );
+        let mode = std::fs::metadata(dir.path().join("token"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        cfg.spire_agent = Some(PathBuf::from("/bin/false"));
+        assert!(try_fetch(&cfg, "eyJ.token.sig").await.is_err());
+    }
+}
@@ -0,0 +1,376 @@
+// TEE Attestation Service Agent — TAS server discovery through DNS SRV records
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With an `[srv]` section and no server URI configured elsewhere, the agent
+// looks up the `_tas._tcp.<domain>` SRV records, so that a fleet can follow
+// the key broker to new hosts through DNS rather than a new image:
+//
+//   _tas._tcp.example.com. 300 IN SRV 10 60 5000 tas1.example.com.
+//   _tas._tcp.example.com. 300 IN SRV 10 40 5000 tas2.example.com.
+//   _tas._tcp.example.com. 300 IN SRV 20 0  5000 tas-dr.example.com.
+//
+// The targets form a failover list in RFC 2782 order: lowest priority
+// first, and within a priority a random order weighted by the records'
+// weights. The first target that accepts a TCP connection becomes the
+// server URI (`<scheme>://<target>:<port>`); when none does, the first one
+// is used and the usual unreachable-server handling applies. The daemon
+// resolves its session for every attestation, so it follows changes to the
+// records as their TTLs allow.
+//
+// The query goes to the first nameserver in /etc/resolv.conf over UDP, and
+// over TCP when the answer is truncated. DNS is not authenticated: the
+// server certificate must still be signed by the configured CA and name the
+// SRV target.
+//
+// No unsafe code.
+
+use crate::rng::rng;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use rand::Rng;
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// The resolver configuration naming the nameserver.
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// Service label of the TAS SRV records.
+const SERVICE: &str = "_tas._tcp";
+
+/// DNS resource record type and class of SRV records.
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+
+const DEFAULT_SCHEME: &str = "https";
+const DEFAULT_TIMEOUT_SECS: u64 = 2;
+
+/// `[srv]` section of the config file.
+#[derive(Deserialize, Clone)]
+pub struct SrvConfig {
+    /// Domain whose _tas._tcp records name the TAS servers
+    pub domain: String,
+    /// Scheme of the server URIs (default: https)
+    pub scheme: Option<String>,
+    /// Seconds to wait for DNS and for each connection attempt (default: 2)
+    pub timeout_secs: Option<u64>,
+}
+
+/// One SRV record.
+#[derive(Debug, Clone, PartialEq)]
+struct Record {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: String,
+}
+
+/// The first nameserver in the resolver configuration `conf`.
+fn nameserver(conf: &str) -> Option<IpAddr> {
+    conf.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("nameserver") {
+            return None;
+        }
+        words.next()?.parse().ok()
+    })
+}
+
+/// A query with `id` for the SRV records of `name`.
+fn query(id: u16, name: &str) -> Result<Vec<u8>> {
+    // Header: recursion desired, one question
+    let mut message = id.to_be_bytes().to_vec();
+    message.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(anyhow!("invalid domain name {:?}", name));
+        }
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(message)
+}
+
+fn u16_at(message: &[u8], pos: usize) -> Result<u16> {
+    message
+        .get(pos..pos + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| anyhow!("truncated DNS response"))
+}
+
+/// The domain name at `pos` in `message`, following compression pointers,
+/// and the position after it.
+fn read_name(message: &[u8], mut pos: usize) -> Result<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Bounds the pointer chain of a malicious response
+    for _ in 0..128 {
+        let len = *message
+            .get(pos)
+            .ok_or_else(|| anyhow!("truncated DNS response"))?;
+        match len {
+            0 => {
+                return Ok((labels.join("."), end.unwrap_or(pos + 1)));
+            }
+            len if len & 0xc0 == 0xc0 => {
+                end.get_or_insert(pos + 2);
+                pos = usize::from(u16_at(message, pos)? & 0x3fff);
+            }
+            len => {
+                let label = message
+                    .get(pos + 1..pos + 1 + usize::from(len))
+                    .ok_or_else(|| anyhow!("truncated DNS response"))?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + usize::from(len);
+            }
+        }
+    }
+    Err(anyhow!("DNS name compression loop"))
+}
+
+/// Outcome of parsing a DNS response.
+#[derive(Debug, PartialEq)]
+enum Answer {
+    Records(Vec<Record>),
+    /// The response did not fit into a UDP datagram
+    Truncated,
+}
+
+/// The SRV records in the response to the query with `id`.
+fn parse_response(id: u16, message: &[u8]) -> Result<Answer> {
+    if u16_at(message, 0)? != id {
+        return Err(anyhow!("DNS response does not match the query"));
+    }
+    let flags = u16_at(message, 2)?;
+    if flags & 0x0200 != 0 {
+        return Ok(Answer::Truncated);
+    }
+    match flags & 0x000f {
+        0 => {}
+        // NXDOMAIN
+        3 => return Ok(Answer::Records(Vec::new())),
+        rcode => return Err(anyhow!("DNS query failed with rcode {}", rcode)),
+    }
+    let questions = u16_at(message, 4)?;
+    let answers = u16_at(message, 6)?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(message, pos)?.1 + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        pos = read_name(message, pos)?.1;
+        let record_type = u16_at(message, pos)?;
+        let length = usize::from(u16_at(message, pos + 8)?);
+        let data = pos + 10;
+        pos = data + length;
+        if pos > message.len() {
+            return Err(anyhow!("truncated DNS response"));
+        }
+        // CNAMEs and other records of the answer section
+        if record_type != TYPE_SRV {
+            continue;
+        }
+        let (target, _) = read_name(message, data + 6)?;
+        // A target of "." means the service is not offered
+        if target.is_empty() {
+            continue;
+        }
+        records.push(Record {
+            priority: u16_at(message, data)?,
+            weight: u16_at(message, data + 2)?,
+            port: u16_at(message, data + 4)?,
+            target,
+        });
+    }
+    Ok(Answer::Records(records))
+}
+
+/// Look up the SRV records of `name` at `server`.
+fn lookup(server: SocketAddr, name: &str, timeout: Duration) -> Result<Vec<Record>> {
+    let id: u16 = rng().gen();
+    let message = query(id, name)?;
+
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(server)?;
+    socket.send(&message)?;
+    let mut response = [0u8; 512];
+    let len = socket.recv(&mut response)?;
+    match parse_response(id, &response[..len])? {
+        Answer::Records(records) => return Ok(records),
+        Answer::Truncated => debug!("Truncated SRV response, retrying over TCP"),
+    }
+
+    let mut stream = TcpStream::connect_timeout(&server, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.write_all(&(message.len() as u16).to_be_bytes())?;
+    stream.write_all(&message)?;
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len)?;
+    let mut response = vec![0u8; usize::from(u16::from_be_bytes(len))];
+    stream.read_exact(&mut response)?;
+    match parse_response(id, &response)? {
+        Answer::Records(records) => Ok(records),
+        Answer::Truncated => Err(anyhow!("truncated DNS response over TCP")),
+    }
+}
+
+/// `records` in RFC 2782 order: by priority, and within a priority in a
+/// random order weighted by weight.
+fn order(mut records: Vec<Record>, rng: &mut impl Rng) -> Vec<Record> {
+    // Zero-weight records first, so that they keep a small chance
+    records.sort_by_key(|record| (record.priority, record.weight != 0));
+    let mut ordered = Vec::with_capacity(records.len());
+    while let Some(first) = records.first() {
+        let priority = first.priority;
+        let end = records
+            .iter()
+            .position(|record| record.priority != priority)
+            .unwrap_or(records.len());
+        let mut group: Vec<Record> = records.drain(..end).collect();
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|record| u32::from(record.weight)).sum();
+            let pick = rng.gen_range(0..=total);
+            let mut sum = 0;
+            let index = group
+                .iter()
+                .position(|record| {
+                    sum += u32::from(record.weight);
+                    sum >= pick
+                })
+                .unwrap_or(0);
+            ordered.push(group.remove(index));
+        }
+    }
+    ordered
+}
+
+/// Whether `target` accepts TCP connections on `port`.
+fn reachable(target: &str, port: u16, timeout: Duration) -> bool {
+    let Ok(addrs) = (target, port).to_socket_addrs() else {
+        return false;
+    };
+    addrs
+        .into_iter()
+        .any(|addr| TcpStream::connect_timeout(&addr, timeout).is_ok())
+}
+
+/// The server URI of the first reachable TAS server in the SRV records of
+/// the configured domain.
+pub fn resolve(config: &SrvConfig) -> Result<String> {
+    let name = format!("{}.{}", SERVICE, config.domain.trim_end_matches('.'));
+    let timeout = Duration::from_secs(config.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    let scheme = config.scheme.as_deref().unwrap_or(DEFAULT_SCHEME);
+
+    let conf = std::fs::read_to_string(RESOLV_CONF)
+        .with_context(|| format!("unable to read {}", RESOLV_CONF))?;
+    let server = nameserver(&conf).ok_or_else(|| anyhow!("no nameserver in {}", RESOLV_CONF))?;
+    let records = lookup((server, 53).into(), &name, timeout)
+        .with_context(|| format!("SRV lookup of {} failed", name))?;
+    let records = order(records, &mut rng());
+    debug!("SRV targets of {}: {:?}", name, records);
+
+    let uri = |record: &Record| format!("{}://{}:{}", scheme, record.target, record.port);
+    for record in &records {
+        if reachable(&record.target, record.port, timeout) {
+            info!("Using TAS server {} from {}", uri(record), name);
+            return Ok(uri(record));
+        }
+        warn!("TAS server {}:{} unreachable", record.target, record.port);
+    }
+    records
+        .first()
+        .map(uri)
+        .ok_or_else(|| anyhow!("no SRV records for {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(priority: u16, weight: u16, target: &str) -> Record {
+        Record {
+            priority,
+            weight,
+            port: 5000,
+            target: target.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_nameserver() {
+        let conf = "# generated\nsearch example.com\nnameserver 10.0.0.2\nnameserver 10.0.0.3\n";
+        assert_eq!(nameserver(conf), Some("10.0.0.2".parse().unwrap()));
+        assert_eq!(nameserver("options ndots:5\n"), None);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let id = 0x1234;
+        let mut response = query(id, "_tas._tcp.example.com").unwrap();
+        // QR, RD, RA; one answer
+        response[2..4].copy_from_slice(&[0x81, 0x80]);
+        response[6..8].copy_from_slice(&[0, 2]);
+        // Answers name the question through a compression pointer
+        let answer = |priority: u16, target: &[u8]| {
+            let mut rr = vec![0xc0, 12];
+            rr.extend_from_slice(&TYPE_SRV.to_be_bytes());
+            rr.extend_from_slice(&CLASS_IN.to_be_bytes());
+            rr.extend_from_slice(&300u32.to_be_bytes());
+            rr.extend_from_slice(&((6 + target.len()) as u16).to_be_bytes());
+            rr.extend_from_slice(&priority.to_be_bytes());
+            rr.extend_from_slice(&5u16.to_be_bytes());
+            rr.extend_from_slice(&5000u16.to_be_bytes());
+            rr.extend_from_slice(target);
+            rr
+        };
+        // tas1 followed by a pointer to example.com in the question
+        response.extend(answer(10, &[4, b't', b'a', b's', b'1', 0xc0, 22]));
+        response.extend(answer(20, &[0]));
+
+        let Answer::Records(records) = parse_response(id, &response).unwrap() else {
+            panic!("unexpected truncation");
+        };
+        assert_eq!(
+            records,
+            vec![Record {
+                priority: 10,
+                weight: 5,
+                port: 5000,
+                target: "tas1.example.com".to_string(),
+            }]
+        );
+
+        assert!(parse_response(id + 1, &response).is_err());
+        response[2] |= 0x02;
+        assert_eq!(parse_response(id, &response).unwrap(), Answer::Truncated);
+    }
+
+    #[test]
+    fn test_order() {
+        let records = vec![
+            record(20, 0, "tas-dr"),
+            record(10, 60, "tas1"),
+            record(10, 40, "tas2"),
+            record(10, 0, "tas3"),
+        ];
+        for _ in 0..20 {
+            let ordered = order(records.clone(), &mut rng());
+            assert_eq!(ordered.len(), 4);
+            assert!(ordered[..3].iter().all(|record| record.priority == 10));
+            assert_eq!(ordered[3].target, "tas-dr");
+        }
+    }
+}
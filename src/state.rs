@@ -0,0 +1,222 @@
+// TEE Attestation Service Agent — daemon state file
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Persists non-sensitive daemon bookkeeping across restarts: for each policy
+// ID the time of the last release attempt, the last successful release and
+// the number of consecutive failures, plus whether TAS was reachable at the
+// last attempt. The file is JSON, rewritten atomically after every release:
+//
+//   {"keys": {"luks-root": {"last_attempt": "<rfc3339>",
+//                           "last_success": "<rfc3339>", "failures": 0}},
+//    "tas_reachable": true}
+//
+// When the previous run ended with failed releases, a restarted daemon waits
+// out an exponential back-off (30s doubling per failure, capped at the
+// re-attestation interval) measured from the last attempt, instead of
+// retrying against the server immediately.
+//
+// Secrets, nonces and evidence are never written. TAS releases secrets
+// against a single-use nonce, so there is no attestation token that could be
+// reused after a restart.
+//
+// No unsafe code.
+
+use crate::hooks::{error_class, ErrorClass};
+use anyhow::{Context, Result};
+use chrono::{DateTime, TimeDelta, Utc};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::time::Duration;
+
+/// Back-off after the first failure, doubled for each further failure.
+const RETRY_BASE: Duration = Duration::from_secs(30);
+
+/// Bookkeeping for one policy ID.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct KeyState {
+    /// When a release was last attempted (RFC 3339)
+    pub last_attempt: Option<String>,
+    /// When a release last succeeded (RFC 3339)
+    pub last_success: Option<String>,
+    /// Consecutive failed releases
+    pub failures: u32,
+}
+
+/// Contents of the state file.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct DaemonState {
+    pub keys: BTreeMap<String, KeyState>,
+    /// Whether TAS answered at the last release attempt
+    pub tas_reachable: Option<bool>,
+}
+
+/// Back-off after `failures` consecutive failures, or None if there were
+/// none.
+pub fn backoff(failures: u32, max: Duration) -> Option<Duration> {
+    if failures == 0 {
+        return None;
+    }
+    let factor = 1u32.checked_shl(failures - 1).unwrap_or(u32::MAX);
+    Some(RETRY_BASE.saturating_mul(factor).min(max))
+}
+
+impl DaemonState {
+    /// Record the result of a release for `policy_id` made at `now`.
+    fn record<T>(&mut self, policy_id: &str, result: &Result<T>, now: DateTime<Utc>) {
+        let key = self.keys.entry(policy_id.to_string()).or_default();
+        let now = now.to_rfc3339();
+        match result {
+            Ok(_) => {
+                key.last_success = Some(now.clone());
+                key.failures = 0;
+                self.tas_reachable = Some(true);
+            }
+            Err(e) => {
+                key.failures = key.failures.saturating_add(1);
+                self.tas_reachable = Some(error_class(e) != ErrorClass::Network);
+            }
+        }
+        key.last_attempt = Some(now);
+    }
+
+    /// How long to wait at `now` before retrying `policy_ids` after failures
+    /// recorded by a previous run, if at all. The back-off is capped at `max`.
+    pub fn hold_off(
+        &self,
+        policy_ids: &[String],
+        max: Duration,
+        now: DateTime<Utc>,
+    ) -> Option<Duration> {
+        policy_ids
+            .iter()
+            .filter_map(|id| self.keys.get(id))
+            .filter_map(|key| {
+                let attempted = DateTime::parse_from_rfc3339(key.last_attempt.as_deref()?).ok()?;
+                let delay = TimeDelta::from_std(backoff(key.failures, max)?).ok()?;
+                (attempted.with_timezone(&Utc) + delay - now).to_std().ok()
+            })
+            .filter(|wait| !wait.is_zero())
+            .max()
+    }
+}
+
+/// Write `state` to `path` via a temporary file and rename.
+fn save(path: &Path, state: &DaemonState) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    let json = serde_json::to_vec_pretty(state).context("unable to serialize daemon state")?;
+    fs::write(&tmp, json).with_context(|| format!("unable to write {:?}", tmp))?;
+    fs::rename(&tmp, path).with_context(|| format!("unable to replace {:?}", path))
+}
+
+/// The daemon state and the file it is persisted to.
+pub struct StateFile {
+    path: PathBuf,
+    state: Mutex<DaemonState>,
+}
+
+impl StateFile {
+    /// Load the state from `path`. A missing or unreadable file starts from an
+    /// empty state.
+    pub fn load(path: PathBuf) -> Self {
+        let state = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Ignoring invalid state file {:?}: {}", path, e);
+                DaemonState::default()
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                debug!("No state file at {:?}", path);
+                DaemonState::default()
+            }
+            Err(e) => {
+                warn!("Unable to read state file {:?}: {}", path, e);
+                DaemonState::default()
+            }
+        };
+        StateFile {
+            path,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// A copy of the current state.
+    pub fn state(&self) -> DaemonState {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Record the result of a release for `policy_id` and persist it.
+    pub fn record<T>(&self, policy_id: &str, result: &Result<T>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.record(policy_id, result, Utc::now());
+        if let Err(e) = save(&self.path, &state) {
+            warn!("Failed to save daemon state: {:#}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let max = Duration::from_secs(3600);
+        assert_eq!(backoff(0, max), None);
+        assert_eq!(backoff(1, max), Some(Duration::from_secs(30)));
+        assert_eq!(backoff(3, max), Some(Duration::from_secs(120)));
+        assert_eq!(backoff(40, max), Some(max));
+    }
+
+    #[test]
+    fn test_record_and_hold_off() {
+        let mut state = DaemonState::default();
+        let start = Utc::now();
+        let ids = vec!["luks".to_string()];
+        let max = Duration::from_secs(3600);
+
+        let refused = || Err(ErrorClass::Network.error("TAS Nonce Error: refused"));
+        state.record::<()>(&ids[0], &refused(), start);
+        state.record::<()>(&ids[0], &refused(), start);
+        assert_eq!(state.keys["luks"].failures, 2);
+        assert_eq!(state.tas_reachable, Some(false));
+        assert_eq!(
+            state.hold_off(&ids, max, start),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            state.hold_off(&ids, max, start + TimeDelta::seconds(61)),
+            None
+        );
+
+        state.record(&ids[0], &Ok(()), start);
+        assert_eq!(state.keys["luks"].failures, 0);
+        assert!(state.keys["luks"].last_success.is_some());
+        assert_eq!(state.hold_off(&ids, max, start), None);
+    }
+
+    #[test]
+    fn test_state_file_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        assert_eq!(
+            StateFile::load(path.clone()).state(),
+            DaemonState::default()
+        );
+
+        StateFile::load(path.clone()).record("luks", &Ok(()));
+        let state = StateFile::load(path.clone()).state();
+        assert_eq!(state.tas_reachable, Some(true));
+        assert!(state.keys["luks"].last_success.is_some());
+
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(StateFile::load(path).state(), DaemonState::default());
+    }
+}
@@ -0,0 +1,260 @@
+// TEE Attestation Service Agent — failure streaks of daemon releases
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// While TAS is down, every scheduled re-attestation and every local API
+// request for a secret the daemon has not got would otherwise be another
+// release attempt and another identical warning. For each policy ID the
+// daemon tracks the streak of consecutive failed releases:
+//
+//   - After a failure, releases of the policy ID wait out a back-off of 30s
+//     doubling per failure (as after a restart, see state.rs), capped at
+//     `max_backoff_secs` in `[daemon]` (default: the re-attestation
+//     interval). The daemon retries on its own once the back-off has passed;
+//     SIGUSR1 and a config reload retry at once.
+//   - The first failure is logged as a warning. The same error again is only
+//     logged at debug level, except for an alert logged as an error after
+//     ESCALATE_AFTER failures and each time the streak doubles from there. A
+//     different error is logged as a warning again.
+//   - The end of a streak is logged with its length.
+//   - The health endpoints report the streaks in progress (see health.rs),
+//     naming each policy ID only by its index in the configured list:
+//
+//       "failure_streaks": [{"policy": 1, "failures": 7,
+//         "since": "<rfc3339>", "retry_at": "<rfc3339>",
+//         "last_error_class": "network"}]
+//
+// No unsafe code.
+
+use crate::hooks::{self, ErrorClass};
+use crate::state::backoff;
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+/// Failures after which a streak is alerted as an error.
+pub const ESCALATE_AFTER: u32 = 5;
+
+/// How a release outcome is to be logged.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Alert {
+    /// A success outside a streak
+    None,
+    /// The first failure of a streak
+    Failed,
+    /// A failure with a different error than the previous one
+    Changed,
+    /// A failure repeating the previous error
+    Repeated,
+    /// A streak that reached ESCALATE_AFTER failures, or doubled since
+    Escalated,
+    /// A success ending a streak of this many failures
+    Recovered(u32),
+}
+
+/// The alert for the `failures`th failure of a streak.
+fn alert(failures: u32, changed: bool) -> Alert {
+    if failures <= 1 {
+        Alert::Failed
+    } else if failures.is_multiple_of(ESCALATE_AFTER)
+        && (failures / ESCALATE_AFTER).is_power_of_two()
+    {
+        Alert::Escalated
+    } else if changed {
+        Alert::Changed
+    } else {
+        Alert::Repeated
+    }
+}
+
+/// Consecutive failed releases of one policy ID.
+struct Streak {
+    failures: u32,
+    since: DateTime<Utc>,
+    last_error: String,
+    last_error_class: ErrorClass,
+    retry_at: Instant,
+    retry_at_wall: DateTime<Utc>,
+    /// Whether the daemon still has to retry once the back-off has passed
+    retry_pending: bool,
+}
+
+/// Failure streaks of all policy IDs.
+#[derive(Default)]
+pub struct Streaks {
+    streaks: Mutex<BTreeMap<String, Streak>>,
+}
+
+impl Streaks {
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Streak>> {
+        self.streaks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record the result of a release for `policy_id`, backing off for at
+    /// most `max_backoff` after a failure.
+    pub fn record<T>(&self, policy_id: &str, result: &Result<T>, max_backoff: Duration) -> Alert {
+        let mut streaks = self.lock();
+        let (error, class) = match result {
+            Ok(_) => {
+                return match streaks.remove(policy_id) {
+                    Some(streak) => Alert::Recovered(streak.failures),
+                    None => Alert::None,
+                };
+            }
+            Err(e) => (format!("{:#}", e), hooks::error_class(e)),
+        };
+        let now = Utc::now();
+        let streak = streaks
+            .entry(policy_id.to_string())
+            .or_insert_with(|| Streak {
+                failures: 0,
+                since: now,
+                last_error: String::new(),
+                last_error_class: class,
+                retry_at: Instant::now(),
+                retry_at_wall: now,
+                retry_pending: false,
+            });
+        let changed = streak.failures > 0 && streak.last_error != error;
+        streak.failures = streak.failures.saturating_add(1);
+        streak.last_error = error;
+        streak.last_error_class = class;
+        let wait = backoff(streak.failures, max_backoff).unwrap_or_default();
+        streak.retry_at = Instant::now() + wait;
+        streak.retry_at_wall = now + TimeDelta::from_std(wait).unwrap_or(TimeDelta::zero());
+        streak.retry_pending = true;
+        alert(streak.failures, changed)
+    }
+
+    /// The failures so far, the last error and the time left to wait if
+    /// `policy_id` is backing off.
+    pub fn hold_off(&self, policy_id: &str) -> Option<(u32, String, Duration)> {
+        let streaks = self.lock();
+        let streak = streaks.get(policy_id)?;
+        let wait = streak.retry_at.saturating_duration_since(Instant::now());
+        (!wait.is_zero()).then(|| (streak.failures, streak.last_error.clone(), wait))
+    }
+
+    /// When the next policy ID is due to be retried, if any.
+    pub fn next_retry(&self) -> Option<Instant> {
+        self.lock()
+            .values()
+            .filter(|streak| streak.retry_pending)
+            .map(|streak| streak.retry_at)
+            .min()
+    }
+
+    /// The policy IDs due to be retried at `now`, which are then no longer
+    /// pending.
+    pub fn take_due(&self, now: Instant) -> Vec<String> {
+        let mut due = Vec::new();
+        for (policy_id, streak) in self.lock().iter_mut() {
+            if streak.retry_pending && streak.retry_at <= now {
+                streak.retry_pending = false;
+                due.push(policy_id.clone());
+            }
+        }
+        due
+    }
+
+    /// Forget the streaks of policy IDs not in `policy_ids`.
+    pub fn retain(&self, policy_ids: &[String]) {
+        self.lock()
+            .retain(|policy_id, _| policy_ids.contains(policy_id));
+    }
+
+    /// The streaks in progress, for the health endpoints. Policy IDs are
+    /// given by their index in `policy_ids`, the configured list.
+    pub fn report(&self, policy_ids: &[String]) -> Value {
+        let streaks = self.lock();
+        let report: Vec<Value> = policy_ids
+            .iter()
+            .enumerate()
+            .filter_map(|(index, policy_id)| {
+                let streak = streaks.get(policy_id)?;
+                Some(json!({
+                    "policy": index,
+                    "failures": streak.failures,
+                    "since": streak.since.to_rfc3339(),
+                    "retry_at": streak.retry_at_wall.to_rfc3339(),
+                    "last_error_class": streak.last_error_class.as_str(),
+                }))
+            })
+            .collect();
+        Value::Array(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_alert() {
+        assert_eq!(alert(1, false), Alert::Failed);
+        assert_eq!(alert(2, false), Alert::Repeated);
+        assert_eq!(alert(3, true), Alert::Changed);
+        let escalated: Vec<u32> = (1..=100)
+            .filter(|&n| alert(n, false) == Alert::Escalated)
+            .collect();
+        assert_eq!(escalated, [5, 10, 20, 40, 80]);
+        // An escalation is reported even if the error changed
+        assert_eq!(alert(10, true), Alert::Escalated);
+    }
+
+    #[test]
+    fn test_record() {
+        let streaks = Streaks::default();
+        let max = Duration::from_secs(3600);
+        let refused = || Err::<(), _>(anyhow!("TAS Nonce Error: refused"));
+
+        assert_eq!(streaks.record("luks", &Ok(()), max), Alert::None);
+        assert_eq!(streaks.record("luks", &refused(), max), Alert::Failed);
+        assert_eq!(streaks.record("luks", &refused(), max), Alert::Repeated);
+        let denied = Err::<(), _>(ErrorClass::Release.error("TAS Secret Error: denied"));
+        assert_eq!(streaks.record("luks", &denied, max), Alert::Changed);
+
+        let (failures, last_error, wait) = streaks.hold_off("luks").unwrap();
+        assert_eq!(failures, 3);
+        assert_eq!(last_error, "TAS Secret Error: denied");
+        assert!(wait > Duration::from_secs(110) && wait <= Duration::from_secs(120));
+        assert!(streaks.hold_off("other").is_none());
+
+        let report = streaks.report(&["other".to_string(), "luks".to_string()]);
+        assert_eq!(report[0]["policy"], 1);
+        assert_eq!(report[0]["failures"], 3);
+        assert_eq!(report[0]["last_error_class"], "release");
+        assert!(report[0]["retry_at"].is_string());
+
+        assert_eq!(streaks.record("luks", &Ok(()), max), Alert::Recovered(3));
+        assert!(streaks.hold_off("luks").is_none());
+        assert_eq!(streaks.report(&["luks".to_string()]), json!([]));
+    }
+
+    #[test]
+    fn test_retries() {
+        let streaks = Streaks::default();
+        let failed = Err::<(), _>(anyhow!("TAS Nonce Error: refused"));
+        assert_eq!(streaks.next_retry(), None);
+
+        // A zero cap retries at once
+        streaks.record("luks", &failed, Duration::ZERO);
+        streaks.record("other", &failed, Duration::from_secs(3600));
+        assert!(streaks.hold_off("luks").is_none());
+        let next = streaks.next_retry().unwrap();
+        assert!(next <= Instant::now());
+        assert_eq!(streaks.take_due(Instant::now()), ["luks"]);
+        // Taken retries are no longer pending
+        assert!(streaks.take_due(Instant::now()).is_empty());
+        assert!(streaks.next_retry().unwrap() > Instant::now());
+
+        streaks.retain(&["luks".to_string()]);
+        assert_eq!(streaks.next_retry(), None);
+        assert_eq!(streaks.report(&["luks".to_string()])[0]["failures"], 1);
+    }
+}
@@ -7,14 +7,25 @@
 //
 // TAS REST API functionality.
 //
-use reqwest::{Certificate, Client};
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use crate::cassette;
+use crate::limits;
+use crate::netif;
+use crate::request_id;
+use crate::schema;
+use crate::tls;
+use crate::transcript;
+use reqwest::{Certificate, Client, Proxy, Url};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, RequestBuilder};
 use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
 use retry_policies::Jitter;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 use std::time::Duration;
 
 /// Retry configuration for HTTP requests to the TAS server.
@@ -38,30 +49,199 @@ impl Default for RetryConfig {
     }
 }
 
+/// Interval of TCP keep-alive probes on shared connections.
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+/// What a client is built from: its trust roots, TLS policy and retry
+/// policy.
+#[derive(PartialEq, Eq, Hash)]
+struct ClientKey {
+    cert_data: Option<Vec<u8>>,
+    /// Whether the `[tls]` policy and key log apply
+    tls_policy: bool,
+    /// Source address of the connections
+    local_address: Option<IpAddr>,
+    /// `--bind-interface` connector the connections go through
+    proxy: Option<&'static str>,
+    max_retries: u32,
+    min_backoff_secs: u64,
+    max_backoff_secs: u64,
+}
+
+/// Source address of connections to TAS.
+static LOCAL_ADDRESS: OnceLock<IpAddr> = OnceLock::new();
+
+/// Send all later TAS requests from `address`.
+pub fn bind(address: IpAddr) {
+    let _ = LOCAL_ADDRESS.set(address);
+}
+
+/// Clients kept between requests once connection sharing is enabled.
+static SHARED_CLIENTS: OnceLock<Mutex<HashMap<ClientKey, ClientWithMiddleware>>> = OnceLock::new();
+
+/// Reuse HTTP clients, with their open connections and TLS sessions, across
+/// requests for the rest of the process.
+///
+/// Connections belong to the Tokio runtime that opened them, so this is for
+/// long-running processes with a single runtime, such as the daemon.
+#[cfg(feature = "daemon")]
+pub fn share_connections() {
+    let _ = SHARED_CLIENTS.set(Mutex::default());
+}
+
+/// Read the CA certificates at `cert_path`: a PEM bundle of one or more
+/// certificates, or a directory whose `*.pem` and `*.crt` files are read in
+/// name order, so that old and new roots can be trusted side by side while
+/// a CA is rotated.
+pub fn read_ca_certs(cert_path: &Path) -> Result<Vec<u8>, String> {
+    let read = |path: &Path| {
+        fs::read(path).map_err(|err| format!("Error reading certificate file {:?}: {}", path, err))
+    };
+    if !cert_path.is_dir() {
+        return read(cert_path);
+    }
+    let entries = fs::read_dir(cert_path).map_err(|err| {
+        format!(
+            "Error reading certificate directory {:?}: {}",
+            cert_path, err
+        )
+    })?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && matches!(
+                    path.extension().and_then(|ext| ext.to_str()),
+                    Some("pem" | "crt")
+                )
+        })
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        return Err(format!("No *.pem or *.crt certificates in {:?}", cert_path));
+    }
+    let mut bundle = Vec::new();
+    for path in paths {
+        bundle.extend(read(&path)?);
+        // Files need not end with a newline
+        bundle.push(b'\n');
+    }
+    Ok(bundle)
+}
+
 /// Helper function to create a `reqwest_middleware::ClientWithMiddleware` with optional root
 /// certificates and retry middleware configured with exponential backoff and jitter.
 ///
-/// When `server_uri` uses `https://`, the cert bundle at `cert_path` is loaded and added
-/// as trusted root certificates. For plain `http://` URIs the cert file is skipped,
-/// which avoids failures in initrd environments that lack a CA bundle.
-fn create_client(
+/// When `server_uri` uses `https://`, the cert bundle or directory at `cert_path` is loaded
+/// and added as trusted root certificates. For plain `http://` URIs the cert file is skipped,
+/// which avoids failures in initrd environments that lack a CA bundle. Once
+/// [`share_connections`] has been called, a client built from the same
+/// certificates and retry settings is reused.
+pub fn create_client(
+    server_uri: &str,
+    cert_path: PathBuf,
+    retry_config: &RetryConfig,
+) -> Result<ClientWithMiddleware, String> {
+    client(server_uri, cert_path, retry_config, false)
+}
+
+/// Like [`create_client`], for connections to TAS: the `[tls]` policy, TLS
+/// key log and source address, if configured, apply.
+pub fn create_tas_client(
     server_uri: &str,
     cert_path: PathBuf,
     retry_config: &RetryConfig,
 ) -> Result<ClientWithMiddleware, String> {
+    client(server_uri, cert_path, retry_config, true)
+}
+
+fn client(
+    server_uri: &str,
+    cert_path: PathBuf,
+    retry_config: &RetryConfig,
+    tas: bool,
+) -> Result<ClientWithMiddleware, String> {
+    // Only load certificates for HTTPS connections
+    let cert_data = if server_uri.starts_with("https://") {
+        Some(read_ca_certs(&cert_path)?)
+    } else {
+        None
+    };
+
+    let proxy = if tas {
+        interface_proxy(server_uri)?
+    } else {
+        None
+    };
+    let key = ClientKey {
+        tls_policy: tas && cert_data.is_some() && tls::custom(),
+        local_address: LOCAL_ADDRESS.get().copied().filter(|_| tas),
+        proxy,
+        cert_data,
+        max_retries: retry_config.max_retries,
+        min_backoff_secs: retry_config.min_backoff_secs,
+        max_backoff_secs: retry_config.max_backoff_secs,
+    };
+    let Some(shared) = SHARED_CLIENTS.get() else {
+        return build_client(&key, false);
+    };
+    let mut shared = shared.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = shared.get(&key) {
+        return Ok(client.clone());
+    }
+    let client = build_client(&key, true)?;
+    shared.insert(key, client.clone());
+    Ok(client)
+}
+
+/// The `--bind-interface` connector for requests to `server_uri`, if any.
+fn interface_proxy(server_uri: &str) -> Result<Option<&'static str>, String> {
+    let url = Url::parse(server_uri).map_err(|err| format!("Invalid server URI: {}", err))?;
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return Err(format!("Invalid server URI: {}", server_uri));
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match netif::proxy_for(host, port) {
+        Some(_) if url.scheme() != "https" => {
+            Err("--bind-interface requires an https:// server URI".to_string())
+        }
+        proxy => Ok(proxy),
+    }
+}
+
+fn build_client(key: &ClientKey, shared: bool) -> Result<ClientWithMiddleware, String> {
     let mut builder = Client::builder()
         .timeout(Duration::from_secs(60))
         .connect_timeout(Duration::from_secs(15));
 
-    // Only load certificates for HTTPS connections
-    if server_uri.starts_with("https://") {
-        let cert_data = fs::read(&cert_path)
-            .map_err(|err| format!("Error reading certificate file {:?}: {}", cert_path, err))?;
-        let certs = Certificate::from_pem_bundle(&cert_data)
-            .map_err(|err| format!("Error parsing certificate bundle: {}", err))?;
-        for cert in certs {
-            builder = builder.add_root_certificate(cert);
+    match &key.cert_data {
+        Some(cert_data) if key.tls_policy => builder = tls::apply(builder, cert_data)?,
+        Some(cert_data) => {
+            let certs = Certificate::from_pem_bundle(cert_data)
+                .map_err(|err| format!("Error parsing certificate bundle: {}", err))?;
+            if certs.is_empty() {
+                return Err("Error parsing certificate bundle: no certificates".to_string());
+            }
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
         }
+        None => {}
+    }
+
+    if let Some(address) = key.local_address {
+        builder = builder.local_address(address);
+    }
+    if let Some(proxy) = key.proxy {
+        let proxy = Proxy::https(proxy)
+            .map_err(|err| format!("Error configuring the interface connector: {}", err))?;
+        builder = builder.proxy(proxy);
+    }
+
+    // Hold idle connections open for the next request; TLS sessions are
+    // resumed from the client's session cache when a connection was closed
+    if shared {
+        builder = builder.pool_idle_timeout(None).tcp_keepalive(TCP_KEEPALIVE);
     }
 
     let client = builder
@@ -71,11 +251,11 @@ fn create_client(
     // Configure exponential backoff with full jitter
     let retry_policy = ExponentialBackoff::builder()
         .retry_bounds(
-            Duration::from_secs(retry_config.min_backoff_secs),
-            Duration::from_secs(retry_config.max_backoff_secs),
+            Duration::from_secs(key.min_backoff_secs),
+            Duration::from_secs(key.max_backoff_secs),
         )
         .jitter(Jitter::Full)
-        .build_with_max_retries(retry_config.max_retries);
+        .build_with_max_retries(key.max_retries);
 
     let client_with_middleware = ClientBuilder::new(client)
         .with(RetryTransientMiddleware::new_with_policy(retry_policy))
@@ -84,39 +264,176 @@ fn create_client(
     Ok(client_with_middleware)
 }
 
+/// Tag `request` with the ID of the current attestation attempt, if any.
+fn with_request_id(request: RequestBuilder) -> RequestBuilder {
+    match request_id::current() {
+        Some(id) => request.header(request_id::HEADER, id),
+        None => request,
+    }
+}
+
+/// Read the body of `response`, failing once it exceeds `limit` bytes.
+/// A Content-Length over the limit fails before anything is read.
+async fn read_limited(mut response: reqwest::Response, limit: usize) -> Result<Vec<u8>, String> {
+    let too_large = |len: u64| {
+        format!(
+            "Error: response of {} bytes exceeds the limit of {} bytes",
+            len, limit
+        )
+    };
+    if let Some(len) = response.content_length() {
+        if len > limit as u64 {
+            return Err(too_large(len));
+        }
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Error reading response: {}", e))?
+    {
+        if body.len() + chunk.len() > limit {
+            return Err(too_large((body.len() + chunk.len()) as u64));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Read a JSON response body of at most `limit` bytes.
+async fn read_json(response: reqwest::Response, limit: usize) -> Result<Value, String> {
+    let body = read_limited(response, limit).await?;
+    serde_json::from_slice(&body).map_err(|err| format!("Error parsing JSON response: {}", err))
+}
+
+/// Read an error response body for the error message, keeping at most
+/// `limit` bytes of it.
+async fn read_error_body(mut response: reqwest::Response, limit: usize) -> String {
+    let mut body = Vec::new();
+    let mut truncated = false;
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                let room = limit - body.len();
+                if chunk.len() > room {
+                    body.extend_from_slice(&chunk[..room]);
+                    truncated = true;
+                    break;
+                }
+                body.extend_from_slice(&chunk);
+            }
+            Ok(None) => break,
+            Err(_) => return "Unable to read response body".to_string(),
+        }
+    }
+    let mut message = String::from_utf8_lossy(&body).into_owned();
+    if truncated {
+        message.push_str("... (truncated)");
+    }
+    message
+}
+
+/// Response of the version API.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerVersion {
+    /// The `version` field, serialized as JSON
+    pub version: String,
+    /// Wrapping key encodings the server accepts, from its optional
+    /// `wrapping-key-encodings` field
+    pub wrapping_key_encodings: Vec<String>,
+    /// Wrapping algorithms the server supports, from its optional
+    /// `wrap-algorithms` field
+    pub wrap_algorithms: Vec<String>,
+}
+
+/// A failed version or get_secret API request.
+#[derive(Debug)]
+pub struct TasError {
+    message: String,
+    /// No response was received, as opposed to the server answering with an
+    /// error
+    pub request_failed: bool,
+    /// The server could not be connected to or did not answer in time
+    pub unreachable: bool,
+}
+
+impl TasError {
+    fn request(err: reqwest_middleware::Error) -> Self {
+        TasError {
+            unreachable: err.is_connect() || err.is_timeout(),
+            request_failed: true,
+            message: format!("Error making request: {}", err),
+        }
+    }
+}
+
+impl std::fmt::Display for TasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for TasError {}
+
+impl From<String> for TasError {
+    fn from(message: String) -> Self {
+        TasError {
+            message,
+            request_failed: false,
+            unreachable: false,
+        }
+    }
+}
+
 /// Function to make the GET request to the version API and return the server version
 pub async fn tas_get_version(
     server_uri: &str,
     api_key: &str,
     cert_path: PathBuf,
     retry_config: &RetryConfig,
-) -> Result<String, String> {
+) -> Result<ServerVersion, TasError> {
     let version_url = format!("{}/version", server_uri);
-    let client = create_client(server_uri, cert_path, retry_config)?;
+    let client = create_tas_client(server_uri, cert_path, retry_config)?;
 
-    match client
-        .get(&version_url)
+    match with_request_id(client.get(&version_url))
         .header("X-API-KEY", api_key)
         .send()
         .await
     {
         Ok(response) => {
+            let status = response.status().as_u16();
             if response.status().is_success() {
-                match response.json::<Value>().await {
-                    Ok(json) => {
-                        if let Some(version) = json.get("version") {
-                            Ok(version.to_string())
-                        } else {
-                            Err("Error: 'version' field not found in response".to_string())
-                        }
-                    }
-                    Err(err) => Err(format!("Error parsing JSON response: {}", err)),
+                let json = read_json(response, limits::response_bytes()).await?;
+                cassette::record("GET", "/version", None, status, &json);
+                transcript::exchange("GET", "/version", None, status, &json);
+                schema::VERSION.validate(&json)?;
+                if let Some(version) = json.get("version") {
+                    let names = |field: &str| -> Vec<String> {
+                        json.get(field)
+                            .and_then(Value::as_array)
+                            .map(|names| {
+                                names
+                                    .iter()
+                                    .filter_map(|name| name.as_str().map(str::to_string))
+                                    .collect()
+                            })
+                            .unwrap_or_default()
+                    };
+                    Ok(ServerVersion {
+                        version: version.to_string(),
+                        wrapping_key_encodings: names("wrapping-key-encodings"),
+                        wrap_algorithms: names("wrap-algorithms"),
+                    })
+                } else {
+                    Err("Error: 'version' field not found in response"
+                        .to_string()
+                        .into())
                 }
             } else {
-                Err(format!("Error: Received HTTP {}", response.status()))
+                Err(format!("Error: Received HTTP {}", response.status()).into())
             }
         }
-        Err(err) => Err(format!("Error making request: {}", err)),
+        Err(err) => Err(TasError::request(err)),
     }
 }
 
@@ -128,25 +445,24 @@ pub async fn tas_get_nonce(
     retry_config: &RetryConfig,
 ) -> Result<String, String> {
     let nonce_url = format!("{}/kb/v0/get_nonce", server_uri);
-    let client = create_client(server_uri, cert_path, retry_config)?;
+    let client = create_tas_client(server_uri, cert_path, retry_config)?;
 
-    match client
-        .get(&nonce_url)
+    match with_request_id(client.get(&nonce_url))
         .header("X-API-KEY", api_key)
         .send()
         .await
     {
         Ok(response) => {
+            let status = response.status().as_u16();
             if response.status().is_success() {
-                match response.json::<Value>().await {
-                    Ok(json) => {
-                        if let Some(nonce) = json.get("nonce") {
-                            Ok(nonce.to_string())
-                        } else {
-                            Err("Error: 'nonce' field not found in response".to_string())
-                        }
-                    }
-                    Err(err) => Err(format!("Error parsing JSON response: {}", err)),
+                let json = read_json(response, limits::response_bytes()).await?;
+                cassette::record("GET", "/kb/v0/get_nonce", None, status, &json);
+                transcript::exchange("GET", "/kb/v0/get_nonce", None, status, &json);
+                schema::GET_NONCE.validate(&json)?;
+                if let Some(nonce) = json.get("nonce") {
+                    Ok(nonce.to_string())
+                } else {
+                    Err("Error: 'nonce' field not found in response".to_string())
                 }
             } else {
                 Err(format!("Error: Received HTTP {}", response.status()))
@@ -156,74 +472,161 @@ pub async fn tas_get_nonce(
     }
 }
 
+/// Response of the get_secret API.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecretResponse {
+    /// The `secret_key` field, serialized as JSON
+    pub secret_key: String,
+    /// EAT Attestation Result, if the server returned one
+    pub attestation_result: Option<String>,
+    /// Attestation of the server itself, if it returned one
+    pub server_attestation: Option<String>,
+}
+
+/// Extract the secret and the optional attestation results from a get_secret
+/// response body.
+pub fn parse_secret_response(json: &Value) -> Result<SecretResponse, String> {
+    schema::GET_SECRET.validate(json)?;
+    let secret_key = json
+        .get("secret_key")
+        .ok_or_else(|| "Error: 'secret_key' field not found in response".to_string())?;
+    Ok(SecretResponse {
+        secret_key: secret_key.to_string(),
+        attestation_result: json
+            .get("attestation-result")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        server_attestation: json
+            .get("server-attestation")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+    })
+}
+
+/// Chain-of-custody signature over a get_secret request (see custody.rs).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceSignature {
+    /// "ES256" or "RS256"
+    pub alg: String,
+    /// Base64 SubjectPublicKeyInfo DER of the signing key
+    pub key: String,
+    /// Base64 signature
+    pub signature: String,
+}
+
+/// Body of a get_secret request.
+///
+/// Borrows the evidence, which can run to megabytes with certificate chains
+/// and event logs, so it is copied only once: straight into the serialized
+/// request. Fields are in lexicographic order to serialize exactly as the
+/// equivalent `serde_json::Value` map would. Optional fields are left out
+/// with `..Default::default()`.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SecretRequest<'a> {
+    /// Token from an external verifier, when one appraised the evidence
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attestation_token: Option<&'a str>,
+    /// Operator-defined claims about the deployment, e.g. a workload ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claims: Option<&'a BTreeMap<String, String>>,
+    /// Component evidence (GPUs, NICs, etc.), when available
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub component_evidence: Option<&'a Value>,
+    /// Signature over the rest of the request, when evidence is signed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evidence_signature: Option<&'a EvidenceSignature>,
+    /// Base64 of the local random bytes mixed into the nonce, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_entropy: Option<&'a str>,
+    pub nonce: &'a str,
+    /// How the nonce was bound if not as is, e.g. "sha512"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce_construction: Option<&'a str>,
+    /// Unmeasured description of the platform, for operators
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform_metadata: Option<&'a Value>,
+    pub policy_id: &'a str,
+    /// Signals key binding to the server
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub report_data_binding: bool,
+    pub tee_evidence: &'a str,
+    pub tee_type: &'a str,
+    /// Algorithm the secret is to be wrapped with, if not RSA-OAEP-256
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrap_algorithm: Option<&'a str>,
+    pub wrapping_key: &'a str,
+}
+
+/// POST a failure record to the telemetry endpoint at `path`
+pub async fn tas_post_telemetry(
+    server_uri: &str,
+    api_key: &str,
+    cert_path: PathBuf,
+    retry_config: &RetryConfig,
+    path: &str,
+    record: &Value,
+) -> Result<(), String> {
+    let telemetry_url = format!("{}{}", server_uri, path);
+    let client = create_tas_client(server_uri, cert_path, retry_config)?;
+
+    match with_request_id(client.post(&telemetry_url))
+        .header("X-API-KEY", api_key)
+        .json(record)
+        .send()
+        .await
+    {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => Err(format!("Error: Received HTTP {}", response.status())),
+        Err(err) => Err(format!("Error making request: {}", err)),
+    }
+}
+
 /// Function to make the POST request to the get_secret API and return the secret key
-#[allow(clippy::too_many_arguments)]
 pub async fn tas_get_secret_key(
     server_uri: &str,
     api_key: &str,
-    nonce: &str,
-    tee_evidence: &str,
-    tee_type: &str,
-    policy_id: &str,
-    wrapping_key: &str,
     cert_path: PathBuf,
     retry_config: &RetryConfig,
-    report_data_binding: bool,
-    component_evidence: Option<&serde_json::Value>,
-) -> Result<String, String> {
+    body: &SecretRequest<'_>,
+) -> Result<SecretResponse, TasError> {
     let secret_url = format!("{}/kb/v0/get_secret", server_uri);
-    let client = create_client(server_uri, cert_path, retry_config)?;
+    let client = create_tas_client(server_uri, cert_path, retry_config)?;
 
-    // Create the JSON body for the POST request
-    let mut body = serde_json::json!({
-        "tee-type": tee_type,
-        "nonce": nonce,
-        "tee-evidence": tee_evidence,
-        "policy-id": policy_id,
-        "wrapping-key": wrapping_key
-    });
+    let request = if cassette::recording() || transcript::active() {
+        serde_json::to_value(body).ok()
+    } else {
+        None
+    };
 
-    // Signal key binding to the server
-    if report_data_binding {
-        body["report-data-binding"] = serde_json::json!(true);
-    }
-
-    // Include component evidence (GPUs, NICs, etc.) when available
-    if let Some(components) = component_evidence {
-        body["component-evidence"] = components.clone();
-    }
-
-    match client
-        .post(&secret_url)
+    match with_request_id(client.post(&secret_url))
         .header("X-API-KEY", api_key)
-        .json(&body)
+        .json(body)
         .send()
         .await
     {
         Ok(response) => {
+            let status = response.status().as_u16();
             if response.status().is_success() {
-                match response.json::<Value>().await {
-                    Ok(json) => {
-                        if let Some(secret_key) = json.get("secret_key") {
-                            Ok(secret_key.to_string())
-                        } else {
-                            Err("Error: 'secret_key' field not found in response".to_string())
-                        }
-                    }
-                    Err(err) => Err(format!("Error parsing JSON response: {}", err)),
-                }
+                let json = read_json(response, limits::secret_bytes()).await?;
+                let path = "/kb/v0/get_secret";
+                cassette::record("POST", path, request.as_ref(), status, &json);
+                transcript::exchange("POST", path, request.as_ref(), status, &json);
+                Ok(parse_secret_response(&json)?)
             } else {
-                Err(format!(
-                    "Error: Received HTTP {} with message: {}",
-                    response.status(),
-                    response
-                        .text()
-                        .await
-                        .unwrap_or_else(|_| "Unable to read response body".to_string())
-                ))
+                let code = response.status();
+                let message = read_error_body(response, limits::error_bytes()).await;
+                transcript::exchange(
+                    "POST",
+                    "/kb/v0/get_secret",
+                    request.as_ref(),
+                    status,
+                    &Value::String(message.clone()),
+                );
+                Err(format!("Error: Received HTTP {} with message: {}", code, message).into())
             }
         }
-        Err(err) => Err(format!("Error making request: {}", err)),
+        Err(err) => Err(TasError::request(err)),
     }
 }
 
@@ -317,7 +720,65 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let cert_path = cert_file.path().to_path_buf();
         let result = tas_get_version(&server_uri, api_key, cert_path, &no_retry_config()).await;
 
-        assert_eq!(result.unwrap(), "\"1.2.3\"");
+        assert_eq!(result.unwrap().version, "\"1.2.3\"");
+    }
+
+    #[tokio::test]
+    async fn test_tas_get_version_wrap_algorithms() {
+        let mut server = Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"version": "1.4.0", "wrap-algorithms": ["RSA-OAEP-256", "HPKE"]}"#)
+            .create_async()
+            .await;
+
+        let cert_file = create_test_cert();
+        let cert_path = cert_file.path().to_path_buf();
+        let result = tas_get_version(&server.url(), "test_api_key", cert_path, &no_retry_config())
+            .await
+            .unwrap();
+
+        assert_eq!(result.wrap_algorithms, ["RSA-OAEP-256", "HPKE"]);
+    }
+
+    #[tokio::test]
+    async fn test_tas_get_version_wrapping_key_encodings() {
+        let mut server = Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"version": "1.2.3", "wrapping-key-encodings": ["pem", "base64-der"]}"#)
+            .create_async()
+            .await;
+
+        let cert_file = create_test_cert();
+        let cert_path = cert_file.path().to_path_buf();
+        let result = tas_get_version(&server.url(), "test_api_key", cert_path, &no_retry_config())
+            .await
+            .unwrap();
+
+        assert_eq!(result.wrapping_key_encodings, ["pem", "base64-der"]);
+        assert!(result.wrap_algorithms.is_empty());
+    }
+
+    #[test]
+    fn test_read_ca_certs_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_ca_certs(dir.path()).is_err());
+
+        let cert = fs::read(create_test_cert().path()).unwrap();
+        fs::write(dir.path().join("old-root.pem"), &cert).unwrap();
+        fs::write(dir.path().join("new-root.crt"), &cert).unwrap();
+        fs::write(dir.path().join("README"), "not a certificate").unwrap();
+        let bundle = String::from_utf8(read_ca_certs(dir.path()).unwrap()).unwrap();
+        assert_eq!(bundle.matches("-----BEGIN CERTIFICATE-----").count(), 2);
+        assert!(!bundle.contains("not a certificate"));
+
+        let file = dir.path().join("old-root.pem");
+        assert_eq!(read_ca_certs(&file).unwrap(), cert);
     }
 
     #[tokio::test]
@@ -340,6 +801,53 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         assert_eq!(result.unwrap(), "\"abc123\"");
     }
 
+    #[tokio::test]
+    async fn test_tas_get_nonce_request_id() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/kb/v0/get_nonce")
+            .match_header(
+                request_id::HEADER,
+                mockito::Matcher::Regex("^[0-9a-f]{8}-[0-9a-f]{4}-4".to_string()),
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"nonce": "abc123"}"#)
+            .create_async()
+            .await;
+
+        let cert_file = create_test_cert();
+        let cert_path = cert_file.path().to_path_buf();
+        let server_uri = server.url();
+        let retry_config = no_retry_config();
+        let nonce = tas_get_nonce(&server_uri, "test_api_key", cert_path, &retry_config);
+        let result = request_id::scope(nonce).await;
+
+        assert_eq!(result.unwrap(), "\"abc123\"");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_tas_get_nonce_response_too_large() {
+        let mut server = Server::new_async().await;
+        let body = format!(r#"{{"nonce": "{}"}}"#, "a".repeat(limits::response_bytes()));
+        let _mock = server
+            .mock("GET", "/kb/v0/get_nonce")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let cert_file = create_test_cert();
+        let cert_path = cert_file.path().to_path_buf();
+        let result =
+            tas_get_nonce(&server.url(), "test_api_key", cert_path, &no_retry_config()).await;
+
+        let err = result.unwrap_err();
+        assert!(err.contains("exceeds the limit"), "{}", err);
+    }
+
     #[tokio::test]
     async fn test_tas_get_secret_key_success() {
         let mut server = Server::new_async().await;
@@ -363,19 +871,20 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let result = tas_get_secret_key(
             &server_uri,
             api_key,
-            nonce,
-            tee_evidence,
-            tee_type,
-            policy_id,
-            wrapping_key,
             cert_path,
             &no_retry_config(),
-            false,
-            None,
+            &SecretRequest {
+                nonce,
+                tee_evidence,
+                tee_type,
+                policy_id,
+                wrapping_key,
+                ..Default::default()
+            },
         )
         .await;
 
-        assert_eq!(result.unwrap(), "\"xyz789\"");
+        assert_eq!(result.unwrap().secret_key, "\"xyz789\"");
     }
 
     #[tokio::test]
@@ -404,10 +913,12 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let result = tas_get_version(&server_uri, api_key, cert_path, &no_retry_config()).await;
 
         // Assert the result
+        let err = result.unwrap_err();
         assert_eq!(
-            result.unwrap_err(),
+            err.to_string(),
             "Error: 'version' field not found in response"
         );
+        assert!(!err.unreachable);
     }
 
     #[tokio::test]
@@ -436,8 +947,29 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let cert_path = cert_file.path().to_path_buf();
         let result = tas_get_version(&server_uri, api_key, cert_path, &no_retry_config()).await;
 
-        // Assert the result
-        assert!(result.unwrap_err().contains("Error: Received HTTP 500"));
+        // Assert the result: the server answered, so it is not unreachable
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("Error: Received HTTP 500"));
+        assert!(!err.unreachable);
+    }
+
+    #[tokio::test]
+    async fn test_tas_get_version_unreachable() {
+        // Nothing listens on a port that was just released
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let server_uri = format!("http://127.0.0.1:{}", port);
+        let cert_file = create_test_cert();
+        let cert_path = cert_file.path().to_path_buf();
+        let result =
+            tas_get_version(&server_uri, "test_api_key", cert_path, &no_retry_config()).await;
+
+        let err = result.unwrap_err();
+        assert!(err.unreachable);
+        assert!(err.request_failed);
     }
 
     #[tokio::test]
@@ -526,23 +1058,26 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let result = tas_get_secret_key(
             &server_uri,
             api_key,
-            nonce,
-            tee_evidence,
-            tee_type,
-            policy_id,
-            wrapping_key,
             cert_path,
             &no_retry_config(),
-            false,
-            None,
+            &SecretRequest {
+                nonce,
+                tee_evidence,
+                tee_type,
+                policy_id,
+                wrapping_key,
+                ..Default::default()
+            },
         )
         .await;
 
         // Assert the result
+        let err = result.unwrap_err();
         assert_eq!(
-            result.unwrap_err(),
+            err.to_string(),
             "Error: 'secret_key' field not found in response"
         );
+        assert!(!err.request_failed);
     }
 
     #[tokio::test]
@@ -570,20 +1105,23 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let result = tas_get_secret_key(
             &server_uri,
             api_key,
-            nonce,
-            tee_evidence,
-            tee_type,
-            policy_id,
-            wrapping_key,
             cert_path,
             &no_retry_config(),
-            false,
-            None,
+            &SecretRequest {
+                nonce,
+                tee_evidence,
+                tee_type,
+                policy_id,
+                wrapping_key,
+                ..Default::default()
+            },
         )
         .await;
 
         // Assert the result
-        assert!(result.unwrap_err().contains("Error: Received HTTP 500"));
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("Error: Received HTTP 500"));
+        assert!(!err.request_failed);
     }
 
     // ===== Retry-specific tests =====
@@ -613,7 +1151,7 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let cert_path = cert_file.path().to_path_buf();
         let result = tas_get_version(&server_uri, api_key, cert_path, &test_retry_config(2)).await;
 
-        assert_eq!(result.unwrap(), "\"1.0.0\"");
+        assert_eq!(result.unwrap().version, "\"1.0.0\"");
     }
 
     #[tokio::test]
@@ -641,7 +1179,7 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let cert_path = cert_file.path().to_path_buf();
         let result = tas_get_version(&server_uri, api_key, cert_path, &test_retry_config(2)).await;
 
-        assert_eq!(result.unwrap(), "\"1.0.0\"");
+        assert_eq!(result.unwrap().version, "\"1.0.0\"");
     }
 
     #[tokio::test]
@@ -686,7 +1224,10 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let result = tas_get_version(&server_uri, api_key, cert_path, &test_retry_config(2)).await;
 
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Error: Received HTTP 400"));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Error: Received HTTP 400"));
         mock.assert_async().await;
     }
 
@@ -709,7 +1250,7 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let cert_path = cert_file.path().to_path_buf();
         let result = tas_get_version(&server_uri, api_key, cert_path, &test_retry_config(2)).await;
 
-        assert_eq!(result.unwrap(), "\"2.0.0\"");
+        assert_eq!(result.unwrap().version, "\"2.0.0\"");
         mock.assert_async().await;
     }
 
@@ -733,19 +1274,21 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let result = tas_get_secret_key(
             &server_uri,
             "api_key",
-            "nonce",
-            "evidence",
-            "amd-sev-snp",
-            "policy1",
-            "wrapping",
             cert_path,
             &no_retry_config(),
-            true,
-            None,
+            &SecretRequest {
+                nonce: "nonce",
+                tee_evidence: "evidence",
+                tee_type: "amd-sev-snp",
+                policy_id: "policy1",
+                wrapping_key: "wrapping",
+                report_data_binding: true,
+                ..Default::default()
+            },
         )
         .await;
 
-        assert_eq!(result.unwrap(), r#""bound_secret""#);
+        assert_eq!(result.unwrap().secret_key, r#""bound_secret""#);
         mock.assert_async().await;
     }
 
@@ -779,19 +1322,115 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let result = tas_get_secret_key(
             &server_uri,
             "api_key",
-            "nonce",
-            "evidence",
-            "amd-sev-snp",
-            "policy1",
-            "wrapping",
             cert_path,
             &no_retry_config(),
-            true,
-            Some(&component_evidence),
+            &SecretRequest {
+                nonce: "nonce",
+                tee_evidence: "evidence",
+                tee_type: "amd-sev-snp",
+                policy_id: "policy1",
+                wrapping_key: "wrapping",
+                report_data_binding: true,
+                component_evidence: Some(&component_evidence),
+                ..Default::default()
+            },
         )
         .await;
 
-        assert_eq!(result.unwrap(), r#""gpu_secret""#);
+        assert_eq!(result.unwrap().secret_key, r#""gpu_secret""#);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_tas_get_secret_key_with_attestation_token() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/kb/v0/get_secret")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"attestation-token":"eyJ.token.sig"}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"secret_key": "token_secret"}"#)
+            .create_async()
+            .await;
+
+        let server_uri = server.url();
+        let cert_file = create_test_cert();
+        let cert_path = cert_file.path().to_path_buf();
+        let result = tas_get_secret_key(
+            &server_uri,
+            "api_key",
+            cert_path,
+            &no_retry_config(),
+            &SecretRequest {
+                nonce: "nonce",
+                tee_evidence: "evidence",
+                tee_type: "intel-tdx",
+                policy_id: "policy1",
+                wrapping_key: "wrapping",
+                report_data_binding: true,
+                attestation_token: Some("eyJ.token.sig"),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        assert_eq!(result.unwrap().secret_key, r#""token_secret""#);
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_parse_secret_response_server_attestation() {
+        let json: Value = serde_json::from_str(
+            r#"{"secret_key": "s", "attestation-result": "a.b.c", "server-attestation": "d.e.f"}"#,
+        )
+        .unwrap();
+        let response = parse_secret_response(&json).unwrap();
+        assert_eq!(response.attestation_result.as_deref(), Some("a.b.c"));
+        assert_eq!(response.server_attestation.as_deref(), Some("d.e.f"));
+
+        let json: Value = serde_json::from_str(r#"{"secret_key": "s"}"#).unwrap();
+        assert!(parse_secret_response(&json)
+            .unwrap()
+            .server_attestation
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tas_get_secret_key_with_attestation_result() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/kb/v0/get_secret")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"secret_key": "ear_secret", "attestation-result": "eyJ.ear.sig"}"#)
+            .create_async()
+            .await;
+
+        let server_uri = server.url();
+        let cert_file = create_test_cert();
+        let cert_path = cert_file.path().to_path_buf();
+        let response = tas_get_secret_key(
+            &server_uri,
+            "api_key",
+            cert_path,
+            &no_retry_config(),
+            &SecretRequest {
+                nonce: "nonce",
+                tee_evidence: "evidence",
+                tee_type: "intel-tdx",
+                policy_id: "policy1",
+                wrapping_key: "wrapping",
+                report_data_binding: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.secret_key, r#""ear_secret""#);
+        assert_eq!(response.attestation_result.as_deref(), Some("eyJ.ear.sig"));
         mock.assert_async().await;
     }
 
@@ -812,19 +1451,20 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let result = tas_get_secret_key(
             &server_uri,
             "api_key",
-            "nonce",
-            "evidence",
-            "amd-sev-snp",
-            "policy1",
-            "wrapping",
             cert_path,
             &no_retry_config(),
-            false,
-            None,
+            &SecretRequest {
+                nonce: "nonce",
+                tee_evidence: "evidence",
+                tee_type: "amd-sev-snp",
+                policy_id: "policy1",
+                wrapping_key: "wrapping",
+                ..Default::default()
+            },
         )
         .await;
 
-        assert_eq!(result.unwrap(), r#""plain_secret""#);
+        assert_eq!(result.unwrap().secret_key, r#""plain_secret""#);
         mock.assert_async().await;
     }
     // --- JSON request / response structure tests ---
@@ -854,15 +1494,16 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let _ = tas_get_secret_key(
             &server.url(),
             "key",
-            "abc123",
-            "evidence",
-            "amd-sev-snp",
-            "policy1",
-            "wrapping",
             cert_file.path().to_path_buf(),
             &no_retry_config(),
-            false,
-            None,
+            &SecretRequest {
+                nonce: "abc123",
+                tee_evidence: "evidence",
+                tee_type: "amd-sev-snp",
+                policy_id: "policy1",
+                wrapping_key: "wrapping",
+                ..Default::default()
+            },
         )
         .await;
 
@@ -887,15 +1528,17 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let _ = tas_get_secret_key(
             &server.url(),
             "key",
-            "nonce",
-            "evidence",
-            "amd-sev-snp",
-            "key1",
-            "wrapping",
             cert_file.path().to_path_buf(),
             &no_retry_config(),
-            true, // report_data_binding
-            None,
+            &SecretRequest {
+                nonce: "nonce",
+                tee_evidence: "evidence",
+                tee_type: "amd-sev-snp",
+                policy_id: "key1",
+                wrapping_key: "wrapping",
+                report_data_binding: true,
+                ..Default::default()
+            },
         )
         .await;
 
@@ -924,15 +1567,16 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let result = tas_get_secret_key(
             &server.url(),
             "key",
-            "nonce",
-            "evidence",
-            "amd-sev-snp",
-            "policy1",
-            "wrapping",
             cert_file.path().to_path_buf(),
             &no_retry_config(),
-            false, // report_data_binding must not add the field
-            None,
+            &SecretRequest {
+                nonce: "nonce",
+                tee_evidence: "evidence",
+                tee_type: "amd-sev-snp",
+                policy_id: "policy1",
+                wrapping_key: "wrapping",
+                ..Default::default()
+            },
         )
         .await;
 
@@ -966,15 +1610,17 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let _ = tas_get_secret_key(
             &server.url(),
             "key",
-            "nonce",
-            "evidence",
-            "amd-sev-snp",
-            "policy1",
-            "wrapping",
             cert_file.path().to_path_buf(),
             &no_retry_config(),
-            false,
-            Some(&component_evidence),
+            &SecretRequest {
+                nonce: "nonce",
+                tee_evidence: "evidence",
+                tee_type: "amd-sev-snp",
+                policy_id: "policy1",
+                wrapping_key: "wrapping",
+                component_evidence: Some(&component_evidence),
+                ..Default::default()
+            },
         )
         .await;
 
@@ -1001,7 +1647,7 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
             &no_retry_config(),
         )
         .await;
-        assert_eq!(result.unwrap(), r#""2.0.0""#);
+        assert_eq!(result.unwrap().version, r#""2.0.0""#);
     }
 
     #[tokio::test]
@@ -1043,17 +1689,179 @@ MRYTnHVgon3F8Lk6ZsKGQ27CXYFMt9iIUAmkg6LmbJDqNR8NLqigo+Nfhq4rPUfP
         let result = tas_get_secret_key(
             &server.url(),
             "key",
-            "nonce",
-            "evidence",
-            "amd-sev-snp",
-            "key1",
-            "wrapping",
             cert_file.path().to_path_buf(),
             &no_retry_config(),
-            false,
-            None,
+            &SecretRequest {
+                nonce: "nonce",
+                tee_evidence: "evidence",
+                tee_type: "amd-sev-snp",
+                policy_id: "key1",
+                wrapping_key: "wrapping",
+                ..Default::default()
+            },
+        )
+        .await;
+        assert_eq!(result.unwrap().secret_key, r#""base64encryptedkey""#);
+    }
+
+    // --- golden request bodies ---
+
+    const GOLDEN_NONCE: &str =
+        "\"0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef\"";
+    const GOLDEN_EVIDENCE: &str = "cmVwb3J0+/8=";
+
+    /// The get_secret body for the golden inputs, serialized as sent.
+    fn golden_request(components: Option<&Value>, token: Option<&str>) -> String {
+        let wrapping_key = crate::golden::read("wrapping-key.b64");
+        let body = SecretRequest {
+            nonce: GOLDEN_NONCE,
+            tee_evidence: GOLDEN_EVIDENCE,
+            tee_type: "amd-sev-snp",
+            policy_id: "policy-1",
+            wrapping_key: &wrapping_key,
+            report_data_binding: true,
+            component_evidence: components,
+            attestation_token: token,
+            ..Default::default()
+        };
+        serde_json::to_string(&body).unwrap()
+    }
+
+    #[test]
+    fn test_secret_request_golden() {
+        crate::golden::assert_golden("get_secret.json", &golden_request(None, None));
+
+        let components = serde_json::json!({
+            "gpu": [{"device_type": "gpu-nvidia", "device_index": 0, "evidence": "Z3B1"}]
+        });
+        let token = "eyJhbGciOiJSUzI1NiJ9.e30.c2ln";
+        crate::golden::assert_golden(
+            "get_secret-verifier.json",
+            &golden_request(Some(&components), Some(token)),
+        );
+    }
+
+    #[test]
+    fn test_secret_request_nonce_construction() {
+        let body = SecretRequest {
+            nonce: "abc",
+            tee_evidence: GOLDEN_EVIDENCE,
+            tee_type: "amd-sev-snp",
+            policy_id: "policy-1",
+            wrapping_key: "a2V5",
+            report_data_binding: true,
+            nonce_construction: Some("sha512"),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&body).unwrap();
+        assert!(
+            json.contains(r#""nonce":"abc","nonce-construction":"sha512","policy-id":"policy-1""#),
+            "{}",
+            json
+        );
+    }
+
+    #[test]
+    fn test_secret_request_wrap_algorithm() {
+        let body = SecretRequest {
+            nonce: "abc",
+            tee_evidence: GOLDEN_EVIDENCE,
+            tee_type: "amd-sev-snp",
+            policy_id: "policy-1",
+            wrapping_key: "a2V5",
+            report_data_binding: true,
+            wrap_algorithm: Some("HPKE"),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&body).unwrap();
+        assert!(
+            json.ends_with(r#""wrap-algorithm":"HPKE","wrapping-key":"a2V5"}"#),
+            "{}",
+            json
+        );
+    }
+
+    #[test]
+    fn test_secret_request_claims() {
+        let claims = BTreeMap::from([
+            ("workload_id".to_string(), "payments-api".to_string()),
+            ("environment".to_string(), "production".to_string()),
+        ]);
+        let body = SecretRequest {
+            nonce: "abc",
+            tee_evidence: GOLDEN_EVIDENCE,
+            tee_type: "amd-sev-snp",
+            policy_id: "policy-1",
+            wrapping_key: "a2V5",
+            report_data_binding: true,
+            claims: Some(&claims),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&body).unwrap();
+        assert!(
+            json.starts_with(
+                r#"{"claims":{"environment":"production","workload_id":"payments-api"},"nonce""#
+            ),
+            "{}",
+            json
+        );
+    }
+
+    #[test]
+    fn test_secret_request_local_entropy() {
+        let body = SecretRequest {
+            nonce: "abc",
+            tee_evidence: GOLDEN_EVIDENCE,
+            tee_type: "amd-sev-snp",
+            policy_id: "policy-1",
+            wrapping_key: "a2V5",
+            report_data_binding: true,
+            nonce_construction: Some("sha512-local-entropy"),
+            local_entropy: Some("cmFuZG9t"),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&body).unwrap();
+        assert!(
+            json.starts_with(
+                r#"{"local-entropy":"cmFuZG9t","nonce":"abc","nonce-construction":"sha512-local-entropy""#
+            ),
+            "{}",
+            json
+        );
+    }
+
+    #[tokio::test]
+    async fn test_secret_request_wire_golden() {
+        // The bytes on the wire, not just the JSON value, must match
+        let mut server = Server::new_async().await;
+        let _mock = server
+            .mock("POST", "/kb/v0/get_secret")
+            .match_body(mockito::Matcher::Exact(crate::golden::read(
+                "get_secret.json",
+            )))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"secret_key":"x"}"#)
+            .create_async()
+            .await;
+
+        let wrapping_key = crate::golden::read("wrapping-key.b64");
+        let result = tas_get_secret_key(
+            &server.url(),
+            "key",
+            PathBuf::new(),
+            &no_retry_config(),
+            &SecretRequest {
+                nonce: GOLDEN_NONCE,
+                tee_evidence: GOLDEN_EVIDENCE,
+                tee_type: "amd-sev-snp",
+                policy_id: "policy-1",
+                wrapping_key: &wrapping_key,
+                report_data_binding: true,
+                ..Default::default()
+            },
         )
         .await;
-        assert_eq!(result.unwrap(), r#""base64encryptedkey""#);
+        assert!(result.is_ok(), "{:?}", result);
     }
 }
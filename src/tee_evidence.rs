@@ -7,37 +7,418 @@
 //
 // TEE Evidence gathering functionality.
 //
-use base64::{engine::general_purpose, Engine};
-use log::debug;
+// Reports are requested through configfs-tsm, optionally through the SVSM
+// (see README "TSM Report Directory" and "SVSM Guests"). Transient errors
+// are retried, and agent instances running at the same time take turns on
+// `[tsm] lock_path`.
+//
+use crate::evidence::{Evidence, TeeType, SVSM_TEE_TYPE};
+use log::{debug, warn};
+use rustix::fs::{flock, FlockOperation};
+use rustix::io::Errno;
+use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::fs;
-use tempfile::{tempdir_in, TempDir};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tempfile::tempdir_in;
+
+/// Environment variable naming the evidence fixture directory.
+#[cfg(feature = "evidence-fixtures")]
+pub const FIXTURE_ENV: &str = "TAS_AGENT_EVIDENCE_FIXTURE";
+
+/// Outblob buffer, kept across evidence requests so the daemon's repeated
+/// attestations read reports without reallocating.
+static OUTBLOB_BUFFER: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+/// When to request SEV-SNP reports through the SVSM.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SvsmMode {
+    /// When the guest runs under an SVSM
+    #[default]
+    Auto,
+    /// Always; fails on kernels without SVSM support
+    Always,
+    /// Never; request reports from the SEV guest driver directly
+    Never,
+}
+
+/// Default configfs-tsm report directory.
+pub const DEFAULT_REPORT_DIR: &str = "/sys/kernel/config/tsm/report";
+
+/// Default file locked by agent instances while they generate a report.
+pub const DEFAULT_LOCK_PATH: &str = "/run/tas_agent/tsm.lock";
+
+/// `[tsm]` section of the config file.
+#[derive(Deserialize, Clone, Default)]
+pub struct TsmConfig {
+    /// configfs-tsm report directory (default: /sys/kernel/config/tsm/report)
+    pub report_dir: Option<PathBuf>,
+    /// TEE types of providers, overriding the built-in mapping
+    #[serde(default)]
+    pub providers: BTreeMap<String, String>,
+    /// Privilege level of SEV-SNP reports (default: the guest's VMPL)
+    pub privlevel: Option<u32>,
+    /// Request SEV-SNP reports through the SVSM (default: auto)
+    pub svsm: Option<SvsmMode>,
+    /// Attest only this SVSM service, e.g. the vTPM
+    pub svsm_service_guid: Option<String>,
+    /// Version of the service manifest to request
+    pub svsm_manifest_version: Option<u32>,
+    /// TEE type to submit the evidence as, instead of the detected one
+    pub tee_type: Option<String>,
+    /// File locked while generating a report (default: /run/tas_agent/tsm.lock)
+    pub lock_path: Option<PathBuf>,
+}
+
+/// configfs-tsm settings, fixed by the first config file loaded.
+static TSM_CONFIG: OnceLock<TsmConfig> = OnceLock::new();
+
+/// Apply the `[tsm]` settings to all later evidence requests.
+pub fn configure(config: TsmConfig) {
+    if TSM_CONFIG.set(config).is_err() {
+        debug!("configfs-tsm settings already applied");
+    }
+}
+
+fn tsm_config() -> &'static TsmConfig {
+    TSM_CONFIG.get_or_init(TsmConfig::default)
+}
+
+/// TEE type forced on the command line.
+static FORCED_TEE_TYPE: OnceLock<String> = OnceLock::new();
+
+/// Submit all later evidence as `tee_type`, overriding `[tsm] tee_type`.
+pub fn force_tee_type(tee_type: String) {
+    let _ = FORCED_TEE_TYPE.set(tee_type);
+}
+
+// The TEE type forced on the command line or in the config, if any.
+fn forced_tee_type() -> Option<&'static str> {
+    FORCED_TEE_TYPE
+        .get()
+        .or(tsm_config().tee_type.as_ref())
+        .map(String::as_str)
+}
+
+impl TsmConfig {
+    pub fn report_dir(&self) -> &Path {
+        self.report_dir
+            .as_deref()
+            .unwrap_or(Path::new(DEFAULT_REPORT_DIR))
+    }
+
+    pub fn lock_path(&self) -> &Path {
+        self.lock_path
+            .as_deref()
+            .unwrap_or(Path::new(DEFAULT_LOCK_PATH))
+    }
+}
+
+// Wait for the other agent instances to finish their reports and lock
+// `path`; the lock is held until the file is dropped. None if the lock
+// cannot be taken: it is advisory, and the generation check still catches
+// interleaved writers.
+fn lock_reports(path: &Path) -> Option<File> {
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let locked = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(path)
+        .and_then(|file| {
+            flock(&file, FlockOperation::LockExclusive)?;
+            Ok(file)
+        });
+    match locked {
+        Ok(file) => {
+            debug!("Holding {:?}", path);
+            Some(file)
+        }
+        Err(err) => {
+            warn!(
+                "Unable to lock {:?}, generating the report without it: {}",
+                path, err
+            );
+            None
+        }
+    }
+}
+
+// TEE type of the configfs-tsm `provider`, from `overrides` or the
+// built-in mapping.
+fn tee_type_for(provider: &str, overrides: &BTreeMap<String, String>) -> Option<String> {
+    if let Some(tee_type) = overrides.get(provider) {
+        return Some(tee_type.clone());
+    }
+    match provider {
+        "sev_guest" => Some("amd-sev-snp".to_string()),
+        "tdx_guest" => Some("intel-tdx".to_string()),
+        _ => None,
+    }
+}
 
 // TODO : implement own error handling, use boxed errors for now
 
 // Internal function to determine the TEE type
 // This function returns the TEE type as a string (e.g., "amd-sev-snp").
-fn get_tee_type(tsm_report_dir: &TempDir) -> Result<String, Box<dyn Error>> {
+// An unknown provider is taken to be of the `forced` type, if any.
+fn get_tee_type(
+    tsm_report_dir: impl AsRef<Path>,
+    forced: Option<&str>,
+) -> Result<String, Box<dyn Error>> {
     // determine TEE type dynamically using tsm report/provider
-    let provider = fs::read_to_string(tsm_report_dir.path().join("provider"))?;
+    let provider = fs::read_to_string(tsm_report_dir.as_ref().join("provider"))?;
 
     debug!("TSM provider: {}", provider.trim());
-    match provider.trim() {
-        "sev_guest" => {
-            debug!("Determined TEE type: amd-sev-snp");
-            Ok("amd-sev-snp".to_string())
+    match tee_type_for(provider.trim(), &tsm_config().providers) {
+        Some(tee_type) => {
+            debug!("Determined TEE type: {}", tee_type);
+            Ok(tee_type)
         }
-        "tdx_guest" => {
-            debug!("Determined TEE type: intel-tdx");
-            Ok("intel-tdx".to_string())
+        None => match forced {
+            Some(tee_type) => {
+                debug!(
+                    "Unknown TEE provider {}, using forced TEE type {}",
+                    provider.trim(),
+                    tee_type
+                );
+                Ok(tee_type.to_string())
+            }
+            None => {
+                debug!("Unknown TEE provider: {}", provider.trim());
+                Err(format!("Unknown TEE provider: {}", provider.trim()).into())
+            }
+        },
+    }
+}
+
+// Expected outblob size for `tee_type`, used when configfs reports no file
+// size: an SNP attestation report is 1184 bytes, a TDX quote with its
+// certification data typically stays below 8 KiB.
+fn outblob_size_hint(tee_type: &str) -> usize {
+    match tee_type {
+        "amd-sev-snp" | SVSM_TEE_TYPE => 0x4a0,
+        _ => 8 * 1024,
+    }
+}
+
+// Read `path` into `buf`, replacing its contents. Capacity for the file size
+// reported by fstat, or `hint` if larger, is reserved up front so the read
+// does not go through `fs::read`'s growth pattern; a buffer already large
+// enough from an earlier read is reused as is.
+fn read_into(path: &Path, buf: &mut Vec<u8>, hint: usize) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let size = file.metadata().map(|m| m.len() as usize).unwrap_or(0);
+    buf.clear();
+    buf.reserve(size.max(hint));
+    file.read_to_end(buf)?;
+    Ok(())
+}
+
+// Attempts at reading a report that other writers keep modifying.
+const GENERATION_ATTEMPTS: u32 = 3;
+
+// Attempts at an operation on a report attribute that fails transiently.
+const TRANSIENT_ATTEMPTS: u32 = 5;
+
+// Delay before the first retry of a transient error, doubled after each.
+const TRANSIENT_BACKOFF: Duration = Duration::from_millis(20);
+
+// Whether `err` may go away on retry: the report is busy, or the TSM driver
+// failed to get a response from the firmware.
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        Errno::from_io_error(err),
+        Some(Errno::BUSY | Errno::AGAIN | Errno::INTR | Errno::IO)
+    )
+}
+
+// Run `op` on the report `attribute`, retrying transient errors with
+// exponential backoff starting at `backoff`.
+fn with_retries<T>(
+    attribute: &str,
+    backoff: Duration,
+    mut op: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut delay = backoff;
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(err) if is_transient(&err) && attempt < TRANSIENT_ATTEMPTS => {
+                warn!(
+                    "configfs-tsm {}: {} (attempt {} of {}); retrying in {:?}",
+                    attribute, err, attempt, TRANSIENT_ATTEMPTS, delay
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(err) if attempt > 1 => {
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!("{} (after {} attempts)", err, attempt),
+                ))
+            }
+            result => return result,
+        }
+    }
+}
+
+// Write `contents` to the report `attribute` in `report_dir`, retrying
+// transient errors.
+fn write_attribute(report_dir: &Path, attribute: &str, contents: &[u8]) -> io::Result<()> {
+    let path = report_dir.join(attribute);
+    with_retries(attribute, TRANSIENT_BACKOFF, || fs::write(&path, contents))
+}
+
+// The `generation` of the report at `report_dir`, if the kernel has one.
+fn read_generation(report_dir: &Path) -> Result<Option<u64>, Box<dyn Error>> {
+    match fs::read_to_string(report_dir.join("generation")) {
+        Ok(generation) => Ok(Some(generation.trim().parse()?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Write the report request: inblob, the SEV-SNP privilege level and the
+// SVSM service. Returns the number of attributes written.
+fn write_request(
+    report_dir: &Path,
+    tee_type: &str,
+    inblob: &[u8],
+    svsm: bool,
+    config: &TsmConfig,
+) -> Result<u64, String> {
+    let mut writes = 0;
+
+    // Write inblob (report_data or nonce) to inblob file
+    write_attribute(report_dir, "inblob", inblob)
+        .map_err(|err| format!("Failed to write to inblob file: {}", err))?;
+    writes += 1;
+    debug!("Wrote to inblob file at: {:?}", report_dir.join("inblob"));
+
+    // if SEV get VMPL level dynamically else skip this step
+    if tee_type == "amd-sev-snp" {
+        debug!("TEE type is SEV-SNP, setting VMPL level");
+        // Set VMPL level
+        let vmpl = privlevel(report_dir, config)?;
+        write_attribute(report_dir, "privlevel", vmpl.to_string().as_bytes())
+            .map_err(|err| format!("Failed to set VMPL: {}", err))?;
+        writes += 1;
+        debug!("Set VMPL level to: {}", vmpl);
+    } else {
+        debug!("TEE type is not SEV-SNP, skipping VMPL level setting");
+    }
+
+    if svsm {
+        debug!("Requesting the report through the SVSM");
+        writes += request_svsm(report_dir, config)
+            .map_err(|err| format!("Failed to select the SVSM service: {}", err))?;
+    }
+    Ok(writes)
+}
+
+// Whether the report at `report_dir` is to be requested through the SVSM.
+fn use_svsm(report_dir: &Path, mode: SvsmMode) -> Result<bool, Box<dyn Error>> {
+    let available = report_dir.join("service_provider").exists();
+    match mode {
+        SvsmMode::Never => Ok(false),
+        SvsmMode::Always if !available => {
+            Err("the kernel does not support SVSM attestation (no service_provider)".into())
         }
-        other => {
-            debug!("Unknown TEE provider: {}", other);
-            Err(format!("Unknown TEE provider: {}", other).into())
+        SvsmMode::Always => Ok(true),
+        SvsmMode::Auto => {
+            let floor = read_privlevel_floor(report_dir)?;
+            debug!("privlevel_floor: {}", floor);
+            Ok(available && floor > 0)
         }
     }
 }
 
+// The lowest privilege level the report at `report_dir` can be requested
+// for. Older kernels have no privlevel_floor: no SVSM below the guest.
+fn read_privlevel_floor(report_dir: &Path) -> Result<u32, Box<dyn Error>> {
+    match fs::read_to_string(report_dir.join("privlevel_floor")) {
+        Ok(floor) => Ok(floor.trim().parse()?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// The SEV-SNP privilege level to request: `[tsm] privlevel` or the guest's
+// VMPL, which must not be below the report's privlevel_floor.
+fn privlevel(report_dir: &Path, config: &TsmConfig) -> Result<u32, String> {
+    let level = match config.privlevel {
+        Some(level) => level,
+        None => get_vmpl()
+            .map_err(|err| format!("Failed to get VMPL: {}", err))?
+            .trim()
+            .parse()
+            .map_err(|err| format!("Invalid VMPL: {}", err))?,
+    };
+    let floor = read_privlevel_floor(report_dir)
+        .map_err(|err| format!("Failed to read privlevel_floor: {}", err))?;
+    if level < floor {
+        return Err(format!(
+            "Privilege level {} is below the report's privlevel_floor {}; \
+             set [tsm] privlevel to at least {}",
+            level, floor, floor
+        ));
+    }
+    Ok(level)
+}
+
+// Select the SVSM service provider and, if configured, the service.
+// Returns the number of attributes written.
+fn request_svsm(report_dir: &Path, config: &TsmConfig) -> io::Result<u64> {
+    write_attribute(report_dir, "service_provider", b"svsm")?;
+    let mut writes = 1;
+    if let Some(guid) = &config.svsm_service_guid {
+        write_attribute(report_dir, "service_guid", guid.as_bytes())?;
+        writes += 1;
+    }
+    if let Some(version) = config.svsm_manifest_version {
+        write_attribute(
+            report_dir,
+            "service_manifest_version",
+            version.to_string().as_bytes(),
+        )?;
+        writes += 1;
+    }
+    Ok(writes)
+}
+
+// Evidence of an SVSM report and its services manifest, sent as base64
+// JSON because the SVSM sets the report data to SHA-512(inblob || manifest):
+//
+//   {"report": "<base64>", "manifest": "<base64>",
+//    "service_guid": "<guid>" | null, "manifest_version": <n> | null}
+fn svsm_evidence(report: &[u8], manifest: &[u8], config: &TsmConfig) -> Evidence {
+    let mut evidence = Evidence::new(
+        submitted_tee_type(SVSM_TEE_TYPE.to_string()),
+        report.to_vec(),
+    );
+    evidence.aux = Some(manifest.to_vec());
+    evidence.claims.insert(
+        "service_guid".to_string(),
+        config.svsm_service_guid.clone().into(),
+    );
+    evidence.claims.insert(
+        "manifest_version".to_string(),
+        config.svsm_manifest_version.into(),
+    );
+    evidence
+}
+
 // Returns the VMPL level of the current process.
 //
 // This function reads the VMPL level from the `/sys/devices/system/cpu/sev/vmpl` file and returns
@@ -60,11 +441,13 @@ fn get_vmpl() -> Result<String, Box<dyn Error>> {
 /// Requires config_tsm to be enabled in the kernel.
 ///
 /// # Arguments
-/// * `nonce` - A string slice that holds the nonce value (must be exactly 64 bytes long)
+/// * `nonce` - A string slice that holds the nonce value (must be exactly 64 bytes long
+///   when written to inblob, i.e. without `report_data`)
 /// * `report_data` - Optional raw bytes (must be exactly 64 bytes) to write to inblob
 ///   instead of the nonce string. When `Some`, enables the caller to bind the RSA public
-///   key (and optional GPU evidence hashes) into the TEE report via
-///   `SHA-512(nonce || pubkey_der [|| gpu_hashes])`. When `None`, the original
+///   key (and optional GPU evidence hashes and evidence signing key) into the TEE
+///   report via `SHA-512(nonce || pubkey_der [|| gpu_hashes] [|| signing_key])`
+///   (see custody.rs for the signing key). When `None`, the original
 ///   nonce-as-string behaviour is used.
 ///
 ///   When GPU attestation is enabled, `gpu_hashes` is constructed as follows:
@@ -75,27 +458,17 @@ fn get_vmpl() -> Result<String, Box<dyn Error>> {
 ///      `SHA-512(nonce || pubkey_der || gpu_hashes)`
 ///
 /// # Returns
-/// * `Result<(String, String), String>` - On success, returns a tuple containing the
-///   Base64-encoded TEE evidence and the TEE type. On failure, returns an error message.
-pub fn tee_get_evidence(
-    nonce: &str,
-    report_data: Option<&[u8]>,
-) -> Result<(String, String), String> {
-    // Setup temp_dir_path to the config tsm report path
-    let temp_dir_path = "/sys/kernel/config/tsm/report";
+/// * `Result<Evidence, String>` - On success, returns the evidence with its TEE type;
+///   `Evidence::encode` gives the Base64-encoded form sent to TAS. On failure,
+///   returns an error message.
+pub fn tee_get_evidence(nonce: &str, report_data: Option<&[u8]>) -> Result<Evidence, String> {
+    let config = tsm_config();
 
     // Strip the nonce of any surrounding quotes
     let nonce = nonce.trim_matches('"');
-    // Ensure the nonce is exactly 64 bytes long
-    let nonce_bytes = nonce.as_bytes();
-    if nonce_bytes.len() != 64 {
-        return Err(format!(
-            "Error: Nonce must be exactly 64 bytes long, but it is {} bytes",
-            nonce_bytes.len()
-        ));
-    }
 
-    // Determine what to write to inblob: custom report_data or the nonce string
+    // Determine what to write to inblob: custom report_data or the nonce string.
+    // The report data may bind a hashed nonce of any length.
     let inblob_bytes: Vec<u8> = match report_data {
         Some(rd) => {
             if rd.len() != 64 {
@@ -106,62 +479,350 @@ pub fn tee_get_evidence(
             }
             rd.to_vec()
         }
-        None => nonce.as_bytes().to_vec(),
+        None => {
+            // Ensure the nonce is exactly 64 bytes long
+            let nonce_bytes = nonce.as_bytes();
+            if nonce_bytes.len() != 64 {
+                return Err(format!(
+                    "Error: Nonce must be exactly 64 bytes long, but it is {} bytes",
+                    nonce_bytes.len()
+                ));
+            }
+            nonce_bytes.to_vec()
+        }
     };
 
+    #[cfg(feature = "evidence-fixtures")]
+    if let Some(dir) = std::env::var_os(FIXTURE_ENV) {
+        let (tee_report, tee_type) = fixture_evidence(Path::new(&dir), &inblob_bytes)?;
+        return Ok(Evidence::new(submitted_tee_type(tee_type), tee_report));
+    }
+
+    // Keep other agent instances out of configfs-tsm until the report is read
+    let _lock = lock_reports(config.lock_path());
+
     // Attempt to create a temporary directory inside the specified path
-    let tmp_dir = tempdir_in(temp_dir_path)
+    let tmp_dir = tempdir_in(config.report_dir())
         .map_err(|err| format!("Failed to create temp directory: {}", err))?;
     debug!("Temp dir created at: {:?}", tmp_dir.path());
     debug!("Inblob bytes (hex): {}", hex::encode(&inblob_bytes));
 
     // Determine TEE type
-    let tee_type =
-        get_tee_type(&tmp_dir).map_err(|err| format!("Failed to determine TEE type: {}", err))?;
-
-    // Write inblob (report_data or nonce) to inblob file
-    let inblob_file_path = tmp_dir.path().join("inblob");
-    fs::write(&inblob_file_path, &inblob_bytes)
-        .map_err(|err| format!("Failed to write to inblob file: {}", err))?;
-    debug!("Wrote to inblob file at: {:?}", inblob_file_path);
+    let tee_type = get_tee_type(&tmp_dir, forced_tee_type())
+        .map_err(|err| format!("Failed to determine TEE type: {}", err))?;
 
-    // if SEV get VMPL level dynamically else skip this step
-    if tee_type == "amd-sev-snp" {
-        debug!("TEE type is SEV-SNP, setting VMPL level");
-        // Set VMPL level
-        let vmpl = get_vmpl().map_err(|err| format!("Failed to get VMPL: {}", err))?;
-        let privlevel_path = tmp_dir.path().join("privlevel");
-        fs::write(privlevel_path, &vmpl).map_err(|err| format!("Failed to set VMPL: {}", err))?;
-        debug!("Set VMPL level to: {}", vmpl);
-    } else {
-        debug!("TEE type is not SEV-SNP, skipping VMPL level setting");
-    }
+    let svsm = tee_type == "amd-sev-snp"
+        && use_svsm(tmp_dir.path(), config.svsm.unwrap_or_default())
+            .map_err(|err| format!("Failed to detect SVSM: {}", err))?;
 
-    // Read outblob file
-    let outblob_file_path = tmp_dir.path().join("outblob");
-    debug!("Reading outblob file at: {:?}", outblob_file_path);
+    let mut tee_report = OUTBLOB_BUFFER.lock().unwrap_or_else(|e| e.into_inner());
+    let mut manifest = Vec::new();
+    let mut attempt = 1;
+    loop {
+        let generation = read_generation(tmp_dir.path())
+            .map_err(|err| format!("Failed to read report generation: {}", err))?;
+        let writes = write_request(tmp_dir.path(), &tee_type, &inblob_bytes, svsm, config)?;
 
-    let tee_report = fs::read(&outblob_file_path)
+        // Read outblob file
+        let outblob_file_path = tmp_dir.path().join("outblob");
+        debug!("Reading outblob file at: {:?}", outblob_file_path);
+        with_retries("outblob", TRANSIENT_BACKOFF, || {
+            read_into(
+                &outblob_file_path,
+                &mut tee_report,
+                outblob_size_hint(&tee_type),
+            )
+        })
         .map_err(|err| format!("Failed to read outblob file: {}", err))?;
+        if svsm {
+            let manifest_path = tmp_dir.path().join("manifest");
+            manifest = with_retries("manifest", TRANSIENT_BACKOFF, || fs::read(&manifest_path))
+                .map_err(|err| format!("Failed to read SVSM manifest: {}", err))?;
+        }
+
+        // Every write bumps the generation; any other writer in between
+        // means the report may be over someone else's inblob
+        let after = read_generation(tmp_dir.path())
+            .map_err(|err| format!("Failed to read report generation: {}", err))?;
+        match (generation, after) {
+            (Some(before), Some(after)) if after != before + writes => {
+                if attempt == GENERATION_ATTEMPTS {
+                    return Err(format!(
+                        "configfs-tsm report was modified by another writer {} times",
+                        attempt
+                    ));
+                }
+                warn!(
+                    "configfs-tsm report generation is {}, expected {}; retrying",
+                    after,
+                    before + writes
+                );
+                attempt += 1;
+            }
+            _ => break,
+        }
+    }
+
+    if svsm {
+        drop(tmp_dir);
+        return Ok(svsm_evidence(&tee_report, &manifest, config));
+    }
 
     // Drop the temporary directory
     drop(tmp_dir);
     debug!("Temp dir dropped");
 
-    // Base64 encode the SNP report using Engine::encode
-    let encoded_report = general_purpose::STANDARD.encode(&tee_report);
+    Ok(Evidence::new(
+        submitted_tee_type(tee_type),
+        tee_report.to_vec(),
+    ))
+}
+
+// The TEE type to submit evidence of the `detected` type as.
+fn submitted_tee_type(detected: String) -> TeeType {
+    match forced_tee_type() {
+        Some(forced) if forced != detected => {
+            debug!("Submitting {} evidence as {}", detected, forced);
+            TeeType::from(forced)
+        }
+        _ => TeeType::from(detected),
+    }
+}
+
+/// Read canned evidence from the fixture directory `dir` and patch
+/// `inblob` into its report data, which invalidates the signature. Never
+/// enable the feature in production builds.
+#[cfg(feature = "evidence-fixtures")]
+fn fixture_evidence(dir: &Path, inblob: &[u8]) -> Result<(Vec<u8>, String), String> {
+    log::warn!("Using fixture evidence from {:?}; it is not genuine", dir);
+    let tee_type = get_tee_type(dir, forced_tee_type())
+        .map_err(|err| format!("Failed to determine TEE type: {}", err))?;
+    let mut report = fs::read(dir.join("outblob"))
+        .map_err(|err| format!("Failed to read fixture outblob: {}", err))?;
 
-    Ok((encoded_report, tee_type))
+    let offset = match tee_type.as_str() {
+        "amd-sev-snp" => 0x50,
+        // REPORTDATA is at the end of the TD report body
+        _ => crate::corim::tdx_body(&report).map_err(|err| format!("{}", err))? + 520,
+    };
+    report
+        .get_mut(offset..offset + inblob.len())
+        .ok_or_else(|| "Fixture outblob is truncated".to_string())?
+        .copy_from_slice(inblob);
+    Ok((report, tee_type))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use base64::{engine::general_purpose, Engine};
     use std::fs;
     use tempfile::tempdir;
 
+    // --- outblob reads ---
+
+    #[test]
+    fn test_read_into_reuses_buffer() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("outblob");
+        let mut buf = Vec::new();
+
+        fs::write(&path, vec![0xaau8; 0x4a0]).unwrap();
+        read_into(&path, &mut buf, outblob_size_hint("amd-sev-snp")).unwrap();
+        assert_eq!(buf, vec![0xaau8; 0x4a0]);
+        let (ptr, capacity) = (buf.as_ptr(), buf.capacity());
+
+        fs::write(&path, b"short").unwrap();
+        read_into(&path, &mut buf, 0).unwrap();
+        assert_eq!(buf, b"short");
+        assert_eq!((buf.as_ptr(), buf.capacity()), (ptr, capacity));
+
+        // Larger than the hint: the buffer grows and keeps all the data
+        fs::write(&path, vec![0x55u8; 3 * 0x4a0]).unwrap();
+        read_into(&path, &mut buf, outblob_size_hint("amd-sev-snp")).unwrap();
+        assert_eq!(buf, vec![0x55u8; 3 * 0x4a0]);
+
+        assert!(read_into(&dir.path().join("missing"), &mut buf, 0).is_err());
+    }
+
+    // --- transient errors ---
+
+    #[test]
+    fn test_with_retries() {
+        let busy = || io::Error::from(Errno::BUSY);
+        let backoff = Duration::from_millis(1);
+
+        let mut calls = 0;
+        let result = with_retries("inblob", backoff, || {
+            calls += 1;
+            if calls < 3 {
+                Err(busy())
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let mut calls = 0;
+        let err = with_retries("outblob", backoff, || -> io::Result<()> {
+            calls += 1;
+            Err(busy())
+        })
+        .unwrap_err();
+        assert_eq!(calls, TRANSIENT_ATTEMPTS);
+        assert!(err.to_string().ends_with("(after 5 attempts)"), "{}", err);
+
+        // Permanent errors are not retried
+        let mut calls = 0;
+        let err = with_retries("privlevel", backoff, || -> io::Result<()> {
+            calls += 1;
+            Err(io::Error::from(Errno::INVAL))
+        })
+        .unwrap_err();
+        assert_eq!(calls, 1);
+        assert_eq!(err.raw_os_error(), Some(Errno::INVAL.raw_os_error()));
+        assert!(is_transient(&io::Error::from(Errno::IO)));
+        assert!(!is_transient(&io::Error::from(io::ErrorKind::NotFound)));
+    }
+
+    #[test]
+    fn test_lock_reports() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("run/tsm.lock");
+        let lock = lock_reports(&path);
+        assert!(lock.is_some());
+        assert!(path.is_file());
+        drop(lock);
+        // Relocking after release does not block
+        assert!(lock_reports(&path).is_some());
+
+        // Without the lock, the report is generated anyway
+        let blocked = path.join("tsm.lock");
+        assert!(lock_reports(&blocked).is_none());
+    }
+
+    // --- report generation ---
+
+    #[test]
+    fn test_write_request_counts_writes() {
+        let dir = tempdir().unwrap();
+        assert_eq!(read_generation(dir.path()).unwrap(), None);
+        fs::write(dir.path().join("generation"), "7\n").unwrap();
+        assert_eq!(read_generation(dir.path()).unwrap(), Some(7));
+
+        let config = TsmConfig::default();
+        let writes = write_request(dir.path(), "intel-tdx", &[0u8; 64], false, &config).unwrap();
+        assert_eq!(writes, 1);
+        assert_eq!(fs::read(dir.path().join("inblob")).unwrap(), vec![0u8; 64]);
+
+        let config = TsmConfig {
+            svsm_service_guid: Some("c476f1eb-0123-45a5-9641-b4e7dde5bfe3".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(request_svsm(dir.path(), &config).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_privlevel_floor() {
+        let dir = tempdir().unwrap();
+        let config = TsmConfig {
+            privlevel: Some(1),
+            ..Default::default()
+        };
+        // No privlevel_floor: any level
+        assert_eq!(privlevel(dir.path(), &config).unwrap(), 1);
+
+        fs::write(dir.path().join("privlevel_floor"), "1\n").unwrap();
+        assert_eq!(privlevel(dir.path(), &config).unwrap(), 1);
+        let writes = write_request(dir.path(), "amd-sev-snp", &[0u8; 64], false, &config).unwrap();
+        assert_eq!(writes, 2);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("privlevel")).unwrap(),
+            "1"
+        );
+
+        fs::write(dir.path().join("privlevel_floor"), "2\n").unwrap();
+        let err = privlevel(dir.path(), &config).unwrap_err();
+        assert!(
+            err.contains("below the report's privlevel_floor 2"),
+            "{}",
+            err
+        );
+        assert!(write_request(dir.path(), "amd-sev-snp", &[0u8; 64], false, &config).is_err());
+    }
+
+    // --- SVSM ---
+
+    #[test]
+    fn test_use_svsm() {
+        let dir = tempdir().unwrap();
+        // Neither attribute: an older kernel
+        assert!(!use_svsm(dir.path(), SvsmMode::Auto).unwrap());
+        assert!(use_svsm(dir.path(), SvsmMode::Always).is_err());
+
+        fs::write(dir.path().join("service_provider"), "").unwrap();
+        fs::write(dir.path().join("privlevel_floor"), "0\n").unwrap();
+        assert!(!use_svsm(dir.path(), SvsmMode::Auto).unwrap());
+        assert!(use_svsm(dir.path(), SvsmMode::Always).unwrap());
+
+        fs::write(dir.path().join("privlevel_floor"), "2\n").unwrap();
+        assert!(use_svsm(dir.path(), SvsmMode::Auto).unwrap());
+        assert!(!use_svsm(dir.path(), SvsmMode::Never).unwrap());
+    }
+
+    #[test]
+    fn test_svsm_evidence() {
+        let config = TsmConfig {
+            svsm_service_guid: Some("c476f1eb-0123-45a5-9641-b4e7dde5bfe3".to_string()),
+            ..Default::default()
+        };
+        let evidence = svsm_evidence(b"report", b"manifest", &config);
+        assert_eq!(evidence.tee_type, TeeType::AmdSevSnpSvsm);
+        let envelope: serde_json::Value =
+            serde_json::from_slice(&general_purpose::STANDARD.decode(evidence.encode()).unwrap())
+                .unwrap();
+        assert_eq!(
+            envelope["report"],
+            general_purpose::STANDARD.encode("report")
+        );
+        assert_eq!(
+            envelope["manifest"],
+            general_purpose::STANDARD.encode("manifest")
+        );
+        assert_eq!(
+            envelope["service_guid"],
+            "c476f1eb-0123-45a5-9641-b4e7dde5bfe3"
+        );
+        assert!(envelope["manifest_version"].is_null());
+    }
+
     // --- get_tee_type tests ---
 
+    #[test]
+    fn test_tee_type_for_overrides() {
+        let mut overrides = BTreeMap::new();
+        assert_eq!(
+            tee_type_for("sev_guest", &overrides).as_deref(),
+            Some("amd-sev-snp")
+        );
+        assert_eq!(tee_type_for("arm_cca_guest", &overrides), None);
+
+        overrides.insert("arm_cca_guest".to_string(), "arm-cca".to_string());
+        overrides.insert("tdx_guest".to_string(), "intel-tdx-custom".to_string());
+        assert_eq!(
+            tee_type_for("arm_cca_guest", &overrides).as_deref(),
+            Some("arm-cca")
+        );
+        assert_eq!(
+            tee_type_for("tdx_guest", &overrides).as_deref(),
+            Some("intel-tdx-custom")
+        );
+        assert_eq!(
+            tee_type_for("sev_guest", &overrides).as_deref(),
+            Some("amd-sev-snp")
+        );
+    }
+
     #[test]
     fn test_get_tee_type_sev_guest() {
         let dir = tempdir().unwrap();
@@ -169,7 +830,7 @@ mod tests {
         // get_tee_type expects a &TempDir from tempfile::TempDir, so we
         // create one via tempdir_in.  But since get_tee_type only reads
         // provider, we can use the same TempDir (path matches).
-        let result = get_tee_type(&dir);
+        let result = get_tee_type(&dir, None);
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "amd-sev-snp");
     }
@@ -178,7 +839,7 @@ mod tests {
     fn test_get_tee_type_tdx_guest() {
         let dir = tempdir().unwrap();
         fs::write(dir.path().join("provider"), "tdx_guest\n").unwrap();
-        let result = get_tee_type(&dir);
+        let result = get_tee_type(&dir, None);
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "intel-tdx");
     }
@@ -187,17 +848,27 @@ mod tests {
     fn test_get_tee_type_unknown_provider() {
         let dir = tempdir().unwrap();
         fs::write(dir.path().join("provider"), "some_unknown\n").unwrap();
-        let result = get_tee_type(&dir);
+        let result = get_tee_type(&dir, None);
         assert!(result.is_err());
         let err_msg = result.unwrap_err().to_string();
         assert!(err_msg.contains("Unknown TEE provider"));
     }
 
+    #[test]
+    fn test_get_tee_type_forced() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("provider"), "some_unknown\n").unwrap();
+        assert_eq!(get_tee_type(&dir, Some("arm-cca")).unwrap(), "arm-cca");
+        // A known provider keeps its own handling
+        fs::write(dir.path().join("provider"), "sev_guest\n").unwrap();
+        assert_eq!(get_tee_type(&dir, Some("arm-cca")).unwrap(), "amd-sev-snp");
+    }
+
     #[test]
     fn test_get_tee_type_missing_provider_file() {
         let dir = tempdir().unwrap();
         // No provider file written
-        let result = get_tee_type(&dir);
+        let result = get_tee_type(&dir, None);
         assert!(result.is_err());
     }
 
@@ -255,6 +926,29 @@ mod tests {
         assert!(err.contains("report_data must be exactly 64 bytes"));
     }
 
+    #[cfg(feature = "evidence-fixtures")]
+    #[test]
+    fn test_fixture_evidence() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("provider"), "sev_guest\n").unwrap();
+        fs::write(dir.path().join("outblob"), vec![0u8; 0x4a0]).unwrap();
+        let (report, tee_type) = fixture_evidence(dir.path(), &[0xab; 64]).unwrap();
+        assert_eq!(tee_type, "amd-sev-snp");
+        assert_eq!(report[0x50..0x90], [0xab; 64]);
+        assert_eq!(report[0x90], 0);
+
+        let mut quote = vec![0u8; 1024];
+        quote[0] = 4;
+        fs::write(dir.path().join("provider"), "tdx_guest\n").unwrap();
+        fs::write(dir.path().join("outblob"), &quote).unwrap();
+        let (report, tee_type) = fixture_evidence(dir.path(), &[0xcd; 64]).unwrap();
+        assert_eq!(tee_type, "intel-tdx");
+        assert_eq!(report[48 + 520..48 + 584], [0xcd; 64]);
+
+        fs::write(dir.path().join("outblob"), &quote[..100]).unwrap();
+        assert!(fixture_evidence(dir.path(), &[0xcd; 64]).is_err());
+    }
+
     #[test]
     fn test_report_data_correct_length_passes_validation() {
         let nonce = "D".repeat(64);
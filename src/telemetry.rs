@@ -0,0 +1,148 @@
+// TEE Attestation Service Agent — failure telemetry
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Fleet operators want to see which hosts fail to attest without scraping
+// their logs. With a `[telemetry]` section, a failed attestation or key
+// release posts a diagnostic record to the TAS server's telemetry endpoint
+// (`path`, default "/kb/v0/telemetry"):
+//
+//   {"event": "failure", "error_class": "release", "tee_type": "amd-sev-snp",
+//    "tcb": {"current_tcb": {...}, "reported_tcb": {...}},
+//    "agent_version": "0.1.0", "timestamp": "..."}
+//
+// The record is sanitized: it carries the error class of hooks::error_class,
+// never the error message, the evidence, the nonce or the policy ID. The TCB
+// summary holds the platform TCB versions of the evidence, if any was
+// collected. Network failures are not reported, as TAS is unreachable, and
+// the report is sent once without retries; failing to send it is logged and
+// never changes the outcome.
+//
+// No unsafe code.
+
+use crate::tas_api::{tas_post_telemetry, RetryConfig};
+use crate::{hooks, local_policy, Session};
+use log::{debug, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use zeroize::Zeroizing;
+
+/// Default path of the telemetry endpoint on the TAS server.
+const DEFAULT_PATH: &str = "/kb/v0/telemetry";
+
+/// `[telemetry]` section of the config file.
+#[derive(Deserialize, Clone, Default)]
+pub struct TelemetryConfig {
+    /// Path of the telemetry endpoint (default: "/kb/v0/telemetry")
+    pub path: Option<String>,
+}
+
+/// The platform TCB versions of `evidence`.
+pub fn tcb_summary(tee_type: &str, evidence: &str) -> Option<Value> {
+    let input = local_policy::input(tee_type, evidence, None, None).ok()?;
+    let report = input.get("report")?;
+    match tee_type {
+        "amd-sev-snp" => Some(json!({
+            "current_tcb": report["current_tcb"],
+            "reported_tcb": report["reported_tcb"],
+        })),
+        "intel-tdx" => Some(json!({ "tee_tcb_svn": report["tee_tcb_svn"] })),
+        _ => None,
+    }
+}
+
+/// The sanitized record of a failure.
+fn record(err: &anyhow::Error, tee_type: Option<&str>, evidence: Option<&str>) -> Value {
+    let tcb = match (tee_type, evidence) {
+        (Some(tee_type), Some(evidence)) => tcb_summary(tee_type, evidence),
+        _ => None,
+    };
+    json!({
+        "event": "failure",
+        "error_class": hooks::error_class(err).as_str(),
+        "tee_type": tee_type,
+        "tcb": tcb,
+        "agent_version": env!("CARGO_PKG_VERSION"),
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+/// Posts failure records to the TAS server of a session.
+pub struct Reporter {
+    server_uri: String,
+    api_key: Zeroizing<String>,
+    cert_path: PathBuf,
+    path: String,
+}
+
+impl Reporter {
+    /// A reporter for `session`, if it has telemetry enabled.
+    pub fn new(session: &Session) -> Option<Reporter> {
+        let config = session.telemetry.as_ref()?;
+        Some(Reporter {
+            server_uri: session.server_uri.clone(),
+            api_key: session.api_key.clone(),
+            cert_path: session.cert_path.clone(),
+            path: config.path.clone().unwrap_or_else(|| DEFAULT_PATH.into()),
+        })
+    }
+
+    /// Report `err`, with the TEE type and evidence if they were collected.
+    pub async fn report(
+        &self,
+        err: &anyhow::Error,
+        tee_type: Option<&str>,
+        evidence: Option<&str>,
+    ) {
+        if hooks::error_class(err) == hooks::ErrorClass::Network {
+            debug!("Not reporting a network failure to TAS");
+            return;
+        }
+        let record = record(err, tee_type, evidence);
+        let once = RetryConfig {
+            max_retries: 0,
+            ..Default::default()
+        };
+        match tas_post_telemetry(
+            &self.server_uri,
+            &self.api_key,
+            self.cert_path.clone(),
+            &once,
+            &self.path,
+            &record,
+        )
+        .await
+        {
+            Ok(()) => debug!("Reported the failure to {}{}", self.server_uri, self.path),
+            Err(e) => warn!("unable to report the failure to TAS: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_is_sanitized() {
+        let err = hooks::ErrorClass::Release
+            .error("TAS Secret Error: policy pol-secret denied nonce abc123");
+        let failure = record(&err, Some("amd-sev-snp"), Some("bm90IGEgcmVwb3J0"));
+        assert_eq!(failure["event"], "failure");
+        assert_eq!(failure["error_class"], "release");
+        assert_eq!(failure["tee_type"], "amd-sev-snp");
+        assert_eq!(failure["tcb"], Value::Null);
+        assert_eq!(failure["agent_version"], env!("CARGO_PKG_VERSION"));
+        let text = failure.to_string();
+        assert!(!text.contains("pol-secret"), "{}", text);
+        assert!(!text.contains("abc123"), "{}", text);
+        assert!(!text.contains("bm90IGEgcmVwb3J0"), "{}", text);
+
+        let err = hooks::ErrorClass::Evidence.error("TEE evidence Error: no TEE");
+        let failure = record(&err, None, None);
+        assert_eq!(failure["error_class"], "evidence");
+        assert_eq!(failure["tee_type"], Value::Null);
+    }
+}
@@ -0,0 +1,257 @@
+// TEE Attestation Service Agent — TLS policy of the TAS connection
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Security baselines for key-distribution channels often pin the protocol
+// version and cipher suites. With a `[tls]` section, HTTPS connections to
+// TAS use rustls, whatever the TLS backend of the build, configured with
+//
+//   min_version = "1.3"        TLS 1.3 only (default "1.2": 1.2 and 1.3)
+//   cipher_suites = [...]      only these suites, by IANA name, e.g.
+//                              "TLS13_AES_256_GCM_SHA384"
+//
+// and trusting only the CA certificates of `cert_path`, not the system's
+// roots. A suite list that leaves no suite for the allowed versions, or an
+// unknown suite name, is an error when the client is built. Connections to
+// verifiers, Vault and cloud KMS are not affected.
+//
+// For troubleshooting interop failures, builds with the `tls-keylog` feature
+// take `--unsafe-tls-keylog FILE`, which appends the TLS secrets of TAS
+// connections to FILE in SSLKEYLOGFILE format, so that a packet capture can
+// be decrypted. Whoever holds the file and a capture can read the wrapped
+// secret's exchange and the API key, so release builds leave the option out,
+// the agent warns about it loudly, resets FILE to mode 0600 even if it
+// already existed, never enables it from the config file and refuses it in
+// daemon mode. Key logging implies rustls like a `[tls]` section.
+//
+// No unsafe code.
+
+use anyhow::Result;
+use reqwest::ClientBuilder;
+use rustls::{SupportedCipherSuite, SupportedProtocolVersion};
+use serde::Deserialize;
+use std::sync::OnceLock;
+#[cfg(feature = "tls-keylog")]
+use {
+    anyhow::Context,
+    log::warn,
+    rustls::KeyLog,
+    std::fs::{File, OpenOptions, Permissions},
+    std::io::Write,
+    std::os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    std::path::Path,
+    std::sync::{Arc, Mutex},
+};
+
+/// Lowest TLS version accepted.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum TlsVersion {
+    #[default]
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// `[tls]` section of the config file.
+#[derive(Deserialize, Clone, Default)]
+pub struct TlsConfig {
+    /// Lowest TLS version: "1.2" (default) or "1.3"
+    #[serde(default)]
+    pub min_version: TlsVersion,
+    /// Cipher suites to offer, by IANA name (default: rustls' defaults)
+    pub cipher_suites: Option<Vec<String>>,
+}
+
+/// TLS policy, fixed by the first config file loaded.
+static TLS_CONFIG: OnceLock<TlsConfig> = OnceLock::new();
+
+/// Apply the `[tls]` settings to all later TAS connections.
+pub fn configure(config: TlsConfig) {
+    if TLS_CONFIG.set(config).is_err() {
+        log::debug!("TLS policy already applied");
+    }
+}
+
+/// The TLS policy, if the config file has one.
+pub fn policy() -> Option<&'static TlsConfig> {
+    TLS_CONFIG.get()
+}
+
+/// Writes TLS secrets in the SSLKEYLOGFILE format.
+#[cfg(feature = "tls-keylog")]
+#[derive(Debug)]
+struct KeyLogFile(Mutex<File>);
+
+#[cfg(feature = "tls-keylog")]
+impl KeyLog for KeyLogFile {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        let line = format!(
+            "{} {} {}\n",
+            label,
+            hex::encode(client_random),
+            hex::encode(secret)
+        );
+        let mut file = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            warn!("unable to write the TLS key log: {}", e);
+        }
+    }
+}
+
+#[cfg(feature = "tls-keylog")]
+static KEY_LOG: OnceLock<Arc<KeyLogFile>> = OnceLock::new();
+
+/// Append the TLS secrets of all later TAS connections to `path`.
+#[cfg(feature = "tls-keylog")]
+pub fn enable_keylog(path: &Path) -> Result<()> {
+    let file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("unable to open TLS key log {:?}", path))?;
+    // The mode above only applies to a file this call creates
+    file.set_permissions(Permissions::from_mode(0o600))
+        .with_context(|| format!("unable to restrict TLS key log {:?}", path))?;
+    warn!("**********************************************************************");
+    warn!("TLS key logging to {:?} is enabled.", path);
+    warn!("Anyone with this file and a capture can decrypt the TAS connection,");
+    warn!("including the API key. Use for troubleshooting only and delete it.");
+    warn!("**********************************************************************");
+    let _ = KEY_LOG.set(Arc::new(KeyLogFile(Mutex::new(file))));
+    Ok(())
+}
+
+/// Whether TLS key logging is enabled.
+fn keylog_enabled() -> bool {
+    #[cfg(feature = "tls-keylog")]
+    return KEY_LOG.get().is_some();
+    #[cfg(not(feature = "tls-keylog"))]
+    false
+}
+
+/// Whether TAS connections use the agent's own rustls configuration.
+pub fn custom() -> bool {
+    policy().is_some() || keylog_enabled()
+}
+
+/// The rustls cipher suites named `names`.
+fn cipher_suites(names: &[String]) -> Result<Vec<SupportedCipherSuite>, String> {
+    names
+        .iter()
+        .map(|name| {
+            rustls::ALL_CIPHER_SUITES
+                .iter()
+                .find(|suite| format!("{:?}", suite.suite()) == *name)
+                .copied()
+                .ok_or_else(|| format!("Unknown TLS cipher suite {:?}", name))
+        })
+        .collect()
+}
+
+static TLS12_AND_LATER: [&SupportedProtocolVersion; 2] =
+    [&rustls::version::TLS12, &rustls::version::TLS13];
+static TLS13_ONLY: [&SupportedProtocolVersion; 1] = [&rustls::version::TLS13];
+
+fn protocol_versions(min_version: TlsVersion) -> &'static [&'static SupportedProtocolVersion] {
+    match min_version {
+        TlsVersion::Tls12 => &TLS12_AND_LATER,
+        TlsVersion::Tls13 => &TLS13_ONLY,
+    }
+}
+
+/// Configure `builder` to connect with the TLS policy and key log, trusting
+/// the PEM certificates in `cert_data`.
+pub fn apply(builder: ClientBuilder, cert_data: &[u8]) -> Result<ClientBuilder, String> {
+    let default = TlsConfig::default();
+    let config = policy().unwrap_or(&default);
+    let mut roots = rustls::RootCertStore::empty();
+    let certs = rustls_pemfile::certs(&mut &cert_data[..])
+        .map_err(|err| format!("Error parsing certificate bundle: {}", err))?;
+    for der in certs {
+        roots
+            .add(&rustls::Certificate(der))
+            .map_err(|err| format!("Error parsing certificate bundle: {}", err))?;
+    }
+
+    let suites = match &config.cipher_suites {
+        Some(names) => cipher_suites(names)?,
+        None => rustls::DEFAULT_CIPHER_SUITES.to_vec(),
+    };
+    #[cfg_attr(not(feature = "tls-keylog"), allow(unused_mut))]
+    let mut tls = rustls::ClientConfig::builder()
+        .with_cipher_suites(&suites)
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(protocol_versions(config.min_version))
+        .map_err(|err| format!("Invalid TLS policy: {}", err))?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    #[cfg(feature = "tls-keylog")]
+    if let Some(key_log) = KEY_LOG.get() {
+        warn!("Logging TLS secrets of the TAS connection");
+        tls.key_log = key_log.clone();
+    }
+    Ok(builder.use_preconfigured_tls(tls))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tls_config() {
+        let config: TlsConfig = toml::from_str(
+            r#"
+            min_version = "1.3"
+            cipher_suites = ["TLS13_AES_256_GCM_SHA384"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.min_version, TlsVersion::Tls13);
+        assert_eq!(protocol_versions(config.min_version).len(), 1);
+
+        let config: TlsConfig = toml::from_str("").unwrap();
+        assert_eq!(config.min_version, TlsVersion::Tls12);
+        assert!(toml::from_str::<TlsConfig>(r#"min_version = "1.1""#).is_err());
+    }
+
+    #[test]
+    fn test_cipher_suites() {
+        let names = vec![
+            "TLS13_AES_256_GCM_SHA384".to_string(),
+            "TLS13_CHACHA20_POLY1305_SHA256".to_string(),
+        ];
+        assert_eq!(cipher_suites(&names).unwrap().len(), 2);
+        let err = cipher_suites(&["TLS_RSA_WITH_RC4_128_MD5".to_string()]).unwrap_err();
+        assert!(err.contains("TLS_RSA_WITH_RC4_128_MD5"), "{}", err);
+    }
+
+    #[cfg(feature = "tls-keylog")]
+    #[test]
+    fn test_key_log_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keylog");
+        let file = File::create(&path).unwrap();
+        let key_log = KeyLogFile(Mutex::new(file));
+        key_log.log("CLIENT_TRAFFIC_SECRET_0", &[0xab; 4], &[0x01, 0x02]);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "CLIENT_TRAFFIC_SECRET_0 abababab 0102\n"
+        );
+    }
+
+    #[cfg(feature = "tls-keylog")]
+    #[test]
+    fn test_enable_keylog_restricts_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keylog");
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, Permissions::from_mode(0o644)).unwrap();
+
+        enable_keylog(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+    }
+}
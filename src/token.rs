@@ -0,0 +1,198 @@
+// TEE Attestation Service Agent — attestation token export
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// `--token-out <path>` writes proof of each successful attestation to a file
+// (mode 0600) that sidecars and applications can present to their own
+// backends. It is written once TAS has released a secret with the
+// attestation, never for evidence that was rejected or a release that was
+// denied:
+//
+//   - with a `[verifier]` section, the verifier-issued JWT, which relying
+//     parties check against the verifier's published keys;
+//   - otherwise, a JWT minted by the agent and signed (RS256) with the TPM
+//     signing key (see tpm::sign), which relying parties check against the
+//     key they enrolled for this machine. Its `kid` header is the base64url
+//     SHA-256 of the key's SubjectPublicKeyInfo.
+//
+// Claims of a minted token:
+//
+//   iss              "tas_agent"
+//   iat, exp         issue time, and issue time + 1 hour
+//   tee_type         TEE type, e.g. "amd-sev-snp"
+//   nonce            TAS nonce the evidence is bound to
+//   report_data      base64url report data of the TEE evidence
+//   evidence_sha512  base64url SHA-512 of the raw TEE report or quote
+//   appraised_by     "tas": TAS appraised the evidence and released a secret
+//   policy_id        the policy ID TAS released the secret for
+//
+// A minted token states only that: the agent vouches that TAS released the
+// secret for `policy_id` against this evidence. TAS's appraisal result itself
+// is not part of it.
+//
+// No unsafe code.
+
+use crate::output::write_secret_file;
+use crate::tpm;
+use anyhow::{Context, Result};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use log::{info, warn};
+use serde_json::json;
+use sha2::{Digest, Sha256, Sha512};
+use std::path::Path;
+
+/// Issuer of minted tokens.
+const ISSUER: &str = "tas_agent";
+/// What appraised the evidence a minted token summarizes.
+const APPRAISED_BY: &str = "tas";
+/// Lifetime of minted tokens in seconds.
+const TOKEN_LIFETIME_SECS: i64 = 3600;
+
+/// What a minted token summarizes.
+pub struct Summary<'a> {
+    pub tee_type: &'a str,
+    pub nonce: &'a str,
+    /// Base64 TEE evidence
+    pub evidence: &'a str,
+    pub report_data: Option<&'a [u8]>,
+    /// Policy ID TAS released a secret for with the evidence
+    pub policy_id: &'a str,
+}
+
+/// Build a JWT for `summary` issued at `now`, signed by `sign` with the key
+/// whose SubjectPublicKeyInfo DER is `signing_key`.
+fn mint_with(
+    summary: &Summary,
+    now: i64,
+    signing_key: &[u8],
+    sign: impl Fn(&[u8]) -> Result<Vec<u8>>,
+) -> Result<String> {
+    let kid = URL_SAFE_NO_PAD.encode(Sha256::digest(signing_key));
+    let header = json!({"alg": "RS256", "typ": "JWT", "kid": kid});
+
+    let report = STANDARD
+        .decode(summary.evidence)
+        .context("evidence is not valid base64")?;
+    let evidence_sha512 = URL_SAFE_NO_PAD.encode(Sha512::digest(&report));
+    let mut claims = json!({
+        "iss": ISSUER,
+        "iat": now,
+        "exp": now + TOKEN_LIFETIME_SECS,
+        "tee_type": summary.tee_type,
+        "nonce": summary.nonce.trim_matches('"'),
+        "evidence_sha512": evidence_sha512,
+        "appraised_by": APPRAISED_BY,
+        "policy_id": summary.policy_id,
+    });
+    if let Some(report_data) = summary.report_data {
+        claims["report_data"] = URL_SAFE_NO_PAD.encode(report_data).into();
+    }
+
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string())
+    );
+    let signature = sign(signing_input.as_bytes())?;
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        URL_SAFE_NO_PAD.encode(signature)
+    ))
+}
+
+/// Mint a TPM-signed JWT summarizing the attestation.
+fn mint(summary: &Summary) -> Result<String> {
+    let signing_key = tpm::signing_key().context("unable to read the TPM signing key")?;
+    mint_with(
+        summary,
+        chrono::Utc::now().timestamp(),
+        &signing_key,
+        |message| tpm::sign(message).context("unable to sign with the TPM"),
+    )
+}
+
+/// Write `verifier_token`, or else a minted token for `summary`, to `path`.
+/// Failures are logged.
+pub fn write(path: &Path, verifier_token: Option<&str>, summary: &Summary) {
+    let written = match verifier_token {
+        Some(token) => write_secret_file(path, token.as_bytes()),
+        None => mint(summary).and_then(|token| write_secret_file(path, token.as_bytes())),
+    };
+    match written {
+        Ok(()) => info!("Wrote attestation token to {:?}", path),
+        Err(e) => warn!("unable to write attestation token: {:#}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::generate_wrapping_key;
+    use rsa::pkcs8::DecodePublicKey;
+    use rsa::signature::Verifier;
+    use serde_json::Value;
+
+    fn decode(part: &str) -> Value {
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(part).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_mint_with() {
+        let key = generate_wrapping_key().unwrap();
+        let spki = key.public_key_to_spki_der().unwrap();
+        let summary = Summary {
+            tee_type: "amd-sev-snp",
+            nonce: "\"n0nce\"",
+            evidence: "AAAA",
+            report_data: Some(&[1, 2, 3]),
+            policy_id: "luks",
+        };
+        let token = mint_with(&summary, 1000, &spki, |m| Ok(key.sign(m))).unwrap();
+
+        let parts: Vec<&str> = token.split('.').collect();
+        assert_eq!(parts.len(), 3);
+        let header = decode(parts[0]);
+        assert_eq!(header["alg"], "RS256");
+        assert_eq!(header["kid"], URL_SAFE_NO_PAD.encode(Sha256::digest(&spki)));
+        let claims = decode(parts[1]);
+        assert_eq!(claims["nonce"], "n0nce");
+        assert_eq!(claims["exp"].as_i64(), Some(1000 + TOKEN_LIFETIME_SECS));
+        assert_eq!(claims["report_data"], "AQID");
+        assert_eq!(claims["appraised_by"], "tas");
+        assert_eq!(claims["policy_id"], "luks");
+        assert_eq!(
+            claims["evidence_sha512"],
+            URL_SAFE_NO_PAD.encode(Sha512::digest([0u8; 3]))
+        );
+
+        let public_key = rsa::RsaPublicKey::from_public_key_der(&spki).unwrap();
+        let verifying_key = rsa::pkcs1v15::VerifyingKey::<Sha256>::new(public_key);
+        let signature =
+            rsa::pkcs1v15::Signature::try_from(&URL_SAFE_NO_PAD.decode(parts[2]).unwrap()[..])
+                .unwrap();
+        let signing_input = format!("{}.{}", parts[0], parts[1]);
+        assert!(verifying_key
+            .verify(signing_input.as_bytes(), &signature)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_write_verifier_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token");
+        let summary = Summary {
+            tee_type: "intel-tdx",
+            nonce: "n0nce",
+            evidence: "AAAA",
+            report_data: None,
+            policy_id: "luks",
+        };
+        write(&path, Some("eyJ.token.sig"), &summary);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "eyJ.token.sig");
+    }
+}
@@ -0,0 +1,248 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// TPM 2.0 sealing and signing helpers.
+//
+// Sealing is delegated to tpm2-tools so no TSS bindings are linked into the
+// agent. Objects are sealed under a transient primary key in the owner
+// hierarchy; the primary is recreated from the same template when unsealing,
+// so only the sealed object's public and private blobs need to be persisted.
+//
+// Objects sealed to a PCR selection carry a PolicyPCR authorization policy
+// and no user auth, so the TPM only unseals them while the selected PCRs
+// hold the values they had at sealing time.
+//
+// Signing uses an RSA signing primary in the owner hierarchy. It is derived
+// from the owner seed, so the same key is recreated on every boot and its
+// private part never leaves the TPM.
+
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// A sealed data object, as produced by `tpm2_create`.
+pub struct SealedObject {
+    /// TPM2B_PUBLIC blob
+    pub public: Vec<u8>,
+    /// TPM2B_PRIVATE blob (encrypted by the TPM; safe to store on disk)
+    pub private: Vec<u8>,
+}
+
+/// Run a tpm2-tools command, optionally feeding `input` on stdin, and return
+/// its stdout.
+fn run_tool(cmd: &mut Command, input: Option<&[u8]>) -> Result<Vec<u8>> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    debug!("Running {}", program);
+
+    let mut child = cmd
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run {} (is tpm2-tools installed?)", program))?;
+
+    if let Some(data) = input {
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("failed to open stdin of {}", program))?;
+        stdin
+            .write_all(data)
+            .with_context(|| format!("failed to write to {}", program))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("failed to wait for {}", program))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Create the transient storage primary key context at `ctx`.
+fn create_primary(ctx: &Path) -> Result<()> {
+    run_tool(
+        Command::new("tpm2_createprimary")
+            .args(["-Q", "-C", "o", "-c"])
+            .arg(ctx),
+        None,
+    )?;
+    Ok(())
+}
+
+/// Create the transient signing primary key context at `ctx`.
+fn create_signing_primary(ctx: &Path) -> Result<()> {
+    run_tool(
+        Command::new("tpm2_createprimary")
+            .args(["-Q", "-C", "o", "-G", "rsa2048:rsassa-sha256:null", "-a"])
+            .arg("fixedtpm|fixedparent|sensitivedataorigin|userwithauth|sign")
+            .arg("-c")
+            .arg(ctx),
+        None,
+    )?;
+    Ok(())
+}
+
+/// SubjectPublicKeyInfo DER of the TPM signing key.
+pub fn signing_key() -> Result<Vec<u8>> {
+    let work = tempfile::tempdir().context("failed to create TPM work directory")?;
+    let primary = work.path().join("signing.ctx");
+    let public = work.path().join("signing.der");
+
+    create_signing_primary(&primary)?;
+    run_tool(
+        Command::new("tpm2_readpublic")
+            .args(["-Q", "-c"])
+            .arg(&primary)
+            .args(["-f", "der", "-o"])
+            .arg(&public),
+        None,
+    )?;
+    std::fs::read(&public).context("failed to read TPM signing key")
+}
+
+/// Sign `message` with RSASSA-PKCS1-v1_5 over SHA-256 using the TPM signing
+/// key.
+pub fn sign(message: &[u8]) -> Result<Vec<u8>> {
+    let work = tempfile::tempdir().context("failed to create TPM work directory")?;
+    let primary = work.path().join("signing.ctx");
+    let signature = work.path().join("signature.bin");
+
+    create_signing_primary(&primary)?;
+    run_tool(
+        Command::new("tpm2_sign")
+            .args(["-Q", "-c"])
+            .arg(&primary)
+            .args(["-g", "sha256", "-s", "rsassa", "-f", "plain", "-o"])
+            .arg(&signature),
+        Some(message),
+    )?;
+    std::fs::read(&signature).context("failed to read TPM signature")
+}
+
+/// Seal `data` to this machine's TPM.
+pub fn seal(data: &[u8]) -> Result<SealedObject> {
+    seal_with_policy(data, None)
+}
+
+/// Seal `data` to this machine's TPM and the current values of the PCR
+/// selection `pcrs`, e.g. "sha256:0,7".
+pub fn seal_to_pcrs(data: &[u8], pcrs: &str) -> Result<SealedObject> {
+    seal_with_policy(data, Some(pcrs))
+}
+
+fn seal_with_policy(data: &[u8], pcrs: Option<&str>) -> Result<SealedObject> {
+    let work = tempfile::tempdir().context("failed to create TPM work directory")?;
+    let primary = work.path().join("primary.ctx");
+    let policy = work.path().join("pcr.policy");
+    let public = work.path().join("sealed.pub");
+    let private = work.path().join("sealed.priv");
+
+    create_primary(&primary)?;
+    let mut create = Command::new("tpm2_create");
+    create.args(["-Q", "-C"]).arg(&primary);
+    if let Some(pcrs) = pcrs {
+        run_tool(
+            Command::new("tpm2_createpolicy")
+                .args(["-Q", "--policy-pcr", "-l", pcrs, "-L"])
+                .arg(&policy),
+            None,
+        )?;
+        create
+            .arg("-L")
+            .arg(&policy)
+            .args(["-a", "fixedtpm|fixedparent"]);
+    }
+    run_tool(
+        create
+            .args(["-i", "-", "-u"])
+            .arg(&public)
+            .arg("-r")
+            .arg(&private),
+        Some(data),
+    )?;
+
+    Ok(SealedObject {
+        public: std::fs::read(&public).context("failed to read sealed public blob")?,
+        private: std::fs::read(&private).context("failed to read sealed private blob")?,
+    })
+}
+
+/// Unseal a previously sealed object and return its contents.
+pub fn unseal(object: &SealedObject) -> Result<Vec<u8>> {
+    unseal_with_policy(object, None)
+}
+
+/// Unseal an object sealed with `seal_to_pcrs` to the same `pcrs`. Fails if
+/// any of the PCRs changed since.
+pub fn unseal_with_pcrs(object: &SealedObject, pcrs: &str) -> Result<Vec<u8>> {
+    unseal_with_policy(object, Some(pcrs))
+}
+
+fn unseal_with_policy(object: &SealedObject, pcrs: Option<&str>) -> Result<Vec<u8>> {
+    let work = tempfile::tempdir().context("failed to create TPM work directory")?;
+    let primary = work.path().join("primary.ctx");
+    let public = work.path().join("sealed.pub");
+    let private = work.path().join("sealed.priv");
+    let loaded = work.path().join("sealed.ctx");
+
+    std::fs::write(&public, &object.public).context("failed to stage sealed public blob")?;
+    std::fs::write(&private, &object.private).context("failed to stage sealed private blob")?;
+
+    create_primary(&primary)?;
+    run_tool(
+        Command::new("tpm2_load")
+            .args(["-Q", "-C"])
+            .arg(&primary)
+            .arg("-u")
+            .arg(&public)
+            .arg("-r")
+            .arg(&private)
+            .arg("-c")
+            .arg(&loaded),
+        None,
+    )?;
+    let mut unseal = Command::new("tpm2_unseal");
+    unseal.arg("-c").arg(&loaded);
+    if let Some(pcrs) = pcrs {
+        unseal.arg("-p").arg(format!("pcr:{}", pcrs));
+    }
+    run_tool(&mut unseal, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_tool_returns_stdout() {
+        let out = run_tool(&mut Command::new("cat"), Some(b"sealed-bytes")).unwrap();
+        assert_eq!(out, b"sealed-bytes");
+    }
+
+    #[test]
+    fn test_run_tool_reports_failure_status() {
+        let err = run_tool(&mut Command::new("false"), None).unwrap_err();
+        assert!(err.to_string().contains("exited with"), "{err}");
+    }
+
+    #[test]
+    fn test_run_tool_missing_binary() {
+        let err = run_tool(&mut Command::new("tpm2_does_not_exist"), None).unwrap_err();
+        assert!(err.to_string().contains("tpm2-tools"), "{err}");
+    }
+}
@@ -0,0 +1,157 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Attestation transcripts for offline debugging and audit evidence.
+//
+// With `--transcript-dir DIR` every artifact of the run is saved into a new
+// directory DIR/<UTC timestamp>-<pid> (mode 0700), one file (mode 0600) per
+// artifact, numbered in the order they were produced:
+//
+//   01-GET-version.json        TAS exchanges: {"method", "path", "request",
+//   02-GET-get_nonce.json       "status", "response"}, including failed
+//   03-nonce.txt                get_secret responses
+//   04-report-data.bin         the report data bound into the evidence
+//   05-report.bin              the raw TEE report (and report-aux.bin, e.g.
+//                              the SVSM services manifest, if any)
+//   06-tas-ca.pem              the CA certificates TAS was verified with
+//   07-POST-get_secret.json
+//   08-attestation-result.jwt  the server's attestation result, if any
+//   09-outcome.json            {"outcome": "released"} or
+//                              {"outcome": "failed", "error": "..."}
+//
+// Unlike a cassette, a transcript keeps the evidence. The wrapped secret and
+// the verifier's attestation token, a bearer credential, are replaced with
+// "<redacted>"; the API key is a header and never saved. Every attestation
+// of a run adds its own files, so the option suits one-shot runs rather than
+// the daemon.
+
+use crate::cassette::{self, Interaction};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+
+/// Placeholder for redacted values.
+const REDACTED: &str = "<redacted>";
+
+/// Fields whose values are never saved.
+const REDACTED_FIELDS: &[&str] = &["secret_key", "attestation-token"];
+
+static TRANSCRIPT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Number of the last artifact saved.
+static SEQUENCE: AtomicU32 = AtomicU32::new(0);
+
+/// Create this run's directory under `dir` and save artifacts into it.
+pub fn init(dir: &Path) -> Result<()> {
+    let run = dir.join(format!(
+        "{}-{}",
+        Utc::now().format("%Y%m%dT%H%M%SZ"),
+        std::process::id()
+    ));
+    fs::create_dir_all(dir).with_context(|| format!("unable to create {:?}", dir))?;
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&run)
+        .with_context(|| format!("unable to create {:?}", run))?;
+    info!("Saving the attestation transcript to {:?}", run);
+    let _ = TRANSCRIPT.set(run);
+    Ok(())
+}
+
+/// Whether a transcript is being saved.
+pub fn active() -> bool {
+    TRANSCRIPT.get().is_some()
+}
+
+/// Save `data` as the next artifact, named `name`.
+pub fn save(name: &str, data: &[u8]) {
+    let Some(dir) = TRANSCRIPT.get() else {
+        return;
+    };
+    let sequence = SEQUENCE.fetch_add(1, Ordering::SeqCst) + 1;
+    let path = dir.join(format!("{:02}-{}", sequence, name));
+    let written = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut file| file.write_all(data));
+    if let Err(e) = written {
+        warn!("unable to save {:?}: {}", path, e);
+    }
+}
+
+/// Format a TAS exchange with its secrets redacted.
+fn format_exchange(
+    method: &str,
+    path: &str,
+    request: Option<&Value>,
+    status: u16,
+    response: &Value,
+) -> Vec<u8> {
+    let interaction = Interaction {
+        method: method.to_string(),
+        path: path.to_string(),
+        request: request.map(|request| cassette::redact(request, REDACTED_FIELDS, REDACTED)),
+        status,
+        response: cassette::redact(response, REDACTED_FIELDS, REDACTED),
+    };
+    serde_json::to_vec_pretty(&interaction).unwrap_or_default()
+}
+
+/// Save a TAS exchange, named after the last segment of `path`.
+pub fn exchange(method: &str, path: &str, request: Option<&Value>, status: u16, response: &Value) {
+    if !active() {
+        return;
+    }
+    let endpoint = path.rsplit('/').next().unwrap_or(path);
+    save(
+        &format!("{}-{}.json", method, endpoint),
+        &format_exchange(method, path, request, status, response),
+    );
+}
+
+/// Save how the run ended.
+pub fn outcome(error: Option<&anyhow::Error>) {
+    let outcome = match error {
+        None => json!({"outcome": "released"}),
+        Some(e) => json!({"outcome": "failed", "error": format!("{:#}", e)}),
+    };
+    save("outcome.json", outcome.to_string().as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_exchange_redacts_secrets() {
+        let request = json!({
+            "tee-type": "amd-sev-snp",
+            "tee-evidence": "cmVwb3J0",
+            "attestation-token": "ZXlK"
+        });
+        let response = json!({
+            "secret_key": {"wrapped_key": "d2s="},
+            "attestation_result": "ZWFy"
+        });
+        let saved = format_exchange("POST", "/kb/v0/get_secret", Some(&request), 200, &response);
+        let interaction: Interaction = serde_json::from_slice(&saved).unwrap();
+        let request = interaction.request.unwrap();
+        // The evidence is kept, credentials and secrets are not
+        assert_eq!(request["tee-evidence"], "cmVwb3J0");
+        assert_eq!(request["attestation-token"], REDACTED);
+        assert_eq!(interaction.response["secret_key"], REDACTED);
+        assert_eq!(interaction.response["attestation_result"], "ZWFy");
+        assert_eq!(interaction.status, 200);
+    }
+}
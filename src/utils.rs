@@ -5,33 +5,43 @@
 //
 // This module provides the client application with utility functions.
 
-use base64::{engine::general_purpose, Engine};
-use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use crate::limits;
+use base64::{engine::general_purpose, DecodeSliceError, Engine};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::fmt;
+use zeroize::{Zeroize, Zeroizing};
 
 /// JSON payload returned by the TAS `get_secret` endpoint.
 ///
 /// All fields are base64-encoded in the JSON response and automatically
-/// decoded during deserialization.
+/// decoded during deserialization, straight from the JSON text into buffers
+/// that are zeroized when dropped.
 ///
 /// - `wrapped_key`: AES-256 key, RSA-OAEP-wrapped with the agent's ephemeral public key
 /// - `blob`: AES-256-GCM ciphertext containing the LUKS passphrase
 /// - `iv`: AES-GCM initialization vector (96 bits)
 /// - `tag`: AES-GCM authentication tag (128 bits)
+/// - `aad`: optional AES-GCM authenticated data binding the payload to the
+///   request nonce or evidence hash
 #[derive(Debug, Deserialize)]
 pub struct SecretsPayload {
     #[serde(deserialize_with = "deserialize_base64")]
-    pub wrapped_key: Vec<u8>,
+    pub wrapped_key: Zeroizing<Vec<u8>>,
     #[serde(deserialize_with = "deserialize_base64")]
-    pub blob: Vec<u8>,
+    pub blob: Zeroizing<Vec<u8>>,
     #[serde(deserialize_with = "deserialize_base64")]
-    pub iv: Vec<u8>,
+    pub iv: Zeroizing<Vec<u8>>,
     #[serde(deserialize_with = "deserialize_base64")]
-    pub tag: Vec<u8>,
+    pub tag: Zeroizing<Vec<u8>>,
     #[serde(
         default = "default_algorithm",
         deserialize_with = "deserialize_base64_to_string_optional"
     )]
     pub algorithm: String,
+    #[serde(default, deserialize_with = "deserialize_base64_optional")]
+    pub aad: Option<Zeroizing<Vec<u8>>>,
 }
 
 fn default_algorithm() -> String {
@@ -48,6 +58,8 @@ where
             if base64_string.is_empty() {
                 return Ok(default_algorithm());
             }
+            check_decoded_len(&base64_string, limits::decoded_bytes())
+                .map_err(serde::de::Error::custom)?;
             let decoded_bytes = general_purpose::STANDARD
                 .decode(&base64_string)
                 .map_err(|e| serde::de::Error::custom(format!("Base64 decoding error: {}", e)))?;
@@ -58,22 +70,274 @@ where
     }
 }
 
-fn deserialize_base64<'de, D, T>(d: D) -> Result<T, D::Error>
+/// Fail if base64 `encoded` would decode to more than `limit` bytes, before
+/// anything is allocated for it.
+fn check_decoded_len(encoded: &str, limit: usize) -> Result<(), String> {
+    let estimate = base64::decoded_len_estimate(encoded.len());
+    if estimate > limit {
+        return Err(format!(
+            "Base64 decoding error: field of {} bytes exceeds the limit of {} bytes",
+            estimate, limit
+        ));
+    }
+    Ok(())
+}
+
+/// Decode base64 `encoded` into `buf`, reusing its allocation when it is
+/// large enough.
+///
+/// The buffer is sized from the base64 length up front, so decoding never
+/// reallocates and leaves stray copies of the plaintext on the heap.
+pub fn decode_base64_into(
+    buf: &mut Zeroizing<Vec<u8>>,
+    encoded: &str,
+) -> Result<(), DecodeSliceError> {
+    let estimate = base64::decoded_len_estimate(encoded.len());
+    if buf.capacity() < estimate {
+        // The old buffer is zeroized as it drops
+        *buf = Zeroizing::new(Vec::with_capacity(estimate));
+    }
+    buf.clear();
+    buf.resize(estimate, 0);
+    match general_purpose::STANDARD.decode_slice(encoded, buf.as_mut_slice()) {
+        Ok(len) => {
+            buf.truncate(len);
+            Ok(())
+        }
+        Err(e) => {
+            buf.zeroize();
+            Err(e)
+        }
+    }
+}
+
+/// Encoding of the nonce string returned by TAS.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NonceEncoding {
+    /// The bytes of the string are the challenge
+    #[default]
+    Raw,
+    /// Hex-encoded challenge
+    Hex,
+    /// Base64-encoded challenge, standard or URL-safe, padded or not
+    Base64,
+    /// Hex if the string is valid hex, else base64 if it is valid base64,
+    /// else raw. Ambiguous: a raw or base64 nonce that happens to consist of
+    /// hex digits is hex-decoded, so the evidence binds different bytes than
+    /// the verifier expects. Only for verifiers whose encoding is unknown.
+    Auto,
+}
+
+/// Decode base64 `text`, standard or URL-safe, padded or not.
+pub fn base64(text: &str) -> Option<Vec<u8>> {
+    [
+        &general_purpose::STANDARD,
+        &general_purpose::URL_SAFE,
+        &general_purpose::STANDARD_NO_PAD,
+        &general_purpose::URL_SAFE_NO_PAD,
+    ]
+    .into_iter()
+    .find_map(|engine| engine.decode(text).ok())
+}
+
+/// Decode the TAS nonce string to the challenge bytes.
+pub fn decode_nonce(nonce: &str, encoding: NonceEncoding) -> Result<Vec<u8>, String> {
+    match encoding {
+        NonceEncoding::Raw => Ok(nonce.as_bytes().to_vec()),
+        NonceEncoding::Hex => {
+            hex::decode(nonce).map_err(|e| format!("nonce is not valid hex: {}", e))
+        }
+        NonceEncoding::Base64 => base64(nonce).ok_or_else(|| "nonce is not valid base64".into()),
+        NonceEncoding::Auto => Ok(hex::decode(nonce)
+            .ok()
+            .or_else(|| base64(nonce))
+            .unwrap_or_else(|| nonce.as_bytes().to_vec())),
+    }
+}
+
+/// Encoding of the binary fields of a mapped secret payload.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadEncoding {
+    /// Base64, standard or URL-safe, padded or not
+    #[default]
+    Base64,
+    /// Hex
+    Hex,
+}
+
+/// `[payload_mapping]` section of the config file: the field names and
+/// encoding of secret payloads from key brokers other than TAS. Unset names
+/// are the `SecretsPayload` field names.
+#[derive(Deserialize, Clone, Default, Debug)]
+pub struct PayloadMapping {
+    pub wrapped_key: Option<String>,
+    pub blob: Option<String>,
+    pub iv: Option<String>,
+    pub tag: Option<String>,
+    pub algorithm: Option<String>,
+    pub aad: Option<String>,
+    /// Encoding of the wrapped key, blob, IV, tag and AAD (default: base64)
+    #[serde(default)]
+    pub encoding: PayloadEncoding,
+    /// The algorithm name is plain text rather than encoded like the other
+    /// fields
+    #[serde(default)]
+    pub plain_algorithm: bool,
+}
+
+impl PayloadMapping {
+    /// Rewrite the JSON `payload` into the shape TAS returns, with the
+    /// `SecretsPayload` field names and standard base64 values. Members not
+    /// mapped are dropped; missing ones are left for the schema to report.
+    pub fn apply(&self, payload: &str) -> Result<String, String> {
+        let Value::Object(mut members) =
+            serde_json::from_str(payload).map_err(|e| format!("invalid payload: {}", e))?
+        else {
+            return Err("payload is not a JSON object".to_string());
+        };
+        let fields = [
+            ("wrapped_key", &self.wrapped_key),
+            ("blob", &self.blob),
+            ("iv", &self.iv),
+            ("tag", &self.tag),
+            ("algorithm", &self.algorithm),
+            ("aad", &self.aad),
+        ];
+        let mut mapped = serde_json::Map::new();
+        for (field, name) in fields {
+            let name = name.as_deref().unwrap_or(field);
+            let Some(value) = members.remove(name) else {
+                continue;
+            };
+            let value = match value {
+                Value::String(text) if field == "algorithm" && self.plain_algorithm => {
+                    Value::String(general_purpose::STANDARD.encode(text))
+                }
+                Value::String(text) => {
+                    let (decoded, encoding) = match self.encoding {
+                        PayloadEncoding::Base64 => (base64(&text), "base64"),
+                        PayloadEncoding::Hex => (hex::decode(&text).ok(), "hex"),
+                    };
+                    let decoded = decoded
+                        .ok_or_else(|| format!("'{}' field is not valid {}", name, encoding))?;
+                    Value::String(general_purpose::STANDARD.encode(decoded))
+                }
+                // Wrong types are reported by the schema
+                other => other,
+            };
+            mapped.insert(field.to_string(), value);
+        }
+        Ok(Value::Object(mapped).to_string())
+    }
+}
+
+struct Base64Visitor;
+
+impl<'de> Visitor<'de> for Base64Visitor {
+    type Value = Zeroizing<Vec<u8>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a base64 string")
+    }
+
+    fn visit_str<E: de::Error>(self, encoded: &str) -> Result<Self::Value, E> {
+        check_decoded_len(encoded, limits::decoded_bytes()).map_err(E::custom)?;
+        let mut buf = Zeroizing::new(Vec::new());
+        decode_base64_into(&mut buf, encoded)
+            .map_err(|e| E::custom(format!("Base64 decoding error: {}", e)))?;
+        Ok(buf)
+    }
+}
+
+fn deserialize_base64<'de, D>(d: D) -> Result<Zeroizing<Vec<u8>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // Borrow the encoded text from the input rather than copying it out
+    d.deserialize_str(Base64Visitor)
+}
+
+fn deserialize_base64_optional<'de, D>(d: D) -> Result<Option<Zeroizing<Vec<u8>>>, D::Error>
 where
     D: Deserializer<'de>,
-    T: DeserializeOwned + From<Vec<u8>>,
 {
-    let base64_string = String::deserialize(d)?;
-    let decoded_bytes = general_purpose::STANDARD
-        .decode(&base64_string)
-        .map_err(|e| serde::de::Error::custom(format!("Base64 decoding error: {}", e)))?;
-    Ok(T::from(decoded_bytes))
+    deserialize_base64(d).map(Some)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_payload_mapping() {
+        let mapping = PayloadMapping {
+            wrapped_key: Some("encrypted_key".to_string()),
+            blob: Some("ciphertext".to_string()),
+            iv: Some("nonce".to_string()),
+            tag: Some("mac".to_string()),
+            algorithm: Some("alg".to_string()),
+            encoding: PayloadEncoding::Hex,
+            plain_algorithm: true,
+            ..PayloadMapping::default()
+        };
+        let payload = serde_json::json!({
+            "encrypted_key": hex::encode(b"key"),
+            "ciphertext": hex::encode(b"blob"),
+            "nonce": hex::encode(b"twelve_byte!"),
+            "mac": hex::encode(b"sixteen_byte_tag"),
+            "alg": "AES-GCM",
+            "kid": "broker-1"
+        });
+        let mapped = mapping.apply(&payload.to_string()).unwrap();
+        let secret: SecretsPayload = serde_json::from_str(&mapped).unwrap();
+        assert_eq!(secret.wrapped_key.as_slice(), b"key");
+        assert_eq!(secret.blob.as_slice(), b"blob");
+        assert_eq!(secret.iv.as_slice(), b"twelve_byte!");
+        assert_eq!(secret.tag.as_slice(), b"sixteen_byte_tag");
+        assert_eq!(secret.algorithm, "AES-GCM");
+        assert!(!mapped.contains("kid"));
+
+        let bad = serde_json::json!({"encrypted_key": "not hex"});
+        assert_eq!(
+            mapping.apply(&bad.to_string()).unwrap_err(),
+            "'encrypted_key' field is not valid hex"
+        );
+        assert!(mapping.apply("\"text\"").is_err());
+    }
+
+    #[test]
+    fn test_decode_nonce() {
+        let challenge = [0xfbu8; 32];
+        let hex = hex::encode(challenge);
+        let b64 = general_purpose::STANDARD.encode(challenge);
+        let b64url = general_purpose::URL_SAFE_NO_PAD.encode(challenge);
+
+        assert_eq!(
+            decode_nonce(&hex, NonceEncoding::Raw).unwrap(),
+            hex.as_bytes()
+        );
+        assert_eq!(decode_nonce(&hex, NonceEncoding::Hex).unwrap(), challenge);
+        assert_eq!(
+            decode_nonce(&b64, NonceEncoding::Base64).unwrap(),
+            challenge
+        );
+        assert_eq!(
+            decode_nonce(&b64url, NonceEncoding::Base64).unwrap(),
+            challenge
+        );
+        assert!(decode_nonce(&b64, NonceEncoding::Hex).is_err());
+        assert!(decode_nonce("not base64!", NonceEncoding::Base64).is_err());
+
+        assert_eq!(decode_nonce(&hex, NonceEncoding::Auto).unwrap(), challenge);
+        assert_eq!(decode_nonce(&b64, NonceEncoding::Auto).unwrap(), challenge);
+        assert_eq!(
+            decode_nonce("not base64!", NonceEncoding::Auto).unwrap(),
+            b"not base64!"
+        );
+    }
+
     #[test]
     fn test_secrets_payload_deserialize_valid() {
         let json = serde_json::json!({
@@ -83,10 +347,27 @@ mod tests {
             "tag": base64::engine::general_purpose::STANDARD.encode(b"sixteen_byte_tag")
         });
         let payload: SecretsPayload = serde_json::from_value(json).unwrap();
-        assert_eq!(payload.wrapped_key, b"wrapped_key_bytes");
-        assert_eq!(payload.blob, b"encrypted_blob_data");
-        assert_eq!(payload.iv, b"twelve_byte!");
-        assert_eq!(payload.tag, b"sixteen_byte_tag");
+        assert_eq!(*payload.wrapped_key, b"wrapped_key_bytes");
+        assert_eq!(*payload.blob, b"encrypted_blob_data");
+        assert_eq!(*payload.iv, b"twelve_byte!");
+        assert_eq!(*payload.tag, b"sixteen_byte_tag");
+        assert!(payload.aad.is_none());
+    }
+
+    #[test]
+    fn test_secrets_payload_with_aad() {
+        let json = serde_json::json!({
+            "wrapped_key": base64::engine::general_purpose::STANDARD.encode(b"key"),
+            "blob": base64::engine::general_purpose::STANDARD.encode(b"blob"),
+            "iv": base64::engine::general_purpose::STANDARD.encode(b"iv"),
+            "tag": base64::engine::general_purpose::STANDARD.encode(b"tag"),
+            "aad": base64::engine::general_purpose::STANDARD.encode(b"n0nce")
+        });
+        let payload: SecretsPayload = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            payload.aad.as_deref().map(Vec::as_slice),
+            Some(&b"n0nce"[..])
+        );
     }
 
     #[test]
@@ -128,4 +409,26 @@ mod tests {
         assert!(payload.iv.is_empty());
         assert!(payload.tag.is_empty());
     }
+
+    #[test]
+    fn test_check_decoded_len() {
+        assert!(check_decoded_len("c2l4dGVlbl9ieXRlX3RhZw==", 18).is_ok());
+        let err = check_decoded_len("c2l4dGVlbl9ieXRlX3RhZw==", 16).unwrap_err();
+        assert!(err.contains("exceeds the limit"), "{}", err);
+    }
+
+    #[test]
+    fn test_decode_base64_into_reuses_buffer() {
+        let mut buf = Zeroizing::new(Vec::new());
+        decode_base64_into(&mut buf, "c2l4dGVlbl9ieXRlX3RhZw==").unwrap();
+        assert_eq!(*buf, b"sixteen_byte_tag");
+        let ptr = buf.as_ptr();
+
+        decode_base64_into(&mut buf, "dGFn").unwrap();
+        assert_eq!(*buf, b"tag");
+        assert_eq!(buf.as_ptr(), ptr);
+
+        assert!(decode_base64_into(&mut buf, "not-valid-base64!!!").is_err());
+        assert!(buf.is_empty());
+    }
 }
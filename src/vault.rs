@@ -0,0 +1,250 @@
+// TEE Attestation Service Agent — HashiCorp Vault secrets
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With a `[vault]` section, every successful attestation also pulls secrets
+// from Vault, so secrets that are not disk keys pass through the same
+// attestation gate:
+//
+//   1. The verifier's attestation token is exchanged for a Vault token at
+//      the JWT auth method (`POST /v1/auth/<auth_mount>/login` with `role`).
+//      The auth role decides which token claims are required.
+//   2. Each `[[vault.secrets]]` path is read with that token and written to
+//      its `out` file (mode 0600): the whole secret as JSON, or only the
+//      value of `field`. KV version 1 and 2 responses are both understood;
+//      for KV v2, `path` includes the `data/` segment.
+//   3. The Vault token is revoked.
+//
+// A failure is logged and never fails the key release.
+//
+// No unsafe code.
+
+use crate::output::write_secret_file;
+use crate::tas_api::{create_client, RetryConfig};
+use crate::verifier::json_response;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use reqwest_middleware::ClientWithMiddleware;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::PathBuf;
+use zeroize::Zeroize;
+
+/// Header carrying the Vault token.
+const TOKEN_HEADER: &str = "X-Vault-Token";
+
+/// A secret to read under `[[vault.secrets]]`.
+#[derive(Deserialize, Clone)]
+pub struct VaultSecret {
+    /// API path of the secret, e.g. secret/data/app/db
+    pub path: String,
+    /// File the secret is written to
+    pub out: PathBuf,
+    /// Write only this field of the secret
+    pub field: Option<String>,
+}
+
+/// `[vault]` section of the config file.
+#[derive(Deserialize, Clone)]
+pub struct VaultConfig {
+    /// Vault server address, e.g. https://vault.example.com:8200
+    pub address: String,
+    /// JWT auth role to log in with
+    pub role: String,
+    /// Mount path of the JWT auth method (default: jwt)
+    pub auth_mount: Option<String>,
+    /// CA bundle for the Vault server (default: the TAS cert_path)
+    pub cert_path: Option<PathBuf>,
+    /// Secrets to read
+    #[serde(default)]
+    pub secrets: Vec<VaultSecret>,
+}
+
+/// URL of the Vault API `path`.
+fn endpoint(config: &VaultConfig, path: &str) -> String {
+    format!(
+        "{}/v1/{}",
+        config.address.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+/// Extract the contents of `field`, or the whole secret, from a KV read.
+fn secret_data(json: &Value, field: Option<&str>) -> Result<Vec<u8>> {
+    let data = json
+        .get("data")
+        .ok_or_else(|| anyhow!("Error: 'data' field not found in response"))?;
+    // KV v2 nests the secret and its metadata under data
+    let data = match data.get("metadata") {
+        Some(_) => data.get("data").unwrap_or(data),
+        None => data,
+    };
+    let value = match field {
+        Some(field) => data
+            .get(field)
+            .ok_or_else(|| anyhow!("secret has no field {:?}", field))?,
+        None => data,
+    };
+    Ok(match value {
+        Value::String(s) => s.clone().into_bytes(),
+        other => other.to_string().into_bytes(),
+    })
+}
+
+/// Exchange `jwt` for a Vault token.
+async fn login(client: &ClientWithMiddleware, config: &VaultConfig, jwt: &str) -> Result<String> {
+    let mount = config.auth_mount.as_deref().unwrap_or("jwt");
+    let url = endpoint(config, &format!("auth/{}/login", mount));
+    debug!("Logging in to Vault at {}", url);
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({"role": config.role, "jwt": jwt}))
+        .send()
+        .await;
+    json_response(response)
+        .await?
+        .pointer("/auth/client_token")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Error: 'auth.client_token' field not found in response"))
+}
+
+/// Read every configured secret with `token` and write it out.
+async fn read_secrets(
+    client: &ClientWithMiddleware,
+    config: &VaultConfig,
+    token: &str,
+) -> Result<()> {
+    for secret in &config.secrets {
+        let url = endpoint(config, &secret.path);
+        debug!("Reading Vault secret {}", secret.path);
+        let response = client.get(&url).header(TOKEN_HEADER, token).send().await;
+        let json = json_response(response)
+            .await
+            .with_context(|| format!("unable to read {}", secret.path))?;
+        let mut data = secret_data(&json, secret.field.as_deref())
+            .with_context(|| format!("unable to read {}", secret.path))?;
+        let written = write_secret_file(&secret.out, &data);
+        data.zeroize();
+        written?;
+    }
+    Ok(())
+}
+
+async fn try_fetch(
+    config: &VaultConfig,
+    jwt: &str,
+    cert_path: PathBuf,
+    retry_config: &RetryConfig,
+) -> Result<()> {
+    let cert_path = config.cert_path.clone().unwrap_or(cert_path);
+    let client = create_client(&config.address, cert_path, retry_config).map_err(|e| anyhow!(e))?;
+    let token = login(&client, config, jwt)
+        .await
+        .context("Vault login failed")?;
+    let result = read_secrets(&client, config, &token).await;
+
+    let revoke = endpoint(config, "auth/token/revoke-self");
+    if let Err(e) = client
+        .post(&revoke)
+        .header(TOKEN_HEADER, &token)
+        .send()
+        .await
+    {
+        warn!("unable to revoke Vault token: {}", e);
+    }
+    result
+}
+
+/// Pull the configured Vault secrets with the attestation token `jwt`.
+/// Failures are logged.
+pub async fn fetch_secrets(
+    config: &VaultConfig,
+    jwt: &str,
+    cert_path: PathBuf,
+    retry_config: &RetryConfig,
+) {
+    match try_fetch(config, jwt, cert_path, retry_config).await {
+        Ok(()) => info!("Wrote {} Vault secret(s)", config.secrets.len()),
+        Err(e) => warn!("unable to fetch Vault secrets: {:#}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Server;
+    use serde_json::json;
+
+    #[test]
+    fn test_secret_data() {
+        let kv2 = json!({"data": {"data": {"password": "s3cret", "port": 5432},
+                                  "metadata": {"version": 3}}});
+        assert_eq!(secret_data(&kv2, Some("password")).unwrap(), b"s3cret");
+        assert_eq!(secret_data(&kv2, Some("port")).unwrap(), b"5432");
+        assert!(secret_data(&kv2, Some("user")).is_err());
+
+        let kv1 = json!({"data": {"password": "s3cret"}});
+        assert_eq!(
+            secret_data(&kv1, None).unwrap(),
+            br#"{"password":"s3cret"}"#
+        );
+        assert!(secret_data(&json!({}), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_try_fetch() {
+        let mut server = Server::new_async().await;
+        let login = server
+            .mock("POST", "/v1/auth/jwt/login")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"role":"tas-agent","jwt":"eyJ.token.sig"}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"auth": {"client_token": "hvs.token"}}"#)
+            .create_async()
+            .await;
+        let read = server
+            .mock("GET", "/v1/secret/data/app/db")
+            .match_header(TOKEN_HEADER, "hvs.token")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"data": {"data": {"password": "s3cret"}, "metadata": {}}}"#)
+            .create_async()
+            .await;
+        let revoke = server
+            .mock("POST", "/v1/auth/token/revoke-self")
+            .match_header(TOKEN_HEADER, "hvs.token")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("db-password");
+        let config = VaultConfig {
+            address: server.url(),
+            role: "tas-agent".to_string(),
+            auth_mount: None,
+            cert_path: None,
+            secrets: vec![VaultSecret {
+                path: "secret/data/app/db".to_string(),
+                out: out.clone(),
+                field: Some("password".to_string()),
+            }],
+        };
+        try_fetch(
+            &config,
+            "eyJ.token.sig",
+            PathBuf::new(),
+            &RetryConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "s3cret");
+        login.assert_async().await;
+        read.assert_async().await;
+        revoke.assert_async().await;
+    }
+}
@@ -0,0 +1,143 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Intel Trust Authority verifier backend.
+//
+// Submits the TDX quote and its runtime data to the ITA v2 appraisal API and
+// returns the signed attestation token (a JWT). ITA checks that the quote's
+// REPORTDATA is the SHA-512 digest of the runtime data, which binds the token
+// to the TAS nonce and the wrapping key.
+
+use super::{json_response, read_credential, token_field, Evidence};
+use crate::tas_api::{create_client, RetryConfig};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine};
+use log::debug;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Default ITA API endpoint.
+const DEFAULT_URL: &str = "https://api.trustauthority.intel.com";
+
+/// ITA settings under `[verifier]` with `backend = "ita"`.
+#[derive(Deserialize, Clone)]
+pub struct ItaConfig {
+    /// ITA API endpoint (default: https://api.trustauthority.intel.com)
+    pub url: Option<String>,
+    /// File holding the ITA attestation API key
+    pub api_key: PathBuf,
+    /// ITA appraisal policies to apply
+    pub policy_ids: Option<Vec<String>>,
+}
+
+/// Build the appraisal request for `evidence`.
+fn request_body(config: &ItaConfig, evidence: &Evidence<'_>) -> Result<Value> {
+    if evidence.tee_type != "intel-tdx" {
+        return Err(anyhow!(
+            "Intel Trust Authority cannot appraise {} evidence",
+            evidence.tee_type
+        ));
+    }
+    Ok(serde_json::json!({
+        "tdx": {
+            "quote": evidence.evidence,
+            "runtime_data": general_purpose::STANDARD.encode(evidence.runtime_data),
+        },
+        "policy_ids": config.policy_ids.clone().unwrap_or_default(),
+    }))
+}
+
+/// Appraise `evidence` with ITA and return the attestation token.
+pub async fn attest(
+    config: &ItaConfig,
+    evidence: &Evidence<'_>,
+    cert_path: PathBuf,
+    retry_config: &RetryConfig,
+) -> Result<String> {
+    let body = request_body(config, evidence)?;
+    let api_key = read_credential(&config.api_key)?;
+    let base = config.url.as_deref().unwrap_or(DEFAULT_URL);
+    let url = format!("{}/appraisal/v2/attest", base.trim_end_matches('/'));
+    debug!("Requesting ITA appraisal from {}", url);
+
+    let client = create_client(&url, cert_path, retry_config).map_err(|e| anyhow!(e))?;
+    let response = client
+        .post(&url)
+        .header("x-api-key", api_key)
+        .header("Accept", "application/json")
+        .json(&body)
+        .send()
+        .await;
+    token_field(&json_response(response).await?, "token")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Server;
+    use std::io::Write;
+
+    fn config(url: String, key: &tempfile::NamedTempFile) -> ItaConfig {
+        ItaConfig {
+            url: Some(url),
+            api_key: key.path().to_path_buf(),
+            policy_ids: Some(vec!["p1".to_string()]),
+        }
+    }
+
+    fn key_file() -> tempfile::NamedTempFile {
+        let mut key = tempfile::NamedTempFile::new().unwrap();
+        writeln!(key, "ita-key").unwrap();
+        key
+    }
+
+    #[test]
+    fn test_request_body_rejects_non_tdx() {
+        let key = key_file();
+        let evidence = Evidence {
+            tee_type: "amd-sev-snp",
+            evidence: "cXVvdGU=",
+            runtime_data: b"data",
+            eat: None,
+        };
+        assert!(request_body(&config(DEFAULT_URL.to_string(), &key), &evidence).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ita_attest() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/appraisal/v2/attest")
+            .match_header("x-api-key", "ita-key")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"tdx":{"quote":"cXVvdGU=","runtime_data":"ZGF0YQ=="},"policy_ids":["p1"]}"#
+                    .to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"token": "eyJ.ita.sig"}"#)
+            .create_async()
+            .await;
+
+        let key = key_file();
+        let evidence = Evidence {
+            tee_type: "intel-tdx",
+            evidence: "cXVvdGU=",
+            runtime_data: b"data",
+            eat: None,
+        };
+        let token = attest(
+            &config(server.url(), &key),
+            &evidence,
+            PathBuf::new(),
+            &RetryConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(token, "eyJ.ita.sig");
+        mock.assert_async().await;
+    }
+}
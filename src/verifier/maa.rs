@@ -0,0 +1,134 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Microsoft Azure Attestation verifier backend.
+//
+// Submits the TDX quote and its runtime data to the `attest/TdxVm` API of an
+// MAA instance and returns the JWT it issues under the instance's policy. MAA
+// checks that the quote's report data is the SHA-512 digest of the runtime
+// data, which binds the token to the TAS nonce and the wrapping key. The
+// request carries an Azure AD bearer token when the instance requires one.
+
+use super::{json_response, read_credential, token_field, Evidence};
+use crate::tas_api::{create_client, RetryConfig};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose, Engine};
+use log::debug;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// MAA API version of the TdxVm endpoint.
+const TDX_API_VERSION: &str = "2023-04-01-preview";
+
+/// MAA settings under `[verifier]` with `backend = "maa"`.
+#[derive(Deserialize, Clone)]
+pub struct MaaConfig {
+    /// Attestation instance URI, e.g. https://myprovider.eus.attest.azure.net
+    pub url: String,
+    /// File holding an Azure AD bearer token for the instance
+    pub bearer_token: Option<PathBuf>,
+}
+
+/// Build the attestation request for `evidence`.
+fn request_body(evidence: &Evidence<'_>) -> Result<Value> {
+    if evidence.tee_type != "intel-tdx" {
+        return Err(anyhow!(
+            "Microsoft Azure Attestation cannot appraise {} evidence",
+            evidence.tee_type
+        ));
+    }
+    let quote = general_purpose::STANDARD
+        .decode(evidence.evidence)
+        .context("TDX quote is not valid base64")?;
+    Ok(serde_json::json!({
+        "report": general_purpose::URL_SAFE_NO_PAD.encode(quote),
+        "runtimeData": {
+            "data": general_purpose::URL_SAFE_NO_PAD.encode(evidence.runtime_data),
+            "dataType": "Binary",
+        },
+    }))
+}
+
+/// Appraise `evidence` with MAA and return the attestation token.
+pub async fn attest(
+    config: &MaaConfig,
+    evidence: &Evidence<'_>,
+    cert_path: PathBuf,
+    retry_config: &RetryConfig,
+) -> Result<String> {
+    let body = request_body(evidence)?;
+    let url = format!(
+        "{}/attest/TdxVm?api-version={}",
+        config.url.trim_end_matches('/'),
+        TDX_API_VERSION
+    );
+    debug!("Requesting MAA attestation from {}", url);
+
+    let client = create_client(&url, cert_path, retry_config).map_err(|e| anyhow!(e))?;
+    let mut request = client.post(&url).json(&body);
+    if let Some(path) = &config.bearer_token {
+        request = request.bearer_auth(read_credential(path)?);
+    }
+    token_field(&json_response(request.send().await).await?, "token")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Server;
+
+    #[test]
+    fn test_request_body() {
+        let evidence = Evidence {
+            tee_type: "intel-tdx",
+            evidence: "+/8=",
+            runtime_data: b"data",
+            eat: None,
+        };
+        let body = request_body(&evidence).unwrap();
+        assert_eq!(body["report"], "-_8");
+        assert_eq!(body["runtimeData"]["data"], "ZGF0YQ");
+        assert_eq!(body["runtimeData"]["dataType"], "Binary");
+
+        let evidence = Evidence {
+            tee_type: "amd-sev-snp",
+            ..evidence
+        };
+        assert!(request_body(&evidence).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_maa_attest() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/attest/TdxVm")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "api-version".to_string(),
+                TDX_API_VERSION.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"token": "eyJ.maa.sig"}"#)
+            .create_async()
+            .await;
+
+        let config = MaaConfig {
+            url: server.url(),
+            bearer_token: None,
+        };
+        let evidence = Evidence {
+            tee_type: "intel-tdx",
+            evidence: "cXVvdGU=",
+            runtime_data: b"data",
+            eat: None,
+        };
+        let token = attest(&config, &evidence, PathBuf::new(), &RetryConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(token, "eyJ.maa.sig");
+        mock.assert_async().await;
+    }
+}
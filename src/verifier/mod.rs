@@ -0,0 +1,111 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// External verifier backends.
+//
+// With a `[verifier]` section, TEE evidence is appraised by an external
+// attestation service after it is collected, and the signed attestation token
+// that service returns is presented to TAS as `attestation-token` alongside
+// the evidence. This suits deployments that outsource appraisal instead of
+// running their own. An appraisal failure fails the key release.
+
+pub mod ita;
+pub mod maa;
+pub mod veraison;
+
+use crate::hooks::ErrorClass;
+use crate::tas_api::RetryConfig;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// `[verifier]` section of the config file.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum VerifierConfig {
+    /// Intel Trust Authority
+    Ita(ita::ItaConfig),
+    /// Microsoft Azure Attestation
+    Maa(maa::MaaConfig),
+    /// Veraison
+    Veraison(veraison::VeraisonConfig),
+}
+
+/// Evidence to be appraised.
+pub struct Evidence<'a> {
+    /// TEE type, e.g. "intel-tdx"
+    pub tee_type: &'a str,
+    /// Base64-encoded TEE report or quote
+    pub evidence: &'a str,
+    /// Data whose SHA-512 digest is the report data of the evidence
+    pub runtime_data: &'a [u8],
+    /// The evidence packaged as a signed EAT, if `[eat]` is configured
+    pub eat: Option<&'a [u8]>,
+}
+
+/// Appraise `evidence` with the configured verifier and return its
+/// attestation token.
+pub async fn appraise(
+    config: &VerifierConfig,
+    evidence: &Evidence<'_>,
+    cert_path: PathBuf,
+    retry_config: &RetryConfig,
+) -> Result<String> {
+    match config {
+        VerifierConfig::Ita(ita) => ita::attest(ita, evidence, cert_path, retry_config).await,
+        VerifierConfig::Maa(maa) => maa::attest(maa, evidence, cert_path, retry_config).await,
+        VerifierConfig::Veraison(veraison) => {
+            veraison::attest(veraison, evidence, cert_path, retry_config).await
+        }
+    }
+}
+
+/// The file the configured verifier reads its API key or token from, if any.
+#[cfg(feature = "landlock")]
+pub fn credential_path(config: &VerifierConfig) -> Option<&Path> {
+    match config {
+        VerifierConfig::Ita(ita) => Some(&ita.api_key),
+        VerifierConfig::Maa(maa) => maa.bearer_token.as_deref(),
+        VerifierConfig::Veraison(veraison) => veraison.bearer_token.as_deref(),
+    }
+}
+
+/// Read a verifier API key or secret from `path`.
+fn read_credential(path: &Path) -> Result<String> {
+    Ok(std::fs::read_to_string(path)
+        .with_context(|| format!("unable to read verifier credential from {:?}", path))?
+        .trim()
+        .to_string())
+}
+
+/// Return the JSON body of a successful verifier response.
+pub(crate) async fn json_response(
+    response: reqwest_middleware::Result<reqwest::Response>,
+) -> Result<Value> {
+    let response =
+        response.map_err(|e| ErrorClass::Network.error(format!("Error making request: {}", e)))?;
+    let status = response.status();
+    if !status.is_success() {
+        let message = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unable to read response body".to_string());
+        return Err(anyhow!(
+            "Error: Received HTTP {} with message: {}",
+            status,
+            message
+        ));
+    }
+    response.json().await.context("Error parsing JSON response")
+}
+
+/// Extract the string field `field` from a verifier response.
+fn token_field(json: &Value, field: &str) -> Result<String> {
+    json.get(field)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Error: '{}' field not found in response", field))
+}
@@ -0,0 +1,180 @@
+// TEE Attestation Service Agent
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// Veraison verifier backend.
+//
+// Uses Veraison's challenge-response API: a session is opened with the
+// report data of the evidence as its nonce, the evidence is posted to the
+// session with the configured media type, and the Attestation Result (an EAR
+// JWT) from the completed session is returned. The session is deleted
+// afterwards. Because the nonce is SHA-512 of the runtime data, Veraison's
+// nonce check binds the EAR to the TAS nonce and the wrapping key.
+//
+// When the evidence is packaged as an EAT, the token is submitted instead of
+// the raw report; its eat_nonce claim carries the same session nonce.
+
+use super::{json_response, read_credential, token_field, Evidence};
+use crate::tas_api::{create_client, RetryConfig};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose, Engine};
+use log::{debug, warn};
+use reqwest::header::{ACCEPT, CONTENT_TYPE, LOCATION};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha512};
+use std::path::PathBuf;
+
+/// Media type of challenge-response session resources.
+const SESSION_MEDIA_TYPE: &str = "application/vnd.veraison.challenge-response-session+json";
+
+/// Veraison settings under `[verifier]` with `backend = "veraison"`.
+#[derive(Deserialize, Clone)]
+pub struct VeraisonConfig {
+    /// Veraison verification service URI, e.g. https://veraison.example.com:8080
+    pub url: String,
+    /// Media type the evidence is submitted as
+    pub media_type: String,
+    /// File holding a bearer token for the service
+    pub bearer_token: Option<PathBuf>,
+}
+
+/// Extract the EAR from a challenge-response session resource.
+fn session_result(session: &Value) -> Result<String> {
+    match session.get("status").and_then(Value::as_str) {
+        Some("complete") => token_field(session, "result"),
+        Some(status) => Err(anyhow!("Veraison session is {}", status)),
+        None => Err(anyhow!("Error: 'status' field not found in response")),
+    }
+}
+
+/// Appraise `evidence` with Veraison and return the EAR.
+pub async fn attest(
+    config: &VeraisonConfig,
+    evidence: &Evidence<'_>,
+    cert_path: PathBuf,
+    retry_config: &RetryConfig,
+) -> Result<String> {
+    let body = match evidence.eat {
+        Some(eat) => eat.to_vec(),
+        None => general_purpose::STANDARD
+            .decode(evidence.evidence)
+            .context("evidence is not valid base64")?,
+    };
+    let nonce = general_purpose::URL_SAFE.encode(Sha512::digest(evidence.runtime_data));
+    let bearer_token = config
+        .bearer_token
+        .as_deref()
+        .map(read_credential)
+        .transpose()?;
+    let new_session = format!(
+        "{}/challenge-response/v1/newSession",
+        config.url.trim_end_matches('/')
+    );
+    debug!("Opening Veraison session at {}", new_session);
+
+    let client = create_client(&new_session, cert_path, retry_config).map_err(|e| anyhow!(e))?;
+    let authorize = |request: reqwest_middleware::RequestBuilder| match &bearer_token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    };
+
+    let response = authorize(client.post(&new_session))
+        .query(&[("nonce", &nonce)])
+        .header(ACCEPT, SESSION_MEDIA_TYPE)
+        .send()
+        .await;
+    let location = response
+        .as_ref()
+        .ok()
+        .and_then(|r| r.headers().get(LOCATION))
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    json_response(response).await?;
+    let location = location.ok_or_else(|| anyhow!("Error: no session location in response"))?;
+    let session_url = Url::parse(&new_session)
+        .and_then(|base| base.join(&location))
+        .with_context(|| format!("invalid session location {:?}", location))?;
+
+    let response = authorize(client.post(session_url.clone()))
+        .header(CONTENT_TYPE, config.media_type.as_str())
+        .header(ACCEPT, SESSION_MEDIA_TYPE)
+        .body(body)
+        .send()
+        .await;
+    let result = match json_response(response).await {
+        Ok(session) => session_result(&session),
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = authorize(client.delete(session_url)).send().await {
+        warn!("unable to delete Veraison session: {}", e);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Server;
+
+    #[test]
+    fn test_session_result() {
+        let complete = serde_json::json!({"status": "complete", "result": "eyJ.ear.sig"});
+        assert_eq!(session_result(&complete).unwrap(), "eyJ.ear.sig");
+        let failed = serde_json::json!({"status": "failed"});
+        assert!(session_result(&failed)
+            .unwrap_err()
+            .to_string()
+            .contains("failed"));
+    }
+
+    #[tokio::test]
+    async fn test_veraison_attest() {
+        let mut server = Server::new_async().await;
+        let new_session = server
+            .mock("POST", "/challenge-response/v1/newSession")
+            .match_query(mockito::Matcher::Any)
+            .with_status(201)
+            .with_header("content-type", SESSION_MEDIA_TYPE)
+            .with_header("location", "session/1234")
+            .with_body(r#"{"status": "waiting"}"#)
+            .create_async()
+            .await;
+        let submit = server
+            .mock("POST", "/challenge-response/v1/session/1234")
+            .match_header("content-type", "application/vnd.example.tdx-quote")
+            .match_body("quote")
+            .with_status(200)
+            .with_header("content-type", SESSION_MEDIA_TYPE)
+            .with_body(r#"{"status": "complete", "result": "eyJ.ear.sig"}"#)
+            .create_async()
+            .await;
+        let delete = server
+            .mock("DELETE", "/challenge-response/v1/session/1234")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let config = VeraisonConfig {
+            url: server.url(),
+            media_type: "application/vnd.example.tdx-quote".to_string(),
+            bearer_token: None,
+        };
+        let evidence = Evidence {
+            tee_type: "intel-tdx",
+            evidence: "cXVvdGU=",
+            runtime_data: b"data",
+            eat: None,
+        };
+        let ear = attest(&config, &evidence, PathBuf::new(), &RetryConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(ear, "eyJ.ear.sig");
+        new_session.assert_async().await;
+        submit.assert_async().await;
+        delete.assert_async().await;
+    }
+}
@@ -0,0 +1,184 @@
+// TEE Attestation Service Agent — cached server version probe
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// With a `[version_cache]` section, the result of a successful TAS version
+// probe is kept in a small JSON file for `ttl_secs`:
+//
+//   {"server_uri": "https://tas:5001", "version": "\"0.1.0\"",
+//    "wrapping_key_encodings": ["pem"], "wrap_algorithms": ["HPKE"],
+//    "checked_at": "<rfc3339>"}
+//
+// One-shot invocations started in quick succession, e.g. one crypttab entry
+// per volume, then skip the `/version` round trip and go straight to the
+// nonce request. An entry only applies to the server URI it was probed
+// from; a missing, stale or unreadable file simply means probing again.
+//
+// The version probe doubles as the reachability check that decides the
+// offline escrow fallback, so the cache is not consulted when
+// `offline_fallback` is enabled. The daemon always probes.
+//
+// No unsafe code.
+
+use crate::tas_api::ServerVersion;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Default location of the cache file; /run is cleared at every boot.
+const DEFAULT_PATH: &str = "/run/tas_agent/version.json";
+
+/// Default lifetime of a cached probe.
+const DEFAULT_TTL_SECS: u64 = 60;
+
+/// `[version_cache]` section of the config file.
+#[derive(Deserialize, Clone)]
+pub struct VersionCacheConfig {
+    /// File the probe result is kept in (default: /run/tas_agent/version.json)
+    pub path: Option<PathBuf>,
+    /// Seconds a probe result stays valid (default: 60)
+    pub ttl_secs: Option<u64>,
+}
+
+impl VersionCacheConfig {
+    pub fn path(&self) -> &Path {
+        self.path.as_deref().unwrap_or(Path::new(DEFAULT_PATH))
+    }
+
+    fn ttl_secs(&self) -> u64 {
+        self.ttl_secs.unwrap_or(DEFAULT_TTL_SECS)
+    }
+}
+
+/// Contents of the cache file.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Entry {
+    server_uri: String,
+    version: String,
+    /// Wrapping key encodings the server advertised
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    wrapping_key_encodings: Vec<String>,
+    /// Wrapping algorithms the server advertised
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    wrap_algorithms: Vec<String>,
+    checked_at: String,
+}
+
+impl Entry {
+    /// The cached version, if the entry is for `server_uri` and younger than
+    /// `ttl_secs` at `now`.
+    fn version_at(&self, server_uri: &str, ttl_secs: u64, now: DateTime<Utc>) -> Option<&str> {
+        if self.server_uri != server_uri {
+            return None;
+        }
+        let checked_at = DateTime::parse_from_rfc3339(&self.checked_at).ok()?;
+        let age = (now - checked_at.with_timezone(&Utc)).to_std().ok()?;
+        (age.as_secs() < ttl_secs).then_some(self.version.as_str())
+    }
+}
+
+/// The cached server version for `server_uri`, if still fresh.
+pub fn load(config: &VersionCacheConfig, server_uri: &str) -> Option<ServerVersion> {
+    let path = config.path();
+    let entry: Entry = match fs::read(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(serde_json::from_slice(&json)?))
+    {
+        Ok(entry) => entry,
+        Err(e) => {
+            debug!("No cached server version in {:?}: {}", path, e);
+            return None;
+        }
+    };
+    let version = entry
+        .version_at(server_uri, config.ttl_secs(), Utc::now())
+        .map(str::to_string);
+    if version.is_none() {
+        debug!("Cached server version in {:?} is stale", path);
+    }
+    version.map(|version| ServerVersion {
+        version,
+        wrapping_key_encodings: entry.wrapping_key_encodings,
+        wrap_algorithms: entry.wrap_algorithms,
+    })
+}
+
+/// Record a successful probe of `server_uri`, via a temporary file and
+/// rename so concurrent invocations never read a partial entry.
+pub fn store(config: &VersionCacheConfig, server_uri: &str, version: &ServerVersion) -> Result<()> {
+    let path = config.path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("unable to create {:?}", dir))?;
+    }
+    let entry = Entry {
+        server_uri: server_uri.to_string(),
+        version: version.version.clone(),
+        wrapping_key_encodings: version.wrapping_key_encodings.clone(),
+        wrap_algorithms: version.wrap_algorithms.clone(),
+        checked_at: Utc::now().to_rfc3339(),
+    };
+    let json = serde_json::to_vec(&entry).context("unable to serialize server version")?;
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&tmp, json).with_context(|| format!("unable to write {:?}", tmp))?;
+    fs::rename(&tmp, path).with_context(|| format!("unable to replace {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn test_entry_freshness() {
+        let now = Utc::now();
+        let entry = Entry {
+            server_uri: "https://tas:5001".to_string(),
+            version: "\"0.1.0\"".to_string(),
+            wrapping_key_encodings: Vec::new(),
+            wrap_algorithms: Vec::new(),
+            checked_at: now.to_rfc3339(),
+        };
+        assert_eq!(
+            entry.version_at("https://tas:5001", 60, now + TimeDelta::seconds(30)),
+            Some("\"0.1.0\"")
+        );
+        assert_eq!(
+            entry.version_at("https://tas:5001", 60, now + TimeDelta::seconds(60)),
+            None
+        );
+        assert_eq!(entry.version_at("https://other:5001", 60, now), None);
+        // A clock that went backwards never makes an entry fresh
+        assert_eq!(
+            entry.version_at("https://tas:5001", 60, now - TimeDelta::seconds(5)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = VersionCacheConfig {
+            path: Some(dir.path().join("cache").join("version.json")),
+            ttl_secs: None,
+        };
+        assert_eq!(load(&config, "https://tas:5001"), None);
+        let version = ServerVersion {
+            version: "\"0.1.0\"".to_string(),
+            wrapping_key_encodings: vec!["pem".to_string()],
+            wrap_algorithms: vec!["HPKE".to_string(), "RSA-OAEP-256".to_string()],
+        };
+        store(&config, "https://tas:5001", &version).unwrap();
+        assert_eq!(load(&config, "https://tas:5001"), Some(version));
+        assert_eq!(load(&config, "https://other:5001"), None);
+
+        let expired = VersionCacheConfig {
+            ttl_secs: Some(0),
+            ..config
+        };
+        assert_eq!(load(&expired, "https://tas:5001"), None);
+    }
+}
@@ -0,0 +1,507 @@
+// TEE Attestation Service Agent — wrapping algorithms
+//
+// Copyright 2026 Hewlett Packard Enterprise Development LP.
+// SPDX-License-Identifier: MIT
+//
+// TAS wraps the AES key of a secret payload to the agent's ephemeral
+// wrapping key. Besides RSA-OAEP with SHA-256 to an RSA-2048 key, a server
+// may offer algorithms on an ephemeral P-256 key, listed in the optional
+// `wrap-algorithms` field of its version response:
+//
+//   {"version": "1.4.0", "wrap-algorithms": ["RSA-OAEP-256", "ECDH-ES", "HPKE"]}
+//
+//   "RSA-OAEP-256"  `wrapped_key` is the AES key encrypted with RSA-OAEP
+//                   (SHA-256, MGF1-SHA-256)
+//   "ECDH-ES"       `wrapped_key` is the server's ephemeral P-256 public
+//                   key (SEC1, uncompressed); the AES key is the 32-byte
+//                   Concat KDF (SHA-256, RFC 7518 section 4.6.2) of the
+//                   shared secret with AlgorithmID "ECDH-ES" and empty
+//                   PartyUInfo and PartyVInfo
+//   "HPKE"          `wrapped_key` is the encapsulated key (65 bytes)
+//                   followed by the AES key sealed with HPKE (RFC 9180) in
+//                   base mode: DHKEM(P-256, HKDF-SHA256), HKDF-SHA256 and
+//                   AES-256-GCM, with empty info and aad
+//
+// The agent uses the strongest algorithm both sides support, HPKE first,
+// unless `wrap_algorithm` names one, and tells TAS its choice in the
+// `wrap-algorithm` field of the get_secret request. A server that lists no
+// algorithms gets RSA-OAEP-256, as before. The P-256 key is bound into the
+// report data like the RSA key, as its SubjectPublicKeyInfo DER.
+//
+// No unsafe code.
+
+use crate::crypto::WrappingKeyEncoding;
+use crate::rng::rng;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hkdf::Hkdf;
+use p256::ecdh::EphemeralSecret;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::pkcs8::{EncodePublicKey, LineEnding};
+use p256::PublicKey;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+/// Bytes of the unwrapped AES key.
+const KEY_LEN: usize = 32;
+
+/// Bytes of an uncompressed SEC1 P-256 point.
+const POINT_LEN: usize = 65;
+
+/// HPKE identifiers of DHKEM(P-256, HKDF-SHA256), HKDF-SHA256 and
+/// AES-256-GCM.
+const HPKE_KEM: u16 = 0x0010;
+const HPKE_KDF: u16 = 0x0001;
+const HPKE_AEAD: u16 = 0x0002;
+
+/// Algorithm TAS wraps the AES key of a secret payload with.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WrapAlgorithm {
+    /// RSA-OAEP with SHA-256 to an RSA-2048 key
+    #[default]
+    #[serde(rename = "RSA-OAEP-256")]
+    RsaOaep256,
+    /// Ephemeral-static ECDH on P-256 with the Concat KDF
+    #[serde(rename = "ECDH-ES")]
+    EcdhEs,
+    /// HPKE base mode on P-256
+    #[serde(rename = "HPKE")]
+    Hpke,
+}
+
+impl WrapAlgorithm {
+    /// All algorithms, strongest first.
+    const PREFERENCE: [WrapAlgorithm; 3] = [
+        WrapAlgorithm::Hpke,
+        WrapAlgorithm::EcdhEs,
+        WrapAlgorithm::RsaOaep256,
+    ];
+
+    /// The name of the algorithm, as in the config file and the TAS
+    /// version probe.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WrapAlgorithm::RsaOaep256 => "RSA-OAEP-256",
+            WrapAlgorithm::EcdhEs => "ECDH-ES",
+            WrapAlgorithm::Hpke => "HPKE",
+        }
+    }
+
+    /// Whether the algorithm wraps to a P-256 key.
+    pub fn is_ec(self) -> bool {
+        self != WrapAlgorithm::RsaOaep256
+    }
+
+    /// Agree on an algorithm with a server that supports the algorithms of
+    /// `supported`, using only RSA-OAEP-256 unless `ec` allows a P-256 key.
+    /// The `preferred` algorithm is used if both sides support it, else the
+    /// strongest one they do; a server that lists nothing predates the
+    /// capability and gets RSA-OAEP-256.
+    pub fn negotiate(preferred: Option<Self>, supported: &[String], ec: bool) -> Self {
+        let usable = |algorithm: Self| {
+            (ec || !algorithm.is_ec()) && supported.iter().any(|s| s == algorithm.as_str())
+        };
+        match preferred {
+            Some(algorithm) if usable(algorithm) => algorithm,
+            _ => Self::PREFERENCE
+                .into_iter()
+                .find(|&algorithm| usable(algorithm))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// `HPKE-v1` labelled HKDF-Extract of RFC 9180, returning the PRK and an
+/// HKDF instance to expand it.
+fn labeled_extract(
+    suite_id: &[u8],
+    salt: &[u8],
+    label: &str,
+    ikm: &[u8],
+) -> (Zeroizing<Vec<u8>>, Hkdf<Sha256>) {
+    let labeled_ikm = Zeroizing::new([&b"HPKE-v1"[..], suite_id, label.as_bytes(), ikm].concat());
+    let (prk, hkdf) = Hkdf::<Sha256>::extract(Some(salt), &labeled_ikm);
+    (Zeroizing::new(prk.to_vec()), hkdf)
+}
+
+/// `HPKE-v1` labelled HKDF-Expand of RFC 9180.
+fn labeled_expand(
+    suite_id: &[u8],
+    hkdf: &Hkdf<Sha256>,
+    label: &str,
+    info: &[u8],
+    len: usize,
+) -> Result<Zeroizing<Vec<u8>>> {
+    let length = u16::try_from(len).map_err(|_| anyhow!("HPKE: output too long"))?;
+    let labeled_info = [
+        &length.to_be_bytes()[..],
+        b"HPKE-v1",
+        suite_id,
+        label.as_bytes(),
+        info,
+    ]
+    .concat();
+    let mut okm = Zeroizing::new(vec![0; len]);
+    hkdf.expand(&labeled_info, &mut okm)
+        .map_err(|e| anyhow!("HPKE: {}", e))?;
+    Ok(okm)
+}
+
+/// AEAD key and base nonce of an HPKE context.
+type HpkeContext = (Zeroizing<Vec<u8>>, Zeroizing<Vec<u8>>);
+
+/// The AEAD key and nonce of an HPKE base mode context with empty info,
+/// from the Diffie-Hellman value `dh` of the encapsulated key `enc` and
+/// the recipient key `recipient`.
+fn hpke_key_schedule(dh: &[u8], enc: &[u8], recipient: &[u8]) -> Result<HpkeContext> {
+    key_schedule(dh, enc, recipient, b"", HPKE_AEAD, KEY_LEN)
+}
+
+/// The AEAD key and nonce of an HPKE base mode context with `info`, for
+/// the AEAD `aead_id` with `key_len` byte keys.
+fn key_schedule(
+    dh: &[u8],
+    enc: &[u8],
+    recipient: &[u8],
+    info: &[u8],
+    aead_id: u16,
+    key_len: usize,
+) -> Result<HpkeContext> {
+    // ExtractAndExpand of the DHKEM
+    let kem_suite = [&b"KEM"[..], &HPKE_KEM.to_be_bytes()].concat();
+    let (_, eae_prk) = labeled_extract(&kem_suite, b"", "eae_prk", dh);
+    let kem_context = [enc, recipient].concat();
+    let shared_secret = labeled_expand(&kem_suite, &eae_prk, "shared_secret", &kem_context, 32)?;
+
+    let suite = [
+        &b"HPKE"[..],
+        &HPKE_KEM.to_be_bytes(),
+        &HPKE_KDF.to_be_bytes(),
+        &aead_id.to_be_bytes(),
+    ]
+    .concat();
+    let (psk_id_hash, _) = labeled_extract(&suite, b"", "psk_id_hash", b"");
+    let (info_hash, _) = labeled_extract(&suite, b"", "info_hash", info);
+    // Mode 0: base
+    let context = [&[0u8][..], &psk_id_hash, &info_hash].concat();
+    let (_, secret) = labeled_extract(&suite, &shared_secret, "secret", b"");
+    let key = labeled_expand(&suite, &secret, "key", &context, key_len)?;
+    let nonce = labeled_expand(&suite, &secret, "base_nonce", &context, 12)?;
+    Ok((key, nonce))
+}
+
+/// Concat KDF of RFC 7518 section 4.6.2 for a 256-bit key with empty
+/// PartyUInfo and PartyVInfo.
+fn concat_kdf(z: &[u8], algorithm_id: &str) -> Zeroizing<Vec<u8>> {
+    concat_kdf_with(z, algorithm_id, b"", b"", KEY_LEN)
+}
+
+/// Concat KDF of RFC 7518 section 4.6.2 for a key of `len` bytes, at most
+/// 32: a single round of SHA-256.
+fn concat_kdf_with(
+    z: &[u8],
+    algorithm_id: &str,
+    party_u: &[u8],
+    party_v: &[u8],
+    len: usize,
+) -> Zeroizing<Vec<u8>> {
+    let mut hash = Sha256::new();
+    hash.update(1u32.to_be_bytes());
+    hash.update(z);
+    for field in [algorithm_id.as_bytes(), party_u, party_v] {
+        hash.update((field.len() as u32).to_be_bytes());
+        hash.update(field);
+    }
+    hash.update(((len * 8) as u32).to_be_bytes());
+    Zeroizing::new(hash.finalize()[..len].to_vec())
+}
+
+/// Ephemeral P-256 wrapping key for the ECDH-ES and HPKE algorithms.
+pub struct EcKey {
+    algorithm: WrapAlgorithm,
+    secret: EphemeralSecret,
+    public_key: PublicKey,
+}
+
+impl EcKey {
+    /// A fresh key for `algorithm`.
+    pub fn generate(algorithm: WrapAlgorithm) -> Self {
+        let secret = EphemeralSecret::random(&mut rng());
+        let public_key = secret.public_key();
+        EcKey {
+            algorithm,
+            secret,
+            public_key,
+        }
+    }
+
+    pub fn algorithm(&self) -> WrapAlgorithm {
+        self.algorithm
+    }
+
+    /// SubjectPublicKeyInfo DER of the public key, as bound into the report
+    /// data
+    pub fn public_key_to_der(&self) -> Result<Vec<u8>> {
+        let der = self
+            .public_key
+            .to_public_key_der()
+            .map_err(|e| anyhow!("Failed to convert public key to DER: {}", e))?;
+        Ok(der.to_vec())
+    }
+
+    /// Encodes the public key for the wrapping-key field of a key release
+    /// request
+    pub fn public_key_encoded(&self, encoding: WrappingKeyEncoding) -> Result<String> {
+        match encoding {
+            WrappingKeyEncoding::Base64Der => Ok(STANDARD.encode(self.public_key_to_der()?)),
+            WrappingKeyEncoding::Pem => self
+                .public_key
+                .to_public_key_pem(LineEnding::LF)
+                .map_err(|e| anyhow!("Failed to convert public key to PEM: {}", e)),
+        }
+    }
+
+    /// Uncompressed SEC1 encoding of the public key
+    fn public_key_sec1(&self) -> Vec<u8> {
+        self.public_key.to_encoded_point(false).as_bytes().to_vec()
+    }
+
+    /// Diffie-Hellman value with the peer key in SEC1 encoding
+    fn diffie_hellman(&self, peer: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        let peer = PublicKey::from_sec1_bytes(peer)
+            .map_err(|_| anyhow!("invalid ephemeral public key"))?;
+        let shared = self.secret.diffie_hellman(&peer);
+        let z: &[u8] = shared.raw_secret_bytes();
+        Ok(Zeroizing::new(z.to_vec()))
+    }
+
+    /// Unwraps the secret's AES encryption key
+    pub fn unwrap_key(&self, wrapped_key: &[u8]) -> Result<Vec<u8>> {
+        match self.algorithm {
+            WrapAlgorithm::EcdhEs => {
+                let z = self.diffie_hellman(wrapped_key)?;
+                Ok(concat_kdf(&z, WrapAlgorithm::EcdhEs.as_str()).to_vec())
+            }
+            WrapAlgorithm::Hpke => {
+                if wrapped_key.len() <= POINT_LEN {
+                    return Err(anyhow!("HPKE: wrapped key too short"));
+                }
+                let (enc, sealed) = wrapped_key.split_at(POINT_LEN);
+                let dh = self.diffie_hellman(enc)?;
+                let (key, nonce) = hpke_key_schedule(&dh, enc, &self.public_key_sec1())?;
+                let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| anyhow!("HPKE: {}", e))?;
+                let payload = Payload {
+                    msg: sealed,
+                    aad: b"",
+                };
+                cipher
+                    .decrypt(Nonce::from_slice(&nonce), payload)
+                    .map_err(|_| anyhow!("HPKE: unable to open the wrapped key"))
+            }
+            WrapAlgorithm::RsaOaep256 => Err(anyhow!("RSA-OAEP-256 needs an RSA key")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seal `key` to `recipient` with HPKE, as TAS would.
+    fn hpke_wrap(recipient: &EcKey, key: &[u8]) -> Vec<u8> {
+        let ephemeral = EcKey::generate(WrapAlgorithm::Hpke);
+        let enc = ephemeral.public_key_sec1();
+        let dh = ephemeral
+            .diffie_hellman(&recipient.public_key_sec1())
+            .unwrap();
+        let (aead_key, nonce) = hpke_key_schedule(&dh, &enc, &recipient.public_key_sec1()).unwrap();
+        let cipher = Aes256Gcm::new_from_slice(&aead_key).unwrap();
+        let sealed = cipher.encrypt(Nonce::from_slice(&nonce), key).unwrap();
+        [enc, sealed].concat()
+    }
+
+    /// Agree on a key with `recipient` by ECDH-ES, as TAS would, returning
+    /// the ephemeral public key and the key.
+    fn ecdh_es_wrap(recipient: &EcKey) -> (Vec<u8>, Zeroizing<Vec<u8>>) {
+        let ephemeral = EcKey::generate(WrapAlgorithm::EcdhEs);
+        let z = ephemeral
+            .diffie_hellman(&recipient.public_key_sec1())
+            .unwrap();
+        (ephemeral.public_key_sec1(), concat_kdf(&z, "ECDH-ES"))
+    }
+
+    #[test]
+    fn test_negotiate_wrap_algorithm() {
+        let all: Vec<String> = ["RSA-OAEP-256", "ECDH-ES", "HPKE"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            WrapAlgorithm::negotiate(None, &all, true),
+            WrapAlgorithm::Hpke
+        );
+        assert_eq!(
+            WrapAlgorithm::negotiate(None, &all[..2], true),
+            WrapAlgorithm::EcdhEs
+        );
+        assert_eq!(
+            WrapAlgorithm::negotiate(Some(WrapAlgorithm::EcdhEs), &all, true),
+            WrapAlgorithm::EcdhEs
+        );
+        // Without a P-256 key only RSA is left
+        assert_eq!(
+            WrapAlgorithm::negotiate(Some(WrapAlgorithm::Hpke), &all, false),
+            WrapAlgorithm::RsaOaep256
+        );
+        // A server predating the capability
+        assert_eq!(
+            WrapAlgorithm::negotiate(Some(WrapAlgorithm::Hpke), &[], true),
+            WrapAlgorithm::RsaOaep256
+        );
+        assert_eq!(
+            WrapAlgorithm::negotiate(None, &["HPKE-X".to_string()], true),
+            WrapAlgorithm::RsaOaep256
+        );
+    }
+
+    /// Diffie-Hellman value of the private scalar `sk` and the SEC1 point
+    /// `pk`, checking that `sk` belongs to `own_pk`.
+    fn dh(sk: &[u8], own_pk: &[u8], pk: &[u8]) -> Vec<u8> {
+        let sk = p256::SecretKey::from_slice(sk).unwrap();
+        assert_eq!(sk.public_key().to_encoded_point(false).as_bytes(), own_pk);
+        let pk = PublicKey::from_sec1_bytes(pk).unwrap();
+        let shared = p256::ecdh::diffie_hellman(sk.to_nonzero_scalar(), pk.as_affine());
+        shared.raw_secret_bytes().to_vec()
+    }
+
+    #[test]
+    fn test_hpke_key_schedule_rfc9180() {
+        // RFC 9180 Appendix A.3.1: DHKEM(P-256, HKDF-SHA256), HKDF-SHA256,
+        // AES-128-GCM, base mode
+        let info = hex::decode("4f6465206f6e2061204772656369616e2055726e").unwrap();
+        let pk_em = hex::decode(
+            "04a92719c6195d5085104f469a8b9814d5838ff72b60501e2c4466e5e67b325ac\
+             98536d7b61a1af4b78e5b7f951c0900be863c403ce65c9bfcb9382657222d18c4",
+        )
+        .unwrap();
+        let pk_rm = hex::decode(
+            "04fe8c19ce0905191ebc298a9245792531f26f0cece2460639e8bc39cb7f706a8\
+             26a779b4cf969b8a0e539c7f62fb3d30ad6aa8f80e30f1d128aafd68a2ce72ea0",
+        )
+        .unwrap();
+        let sk_rm = hex::decode("f3ce7fdae57e1a310d87f1ebbde6f328be0a99cdbcadf4d6589cf29de4b8ffd2")
+            .unwrap();
+        let sk_em = hex::decode("4995788ef4b9d6132b249ce59a77281493eb39af373d236a1fe415cb0c2d7beb")
+            .unwrap();
+
+        let dh_r = dh(&sk_rm, &pk_rm, &pk_em);
+        assert_eq!(dh(&sk_em, &pk_em, &pk_rm), dh_r);
+        let (key, nonce) = key_schedule(&dh_r, &pk_em, &pk_rm, &info, 0x0001, 16).unwrap();
+        assert_eq!(hex::encode(&key), "868c066ef58aae6dc589b6cfdd18f97e");
+        assert_eq!(hex::encode(&nonce), "4e0bc5018beba4bf004cca59");
+
+        // Sequence number 0
+        let ct = hex::decode(
+            "5ad590bb8baa577f8619db35a36311226a896e7342a6d836d8b7bcd2f20b6c7f\
+             9076ac232e3ab2523f39513434",
+        )
+        .unwrap();
+        let cipher = aes_gcm::Aes128Gcm::new_from_slice(&key).unwrap();
+        let payload = Payload {
+            msg: &ct,
+            aad: b"Count-0",
+        };
+        let pt = cipher.decrypt(Nonce::from_slice(&nonce), payload).unwrap();
+        assert_eq!(pt, b"Beauty is truth, truth beauty");
+    }
+
+    #[test]
+    fn test_concat_kdf_rfc7518() {
+        // RFC 7518 Appendix C: ECDH-ES with P-256 for A128GCM
+        let d = |b64: &str| {
+            base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .decode(b64)
+                .unwrap()
+        };
+        let point = |x: &str, y: &str| [vec![4], d(x), d(y)].concat();
+        let alice = point(
+            "gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0",
+            "SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps",
+        );
+        let bob = point(
+            "weNJy2HscCSM6AEDTDg04biOvhFhyyWvOHQfeF_PxMQ",
+            "e8lnCO-AlStT-NJVX-crhB7QRYhiix03illJOVAOyck",
+        );
+        let z = dh(
+            &d("0_NxaRPUMQoAJt50Gz8YiTr8gRTwyEaCumd-MToTmIo"),
+            &alice,
+            &bob,
+        );
+        assert_eq!(
+            z,
+            [
+                158, 86, 217, 29, 129, 113, 53, 211, 114, 131, 66, 131, 191, 132, 38, 156, 251, 49,
+                110, 163, 218, 128, 106, 72, 246, 218, 167, 121, 140, 254, 144, 196
+            ]
+        );
+        assert_eq!(
+            dh(
+                &d("VEmDZpDXXK8p8N0Cndsxs924q6nS1RXFASRl6BfUqdw"),
+                &bob,
+                &alice
+            ),
+            z
+        );
+        let key = concat_kdf_with(&z, "A128GCM", b"Alice", b"Bob", 16);
+        assert_eq!(
+            *key,
+            [86, 170, 141, 234, 248, 35, 109, 32, 92, 34, 40, 205, 113, 167, 16, 26]
+        );
+    }
+
+    #[test]
+    fn test_unwrap_key_hpke() {
+        let aes_key = [0x42u8; KEY_LEN];
+        let recipient = EcKey::generate(WrapAlgorithm::Hpke);
+        let wrapped = hpke_wrap(&recipient, &aes_key);
+        assert_eq!(wrapped.len(), POINT_LEN + KEY_LEN + 16);
+        assert_eq!(recipient.unwrap_key(&wrapped).unwrap(), aes_key);
+
+        let mut corrupted = wrapped.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(recipient.unwrap_key(&corrupted).is_err());
+        assert!(recipient.unwrap_key(&wrapped[..POINT_LEN]).is_err());
+        // Sealed to another key
+        let other = EcKey::generate(WrapAlgorithm::Hpke);
+        assert!(other.unwrap_key(&wrapped).is_err());
+    }
+
+    #[test]
+    fn test_unwrap_key_ecdh_es() {
+        let recipient = EcKey::generate(WrapAlgorithm::EcdhEs);
+        let (enc, key) = ecdh_es_wrap(&recipient);
+        assert_eq!(key.len(), KEY_LEN);
+        assert_eq!(recipient.unwrap_key(&enc).unwrap(), *key);
+
+        let other = EcKey::generate(WrapAlgorithm::EcdhEs);
+        assert_ne!(other.unwrap_key(&enc).unwrap(), *key);
+        assert!(recipient.unwrap_key(b"not a point").is_err());
+    }
+
+    #[test]
+    fn test_public_key_encoded() {
+        let key = EcKey::generate(WrapAlgorithm::EcdhEs);
+        let der = STANDARD
+            .decode(
+                key.public_key_encoded(WrappingKeyEncoding::Base64Der)
+                    .unwrap(),
+            )
+            .unwrap();
+        assert_eq!(der, key.public_key_to_der().unwrap());
+        let pem = key.public_key_encoded(WrappingKeyEncoding::Pem).unwrap();
+        assert!(pem.starts_with("-----BEGIN PUBLIC KEY-----"));
+    }
+}